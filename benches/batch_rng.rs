@@ -0,0 +1,111 @@
+//! Compares the sequential scatter shuffle with a scalar generator and with eight
+//! interleaved lanes of the same generator served through
+//! [`rip_shuffle::batch_rng::Batched`]. The lanes only use shifts, additions and xors,
+//! so the compiler vectorizes [`BatchRng::fill_u64x8`] on AVX2/AVX-512 machines.
+//!
+//! Run with `cargo bench --bench batch_rng`.
+
+use rand::{Rng, RngCore, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use rip_shuffle::batch_rng::{BatchRng, Batched};
+use rip_shuffle::scatter_shuffle::sequential::seq_scatter_shuffle;
+use std::time::{Duration, Instant};
+
+const LANES: usize = 8;
+
+/// Eight independent xorshift128+ generators in structure-of-arrays layout
+#[derive(Clone)]
+struct XorShiftLanes {
+    s0: [u64; LANES],
+    s1: [u64; LANES],
+}
+
+impl XorShiftLanes {
+    fn seed_from_u64(seed: u64) -> Self {
+        let mut rng = Pcg64Mcg::seed_from_u64(seed);
+        // xorshift128+ must not be seeded with zeros
+        Self {
+            s0: std::array::from_fn(|_| rng.gen::<u64>() | 1),
+            s1: std::array::from_fn(|_| rng.gen::<u64>() | 1),
+        }
+    }
+
+    /// Scalar use of the first lane only
+    fn next_scalar(&mut self) -> u64 {
+        let (mut x, y) = (self.s0[0], self.s1[0]);
+        self.s0[0] = y;
+        x ^= x << 23;
+        self.s1[0] = x ^ y ^ (x >> 17) ^ (y >> 26);
+        self.s1[0].wrapping_add(y)
+    }
+}
+
+impl BatchRng for XorShiftLanes {
+    #[inline]
+    fn fill_u64x8(&mut self, dest: &mut [u64; LANES]) {
+        for (i, word) in dest.iter_mut().enumerate() {
+            let (mut x, y) = (self.s0[i], self.s1[i]);
+            self.s0[i] = y;
+            x ^= x << 23;
+            self.s1[i] = x ^ y ^ (x >> 17) ^ (y >> 26);
+            *word = self.s1[i].wrapping_add(y);
+        }
+    }
+}
+
+impl RngCore for XorShiftLanes {
+    fn next_u32(&mut self) -> u32 {
+        self.next_scalar() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.next_scalar()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_scalar().to_le_bytes()[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Returns the median duration per element of shuffling `data` with `rng`
+fn measure<R: Rng>(data: &mut [u32], mut rng: R) -> f64 {
+    let reps = (1 << 24) / data.len() + 3;
+
+    let mut times: Vec<Duration> = (0..reps)
+        .map(|_| {
+            let start = Instant::now();
+            seq_scatter_shuffle(&mut rng, data);
+            start.elapsed()
+        })
+        .collect();
+
+    times.sort();
+    times[reps / 2].as_nanos() as f64 / data.len() as f64
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>14} {:>14} {:>14}",
+        "n", "pcg [ns]", "scalar [ns]", "batched [ns]"
+    );
+
+    for log_n in (16..=24).step_by(2) {
+        let mut data: Vec<u32> = (0..1 << log_n).collect();
+
+        let pcg = measure(&mut data, Pcg64Mcg::seed_from_u64(1234));
+        let scalar = measure(&mut data, XorShiftLanes::seed_from_u64(1234));
+        let batched = measure(&mut data, Batched::new(XorShiftLanes::seed_from_u64(1234)));
+
+        println!(
+            "{:>10} {pcg:>14.2} {scalar:>14.2} {batched:>14.2}",
+            1u64 << log_n
+        );
+    }
+}
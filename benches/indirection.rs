@@ -0,0 +1,52 @@
+//! Compares shuffling large elements directly with the sequential scatter shuffle and
+//! via an index array (see [`rip_shuffle::indirection`]) for several element sizes.
+//!
+//! Run with `cargo bench --bench indirection`.
+
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
+use rip_shuffle::indirection::seq_shuffle_indirect;
+use rip_shuffle::scatter_shuffle::sequential::seq_scatter_shuffle;
+use std::time::{Duration, Instant};
+
+/// Returns the median duration per element of shuffling `data` with `algo`
+fn measure<T>(data: &mut [T], mut algo: impl FnMut(&mut Pcg64Mcg, &mut [T])) -> f64 {
+    let mut rng = Pcg64Mcg::seed_from_u64(1234);
+    let reps = (1 << 22) / data.len().max(1) + 3;
+
+    let mut times: Vec<Duration> = (0..reps)
+        .map(|_| {
+            let start = Instant::now();
+            algo(&mut rng, data);
+            start.elapsed()
+        })
+        .collect();
+
+    times.sort();
+    times[reps / 2].as_nanos() as f64 / data.len() as f64
+}
+
+fn compare<const BYTES: usize>() {
+    for log_n in (10..=20).step_by(2) {
+        let mut data = vec![[0u8; BYTES]; 1 << log_n];
+
+        let direct = measure(&mut data, seq_scatter_shuffle);
+        let indirect = measure(&mut data, seq_shuffle_indirect);
+
+        println!(
+            "{BYTES:>8} {:>10} {direct:>14.2} {indirect:>14.2}",
+            1u64 << log_n
+        );
+    }
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>10} {:>14} {:>14}",
+        "bytes", "n", "direct [ns]", "indirect [ns]"
+    );
+    compare::<64>();
+    compare::<128>();
+    compare::<256>();
+    compare::<512>();
+}
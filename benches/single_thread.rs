@@ -0,0 +1,57 @@
+//! Compares the sequential and the parallel scatter shuffle in a rayon pool with a
+//! single thread, where [`RipShuffleParallel::par_shuffle`] falls back to the former.
+//!
+//! Run with `cargo bench --bench single_thread`.
+
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
+use rip_shuffle::scatter_shuffle::{
+    parallel::par_scatter_shuffle, sequential::seq_scatter_shuffle,
+};
+use rip_shuffle::RipShuffleParallel;
+use std::time::{Duration, Instant};
+
+/// Returns the median duration per element of shuffling `data` with `algo`
+fn measure(data: &mut [u64], mut algo: impl FnMut(&mut Pcg64Mcg, &mut [u64])) -> f64 {
+    let mut rng = Pcg64Mcg::seed_from_u64(1234);
+    let reps = (1 << 24) / data.len().max(1) + 3;
+
+    let mut times: Vec<Duration> = (0..reps)
+        .map(|_| {
+            let start = Instant::now();
+            algo(&mut rng, data);
+            start.elapsed()
+        })
+        .collect();
+
+    times.sort();
+    times[reps / 2].as_nanos() as f64 / data.len() as f64
+}
+
+fn main() {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+
+    println!(
+        "{:>10} {:>12} {:>12} {:>12}",
+        "n", "seq [ns]", "par [ns]", "fallback [ns]"
+    );
+    for log_n in (10..=26).step_by(2) {
+        let mut data: Vec<u64> = (0..1 << log_n).collect();
+
+        let (seq, par, fallback) = pool.install(|| {
+            (
+                measure(&mut data, seq_scatter_shuffle),
+                measure(&mut data, par_scatter_shuffle),
+                measure(&mut data, |rng, data| data.par_shuffle(rng)),
+            )
+        });
+
+        println!(
+            "{:>10} {seq:>12.2} {par:>12.2} {fallback:>12.2}",
+            1u64 << log_n
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Limiting the number of threads of parallel shuffles to the point where memory
+//! bandwidth is saturated.
+//!
+//! Shuffling large inputs is bound by memory bandwidth. Once the bandwidth is saturated,
+//! additional threads only add contention (and occupy cores other work could use).
+//! [`AdaptiveConcurrency`] finds this point over repeated shuffles, e.g., the epochs of
+//! a training loop: the first shuffle runs on a single thread, and each subsequent one
+//! doubles the number of threads as long as the measured throughput grows by at least
+//! [`MIN_GAIN`]. It then keeps the smallest number of threads whose throughput is
+//! within [`MIN_GAIN`] of the best one. Each shuffle runs in a dedicated Rayon pool of
+//! the current size, so the recursion and the base cases are limited as well.
+//!
+//! The measurements are recorded by a [`ParProfiler`], which
+//! [`AdaptiveConcurrency::profiler`] exposes together with the regions of the shuffles.
+//! A single slow measurement (e.g., caused by other processes) can end the search
+//! early; [`AdaptiveConcurrency::reset`] starts it again.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::adaptive::AdaptiveConcurrency;
+//! use rand::SeedableRng;
+//!
+//! let adaptive = AdaptiveConcurrency::new();
+//! let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1);
+//! let mut data: Vec<u64> = (0..1 << 21).collect();
+//!
+//! for _epoch in 0..4 {
+//!     adaptive.shuffle(&mut rng, &mut data);
+//! }
+//!
+//! println!("{} threads, saturated at {:?}", adaptive.num_threads(), adaptive.saturation_point());
+//! println!("{}", adaptive.profiler().report());
+//! ```
+
+use super::fisher_yates::fisher_yates;
+use super::profiler::par_profile::ParProfiler;
+use super::scatter_shuffle::parallel::{
+    num_buckets_for_bytes, ParScatterShuffleImpl, DEFAULT_BASE_CASE_SIZE,
+};
+use super::scatter_shuffle::{ParConfiguration, SeqConfiguration};
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, Mutex};
+
+/// Minimal relative gain in throughput (10%) that justifies doubling the number of threads.
+pub const MIN_GAIN: f64 = 0.1;
+
+/// See the [module documentation](self).
+pub struct AdaptiveConcurrency {
+    max_threads: usize,
+    profiler: ParProfiler,
+    state: Mutex<State>,
+}
+
+struct State {
+    num_threads: usize,
+    /// Number of threads of the previous step of the search; not necessarily half of
+    /// `num_threads`, since the last step is capped by `max_threads`.
+    previous_threads: Option<usize>,
+    saturation_point: Option<usize>,
+    pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+impl Default for AdaptiveConcurrency {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdaptiveConcurrency {
+    /// Uses at most as many threads as the current Rayon pool.
+    pub fn new() -> Self {
+        Self::with_max_threads(rayon::current_num_threads())
+    }
+
+    /// Uses at most `max_threads` threads (at least one).
+    pub fn with_max_threads(max_threads: usize) -> Self {
+        Self {
+            max_threads: max_threads.max(1),
+            profiler: ParProfiler::new(),
+            state: Mutex::new(State {
+                num_threads: 1,
+                previous_threads: None,
+                saturation_point: None,
+                pool: None,
+            }),
+        }
+    }
+
+    /// Number of threads of the next shuffle.
+    pub fn num_threads(&self) -> usize {
+        self.state.lock().unwrap().num_threads
+    }
+
+    /// Number of threads at which the throughput stopped growing, once it is found.
+    pub fn saturation_point(&self) -> Option<usize> {
+        self.state.lock().unwrap().saturation_point
+    }
+
+    /// The profiler recording the shuffles and their throughput per number of threads
+    /// (see [`crate::profiler::par_profile::ProfileReport::saturation_point`]).
+    pub fn profiler(&self) -> &ParProfiler {
+        &self.profiler
+    }
+
+    /// Discards all measurements and restarts the search with a single thread.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.profiler.reset();
+        state.num_threads = 1;
+        state.previous_threads = None;
+        state.saturation_point = None;
+        state.pool = None;
+    }
+
+    /// Shuffles `data` with [`crate::scatter_shuffle::parallel::par_scatter_shuffle`] in a
+    /// pool of [`AdaptiveConcurrency::num_threads`] threads and adjusts the number of
+    /// threads for the next shuffle. Inputs that `par_scatter_shuffle` shuffles
+    /// sequentially are not measured.
+    pub fn shuffle<R, T>(&self, rng: &mut R, data: &mut [T])
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        T: Send,
+    {
+        if crate::oracle::is_enabled() {
+            return crate::fisher_yates::naive::fisher_yates(rng, data);
+        }
+
+        let Some(num_buckets) = num_buckets_for_bytes(std::mem::size_of_val(data)) else {
+            return fisher_yates(rng, data);
+        };
+
+        let (num_threads, pool) = self.current_pool();
+        let config = AdaptiveConfiguration {
+            profiler: self.profiler.clone(),
+        };
+        let shuffle = || match num_buckets {
+            64 => ParScatterShuffleImpl::<R, T, _, 64>::new(config).shuffle(rng, data),
+            256 => ParScatterShuffleImpl::<R, T, _, 256>::new(config).shuffle(rng, data),
+            512 => ParScatterShuffleImpl::<R, T, _, 512>::new(config).shuffle(rng, data),
+            _ => ParScatterShuffleImpl::<R, T, _, 1024>::new(config).shuffle(rng, data),
+        };
+
+        match pool {
+            Some(pool) => pool.install(shuffle),
+            None => shuffle(),
+        }
+
+        self.advance(num_threads);
+    }
+
+    /// Returns the pool of the current number of threads, which is built on first use.
+    /// Falls back to the current pool if no pool can be built.
+    fn current_pool(&self) -> (usize, Option<Arc<rayon::ThreadPool>>) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.pool.is_none() {
+            state.pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(state.num_threads)
+                .build()
+                .ok()
+                .map(Arc::new);
+        }
+
+        (state.num_threads, state.pool.clone())
+    }
+
+    /// Chooses the number of threads of the next shuffle after a shuffle with
+    /// `num_threads` threads was measured.
+    fn advance(&self, num_threads: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.saturation_point.is_some() || state.num_threads != num_threads {
+            return; // another shuffle already advanced the search
+        }
+
+        let report = self.profiler.report();
+        let throughput = |threads: usize| {
+            report
+                .throughput()
+                .iter()
+                .find(|e| e.num_threads == threads)
+                .map(|e| e.bytes_per_second())
+        };
+
+        let current = throughput(num_threads);
+        let previous = state.previous_threads.and_then(throughput);
+        let gained = match (previous, current) {
+            (Some(previous), Some(current)) => current >= previous * (1.0 + MIN_GAIN),
+            _ => true,
+        };
+
+        if gained && num_threads < self.max_threads {
+            state.previous_threads = Some(num_threads);
+            state.num_threads = (2 * num_threads).min(self.max_threads);
+        } else {
+            let saturation_point = report.saturation_point(MIN_GAIN).unwrap_or(num_threads);
+            state.num_threads = saturation_point;
+            state.saturation_point = Some(saturation_point);
+        }
+
+        state.pool = None;
+    }
+}
+
+/// The default configuration of the parallel scatter shuffle with a [`ParProfiler`].
+#[derive(Clone)]
+struct AdaptiveConfiguration {
+    profiler: ParProfiler,
+}
+
+crate::implement_seq_config!(AdaptiveConfiguration, fisher_yates, 1 << 16);
+
+crate::implement_par_config!(
+    AdaptiveConfiguration,
+    fisher_yates,
+    DEFAULT_BASE_CASE_SIZE,
+    default,
+    profiler: ParProfiler
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+    use std::time::Duration;
+
+    const GIB: usize = 1 << 30;
+
+    #[test]
+    fn doubles_until_saturated() {
+        let adaptive = AdaptiveConcurrency::with_max_threads(16);
+        let record = |threads, ms| {
+            let elapsed = Duration::from_millis(ms);
+            adaptive.profiler.record_throughput(threads, GIB, elapsed);
+            adaptive.advance(threads);
+        };
+
+        assert_eq!(adaptive.num_threads(), 1);
+        record(1, 1000);
+        assert_eq!(adaptive.num_threads(), 2);
+        record(2, 500);
+        assert_eq!(adaptive.num_threads(), 4);
+        record(4, 300);
+        assert_eq!(adaptive.num_threads(), 8);
+        assert_eq!(adaptive.saturation_point(), None);
+
+        // 8 threads gain less than 10% over 4 threads
+        record(8, 290);
+        assert_eq!(adaptive.saturation_point(), Some(4));
+        assert_eq!(adaptive.num_threads(), 4);
+
+        // further measurements do not change the decision
+        record(4, 1000);
+        assert_eq!(adaptive.num_threads(), 4);
+
+        adaptive.reset();
+        assert_eq!(adaptive.num_threads(), 1);
+        assert_eq!(adaptive.saturation_point(), None);
+        assert!(adaptive.profiler().report().throughput().is_empty());
+    }
+
+    #[test]
+    fn stops_at_max_threads() {
+        let adaptive = AdaptiveConcurrency::with_max_threads(3);
+        for (threads, ms) in [(1, 1000), (2, 500)] {
+            adaptive
+                .profiler
+                .record_throughput(threads, GIB, Duration::from_millis(ms));
+            adaptive.advance(threads);
+        }
+        assert_eq!(adaptive.num_threads(), 3);
+
+        adaptive
+            .profiler
+            .record_throughput(3, GIB, Duration::from_millis(330));
+        adaptive.advance(3);
+        assert_eq!(adaptive.saturation_point(), Some(3));
+    }
+
+    // the oracle mode bypasses the parallel shuffle, so there is nothing to measure
+    #[test]
+    #[cfg(not(feature = "oracle"))]
+    fn shuffles_and_measures() {
+        let adaptive = AdaptiveConcurrency::with_max_threads(2);
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let mut data: Vec<u64> = (0..1 << 21).collect();
+
+        for _ in 0..3 {
+            adaptive.shuffle(&mut rng, &mut data);
+        }
+
+        let report = adaptive.profiler().report();
+        assert_eq!(report.throughput()[0].num_threads, 1);
+        assert!(adaptive.saturation_point().is_some());
+        assert!(report.get("ParScatter", 0).is_some());
+
+        assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i as u64 == x));
+    }
+}
@@ -1,5 +1,6 @@
+use super::error::ShuffleError;
 use super::*;
-use rand::{Rng, SeedableRng};
+use rand::{CryptoRng, Rng, SeedableRng};
 
 pub trait RipShuffleSequential {
     /// Rearranges the input in a random permutation, such that any order appears
@@ -7,6 +8,12 @@ pub trait RipShuffleSequential {
     /// generator. If a deterministic sequence is provided, the output is the same
     /// each run with the same build on the same machine.
     ///
+    /// The implementation for slices performs no heap allocations (which is asserted
+    /// by our test suite), and is hence suited for real-time and embedded contexts.
+    /// The only exception are elements of at least [`indirection::MIN_ELEMENT_SIZE`]
+    /// bytes if [`indirection::enable`] was called; they are then shuffled via an
+    /// index array (see [`indirection`]).
+    ///
     /// # Warning
     /// We might change the algorithm or fine-tune the its parameters. Therefore,
     /// the emitted order might change with future revisions of the code.
@@ -22,13 +29,80 @@ pub trait RipShuffleSequential {
     /// assert_ne!(data, org); // might fail with probility 1 / 100!
     /// ```
     fn seq_shuffle<R: Rng>(&mut self, rng: &mut R);
+
+    /// Same as [`RipShuffleSequential::seq_shuffle`], but only accepts random number
+    /// generators marked as cryptographically secure (implementing [`rand::CryptoRng`]).
+    /// Use this variant in security-sensitive contexts (e.g., shuffling ballots or keys)
+    /// to rule out that a fast non-cryptographic generator is passed by accident.
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::RipShuffleSequential;
+    /// let mut data : Vec<_> = (0..100).collect();
+    ///
+    /// // `ThreadRng` is a CSPRNG; passing e.g. a `Pcg64Mcg` does not compile
+    /// data.secure_seq_shuffle(&mut rand::thread_rng());
+    /// ```
+    ///
+    /// Non-cryptographic generators are rejected at compile time:
+    /// ```compile_fail
+    /// use rip_shuffle::RipShuffleSequential;
+    /// use rand::SeedableRng;
+    /// let mut data : Vec<_> = (0..100).collect();
+    ///
+    /// data.secure_seq_shuffle(&mut rand_pcg::Pcg64Mcg::seed_from_u64(1234));
+    /// ```
+    fn secure_seq_shuffle<R: Rng + CryptoRng>(&mut self, rng: &mut R) {
+        self.seq_shuffle(rng)
+    }
+
+    /// Same as [`RipShuffleSequential::seq_shuffle`], but first checks a clone of
+    /// `rng` for obviously broken behavior (see [`rng_guard`]). If the check fails,
+    /// the input remains untouched and [`ShuffleError::DegenerateRng`] is returned.
+    /// Otherwise, the result is identical to [`RipShuffleSequential::seq_shuffle`].
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::RipShuffleSequential;
+    /// use rand::rngs::mock::StepRng;
+    /// let mut data : Vec<_> = (0..100).collect();
+    ///
+    /// assert!(data.checked_seq_shuffle(&mut rand::thread_rng()).is_ok());
+    /// assert!(data.checked_seq_shuffle(&mut StepRng::new(0, 0)).is_err());
+    /// ```
+    fn checked_seq_shuffle<R: Rng + Clone>(&mut self, rng: &mut R) -> Result<(), ShuffleError> {
+        rng_guard::check_rng(&mut rng.clone())?;
+        self.seq_shuffle(rng);
+        Ok(())
+    }
+
+    /// Moves `k` uniformly drawn elements to the front in random order, i.e., the first
+    /// `k` positions are distributed as after [`RipShuffleSequential::seq_shuffle`]; the
+    /// order of the remaining elements is unspecified. If `k` exceeds the length, the
+    /// whole input is shuffled. For large inputs, the implementation for slices is
+    /// considerably faster than a truncated Fisher-Yates shuffle (see [`partial_shuffle`]).
+    /// By default, the whole input is shuffled.
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::RipShuffleSequential;
+    /// let mut data : Vec<_> = (0..1_000_000).collect();
+    ///
+    /// data.seq_partial_shuffle(&mut rand::thread_rng(), 10);
+    /// let winners = &data[..10];
+    /// ```
+    fn seq_partial_shuffle<R: Rng>(&mut self, rng: &mut R, k: usize) {
+        let _ = k;
+        self.seq_shuffle(rng)
+    }
 }
 
-pub trait RipShuffleParallel: Send + Sync {
+pub trait RipShuffleParallel: Send {
     /// Rearranges the input in a random permutation, such that any order appears
     /// with equal probability. The permutation only depends on the random number
-    /// generator. If a deterministic sequence is provided, the output is the same
-    /// each run with the same build on the same machine.
+    /// generator and the number of threads of the current rayon pool. If a
+    /// deterministic sequence is provided, the output is the same each run with the
+    /// same build on the same machine.
     ///
     /// In contrast to [`RipShuffleSequential::seq_shuffle`], this implementation
     /// uses a rayon worker pool to balance the work over multiple threads (if the
@@ -43,12 +117,28 @@ pub trait RipShuffleParallel: Send + Sync {
     /// is your default source of randomness, consider seeding a compatible RNG as
     /// shown in the example. We suggest the very fast [`rand_pcg::Pcg64Mcg`].
     /// If you enable the `seed_with` flag for this crate, you can use the
-    /// [`RipShuffleParallel::par_shuffle_seed_with`] short-hand.
+    /// [`RipShuffleParallel::par_shuffle_seed_with`] short-hand, or
+    /// [`par_shuffle_thread_local`] which does not need a generator at all.
     ///
     /// # Warning
     /// We might change the algorithm or fine-tune the its parameters. Therefore,
     /// the emitted order might change with future revisions of the code.
     ///
+    /// # Allocations
+    /// The algorithm itself performs no heap allocations. After a first call has set up
+    /// the thread-local state of Rayon, calls from a worker thread (e.g., within
+    /// [`rayon::ThreadPool::install`]) are allocation-free, which is asserted by our test
+    /// suite. Calls from other threads may allocate occasionally when Rayon extends its
+    /// job queue.
+    ///
+    /// # Single thread
+    /// If the current rayon pool has a single thread (see [`rayon::current_num_threads`]),
+    /// e.g., within [`rayon::ThreadPool::install`] of a pool with one thread, inputs of
+    /// more than [`scatter_shuffle::parallel::SEQUENTIAL_MAX_BYTES`] bytes are shuffled
+    /// with [`scatter_shuffle::sequential::seq_scatter_shuffle`] instead, which avoids
+    /// the overhead of the parallel algorithm. Smaller inputs are shuffled sequentially
+    /// in either case. The benchmark `single_thread` compares both algorithms.
+    ///
     /// # Example
     /// ```
     /// use rip_shuffle::RipShuffleParallel;
@@ -63,6 +153,46 @@ pub trait RipShuffleParallel: Send + Sync {
     /// ```
     fn par_shuffle<R: SeedableRng + Rng + Send + Sync>(&mut self, rng: &mut R);
 
+    /// Same as [`RipShuffleParallel::par_shuffle`], but only accepts random number
+    /// generators marked as cryptographically secure (implementing [`rand::CryptoRng`]).
+    /// The generators of the worker threads are seeded from `rng` and have the same
+    /// type, so they are cryptographically secure as well.
+    ///
+    /// In contrast to [`RipShuffleParallel::par_shuffle_seed_with`], there is no
+    /// short-hand that seeds a faster generator, as this would void the guarantee.
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::RipShuffleParallel;
+    /// use rand::prelude::*;
+    /// let mut data : Vec<_> = (0..1_000_000).collect();
+    ///
+    /// let mut rng = StdRng::from_entropy(); // a CSPRNG
+    /// data.secure_par_shuffle(&mut rng);
+    /// ```
+    fn secure_par_shuffle<R: SeedableRng + Rng + CryptoRng + Send + Sync>(&mut self, rng: &mut R) {
+        self.par_shuffle(rng)
+    }
+
+    /// Same as [`RipShuffleParallel::par_shuffle`], but first checks a clone of
+    /// `rng` for obviously broken behavior. See [`RipShuffleSequential::checked_seq_shuffle`].
+    fn checked_par_shuffle<R: SeedableRng + Rng + Clone + Send + Sync>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<(), ShuffleError> {
+        rng_guard::check_rng(&mut rng.clone())?;
+        self.par_shuffle(rng);
+        Ok(())
+    }
+
+    /// Same as [`RipShuffleSequential::seq_partial_shuffle`], but shuffles in parallel
+    /// (see [`partial_shuffle::par_partial_shuffle`]). By default, the whole input is
+    /// shuffled.
+    fn par_partial_shuffle<R: SeedableRng + Rng + Send + Sync>(&mut self, rng: &mut R, k: usize) {
+        let _ = k;
+        self.par_shuffle(rng)
+    }
+
     /// Invokes [`RipShuffleParallel::par_shuffle`] with a compatible RNG that
     /// is seeded with an arbitrary RNG provided.
     ///
@@ -84,14 +214,412 @@ pub trait RipShuffleParallel: Send + Sync {
     }
 }
 
+/// An algorithm selected at runtime via [`RipShuffle::shuffle_with`]. Strategies are
+/// parsed from and printed as their snake-case names (e.g., `"par_scatter"`), so they
+/// can be read from configuration files or command lines.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::{RipShuffle, Strategy};
+/// use rand::SeedableRng;
+///
+/// let strategy: Strategy = "seq_merge".parse().unwrap();
+/// let mut data: Vec<_> = (0..1000).collect();
+/// data.shuffle_with(&mut rand_pcg::Pcg64Mcg::seed_from_u64(1), strategy);
+///
+/// assert_eq!(strategy.to_string(), "seq_merge");
+/// assert!("bogo".parse::<Strategy>().is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// [`crate::fisher_yates::fisher_yates`]
+    FisherYates,
+    /// [`crate::scatter_shuffle::sequential::seq_scatter_shuffle`]
+    SeqScatter,
+    /// [`crate::scatter_shuffle::parallel::par_scatter_shuffle`]
+    ParScatter,
+    /// [`crate::merge_shuffle::seq_merge_shuffle`]
+    SeqMerge,
+    /// [`crate::merge_shuffle::par_merge_shuffle`]
+    ParMerge,
+    /// [`RipShuffleParallel::par_shuffle`], which shuffles small inputs and inputs
+    /// in single-threaded pools sequentially.
+    #[default]
+    Auto,
+}
+
+impl Strategy {
+    /// All strategies in the order of their declaration.
+    pub const ALL: [Strategy; 6] = [
+        Strategy::FisherYates,
+        Strategy::SeqScatter,
+        Strategy::ParScatter,
+        Strategy::SeqMerge,
+        Strategy::ParMerge,
+        Strategy::Auto,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::FisherYates => "fisher_yates",
+            Strategy::SeqScatter => "seq_scatter",
+            Strategy::ParScatter => "par_scatter",
+            Strategy::SeqMerge => "seq_merge",
+            Strategy::ParMerge => "par_merge",
+            Strategy::Auto => "auto",
+        }
+    }
+
+    /// Whether the strategy may use several threads of the current rayon pool.
+    pub fn is_parallel(self) -> bool {
+        matches!(
+            self,
+            Strategy::ParScatter | Strategy::ParMerge | Strategy::Auto
+        )
+    }
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = ShuffleError;
+
+    /// Accepts the names of [`Strategy::name`], ignoring surrounding whitespace and case.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.trim();
+        Strategy::ALL
+            .into_iter()
+            .find(|s| s.name().eq_ignore_ascii_case(name))
+            .ok_or(ShuffleError::UnknownStrategy)
+    }
+}
+
+/// A single entry point for all algorithms, selected at runtime by a [`Strategy`].
+/// Since the strategy may be parallel, the random number generator has to meet the
+/// requirements of [`RipShuffleParallel::par_shuffle`].
+pub trait RipShuffle: RipShuffleSequential + RipShuffleParallel {
+    /// Rearranges the input in a random permutation with the algorithm of `strategy`.
+    /// All strategies yield uniform permutations, but they differ in the permutation
+    /// obtained for the same random number generator.
+    fn shuffle_with<R: SeedableRng + Rng + Send + Sync>(&mut self, rng: &mut R, strategy: Strategy);
+}
+
+/// Shuffles `data` sequentially with a cryptographically secure generator
+/// ([`rand::rngs::StdRng`]) that is seeded from the entropy source of the operating
+/// system. This is a short-hand for scripts and tools that need a securely seeded
+/// shuffle, but do not want to deal with random number generators.
+///
+/// # Panics
+/// Panics if the operating system fails to provide entropy.
+///
+/// # Example
+/// ```
+/// let mut data : Vec<_> = (0..100).collect();
+/// rip_shuffle::shuffle_os_random(&mut data);
+/// ```
+pub fn shuffle_os_random<T>(data: &mut [T]) {
+    data.secure_seq_shuffle(&mut rand::rngs::StdRng::from_entropy())
+}
+
+/// Same as [`shuffle_os_random`], but shuffles in parallel (see
+/// [`RipShuffleParallel::secure_par_shuffle`]).
+///
+/// # Example
+/// ```
+/// let mut data : Vec<_> = (0..1_000_000).collect();
+/// rip_shuffle::par_shuffle_os_random(&mut data);
+/// ```
+pub fn par_shuffle_os_random<T: Send>(data: &mut [T]) {
+    data.secure_par_shuffle(&mut rand::rngs::StdRng::from_entropy())
+}
+
+/// Number of elements shuffled with the thread-local generator before it is reseeded
+/// from the operating system (see [`shuffle_thread_local`]).
+#[cfg(feature = "seed_with")]
+pub const THREAD_LOCAL_RESEED_INTERVAL: usize = 1 << 24;
+
+#[cfg(feature = "seed_with")]
+thread_local! {
+    static THREAD_LOCAL_RNG: std::cell::RefCell<Option<(rand_pcg::Pcg64Mcg, usize)>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Returns a generator for shuffling `len` elements that is seeded from the lazily
+/// initialized generator of the current thread. The borrow ends before the shuffle
+/// starts, so Rayon workers may steal other calls in the meantime.
+#[cfg(feature = "seed_with")]
+fn fork_thread_local_rng(len: usize) -> rand_pcg::Pcg64Mcg {
+    THREAD_LOCAL_RNG.with(|cell| {
+        let mut state = cell.borrow_mut();
+
+        let needs_reseed = state
+            .as_ref()
+            .is_none_or(|(_, shuffled)| *shuffled >= THREAD_LOCAL_RESEED_INTERVAL);
+        if needs_reseed {
+            *state = Some((rand_pcg::Pcg64Mcg::from_entropy(), 0));
+        }
+
+        let (rng, shuffled) = state.as_mut().unwrap();
+        *shuffled = shuffled.saturating_add(len);
+        rand_pcg::Pcg64Mcg::from_rng(rng).unwrap()
+    })
+}
+
+/// Shuffles `data` sequentially with a fast generator ([`rand_pcg::Pcg64Mcg`]) that
+/// is kept per thread and reseeded from the entropy source of the operating system
+/// after every [`THREAD_LOCAL_RESEED_INTERVAL`] shuffled elements. This mirrors the
+/// ergonomics of [`rand::thread_rng`]; the generator is not cryptographically
+/// secure, though (see [`shuffle_os_random`]).
+///
+/// # Panics
+/// Panics if the operating system fails to provide entropy.
+///
+/// # Example
+/// ```
+/// let mut data : Vec<_> = (0..100).collect();
+/// rip_shuffle::shuffle_thread_local(&mut data);
+/// ```
+#[cfg(feature = "seed_with")]
+pub fn shuffle_thread_local<T>(data: &mut [T]) {
+    data.seq_shuffle(&mut fork_thread_local_rng(data.len()))
+}
+
+/// Same as [`shuffle_thread_local`], but shuffles in parallel. In contrast to
+/// [`rand::rngs::ThreadRng`], the generator meets the requirements of
+/// [`RipShuffleParallel::par_shuffle`].
+///
+/// # Example
+/// ```
+/// let mut data : Vec<_> = (0..1_000_000).collect();
+/// rip_shuffle::par_shuffle_thread_local(&mut data);
+/// ```
+#[cfg(feature = "seed_with")]
+pub fn par_shuffle_thread_local<T: Send>(data: &mut [T]) {
+    data.par_shuffle(&mut fork_thread_local_rng(data.len()))
+}
+
+/// Shuffles `data` sequentially; the index array of [`indirection`] is only used if
+/// `allow_indirection` is set (and the indirection is applicable).
+fn seq_shuffle_slice<R: Rng, T>(rng: &mut R, data: &mut [T], allow_indirection: bool) {
+    let _telemetry =
+        telemetry::ShuffleGuard::new(telemetry::Mode::Sequential, std::mem::size_of_val(data));
+
+    if allow_indirection && indirection::is_applicable::<T>(data.len()) {
+        return indirection::seq_shuffle_indirect(rng, data);
+    }
+
+    scatter_shuffle::sequential::seq_scatter_shuffle(rng, data)
+}
+
 impl<T> RipShuffleSequential for [T] {
     fn seq_shuffle<R: Rng>(&mut self, rng: &mut R) {
-        scatter_shuffle::sequential::seq_scatter_shuffle(rng, self)
+        seq_shuffle_slice(rng, self, true)
+    }
+
+    fn seq_partial_shuffle<R: Rng>(&mut self, rng: &mut R, k: usize) {
+        partial_shuffle::seq_partial_shuffle(rng, self, k)
     }
 }
 
-impl<T: Send + Sync> RipShuffleParallel for [T] {
+impl<T: Send> RipShuffleParallel for [T] {
     fn par_shuffle<R: SeedableRng + Rng + Send + Sync>(&mut self, rng: &mut R) {
+        let _telemetry =
+            telemetry::ShuffleGuard::new(telemetry::Mode::Parallel, std::mem::size_of_val(self));
+
+        // also holds for nested calls, as rayon reports the pool of the current worker
+        if rayon::current_num_threads() == 1
+            && scatter_shuffle::parallel::num_buckets_for_bytes(std::mem::size_of_val(self))
+                .is_some()
+        {
+            return scatter_shuffle::sequential::seq_scatter_shuffle(rng, self);
+        }
+
         scatter_shuffle::parallel::par_scatter_shuffle(rng, self)
     }
+
+    fn par_partial_shuffle<R: SeedableRng + Rng + Send + Sync>(&mut self, rng: &mut R, k: usize) {
+        partial_shuffle::par_partial_shuffle(rng, self, k)
+    }
+}
+
+impl<T: Send> RipShuffle for [T] {
+    fn shuffle_with<R: SeedableRng + Rng + Send + Sync>(
+        &mut self,
+        rng: &mut R,
+        strategy: Strategy,
+    ) {
+        match strategy {
+            Strategy::FisherYates => fisher_yates::fisher_yates(rng, self),
+            Strategy::SeqScatter => scatter_shuffle::sequential::seq_scatter_shuffle(rng, self),
+            Strategy::ParScatter => scatter_shuffle::parallel::par_scatter_shuffle(rng, self),
+            Strategy::SeqMerge => merge_shuffle::seq_merge_shuffle(rng, self),
+            Strategy::ParMerge => merge_shuffle::par_merge_shuffle(rng, self),
+            Strategy::Auto => self.par_shuffle(rng),
+        }
+    }
+}
+
+/// Fixed-capacity vectors are shuffled as slices, but never via an index array (see
+/// [`indirection`]), i.e., without heap allocations.
+impl<T, const CAP: usize> RipShuffleSequential for arrayvec::ArrayVec<T, CAP> {
+    fn seq_shuffle<R: Rng>(&mut self, rng: &mut R) {
+        seq_shuffle_slice(rng, self.as_mut_slice(), false)
+    }
+
+    fn seq_partial_shuffle<R: Rng>(&mut self, rng: &mut R, k: usize) {
+        self.as_mut_slice().seq_partial_shuffle(rng, k)
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<T, const N: usize> RipShuffleSequential for ::heapless::Vec<T, N> {
+    fn seq_shuffle<R: Rng>(&mut self, rng: &mut R) {
+        seq_shuffle_slice(rng, self.as_mut_slice(), false)
+    }
+
+    fn seq_partial_shuffle<R: Rng>(&mut self, rng: &mut R, k: usize) {
+        self.as_mut_slice().seq_partial_shuffle(rng, k)
+    }
+}
+
+#[cfg(feature = "bitvec")]
+impl<T: ::bitvec::store::BitStore, O: ::bitvec::order::BitOrder> RipShuffleSequential
+    for ::bitvec::slice::BitSlice<T, O>
+where
+    ::bitvec::slice::BitSlice<T, O>: ::bitvec::field::BitField,
+{
+    fn seq_shuffle<R: Rng>(&mut self, rng: &mut R) {
+        bit_slice::shuffle_bit_slice(rng, self)
+    }
+}
+
+#[cfg(all(test, feature = "seed_with"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thread_local_shuffles_preserve_elements() {
+        for n in [0, 1, 100, 100_000] {
+            let mut data: Vec<usize> = (0..n).collect();
+            shuffle_thread_local(&mut data);
+            par_shuffle_thread_local(&mut data);
+            data.sort();
+            assert!(data.iter().enumerate().all(|(i, &x)| i == x), "n={n}");
+        }
+    }
+
+    #[test]
+    fn single_thread_falls_back_to_sequential() {
+        use rand::SeedableRng;
+        use rand_pcg::Pcg64Mcg;
+
+        let single = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let outer = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+
+        // large enough for the parallel algorithm to recurse
+        let mut expected: Vec<u64> = (0..1 << 21).collect();
+        let mut actual = expected.clone();
+        scatter_shuffle::sequential::seq_scatter_shuffle(
+            &mut Pcg64Mcg::seed_from_u64(1),
+            &mut expected,
+        );
+
+        // nested within a pool with multiple threads
+        outer.install(|| single.install(|| actual.par_shuffle(&mut Pcg64Mcg::seed_from_u64(1))));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn shuffles_elements_that_are_not_sync() {
+        use rand::SeedableRng;
+        use rand_pcg::Pcg64Mcg;
+        use std::cell::Cell;
+
+        // records with interior mutability are Send, but not Sync
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let mut data: Vec<(u64, Cell<u64>)> = (0..1 << 21).map(|i| (i, Cell::new(i))).collect();
+        pool.install(|| data.par_shuffle(&mut Pcg64Mcg::seed_from_u64(1)));
+
+        assert!(data[..100].iter().any(|(i, _)| *i >= 100));
+        assert!(data.iter().all(|(i, c)| *i == c.get()));
+        data.sort_by_key(|(i, _)| *i);
+        assert!(data.iter().enumerate().all(|(i, (x, _))| i as u64 == *x));
+
+        par_shuffle_thread_local(&mut data[..1000]);
+    }
+
+    #[test]
+    fn partial_shuffles_preserve_elements() {
+        use rand::SeedableRng;
+        use rand_pcg::Pcg64Mcg;
+
+        let mut rng = Pcg64Mcg::seed_from_u64(3);
+        for (n, k) in [(0, 5), (100, 10), (1 << 20, 1 << 19)] {
+            let mut data: Vec<u32> = (0..n).collect();
+            data.seq_partial_shuffle(&mut rng, k);
+            data.par_partial_shuffle(&mut rng, k);
+            data.sort();
+            assert!(data.iter().copied().eq(0..n), "n={n}");
+        }
+
+        let mut data: arrayvec::ArrayVec<u32, 16> = (0..16).collect();
+        data.seq_partial_shuffle(&mut rng, 4);
+        data.sort();
+        assert!(data.iter().copied().eq(0..16));
+    }
+
+    #[test]
+    fn shuffles_with_each_strategy() {
+        use rand::SeedableRng;
+        use rand_pcg::Pcg64Mcg;
+
+        for strategy in Strategy::ALL {
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+
+            for n in [0, 1, 1000, 1 << 20] {
+                let mut data: Vec<u32> = (0..n).collect();
+                data.shuffle_with(&mut Pcg64Mcg::seed_from_u64(1), strategy);
+
+                if n > 100 {
+                    assert!(data[..100].iter().any(|&x| x >= 100), "{strategy}");
+                }
+                data.sort();
+                assert!(data.iter().copied().eq(0..n), "{strategy} n={n}");
+            }
+        }
+
+        assert_eq!(" Par_Scatter\n".parse(), Ok(Strategy::ParScatter));
+        assert_eq!(
+            "fisher-yates".parse::<Strategy>(),
+            Err(ShuffleError::UnknownStrategy)
+        );
+        assert_eq!(Strategy::default(), Strategy::Auto);
+        assert!(!Strategy::SeqMerge.is_parallel());
+    }
+
+    #[test]
+    fn thread_local_rng_is_reseeded() {
+        // forking twice from the same state yields different generators
+        let a = fork_thread_local_rng(1);
+        let b = fork_thread_local_rng(THREAD_LOCAL_RESEED_INTERVAL);
+        assert_ne!(a, b);
+
+        let shuffled = || THREAD_LOCAL_RNG.with(|cell| cell.borrow().as_ref().unwrap().1);
+        assert!(shuffled() > THREAD_LOCAL_RESEED_INTERVAL);
+        fork_thread_local_rng(10);
+        assert_eq!(shuffled(), 10);
+    }
 }
@@ -0,0 +1,182 @@
+//! An approximate shuffle that trades exact uniformity for speed.
+//!
+//! The scatter shuffle consists of a rough-shuffle pass that moves each element
+//! into a random bucket, followed by exact fix-up phases: the elements left in the
+//! stash are shuffled, bucket sizes are drawn from a multinomial distribution, the
+//! bucket boundaries are moved accordingly, and finally the buckets are shuffled
+//! recursively. [`rough_shuffle_only`] keeps the rough-shuffle pass but replaces
+//! all fix-up phases by a single Fisher-Yates pass over each (fixed-size) bucket.
+//!
+//! # Deviation from a uniform permutation
+//! Relative to the exact algorithm, which executes the identical rough-shuffle
+//! pass, only two things change:
+//!  1. Elements left in the stash are not redistributed and remain in their input
+//!     bucket (they are still shuffled within it). In expectation, the stash contains
+//!     `O(sqrt(B n log B))` of the `n` elements for `B` buckets; we measured a fraction
+//!     of about `1.2 sqrt(B ln(B) / n)`, i.e. roughly 0.3% for `n = 10^8`.
+//!  2. All buckets have equal size, while the exact algorithm samples their sizes.
+//!     This shifts the final position of an element by `O(sqrt(n))`, i.e. by a
+//!     relative distance of `O(1/sqrt(n))`.
+//!
+//! For a single element, both effects are captured by the total variation distance
+//! between its final position and a uniform one: the exact algorithm moves a stashed
+//! element into a uniform bucket, while here it stays in its input bucket. So if the
+//! element is stashed with probability `p`, the distance is `(1 - 1/B) p`. Averaged
+//! over all elements, it is `(1 - 1/B) E[stash] / n`, which is below
+//! `2 sqrt(B ln(B) / n)` for one round (e.g., below 5% for `n = 2^20` and `B = 128`).
+//! Elements at the end of a bucket are stashed most often, so their distance is larger.
+//!
+//! Each further round rough-shuffles the output of the previous one again. An element
+//! stays in its input bucket only if it is left in the stash in every round.
+//! The number of elements stashed in the final round is returned, so callers can
+//! monitor the deviation of individual calls.
+
+use crate::bucketing::*;
+use crate::fisher_yates::fisher_yates;
+use crate::rough_shuffle::*;
+use rand::Rng;
+
+const LOG_NUM_BUCKETS: usize = 7;
+const NUM_BUCKETS: usize = 1 << LOG_NUM_BUCKETS;
+
+/// Inputs of at most this many elements are shuffled exactly with Fisher-Yates.
+pub const EXACT_THRESHOLD: usize = 1 << 16;
+
+/// Rearranges the input in an approximately uniform random permutation by executing
+/// `rounds` rough-shuffle passes, each followed by Fisher-Yates shuffles of the
+/// (fixed-size) buckets. See the [module documentation](self) for the deviation bound.
+/// Returns the number of elements that remained in the stash in the last round.
+///
+/// Inputs with at most [`EXACT_THRESHOLD`] elements (or `rounds == 0`) are shuffled
+/// exactly and zero is returned.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::approximate_shuffle::rough_shuffle_only;
+///
+/// let mut data: Vec<_> = (0..1_000_000).collect();
+/// let num_stashed = rough_shuffle_only(&mut rand::thread_rng(), &mut data, 1);
+///
+/// assert!(num_stashed < data.len() / 10);
+/// ```
+pub fn rough_shuffle_only<R: Rng, T>(rng: &mut R, data: &mut [T], rounds: usize) -> usize {
+    rough_shuffle_only_impl::<R, T, NUM_BUCKETS>(rng, data, rounds, EXACT_THRESHOLD)
+}
+
+fn rough_shuffle_only_impl<R: Rng, T, const NUM_BUCKETS: usize>(
+    rng: &mut R,
+    data: &mut [T],
+    rounds: usize,
+    exact_threshold: usize,
+) -> usize
+where
+    NumberOfBuckets<NUM_BUCKETS>: IsPowerOfTwo,
+{
+    if rounds == 0 || data.len() <= exact_threshold.max(NUM_BUCKETS) {
+        fisher_yates(rng, data);
+        return 0;
+    }
+
+    let mut num_stashed = 0;
+    for _ in 0..rounds {
+        let mut buckets: Buckets<T, NUM_BUCKETS> = split_slice_into_equally_sized_buckets(data);
+        rough_shuffle(rng, &mut buckets);
+
+        num_stashed = buckets.total_unprocessed();
+
+        for bucket in &mut buckets {
+            fisher_yates(rng, bucket.data_mut());
+        }
+    }
+
+    num_stashed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    // The statistical tests for exact shuffles do not apply: for tiny inputs, most
+    // elements end up in the stash and hence cannot leave their input bucket.
+    #[test]
+    fn preserve_elements() {
+        let mut rng = Pcg64Mcg::seed_from_u64(123);
+
+        for rounds in [1, 2] {
+            for n in 0..1000 {
+                let mut data: Vec<_> = (0..n).map(|x| 3 * x).collect();
+                rough_shuffle_only_impl::<_, _, 4>(&mut rng, &mut data, rounds, 0);
+                data.sort();
+
+                assert!(data.iter().enumerate().all(|(i, &x)| 3 * i == x), "n={n}");
+            }
+        }
+    }
+
+    #[test]
+    fn stash_within_bound() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for log_n in [17, 20] {
+            let n = 1usize << log_n;
+            let mut data: Vec<usize> = (0..n).collect();
+
+            let num_stashed = rough_shuffle_only(&mut rng, &mut data, 1);
+
+            let b = NUM_BUCKETS as f64;
+            let bound = (b * b.ln() * n as f64).sqrt();
+            assert!(
+                (num_stashed as f64) < 2.0 * bound,
+                "n={n} stashed={num_stashed}"
+            );
+
+            data.sort();
+            assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+        }
+    }
+
+    #[test]
+    fn displacement_is_uniform_across_buckets() {
+        // an element either ends in its input bucket (if it was stashed) or in a uniform
+        // bucket; this also covers the tail of each bucket, which is mostly stashed
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+        const N: usize = EXACT_THRESHOLD * 2;
+        const RUNS: usize = 20;
+
+        let bucket_len = N / NUM_BUCKETS;
+        let is_tail = |x: usize| x % bucket_len >= bucket_len - bucket_len / 8;
+
+        // number of elements (and of tail elements) per displacement in buckets
+        let mut counts = vec![0usize; NUM_BUCKETS];
+        let mut tail_counts = vec![0usize; NUM_BUCKETS];
+        let mut data: Vec<usize> = vec![0; N];
+
+        for _ in 0..RUNS {
+            data.iter_mut().enumerate().for_each(|(i, x)| *x = i);
+            rough_shuffle_only(&mut rng, &mut data, 1);
+            for (pos, &x) in data.iter().enumerate() {
+                let displacement = (pos / bucket_len + NUM_BUCKETS - x / bucket_len) % NUM_BUCKETS;
+                counts[displacement] += 1;
+                if is_tail(x) {
+                    tail_counts[displacement] += 1;
+                }
+            }
+        }
+
+        for counts in [&counts, &tail_counts] {
+            let expected = counts[1..].iter().sum::<usize>() as f64 / (NUM_BUCKETS - 1) as f64;
+            for (displacement, &count) in counts.iter().enumerate().skip(1) {
+                let dev = (count as f64 - expected).abs() / expected;
+                assert!(dev < 0.2, "displacement={displacement} count={count}");
+            }
+        }
+
+        // the average total variation distance to a uniform position
+        let b = NUM_BUCKETS as f64;
+        let tv = counts[0] as f64 / (RUNS * N) as f64 - 1.0 / b;
+        let bound = 2.0 * (b * b.ln() / N as f64).sqrt();
+        assert!(0.0 < tv && tv < bound, "tv={tv} bound={bound}");
+    }
+}
@@ -0,0 +1,204 @@
+//! Shuffling from asynchronous code (requires the `async` feature).
+//!
+//! Shuffling a large buffer directly in an async task blocks the executor thread for
+//! the whole duration and starves all other tasks scheduled on it. [`par_shuffle_async`]
+//! instead hands the buffer over to the rayon thread pool, where it is shuffled with
+//! [`crate::scatter_shuffle::parallel::par_scatter_shuffle`], and returns a future that
+//! resolves to the shuffled buffer. The future does not depend on a particular
+//! executor; it only relies on the [`std::task::Waker`] passed to it.
+//!
+//! Since the executor may drop the future at any point, the buffer is moved into the
+//! background task rather than borrowed. If the background task panics, polling the
+//! future resumes the panic, so the caller is not left with a future that never
+//! resolves.
+
+use super::scatter_shuffle::parallel::{par_scatter_shuffle, seed_new_rng};
+use rand::{Rng, SeedableRng};
+use std::future::Future;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Starts to shuffle `data` in the rayon thread pool and returns a future that
+/// resolves to the shuffled buffer. The work begins immediately, i.e. even if the
+/// future is not polled. The generator used by the background task is seeded from
+/// `rng`, so the result only depends on `rng`.
+///
+/// # Panics
+/// Polling the future panics if the shuffle panicked in the background task (e.g.,
+/// because the generator panicked); the buffer is lost in this case.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::async_shuffle::par_shuffle_async;
+/// use rand::SeedableRng;
+///
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+///
+/// let data: Vec<u64> = pollster::block_on(async {
+///     let data = (0..1_000_000).collect();
+///     par_shuffle_async(&mut rng, data).await
+/// });
+///
+/// assert_ne!(data[..10], [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+/// ```
+pub fn par_shuffle_async<R, T>(rng: &mut R, mut data: Vec<T>) -> ShuffleFuture<T>
+where
+    R: Rng + SeedableRng + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        result: None,
+        waker: None,
+    }));
+
+    let mut task_rng: R = seed_new_rng(rng);
+    let task_shared = shared.clone();
+    rayon::spawn(move || {
+        // the buffer is not observed after a panic, so unwind safety does not matter
+        let result = catch_unwind(AssertUnwindSafe(move || {
+            par_scatter_shuffle(&mut task_rng, &mut data);
+            data
+        }));
+
+        let mut shared = task_shared.lock().unwrap();
+        shared.result = Some(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    });
+
+    ShuffleFuture { shared }
+}
+
+struct Shared<T> {
+    result: Option<std::thread::Result<Vec<T>>>,
+    waker: Option<Waker>,
+}
+
+/// Future returned by [`par_shuffle_async`].
+pub struct ShuffleFuture<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Future for ShuffleFuture<T> {
+    type Output = Vec<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<T>> {
+        let mut shared = self.shared.lock().unwrap();
+
+        match shared.result.take() {
+            Some(Ok(data)) => Poll::Ready(data),
+            Some(Err(payload)) => {
+                drop(shared);
+                resume_unwind(payload)
+            }
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn matches_seeded_parallel_shuffle() {
+        for n in [0, 1, 1000, 1 << 22] {
+            let mut rng = Pcg64Mcg::seed_from_u64(n as u64);
+            let data: Vec<usize> = (0..n).collect();
+            let shuffled = pollster::block_on(par_shuffle_async(&mut rng, data));
+
+            let mut rng = Pcg64Mcg::seed_from_u64(n as u64);
+            let mut task_rng: Pcg64Mcg = seed_new_rng(&mut rng);
+            let mut expected: Vec<usize> = (0..n).collect();
+            par_scatter_shuffle(&mut task_rng, &mut expected);
+
+            assert_eq!(shuffled, expected);
+        }
+    }
+
+    #[test]
+    fn concurrent_shuffles() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        let futures: Vec<_> = (0..4)
+            .map(|_| par_shuffle_async(&mut rng, (0..100_000).collect::<Vec<u32>>()))
+            .collect();
+
+        let results: Vec<_> = futures.into_iter().map(pollster::block_on).collect();
+        assert_ne!(results[0], results[1]);
+        for mut data in results {
+            data.sort();
+            assert!(data.iter().enumerate().all(|(i, &x)| i as u32 == x));
+        }
+    }
+
+    #[test]
+    fn panic_is_resumed() {
+        // the elements are only moved, so a panic can only originate from the generator;
+        // this one works until it is used to seed another generator, which then panics
+        struct PanickingRng(Option<Pcg64Mcg>);
+
+        impl PanickingRng {
+            fn inner(&mut self) -> &mut Pcg64Mcg {
+                self.0.as_mut().expect("generator failed")
+            }
+        }
+
+        impl rand::RngCore for PanickingRng {
+            fn next_u32(&mut self) -> u32 {
+                self.inner().next_u32()
+            }
+            fn next_u64(&mut self) -> u64 {
+                self.inner().next_u64()
+            }
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                self.inner().fill_bytes(dest)
+            }
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+                self.inner().try_fill_bytes(dest)
+            }
+        }
+
+        impl SeedableRng for PanickingRng {
+            type Seed = [u8; 8];
+            fn from_seed(_seed: Self::Seed) -> Self {
+                PanickingRng(None)
+            }
+        }
+
+        let mut rng = PanickingRng(Some(Pcg64Mcg::seed_from_u64(1)));
+        let future = par_shuffle_async(&mut rng, vec![String::new(); 100]);
+
+        let panic = std::panic::catch_unwind(AssertUnwindSafe(|| pollster::block_on(future)))
+            .expect_err("the shuffle panicked");
+        assert!(panic
+            .downcast_ref::<String>()
+            .unwrap()
+            .contains("generator failed"));
+    }
+
+    #[test]
+    fn dropped_future() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        drop(par_shuffle_async(&mut rng, vec![1, 2, 3]));
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn shuffle_async<R: Rng, T: Clone + Send + Sync + 'static>(rng: &mut R, data: &mut [T]) {
+            let mut task_rng = Pcg64Mcg::seed_from_u64(rng.gen());
+            let shuffled = pollster::block_on(par_shuffle_async(&mut task_rng, data.to_vec()));
+            data.clone_from_slice(&shuffled);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_async);
+    }
+}
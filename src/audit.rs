@@ -0,0 +1,322 @@
+//! Provably fair shuffles with a seed commitment.
+//!
+//! Games and lotteries need to convince third parties that a shuffle was not chosen
+//! by the operator. The usual protocol is:
+//!
+//! 1. The operator draws a secret [`Seed`] and publishes its [`Commitment`] (a SHA-256
+//!    hash) before the round starts.
+//! 2. Optionally, the players contribute a public input (e.g., a client seed or the
+//!    round number) after the commitment was published, so the operator cannot search
+//!    for a favorable seed.
+//! 3. The operator shuffles with [`audited_shuffle`] and, after the round, reveals the
+//!    seed.
+//! 4. Anyone can check with [`verify_shuffle`] that the seed matches the commitment and
+//!    that re-running the shuffle yields the published outcome.
+//!
+//! Re-running a shuffle has to give the same permutation on every platform and with
+//! every future version of this crate. Therefore, [`audited_shuffle`] does not use the
+//! tuned algorithms of this crate, but a frozen Fisher-Yates shuffle whose random bits
+//! are SHA-256 hashes of the seed, the public input, the input length and a counter.
+//! It is identified by [`ALGORITHM_VERSION`]; a change of its output would introduce a
+//! new version. The shuffle is sequential and meant for decks, draws, and other inputs
+//! of moderate size.
+//!
+//! Requires the `audit` feature.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::audit::{audited_shuffle, verify_shuffle, Commitment, Seed};
+//! use rand::rngs::OsRng;
+//!
+//! // operator: draw a seed and publish the commitment
+//! let seed = Seed::generate(&mut OsRng);
+//! let commitment = seed.commit().to_string();
+//!
+//! // operator: shuffle with the client seed and reveal the seed afterwards
+//! let deck: Vec<u8> = (0..52).collect();
+//! let mut shuffled = deck.clone();
+//! audited_shuffle(&seed, b"client seed 42", &mut shuffled);
+//! let revealed = seed.to_string();
+//!
+//! // player: verify the outcome
+//! let commitment: Commitment = commitment.parse().unwrap();
+//! let seed: Seed = revealed.parse().unwrap();
+//! assert!(verify_shuffle(&commitment, &seed, b"client seed 42", &deck, &shuffled).is_ok());
+//! ```
+
+use super::error::ShuffleError;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// Version of the frozen shuffle implemented by [`audited_shuffle`].
+pub const ALGORITHM_VERSION: u32 = 1;
+
+const COMMIT_DOMAIN: &[u8] = b"rip_shuffle audit commitment v1";
+const SHUFFLE_DOMAIN: &[u8] = b"rip_shuffle audit shuffle v1";
+
+/// The secret seed of an audited shuffle. It is displayed as (and parsed from) 64
+/// lower-case hexadecimal digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Seed([u8; 32]);
+
+/// The SHA-256 hash of a [`Seed`], which is published before the shuffle. It is
+/// displayed as (and parsed from) 64 lower-case hexadecimal digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Commitment([u8; 32]);
+
+impl Seed {
+    /// Draws a seed from a cryptographically secure generator.
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut bytes = [0; 32];
+        rng.fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Returns the commitment to this seed.
+    pub fn commit(&self) -> Commitment {
+        let mut hasher = Sha256::new();
+        hasher.update(COMMIT_DOMAIN);
+        hasher.update(self.0);
+        Commitment(hasher.finalize().into())
+    }
+}
+
+impl Commitment {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Returns whether `seed` is the seed this commitment was computed from.
+    pub fn matches(&self, seed: &Seed) -> bool {
+        seed.commit() == *self
+    }
+}
+
+/// Shuffles `data` with the frozen algorithm of version [`ALGORITHM_VERSION`] (see the
+/// [module documentation](self)). The permutation only depends on `seed`,
+/// `public_input`, and `data.len()`.
+pub fn audited_shuffle<T>(seed: &Seed, public_input: &[u8], data: &mut [T]) {
+    let mut rng = AuditRng::new(seed, public_input, data.len());
+
+    for i in (1..data.len()).rev() {
+        let j = rng.gen_index(i as u64 + 1) as usize;
+        data.swap(i, j);
+    }
+}
+
+/// Checks that `seed` matches `commitment` and that [`audited_shuffle`] rearranges
+/// `original` into `shuffled`. Returns [`ShuffleError::AuditFailed`] otherwise.
+pub fn verify_shuffle<T: Clone + PartialEq>(
+    commitment: &Commitment,
+    seed: &Seed,
+    public_input: &[u8],
+    original: &[T],
+    shuffled: &[T],
+) -> Result<(), ShuffleError> {
+    if !commitment.matches(seed) || original.len() != shuffled.len() {
+        return Err(ShuffleError::AuditFailed);
+    }
+
+    let mut expected = original.to_vec();
+    audited_shuffle(seed, public_input, &mut expected);
+
+    if expected != shuffled {
+        return Err(ShuffleError::AuditFailed);
+    }
+
+    Ok(())
+}
+
+/// Counter-mode generator: block `k` is `SHA-256(domain, seed, public input, len, k)`,
+/// which yields four little-endian 64-bit words.
+struct AuditRng {
+    prefix: Sha256,
+    counter: u64,
+    words: [u64; 4],
+    next_word: usize,
+}
+
+impl AuditRng {
+    fn new(seed: &Seed, public_input: &[u8], len: usize) -> Self {
+        let mut prefix = Sha256::new();
+        prefix.update(SHUFFLE_DOMAIN);
+        prefix.update(seed.0);
+        prefix.update((public_input.len() as u64).to_le_bytes());
+        prefix.update(public_input);
+        prefix.update((len as u64).to_le_bytes());
+
+        Self {
+            prefix,
+            counter: 0,
+            words: [0; 4],
+            next_word: 4,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.next_word == 4 {
+            let block = self
+                .prefix
+                .clone()
+                .chain_update(self.counter.to_le_bytes())
+                .finalize();
+            for (word, bytes) in self.words.iter_mut().zip(block.chunks_exact(8)) {
+                *word = u64::from_le_bytes(bytes.try_into().unwrap());
+            }
+            self.counter += 1;
+            self.next_word = 0;
+        }
+
+        self.next_word += 1;
+        self.words[self.next_word - 1]
+    }
+
+    /// Uniform index in `0..ub` via Lemire's multiply-and-reject method.
+    fn gen_index(&mut self, ub: u64) -> u64 {
+        let threshold = ub.wrapping_neg() % ub;
+        loop {
+            let product = self.next_u64() as u128 * ub as u128;
+            if product as u64 >= threshold {
+                return (product >> 64) as u64;
+            }
+        }
+    }
+}
+
+fn fmt_hex(bytes: &[u8; 32], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))
+}
+
+fn parse_hex(s: &str) -> Result<[u8; 32], ShuffleError> {
+    if s.len() != 64 || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ShuffleError::MalformedHex);
+    }
+
+    let mut bytes = [0; 32];
+    for (byte, digits) in bytes.iter_mut().zip(s.as_bytes().chunks_exact(2)) {
+        let digits = std::str::from_utf8(digits).unwrap();
+        *byte = u8::from_str_radix(digits, 16).map_err(|_| ShuffleError::MalformedHex)?;
+    }
+
+    Ok(bytes)
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_hex(&self.0, f)
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_hex(&self.0, f)
+    }
+}
+
+impl FromStr for Seed {
+    type Err = ShuffleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hex(s).map(Self)
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = ShuffleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hex(s).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn output_is_frozen() {
+        let seed = Seed::from_bytes([7; 32]);
+        assert_eq!(
+            seed.commit().to_string(),
+            "76187fd530ba16a85c37cdeda2dd66ade76de35266af9821a76a5bf932742723"
+        );
+
+        let mut data: Vec<u32> = (0..16).collect();
+        audited_shuffle(&seed, b"round 1", &mut data);
+        assert_eq!(data, [14, 6, 4, 5, 15, 2, 12, 3, 9, 13, 11, 7, 1, 10, 0, 8]);
+    }
+
+    #[test]
+    fn verifies_shuffles() {
+        let seed = Seed::from_bytes([1; 32]);
+        let commitment = seed.commit();
+        let original: Vec<u32> = (0..100).collect();
+
+        let mut shuffled = original.clone();
+        audited_shuffle(&seed, b"public", &mut shuffled);
+        assert_ne!(shuffled, original);
+        assert_eq!(
+            verify_shuffle(&commitment, &seed, b"public", &original, &shuffled),
+            Ok(())
+        );
+
+        let other = Seed::from_bytes([2; 32]);
+        let mut tampered = shuffled.clone();
+        tampered.swap(0, 1);
+
+        for result in [
+            verify_shuffle(&commitment, &other, b"public", &original, &shuffled),
+            verify_shuffle(&other.commit(), &other, b"public", &original, &shuffled),
+            verify_shuffle(&commitment, &seed, b"other", &original, &shuffled),
+            verify_shuffle(&commitment, &seed, b"public", &original, &tampered),
+            verify_shuffle(&commitment, &seed, b"public", &original, &shuffled[1..]),
+        ] {
+            assert_eq!(result, Err(ShuffleError::AuditFailed));
+        }
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        let seed = Seed::generate(&mut rand::rngs::OsRng);
+        assert_eq!(seed.to_string().parse(), Ok(seed));
+        assert_eq!(seed.commit().to_string().parse(), Ok(seed.commit()));
+
+        let valid = "ab".repeat(32);
+        assert_eq!(valid.parse::<Seed>().map(|s| *s.as_bytes()), Ok([0xab; 32]));
+
+        for invalid in [
+            "",
+            "ab",
+            &"ab".repeat(33),
+            &"xy".repeat(32),
+            &"+1".repeat(32),
+        ] {
+            assert_eq!(invalid.parse::<Seed>(), Err(ShuffleError::MalformedHex));
+        }
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn audited<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            audited_shuffle(&Seed::from_bytes(rng.gen()), b"", data);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(audited);
+    }
+}
@@ -0,0 +1,284 @@
+//! Shuffling behind an interactive application.
+//!
+//! [`crate::scatter_shuffle::parallel::par_scatter_shuffle`] occupies every thread of the
+//! Rayon pool with large tasks until it is done. An application that shuffles a large
+//! input while it renders frames or serves requests then sees latency spikes of several
+//! milliseconds. [`BackgroundShuffle`] trades throughput for responsiveness:
+//!
+//! - It runs in a dedicated pool that, by default, uses half of the available cores.
+//!   The workers are named `rip_shuffle-background-{i}`, and
+//!   [`BackgroundShuffle::with_start_handler`] runs a caller-supplied hint on each of
+//!   them, e.g., to lower their OS priority.
+//! - Its tasks are small: the input is split into base cases of [`DEFAULT_GRAIN`]
+//!   elements and into several subproblems per thread, so the OS scheduler and Rayon get
+//!   frequent opportunities to interleave other work.
+//! - The base cases call [`std::thread::yield_now`] every [`DEFAULT_YIELD_INTERVAL`]
+//!   swaps.
+//!
+//! The output is a uniform random permutation, but differs from the one of
+//! `par_scatter_shuffle` for the same random number generator.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::background::BackgroundShuffle;
+//! use rand::SeedableRng;
+//!
+//! let background = BackgroundShuffle::new(0).unwrap();
+//! let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1);
+//! let mut data: Vec<u64> = (0..1 << 21).collect();
+//!
+//! std::thread::scope(|s| {
+//!     s.spawn(|| background.shuffle(&mut rng, &mut data));
+//!     // ... the interactive work continues here
+//! });
+//! ```
+
+use super::error::ShuffleError;
+use super::scatter_shuffle::parallel::{
+    num_buckets_for_bytes, ParScatterShuffleImpl, DEFAULT_MAX_SUBPROBLEMS,
+};
+use super::scatter_shuffle::{implement_no_profiler, ParConfiguration, SeqConfiguration};
+use super::session::ShuffleSession;
+use rand::{Rng, SeedableRng};
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+
+/// Default number of elements of a base case of [`BackgroundShuffle`].
+pub const DEFAULT_GRAIN: usize = 1 << 14;
+
+/// Default number of swaps between two yields of [`BackgroundShuffle`].
+pub const DEFAULT_YIELD_INTERVAL: usize = 1 << 12;
+
+/// Number of subproblems per thread the input is split into.
+const SUBPROBLEMS_PER_THREAD: usize = 4;
+
+/// A dedicated thread pool for low-priority shuffles (see the
+/// [module documentation](self)). Building a pool spawns threads, so it should be reused
+/// for repeated shuffles.
+pub struct BackgroundShuffle {
+    pool: ThreadPool,
+    config: BackgroundConfiguration,
+}
+
+impl BackgroundShuffle {
+    /// Builds a pool with `num_threads` workers, or half of the available cores (at least
+    /// one) if `num_threads` is zero.
+    pub fn new(num_threads: usize) -> Result<Self, ThreadPoolBuildError> {
+        Self::build(num_threads, |_| {})
+    }
+
+    /// Same as [`BackgroundShuffle::new`], but runs `hint` on the `i`-th worker as
+    /// `hint(i)` before it executes any work. This is the place to lower the priority of
+    /// the workers with the means of the platform; the hint is best-effort and should not
+    /// panic.
+    pub fn with_start_handler<H>(num_threads: usize, hint: H) -> Result<Self, ThreadPoolBuildError>
+    where
+        H: Fn(usize) + Send + Sync + 'static,
+    {
+        Self::build(num_threads, hint)
+    }
+
+    fn build<H>(num_threads: usize, hint: H) -> Result<Self, ThreadPoolBuildError>
+    where
+        H: Fn(usize) + Send + Sync + 'static,
+    {
+        let num_threads = match num_threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| (n.get() / 2).max(1)),
+            n => n,
+        };
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|i| format!("rip_shuffle-background-{i}"))
+            .start_handler(hint)
+            .build()?;
+
+        Ok(Self {
+            pool,
+            config: BackgroundConfiguration {
+                grain: DEFAULT_GRAIN,
+                yield_interval: DEFAULT_YIELD_INTERVAL,
+            },
+        })
+    }
+
+    /// Sets the number of elements of a base case. Smaller grains reduce the time
+    /// between two scheduling points, but add overhead. Returns
+    /// [`ShuffleError::ZeroBaseCaseSize`] if `grain` is zero.
+    pub fn with_grain(mut self, grain: usize) -> Result<Self, ShuffleError> {
+        if grain == 0 {
+            return Err(ShuffleError::ZeroBaseCaseSize);
+        }
+
+        self.config.grain = grain;
+        Ok(self)
+    }
+
+    /// Sets the number of swaps between two yields; zero disables yielding.
+    pub fn with_yield_interval(mut self, yield_interval: usize) -> Self {
+        self.config.yield_interval = yield_interval;
+        self
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    pub fn grain(&self) -> usize {
+        self.config.grain
+    }
+
+    pub fn yield_interval(&self) -> usize {
+        self.config.yield_interval
+    }
+
+    /// Shuffles `data` in the background pool; blocks the calling thread until done.
+    /// Inputs that [`crate::scatter_shuffle::parallel::par_scatter_shuffle`] shuffles
+    /// sequentially are shuffled by a single worker (with yields).
+    pub fn shuffle<R, T>(&self, rng: &mut R, data: &mut [T])
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        T: Send,
+    {
+        if crate::oracle::is_enabled() {
+            return crate::fisher_yates::naive::fisher_yates(rng, data);
+        }
+
+        let config = self.config;
+        self.pool.install(
+            || match num_buckets_for_bytes(std::mem::size_of_val(data)) {
+                None => config.par_base_case_shuffle(rng, data),
+                Some(64) => ParScatterShuffleImpl::<R, T, _, 64>::new(config).shuffle(rng, data),
+                Some(256) => ParScatterShuffleImpl::<R, T, _, 256>::new(config).shuffle(rng, data),
+                Some(512) => ParScatterShuffleImpl::<R, T, _, 512>::new(config).shuffle(rng, data),
+                Some(_) => ParScatterShuffleImpl::<R, T, _, 1024>::new(config).shuffle(rng, data),
+            },
+        );
+    }
+}
+
+#[derive(Clone, Copy)]
+struct BackgroundConfiguration {
+    grain: usize,
+    yield_interval: usize,
+}
+
+impl SeqConfiguration for BackgroundConfiguration {
+    fn seq_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+        self.par_base_case_shuffle(rng, data)
+    }
+
+    fn seq_base_case_size(&self) -> usize {
+        self.grain
+    }
+}
+
+impl ParConfiguration for BackgroundConfiguration {
+    /// Fisher-Yates that yields every `yield_interval` swaps and after the base case.
+    fn par_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+        if self.yield_interval == 0 {
+            return crate::fisher_yates::fisher_yates(rng, data);
+        }
+
+        let mut session = ShuffleSession::new(data.len());
+        while !session.step(rng, data, self.yield_interval).unwrap() {
+            std::thread::yield_now();
+        }
+        std::thread::yield_now();
+    }
+
+    fn par_base_case_size(&self) -> usize {
+        self.grain
+    }
+
+    fn par_number_of_subproblems(&self, n: usize) -> usize {
+        (n / self.grain)
+            .min(SUBPROBLEMS_PER_THREAD * rayon::current_num_threads())
+            .clamp(1, DEFAULT_MAX_SUBPROBLEMS)
+    }
+
+    implement_no_profiler!();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, OnceLock};
+
+    #[test]
+    fn runs_start_handler() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let counter = started.clone();
+        let background = BackgroundShuffle::with_start_handler(3, move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+
+        assert_eq!(background.num_threads(), 3);
+        background.pool.broadcast(|_| ());
+        assert_eq!(started.load(Ordering::Relaxed), 3);
+
+        let name = background
+            .pool
+            .install(|| std::thread::current().name().map(String::from));
+        assert!(name.unwrap().starts_with("rip_shuffle-background-"));
+
+        assert!(BackgroundShuffle::new(0).unwrap().num_threads() >= 1);
+    }
+
+    #[test]
+    fn options() {
+        let background = BackgroundShuffle::new(1).unwrap();
+        assert_eq!(background.grain(), DEFAULT_GRAIN);
+        assert_eq!(background.yield_interval(), DEFAULT_YIELD_INTERVAL);
+
+        let background = background.with_grain(100).unwrap().with_yield_interval(0);
+        assert_eq!(background.grain(), 100);
+        assert_eq!(background.yield_interval(), 0);
+
+        assert!(matches!(
+            background.with_grain(0),
+            Err(ShuffleError::ZeroBaseCaseSize)
+        ));
+    }
+
+    #[test]
+    fn shuffles_large_inputs() {
+        let background = BackgroundShuffle::new(2).unwrap();
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+
+        for n in [0, 1, 1000, 1 << 21] {
+            let mut data: Vec<u64> = (0..n).collect();
+            background.shuffle(&mut rng, &mut data);
+
+            if n > 100 {
+                assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+            }
+            data.sort();
+            assert!(data.iter().enumerate().all(|(i, &x)| i as u64 == x));
+        }
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn background<R: Rng + SeedableRng + Send + Sync, T: Send>(rng: &mut R, data: &mut [T]) {
+            static POOL: OnceLock<BackgroundShuffle> = OnceLock::new();
+            let background = POOL.get_or_init(|| {
+                BackgroundShuffle::new(2)
+                    .unwrap()
+                    .with_grain(4)
+                    .unwrap()
+                    .with_yield_interval(3)
+            });
+
+            let config = background.config;
+            background
+                .pool
+                .install(|| ParScatterShuffleImpl::<R, T, _, 4>::new(config).shuffle(rng, data))
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(background);
+    }
+}
@@ -0,0 +1,262 @@
+//! Support for random number generators that produce several words at once.
+//!
+//! SIMD generators (e.g., eight interleaved xoshiro or PCG lanes in an AVX-512
+//! register) are fastest if their output is consumed in batches. They can expose
+//! this by implementing [`BatchRng`]. Wrapping such a generator in [`Batched`]
+//! yields an ordinary [`rand::Rng`] that buffers one batch and serves the hot loops
+//! of this crate (e.g., the index generation of the rough shuffle and Fisher-Yates)
+//! from it; the generator is only invoked once per eight words. Generators without
+//! batch support are used directly as before.
+//!
+//! Batching only pays off if producing a word is expensive compared to serving it from
+//! the buffer. For cheap generators such as xorshift128+ the bookkeeping of [`Batched`]
+//! costs more than it saves; `cargo bench --bench batch_rng` compares both on the
+//! machine at hand.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::batch_rng::{Batched, BatchRng};
+//! use rip_shuffle::RipShuffleSequential;
+//! use rand::{RngCore, SeedableRng};
+//! use rand_pcg::Pcg64Mcg;
+//!
+//! // stand-in for a vectorized generator
+//! struct Lanes([Pcg64Mcg; 8]);
+//!
+//! impl RngCore for Lanes {
+//!     fn next_u32(&mut self) -> u32 { self.0[0].next_u32() }
+//!     fn next_u64(&mut self) -> u64 { self.0[0].next_u64() }
+//!     fn fill_bytes(&mut self, dest: &mut [u8]) { self.0[0].fill_bytes(dest) }
+//!     fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+//!         self.0[0].try_fill_bytes(dest)
+//!     }
+//! }
+//!
+//! impl BatchRng for Lanes {
+//!     fn fill_u64x8(&mut self, dest: &mut [u64; 8]) {
+//!         for (d, lane) in dest.iter_mut().zip(self.0.iter_mut()) {
+//!             *d = lane.next_u64();
+//!         }
+//!     }
+//! }
+//!
+//! let mut rng = Batched::new(Lanes(std::array::from_fn(|i| Pcg64Mcg::seed_from_u64(i as u64))));
+//! let mut data: Vec<_> = (0..1000).collect();
+//! data.seq_shuffle(&mut rng);
+//! ```
+
+use rand::{CryptoRng, RngCore, SeedableRng};
+
+const BATCH_SIZE: usize = 8;
+
+/// A random number generator that can produce batches of random words.
+pub trait BatchRng: RngCore {
+    /// Fills `dest` with uniformly random words.
+    fn fill_u64x8(&mut self, dest: &mut [u64; BATCH_SIZE]);
+
+    /// Fills `dest` with indices drawn uniformly at random from `0..ub`.
+    /// The default implementation applies Lemire's multiply-and-reject method to
+    /// both halves of each word obtained from [`BatchRng::fill_u64x8`]; vectorized
+    /// generators may override it with a SIMD variant.
+    ///
+    /// # Warning
+    /// The upper bound must be strictly positive. This is not
+    /// checked in release builds!
+    fn fill_bounded(&mut self, ub: u32, dest: &mut [u32]) {
+        debug_assert!(ub != 0);
+        let threshold = ub.wrapping_neg() % ub;
+
+        let mut batch = [0u64; BATCH_SIZE];
+        let mut num_filled = 0;
+
+        while num_filled < dest.len() {
+            self.fill_u64x8(&mut batch);
+
+            for candidate in batch.iter().flat_map(|&w| [w as u32, (w >> 32) as u32]) {
+                let product = candidate as u64 * ub as u64;
+                if (product as u32) < threshold {
+                    continue;
+                }
+
+                dest[num_filled] = (product >> 32) as u32;
+                num_filled += 1;
+                if num_filled == dest.len() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Adapter that turns a [`BatchRng`] into a [`rand::Rng`] whose words are served
+/// from a buffered batch. See the [module documentation](self) for an example.
+#[derive(Clone, Debug)]
+pub struct Batched<R> {
+    rng: R,
+    buffer: [u64; BATCH_SIZE],
+    next: usize,
+}
+
+impl<R: BatchRng> Batched<R> {
+    pub fn new(rng: R) -> Self {
+        Self {
+            rng,
+            buffer: [0; BATCH_SIZE],
+            next: BATCH_SIZE,
+        }
+    }
+
+    /// Returns the wrapped generator; buffered words are discarded.
+    pub fn into_inner(self) -> R {
+        self.rng
+    }
+
+    /// Forwards to [`BatchRng::fill_bounded`] of the wrapped generator.
+    pub fn fill_bounded(&mut self, ub: u32, dest: &mut [u32]) {
+        self.rng.fill_bounded(ub, dest)
+    }
+}
+
+impl<R: BatchRng> RngCore for Batched<R> {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        if self.next == BATCH_SIZE {
+            self.rng.fill_u64x8(&mut self.buffer);
+            self.next = 0;
+        }
+
+        let word = self.buffer[self.next];
+        self.next += 1;
+        word
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_next_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl<R: BatchRng + SeedableRng> SeedableRng for Batched<R> {
+    type Seed = R::Seed;
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::new(R::from_seed(seed))
+    }
+}
+
+impl<R: BatchRng + CryptoRng> CryptoRng for Batched<R> {}
+
+fn fill_bytes_via_next_u64<R: RngCore>(rng: &mut R, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+        let bytes = rng.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[derive(Clone)]
+    struct CountingBatches {
+        rng: Pcg64Mcg,
+        batches: usize,
+    }
+
+    impl SeedableRng for CountingBatches {
+        type Seed = <Pcg64Mcg as SeedableRng>::Seed;
+
+        fn from_seed(seed: Self::Seed) -> Self {
+            Self {
+                rng: Pcg64Mcg::from_seed(seed),
+                batches: 0,
+            }
+        }
+    }
+
+    impl RngCore for CountingBatches {
+        fn next_u32(&mut self) -> u32 {
+            unreachable!("scalar path must not be used")
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            unreachable!("scalar path must not be used")
+        }
+
+        fn fill_bytes(&mut self, _dest: &mut [u8]) {
+            unreachable!("scalar path must not be used")
+        }
+
+        fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand::Error> {
+            unreachable!("scalar path must not be used")
+        }
+    }
+
+    impl BatchRng for CountingBatches {
+        fn fill_u64x8(&mut self, dest: &mut [u64; BATCH_SIZE]) {
+            self.batches += 1;
+            for d in dest {
+                *d = self.rng.next_u64();
+            }
+        }
+    }
+
+    #[test]
+    fn serves_words_in_batch_order() {
+        let mut reference = Pcg64Mcg::seed_from_u64(1234);
+        let mut batched = Batched::new(CountingBatches::seed_from_u64(1234));
+
+        for _ in 0..100 {
+            assert_eq!(batched.next_u64(), reference.next_u64());
+        }
+
+        assert_eq!(batched.into_inner().batches, 100_usize.div_ceil(BATCH_SIZE));
+    }
+
+    #[test]
+    fn fill_bounded() {
+        let mut rng = CountingBatches::seed_from_u64(12345);
+
+        for ub in [1, 2, 3, 10, 1000, u32::MAX] {
+            for len in [0, 1, 15, 16, 17, 10_000] {
+                let mut indices = vec![u32::MAX; len];
+                rng.fill_bounded(ub, &mut indices);
+                assert!(indices.iter().all(|&i| i < ub));
+            }
+        }
+
+        const RUNS: usize = 10_000;
+        let mut counts = [0usize; 10];
+        let mut indices = vec![0u32; RUNS];
+        Batched::new(rng).fill_bounded(10, &mut indices);
+
+        for &i in &indices {
+            counts[i as usize] += 1;
+        }
+        assert!(counts
+            .iter()
+            .all(|&c| 4 * c > 3 * RUNS / 10 && 4 * c < 5 * RUNS / 10));
+    }
+
+    fn shuffle_batched<R: rand::Rng, T>(rng: &mut R, data: &mut [T]) {
+        let mut batched = Batched::new(CountingBatches::seed_from_u64(rng.gen()));
+        crate::scatter_shuffle::sequential::seq_scatter_shuffle(&mut batched, data);
+    }
+
+    mod scatter_shuffle {
+        use super::shuffle_batched;
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_batched);
+    }
+}
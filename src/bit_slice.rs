@@ -0,0 +1,162 @@
+//! Shuffling of packed bit sequences provided by the `bitvec` crate.
+//!
+//! We use the same approach as [`crate::bitset::shuffle_bits`]: the ones of the
+//! sequence are placed into a uniformly random subset of the positions, which
+//! is done chunk by chunk with word-level operations. Each chunk of 64 bits is built
+//! as a `u64` and written at once via [`BitField::store`], regardless of the storage
+//! type, the bit order, and the alignment of the slice. Hence, there are no random
+//! memory accesses and no `bool` expansion.
+//!
+//! [`BitField`] is implemented for the orders [`Lsb0`] and [`Msb0`], so custom bit
+//! orders are not supported.
+
+use crate::bitset::{random_word_with_ones, sample_ones_in_chunk, WORD_BITS};
+use ::bitvec::field::BitField;
+use ::bitvec::prelude::*;
+use rand::Rng;
+
+/// Rearranges the bits of `bits` in a random permutation, such that any order
+/// appears with equal probability.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::bit_slice::shuffle_bit_slice;
+/// use bitvec::prelude::*;
+///
+/// let mut bits = bitvec![0; 1000];
+/// bits[..10].fill(true);
+///
+/// shuffle_bit_slice(&mut rand::thread_rng(), &mut bits);
+///
+/// assert_eq!(bits.count_ones(), 10);
+/// ```
+pub fn shuffle_bit_slice<R, T, O>(rng: &mut R, bits: &mut BitSlice<T, O>)
+where
+    R: Rng,
+    T: BitStore,
+    O: BitOrder,
+    BitSlice<T, O>: BitField,
+{
+    let mut remaining_bits = bits.len();
+    let mut remaining_ones = bits.count_ones();
+
+    for chunk_begin in (0..bits.len()).step_by(WORD_BITS) {
+        let chunk_len = WORD_BITS.min(remaining_bits);
+        let ones_in_chunk = sample_ones_in_chunk(rng, remaining_bits, remaining_ones, chunk_len);
+
+        // the positions of the word map to the positions of the chunk in a fixed way,
+        // which keeps the arrangement uniform
+        let word = random_word_with_ones(rng, chunk_len, ones_in_chunk);
+        bits[chunk_begin..chunk_begin + chunk_len].store::<u64>(word);
+
+        remaining_bits -= chunk_len;
+        remaining_ones -= ones_in_chunk;
+    }
+
+    debug_assert_eq!(remaining_ones, 0);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn preserve_number_of_ones() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for n in [0usize, 1, 2, 10, 63, 64, 65, 200, 1000] {
+            for k in [0, 1, n / 3, n / 2, n.saturating_sub(1), n] {
+                let k = k.min(n);
+                let mut bits = bitvec![u8, Msb0; 0; n];
+                bits[..k].fill(true);
+
+                shuffle_bit_slice(&mut rng, &mut bits);
+
+                assert_eq!(bits.len(), n);
+                assert_eq!(bits.count_ones(), k, "n={n} k={k}");
+            }
+        }
+    }
+
+    /// Shuffles the unaligned range `HEAD..len - TAIL` of a bit vector whose other bits
+    /// are set, and asserts that they remain set and that each position of the range
+    /// receives a one in some run.
+    fn check_unaligned_range<T, O>()
+    where
+        T: BitStore,
+        O: BitOrder,
+        BitSlice<T, O>: BitField,
+    {
+        const HEAD: usize = 3;
+        const TAIL: usize = 5;
+        const RUNS: usize = 200;
+
+        let mut rng = Pcg64Mcg::seed_from_u64(2345);
+
+        for n in [1, 7, 64, 100, 129] {
+            let k = n / 4 + 1;
+            let mut seen = vec![false; n];
+
+            for _ in 0..RUNS {
+                let mut bits = BitVec::<T, O>::repeat(true, HEAD + n + TAIL);
+                bits[HEAD + k..HEAD + n].fill(false);
+
+                shuffle_bit_slice(&mut rng, &mut bits[HEAD..HEAD + n]);
+
+                assert!(bits[..HEAD].all() && bits[HEAD + n..].all(), "n={n}");
+                assert_eq!(bits[HEAD..HEAD + n].count_ones(), k, "n={n}");
+                for i in bits[HEAD..HEAD + n].iter_ones() {
+                    seen[i] = true;
+                }
+            }
+
+            assert!(seen.iter().all(|&s| s), "n={n} {seen:?}");
+        }
+    }
+
+    #[test]
+    fn lsb0_unaligned() {
+        check_unaligned_range::<u8, Lsb0>();
+        check_unaligned_range::<u16, Lsb0>();
+        check_unaligned_range::<u32, Lsb0>();
+        check_unaligned_range::<u64, Lsb0>();
+    }
+
+    #[test]
+    fn msb0_unaligned() {
+        check_unaligned_range::<u8, Msb0>();
+        check_unaligned_range::<u16, Msb0>();
+        check_unaligned_range::<u32, Msb0>();
+        check_unaligned_range::<usize, Msb0>();
+    }
+
+    #[test]
+    fn chunks_spanning_several_elements_are_uniform() {
+        // three ones among six bits starting at bit 6 of a `u8`, so each chunk spans two
+        // elements; there are binomial(6, 3) = 20 arrangements
+        const RUNS: u32 = 20_000;
+
+        let mut rng = Pcg64Mcg::seed_from_u64(3456);
+        let mut counts = [0u32; 64];
+
+        for _ in 0..RUNS {
+            let mut bits = bitvec![u8, Msb0; 0; 16];
+            bits[6..9].fill(true);
+            shuffle_bit_slice(&mut rng, &mut bits[6..12]);
+            counts[bits[6..12].load_be::<usize>()] += 1;
+        }
+
+        for (pattern, &count) in counts.iter().enumerate() {
+            if pattern.count_ones() == 3 {
+                assert!(
+                    count.abs_diff(RUNS / 20) < RUNS / 80,
+                    "{pattern:06b}: {count}"
+                );
+            } else {
+                assert_eq!(count, 0);
+            }
+        }
+    }
+}
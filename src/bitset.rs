@@ -0,0 +1,264 @@
+//! Shuffling of packed bitsets stored as `u64` words.
+//!
+//! Since all ones (and all zeros) of a bit sequence are indistinguishable, a
+//! uniform random permutation of the sequence is obtained by placing its `k`
+//! ones into a uniformly random `k`-subset of the positions. We process the
+//! input word by word: the number of ones in each word is drawn from the
+//! hypergeometric distribution, and then a random word with exactly that many
+//! ones is produced with rank/select operations within the word. Hence, there
+//! are no random memory accesses at all.
+
+use super::random_bits::RandomBitsSource;
+use rand::Rng;
+use rand_distr::{Distribution, Hypergeometric};
+
+pub(crate) const WORD_BITS: usize = u64::BITS as usize;
+
+/// Uniformly permutes the `num_bits` least significant bits of the bitset
+/// `words`. Bit `i` of the bitset is stored in bit `i % 64` of `words[i / 64]`.
+/// Bits at positions `num_bits` and beyond remain untouched.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::bitset::shuffle_bits;
+///
+/// let mut words = vec![0u64; 16];
+/// words[0] = 0xff;
+///
+/// shuffle_bits(&mut rand::thread_rng(), &mut words, 1000);
+///
+/// assert_eq!(words.iter().map(|w| w.count_ones()).sum::<u32>(), 8);
+/// ```
+pub fn shuffle_bits<R: Rng>(rng: &mut R, words: &mut [u64], num_bits: usize) {
+    assert!(num_bits <= words.len() * WORD_BITS);
+
+    let words = &mut words[..num_bits.div_ceil(WORD_BITS)];
+
+    let mut remaining_bits = num_bits;
+    let mut remaining_ones: usize = words
+        .iter()
+        .enumerate()
+        .map(|(i, &w)| (w & valid_bits(num_bits - i * WORD_BITS)).count_ones() as usize)
+        .sum();
+
+    for word in words {
+        let bits_in_word = WORD_BITS.min(remaining_bits);
+        let ones_in_word = sample_ones_in_chunk(rng, remaining_bits, remaining_ones, bits_in_word);
+
+        let untouched = *word & !valid_bits(bits_in_word);
+        *word = untouched | random_word_with_ones(rng, bits_in_word, ones_in_word);
+
+        remaining_bits -= bits_in_word;
+        remaining_ones -= ones_in_word;
+    }
+
+    debug_assert_eq!(remaining_ones, 0);
+}
+
+/// Number of ones that fall into the next `chunk_len` positions if `remaining_ones`
+/// ones are distributed uniformly over `remaining_bits` positions.
+pub(crate) fn sample_ones_in_chunk<R: Rng>(
+    rng: &mut R,
+    remaining_bits: usize,
+    remaining_ones: usize,
+    chunk_len: usize,
+) -> usize {
+    if remaining_ones == 0 || chunk_len == remaining_bits {
+        return remaining_ones;
+    }
+
+    if remaining_ones == remaining_bits {
+        return chunk_len;
+    }
+
+    Hypergeometric::new(
+        remaining_bits as u64,
+        remaining_ones as u64,
+        chunk_len as u64,
+    )
+    .unwrap()
+    .sample(rng) as usize
+}
+
+/// Returns a word whose `num_ones` set bits form a uniformly random subset of
+/// the `num_bits` least significant positions.
+pub(crate) fn random_word_with_ones<R: Rng>(rng: &mut R, num_bits: usize, num_ones: usize) -> u64 {
+    debug_assert!(num_bits <= WORD_BITS && num_ones <= num_bits);
+    let valid = valid_bits(num_bits);
+
+    // We start with a word whose distribution is invariant under permutation of
+    // the bit positions and then set/clear uniformly chosen bits until the target
+    // is met. Each step preserves the invariance, so the result is uniform amongst
+    // all words with `num_ones` ones. The start is chosen to minimize the corrections.
+    let mut word = if 4 * num_ones < num_bits {
+        0
+    } else if 4 * num_ones > 3 * num_bits {
+        valid
+    } else {
+        rng.gen::<u64>() & valid
+    };
+
+    let mut ones = word.count_ones() as usize;
+
+    // the bounds are at most 64, so we draw them from cached bits
+    let mut rbs = RandomBitsSource::new();
+
+    while ones > num_ones {
+        word ^= 1 << select_in_word(word, rbs.gen_below(rng, ones));
+        ones -= 1;
+    }
+
+    while ones < num_ones {
+        let zeros = !word & valid;
+        word |= 1 << select_in_word(zeros, rbs.gen_below(rng, num_bits - ones));
+        ones += 1;
+    }
+
+    word
+}
+
+/// Returns the position of the `rank`-th (zero-based) set bit of `word`.
+#[inline]
+pub(crate) fn select_in_word(word: u64, rank: usize) -> u32 {
+    debug_assert!(rank < word.count_ones() as usize);
+
+    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    {
+        // safe since the target feature is enabled at compile time
+        unsafe { std::arch::x86_64::_pdep_u64(1 << rank, word).trailing_zeros() }
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "bmi2")))]
+    {
+        let mut word = word;
+        for _ in 0..rank {
+            word &= word - 1;
+        }
+        word.trailing_zeros()
+    }
+}
+
+#[inline]
+fn valid_bits(num_bits: usize) -> u64 {
+    if num_bits >= WORD_BITS {
+        u64::MAX
+    } else {
+        (1 << num_bits) - 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn select_in_word() {
+        let mut rng = Pcg64Mcg::seed_from_u64(123);
+        for _ in 0..1000 {
+            let word: u64 = rng.gen();
+            let positions: Vec<u32> = (0..64).filter(|i| (word >> i) & 1 == 1).collect();
+            for (rank, &pos) in positions.iter().enumerate() {
+                assert_eq!(super::select_in_word(word, rank), pos);
+            }
+        }
+    }
+
+    #[test]
+    fn random_word_with_ones() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+        for num_bits in [1, 2, 7, 31, 63, 64] {
+            for num_ones in 0..=num_bits {
+                let word = super::random_word_with_ones(&mut rng, num_bits, num_ones);
+                assert_eq!(word.count_ones() as usize, num_ones);
+                assert_eq!(word & !valid_bits(num_bits), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn preserve_number_of_ones() {
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+
+        for num_bits in [0usize, 1, 2, 10, 63, 64, 65, 200, 1000] {
+            let mut words = vec![0u64; num_bits.div_ceil(WORD_BITS) + 1];
+            rng.fill(words.as_mut_slice());
+            let before = words.clone();
+
+            shuffle_bits(&mut rng, &mut words, num_bits);
+
+            let ones = |ws: &[u64]| -> usize {
+                (0..num_bits)
+                    .filter(|i| (ws[i / WORD_BITS] >> (i % WORD_BITS)) & 1 == 1)
+                    .count()
+            };
+            assert_eq!(ones(&words), ones(&before), "num_bits={num_bits}");
+
+            // bits beyond `num_bits` are untouched
+            let first_untouched = num_bits / WORD_BITS;
+            let untouched_mask = !valid_bits(num_bits % WORD_BITS);
+            assert_eq!(
+                words[first_untouched] & untouched_mask,
+                before[first_untouched] & untouched_mask
+            );
+            assert_eq!(words[first_untouched + 1..], before[first_untouched + 1..]);
+        }
+    }
+
+    #[test]
+    fn all_arrangements_appear() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2345);
+
+        // there are binomial(6, 3) = 20 arrangements of three ones among six bits
+        let mut counts = [0u32; 64];
+        const RUNS: u32 = 20_000;
+
+        for _ in 0..RUNS {
+            let mut words = [0b111u64];
+            shuffle_bits(&mut rng, &mut words, 6);
+            counts[words[0] as usize] += 1;
+        }
+
+        for (pattern, &count) in counts.iter().enumerate() {
+            if pattern.count_ones() == 3 {
+                assert!(count > RUNS / 20 * 3 / 4, "pattern={pattern:06b}");
+                assert!(count < RUNS / 20 * 5 / 4, "pattern={pattern:06b}");
+            } else {
+                assert_eq!(count, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn ones_spread_over_all_positions() {
+        let mut rng = Pcg64Mcg::seed_from_u64(34567);
+
+        for num_bits in [65, 130, 300] {
+            const RUNS: usize = 2000;
+            let k = num_bits / 4;
+            let mut counts = vec![0usize; num_bits];
+
+            for _ in 0..RUNS {
+                let mut words = vec![0u64; num_bits.div_ceil(WORD_BITS)];
+                for i in 0..k {
+                    words[i / WORD_BITS] |= 1 << (i % WORD_BITS);
+                }
+
+                shuffle_bits(&mut rng, &mut words, num_bits);
+
+                for (i, count) in counts.iter_mut().enumerate() {
+                    *count += ((words[i / WORD_BITS] >> (i % WORD_BITS)) & 1) as usize;
+                }
+            }
+
+            let expected = RUNS * k / num_bits;
+            for (i, &count) in counts.iter().enumerate() {
+                assert!(
+                    2 * count > expected && count < 2 * expected,
+                    "num_bits={num_bits} i={i}"
+                );
+            }
+        }
+    }
+}
@@ -15,6 +15,16 @@ impl<'a, T> Default for Bucket<'a, T> {
     }
 }
 
+/// Shows the length and the number of processed elements, but not the elements.
+impl<T> std::fmt::Debug for Bucket<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bucket")
+            .field("len", &self.len())
+            .field("num_processed", &self.num_processed)
+            .finish()
+    }
+}
+
 impl<'a, T> Bucket<'a, T> {
     pub fn new(data: &'a mut [T]) -> Self {
         Self {
@@ -102,6 +112,10 @@ impl<'a, T> Bucket<'a, T> {
         self.data
     }
 
+    pub fn into_data(self) -> &'a mut [T] {
+        self.data
+    }
+
     pub fn data_unprocessed_mut(&mut self) -> &mut [T] {
         &mut self.data[self.num_processed..]
     }
@@ -169,15 +183,29 @@ impl<'a, T> Bucket<'a, T> {
     }
 
     pub fn split_in_half(&mut self) -> Self {
-        let num_half = self.len() / 2;
+        self.split_off(self.len() / 2)
+    }
 
+    /// Splits the bucket into its first `mid` elements and the remainder. The processed
+    /// prefix is split accordingly, so the left part only has unprocessed elements if
+    /// the right part has no processed ones.
+    ///
+    /// # Panics
+    /// If `mid > self.len()`.
+    pub fn split_at(mut self, mid: usize) -> (Self, Self) {
+        let right = self.split_off(mid);
+        (self, right)
+    }
+
+    /// Keeps the first `at` elements and returns the remainder as a new bucket.
+    pub fn split_off(&mut self, at: usize) -> Self {
         let data = std::mem::take(&mut self.data);
-        let (left, right) = data.split_at_mut(num_half);
+        let (left, right) = data.split_at_mut(at);
 
-        let right_processed = self.num_processed.saturating_sub(num_half);
+        let right_processed = self.num_processed.saturating_sub(at);
 
         self.data = left;
-        self.num_processed = self.num_processed.min(num_half);
+        self.num_processed = self.num_processed.min(at);
 
         Self {
             data: right,
@@ -298,6 +326,20 @@ mod test {
         }
     }
 
+    #[test]
+    fn split_at() {
+        let mut data: Vec<_> = (0..10).collect();
+        let mut bucket = Bucket::new(&mut data);
+        bucket.set_num_processed(6);
+
+        let (left, right) = bucket.split_at(4);
+        assert_eq!(left.data(), [0, 1, 2, 3]);
+        assert_eq!(right.data(), [4, 5, 6, 7, 8, 9]);
+        assert!(left.is_fully_processed());
+        assert_eq!(right.num_processed(), 2);
+        assert!(left.is_left_neighbor_of(&right));
+    }
+
     #[test]
     fn len() {
         for i in 0..4 {
@@ -331,7 +373,6 @@ mod test {
     fn data() {
         fn ref_vec(len: usize, processed: usize) -> Vec<usize> {
             (1..=processed + 1)
-                .into_iter()
                 .chain(std::iter::repeat(0))
                 .take(len)
                 .collect()
@@ -1,13 +1,14 @@
 #![allow(dead_code)]
 
 use super::bucket::Bucket;
+use super::slicing::Slicing;
 use arrayvec::ArrayVec;
 
 pub type Buckets<'a, T, const N: usize> = ArrayVec<Bucket<'a, T>, N>;
 
 pub fn split_slice_into_equally_sized_buckets<T, const N: usize>(
     mut data: &mut [T],
-) -> Buckets<T, N> {
+) -> Buckets<'_, T, N> {
     let total_len = data.len();
     let mut buckets = ArrayVec::new();
 
@@ -39,6 +40,242 @@ pub fn split_each_bucket_in_half<'a, T, const N: usize>(
         .collect()
 }
 
+/// Conceptually splits each bucket into `num_parts` parts of (almost) equal size,
+/// keeps the first `num_left_parts` of them in `buckets`, and returns the others.
+/// Applied recursively, this yields exactly `num_parts` subproblems of equal size
+/// (rather than a power of two), without allocating the parts up front. The parts may
+/// also be units of weight, which splits the buckets in proportion to the weights.
+pub fn split_each_bucket_into<'a, T, const N: usize>(
+    buckets: &mut Buckets<'a, T, N>,
+    num_parts: usize,
+    num_left_parts: usize,
+) -> Buckets<'a, T, N> {
+    assert!(0 < num_left_parts && num_left_parts < num_parts);
+
+    buckets
+        .iter_mut()
+        .map(|left| {
+            let len = left.len() as u128 * num_left_parts as u128 / num_parts as u128;
+            left.split_off(len as usize)
+        })
+        .collect()
+}
+
+/// Splits `data` into consecutive unprocessed buckets with the given lengths.
+///
+/// # Panics
+/// If there are more than `N` lengths or they do not add up to `data.len()`.
+pub fn split_slice_into_buckets_of_len<'a, T, const N: usize>(
+    mut data: &'a mut [T],
+    lengths: &[usize],
+) -> Buckets<'a, T, N> {
+    assert!(lengths.len() <= N);
+    assert_eq!(lengths.iter().sum::<usize>(), data.len());
+
+    let mut buckets = ArrayVec::new();
+    for &len in lengths {
+        let bucket_data;
+        (bucket_data, data) = std::mem::take(&mut data).split_at_mut(len);
+        buckets.push(Bucket::new(bucket_data));
+    }
+
+    buckets
+}
+
+/// Re-splits a sequence of adjacent buckets into buckets of the given lengths. The
+/// elements stay in place; all resulting buckets are unprocessed, since the processed
+/// prefixes of the input do not align with the new boundaries in general.
+///
+/// # Panics
+/// If the buckets are not adjacent, if there are more than `M` lengths, or if the
+/// lengths do not add up to the total length of the buckets.
+pub fn rechunk<'a, T, const N: usize, const M: usize>(
+    buckets: Buckets<'a, T, N>,
+    target_lengths: &[usize],
+) -> Buckets<'a, T, M> {
+    split_slice_into_buckets_of_len(concat_buckets(buckets), target_lengths)
+}
+
+/// Returns the concatenation of a sequence of adjacent buckets, ignoring which of
+/// their elements were processed (in contrast to [`compact_into_single_bucket`],
+/// no elements are moved).
+///
+/// # Panics
+/// If the buckets are not adjacent.
+pub fn concat_buckets<'a, T, const N: usize>(buckets: Buckets<'a, T, N>) -> &'a mut [T] {
+    buckets
+        .into_iter()
+        .map(Bucket::into_data)
+        .reduce(|left, right| left.merge_with_right_neighbor(right))
+        .unwrap_or_default()
+}
+
+/// Returns the slices of the buckets.
+pub fn buckets_into_slices<'a, T, const N: usize>(
+    buckets: Buckets<'a, T, N>,
+) -> ArrayVec<&'a mut [T], N> {
+    buckets.into_iter().map(Bucket::into_data).collect()
+}
+
+/// Wraps each slice into an unprocessed bucket.
+///
+/// # Panics
+/// If there are more than `N` slices.
+pub fn buckets_from_slices<'a, T, const N: usize>(
+    slices: impl IntoIterator<Item = &'a mut [T]>,
+) -> Buckets<'a, T, N> {
+    slices.into_iter().map(Bucket::new).collect()
+}
+
+/// Moves the buckets into a sequence of a different capacity, keeping their state.
+///
+/// # Panics
+/// If there are more than `M` buckets.
+pub fn convert_capacity<'a, T, const N: usize, const M: usize>(
+    buckets: Buckets<'a, T, N>,
+) -> Buckets<'a, T, M> {
+    buckets.into_iter().collect()
+}
+
+/// Lengths of a sequence of buckets, see [`BucketSlice::len_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LenStats {
+    pub min: usize,
+    pub max: usize,
+    pub total: usize,
+}
+
+/// Statistics and iteration helpers for sequences of buckets. Implemented for slices,
+/// so they apply to [`Buckets`] as well.
+pub trait BucketSlice<T> {
+    /// Number of unprocessed elements (i.e., the size of the stashes) in all buckets.
+    fn total_unprocessed(&self) -> usize;
+
+    /// Iterates over the unprocessed part of each bucket.
+    fn iter_unprocessed_slices<'s>(&'s self) -> impl Iterator<Item = &'s [T]>
+    where
+        T: 's;
+
+    /// Smallest, largest and total length of the buckets; all zero if there are none.
+    fn len_stats(&self) -> LenStats;
+
+    /// Ratio of the largest to the average bucket length; `1.0` for perfectly balanced
+    /// (or empty) buckets.
+    fn imbalance(&self) -> f64;
+
+    /// Renders the layout of the buckets as an ASCII diagram of about `width` columns,
+    /// followed by one line per bucket. In the diagram, `|` marks a bucket boundary, `#`
+    /// processed elements, and `.` the stash (i.e., the unprocessed suffix); each
+    /// non-empty region occupies at least one column. Positions are offsets from the
+    /// start of the first bucket, assuming the buckets are adjacent.
+    ///
+    /// ```text
+    /// |####..|##....|######|
+    /// bucket      start        len  processed  stash
+    ///      0          0          6          4  4..6
+    ///      1          6          6          2  8..12
+    ///      2         12          6          6  -
+    /// ```
+    fn dump(&self, width: usize) -> String;
+}
+
+impl<T> BucketSlice<T> for [Bucket<'_, T>] {
+    fn total_unprocessed(&self) -> usize {
+        self.iter().map(|b| b.num_unprocessed()).sum()
+    }
+
+    fn iter_unprocessed_slices<'s>(&'s self) -> impl Iterator<Item = &'s [T]>
+    where
+        T: 's,
+    {
+        self.iter().map(|b| b.data_unprocessed())
+    }
+
+    fn len_stats(&self) -> LenStats {
+        let mut lens = self.iter().map(|b| b.len());
+        let Some(first) = lens.next() else {
+            return LenStats::default();
+        };
+
+        lens.fold(
+            LenStats {
+                min: first,
+                max: first,
+                total: first,
+            },
+            |stats, len| LenStats {
+                min: stats.min.min(len),
+                max: stats.max.max(len),
+                total: stats.total + len,
+            },
+        )
+    }
+
+    fn imbalance(&self) -> f64 {
+        let stats = self.len_stats();
+        if stats.total == 0 {
+            return 1.0;
+        }
+
+        stats.max as f64 * self.len() as f64 / stats.total as f64
+    }
+
+    fn dump(&self, width: usize) -> String {
+        use std::fmt::Write;
+
+        let total = self.len_stats().total.max(1);
+        let mut diagram = String::from("|");
+        for bucket in self {
+            let (len, processed) = (bucket.len(), bucket.num_processed());
+            if len == 0 {
+                diagram.push('|');
+                continue;
+            }
+
+            // a processed prefix and a stash occupy at least one column each
+            let min_columns = usize::from(processed > 0) + usize::from(processed < len);
+            let columns = (len * width).div_ceil(total).max(min_columns);
+            let processed_columns = ((processed * columns + len / 2) / len).clamp(
+                usize::from(processed > 0),
+                columns - usize::from(processed < len),
+            );
+
+            diagram.extend(std::iter::repeat_n('#', processed_columns));
+            diagram.extend(std::iter::repeat_n('.', columns - processed_columns));
+            diagram.push('|');
+        }
+
+        writeln!(diagram).unwrap();
+        writeln!(
+            diagram,
+            "{:>6} {:>10} {:>10} {:>10}  stash",
+            "bucket", "start", "len", "processed"
+        )
+        .unwrap();
+
+        let mut start = 0;
+        for (i, bucket) in self.iter().enumerate() {
+            write!(
+                diagram,
+                "{i:>6} {start:>10} {:>10} {:>10}  ",
+                bucket.len(),
+                bucket.num_processed()
+            )
+            .unwrap();
+
+            if bucket.num_unprocessed() == 0 {
+                writeln!(diagram, "-").unwrap();
+            } else {
+                let end = start + bucket.len();
+                writeln!(diagram, "{}..{end}", start + bucket.num_processed()).unwrap();
+            }
+            start += bucket.len();
+        }
+
+        diagram
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -46,7 +283,7 @@ mod test {
 
     #[test]
     fn split_slice_into_buckets() {
-        let mut data: Vec<_> = (0..8).into_iter().collect();
+        let mut data: Vec<_> = (0..8).collect();
 
         let buckets: Buckets<_, 2> = super::split_slice_into_equally_sized_buckets(&mut data);
         assert_eq!(buckets.len(), 2);
@@ -54,6 +291,107 @@ mod test {
         assert_eq!(buckets.as_slice()[1].len(), 4);
     }
 
+    #[test]
+    fn split_each_bucket_into() {
+        let mut data: Vec<_> = (0..24).collect();
+        let mut buckets: Buckets<_, 2> = split_slice_into_equally_sized_buckets(&mut data);
+
+        let mut right = super::split_each_bucket_into(&mut buckets, 3, 1);
+        let lens = |b: &Buckets<_, 2>| b.iter().map(|b| b.len()).collect_vec();
+        assert_eq!(lens(&buckets), [4, 4]);
+        assert_eq!(lens(&right), [8, 8]);
+
+        let rightmost = super::split_each_bucket_into(&mut right, 2, 1);
+        assert_eq!(lens(&right), [4, 4]);
+        assert_eq!(lens(&rightmost), [4, 4]);
+        assert_eq!(rightmost[1].data(), [20, 21, 22, 23]);
+    }
+
+    #[test]
+    fn rechunk() {
+        let mut data: Vec<_> = (0..10).collect();
+        let mut buckets: Buckets<_, 4> = split_slice_into_equally_sized_buckets(&mut data);
+        buckets[0].process_element();
+
+        let rechunked: Buckets<_, 3> = super::rechunk(buckets, &[0, 7, 3]);
+        let lens = rechunked.iter().map(|b| b.len()).collect_vec();
+        assert_eq!(lens, [0, 7, 3]);
+        assert_eq!(rechunked.total_unprocessed(), 10);
+        assert_eq!(rechunked[2].data(), [7, 8, 9]);
+
+        let slices = buckets_into_slices(convert_capacity::<_, 3, 8>(rechunked));
+        assert_eq!(slices[1], [0, 1, 2, 3, 4, 5, 6]);
+
+        let buckets: Buckets<_, 3> = buckets_from_slices(slices);
+        assert_eq!(concat_buckets(buckets), (0..10).collect_vec());
+    }
+
+    #[test]
+    #[should_panic]
+    fn rechunk_rejects_wrong_total() {
+        let mut data: Vec<_> = (0..10).collect();
+        let buckets: Buckets<_, 2> = split_slice_into_equally_sized_buckets(&mut data);
+        let _: Buckets<_, 2> = super::rechunk(buckets, &[5, 4]);
+    }
+
+    #[test]
+    fn bucket_slice_statistics() {
+        let mut data: Vec<_> = (0..10).collect();
+        let (left, right) = data.split_at_mut(2);
+        let mut buckets: Buckets<_, 2> = [Bucket::new(left), Bucket::new(right)].into();
+        buckets[1].process_element();
+
+        assert_eq!(buckets.total_unprocessed(), 9);
+        assert_eq!(
+            buckets.iter_unprocessed_slices().collect_vec(),
+            [&[0, 1][..], &[3, 4, 5, 6, 7, 8, 9][..]]
+        );
+        assert_eq!(
+            buckets.len_stats(),
+            LenStats {
+                min: 2,
+                max: 8,
+                total: 10
+            }
+        );
+        assert_eq!(buckets.imbalance(), 1.6);
+
+        let empty: Buckets<usize, 2> = Buckets::new();
+        assert_eq!(empty.len_stats(), LenStats::default());
+        assert_eq!(empty.imbalance(), 1.0);
+    }
+
+    #[test]
+    fn dump() {
+        let mut data: Vec<_> = (0..18).collect();
+        let mut buckets: Buckets<_, 4> = split_slice_into_buckets_of_len(&mut data, &[6, 6, 6]);
+        for (bucket, processed) in buckets.iter_mut().zip([4, 2, 6]) {
+            bucket.set_num_processed(processed);
+        }
+
+        assert_eq!(
+            buckets.dump(18),
+            "|####..|##....|######|\n\
+             bucket      start        len  processed  stash\n\
+             \x20    0          0          6          4  4..6\n\
+             \x20    1          6          6          2  8..12\n\
+             \x20    2         12          6          6  -\n"
+        );
+        assert_eq!(
+            format!("{:?}", buckets[1]),
+            "Bucket { len: 6, num_processed: 2 }"
+        );
+
+        // tiny regions remain visible, empty buckets are a plain boundary
+        let mut data: Vec<_> = (0..1002).collect();
+        let mut buckets: Buckets<_, 4> = split_slice_into_buckets_of_len(&mut data, &[1000, 0, 2]);
+        buckets[0].set_num_processed(999);
+        buckets[2].set_num_processed(1);
+        let diagram = buckets.dump(10);
+        assert_eq!(diagram.lines().next(), Some("|#########.||#.|"));
+        assert_eq!(diagram.lines().count(), 5);
+    }
+
     #[test]
     fn compact_into_single_bucket() {
         for (((n0, n1), n2), n3) in (0..3)
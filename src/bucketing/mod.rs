@@ -1,3 +1,8 @@
+//! Buckets, i.e., contiguous subslices of the input whose prefix has been processed,
+//! as used by the rough shuffles of the scatter shuffle. The helpers in [`buckets`]
+//! split, merge, and re-chunk sequences of adjacent buckets, so custom (parallel)
+//! schedules can be assembled without pointer arithmetic.
+
 pub mod bucket;
 pub mod buckets;
 pub mod slicing;
@@ -62,11 +62,11 @@ macro_rules! slicing_impl {
     };
 }
 
-impl<'a, T> Slicing for &'a mut [T] {
+impl<T> Slicing for &mut [T] {
     slicing_impl!(as_mut_ptr, split_at_mut, std::slice::from_raw_parts_mut);
 }
 
-impl<'a, T> Slicing for &'a [T] {
+impl<T> Slicing for &[T] {
     slicing_impl!(as_ptr, split_at, std::slice::from_raw_parts);
 }
 
@@ -83,10 +83,7 @@ mod test {
         for len_pref in 0..N {
             let len_suffix = N - len_pref;
 
-            let data: Vec<_> = (0..N)
-                .into_iter()
-                .map(|i| (i < len_pref) as usize)
-                .collect();
+            let data: Vec<_> = (0..N).map(|i| (i < len_pref) as usize).collect();
 
             assert_eq!(data.as_slice().prefix(len_pref).len(), len_pref);
             assert!(data.as_slice().prefix(len_pref).iter().all(|x| *x == 1));
@@ -136,7 +133,7 @@ mod test {
     #[test]
     fn give_to_right_neighbor() {
         for total_len in [1, 2, 3, 10] {
-            let data: Vec<_> = (0..total_len).into_iter().collect();
+            let data: Vec<_> = (0..total_len).collect();
             for left_len in 0..total_len {
                 let right_len = total_len - left_len;
 
@@ -160,7 +157,7 @@ mod test {
     #[test]
     fn give_to_left_neighbor() {
         for total_len in [1, 2, 3, 10] {
-            let data: Vec<_> = (0..total_len).into_iter().collect();
+            let data: Vec<_> = (0..total_len).collect();
             for left_len in 0..total_len {
                 let right_len = total_len - left_len;
 
@@ -264,7 +261,7 @@ mod test_mut {
     #[test]
     fn partial_merge_with_right_neighbor() {
         for total_len in [1, 2, 3, 10] {
-            let mut data: Vec<_> = (0..total_len).into_iter().collect();
+            let mut data: Vec<_> = (0..total_len).collect();
             for left_len in 0..total_len {
                 let right_len = total_len - left_len;
 
@@ -291,7 +288,7 @@ mod test_mut {
     #[test]
     fn give_to_left_neighbor() {
         for total_len in [1, 2, 3, 10] {
-            let mut data: Vec<_> = (0..total_len).into_iter().collect();
+            let mut data: Vec<_> = (0..total_len).collect();
             for left_len in 0..total_len {
                 let right_len = total_len - left_len;
 
@@ -0,0 +1,223 @@
+//! Random sampling without replacement that leaves the input untouched.
+//!
+//! [`choose_multiple`] returns references to `k` distinct elements of a slice, drawn
+//! uniformly at random and in uniformly random order, similar to
+//! [`rand::seq::SliceRandom::choose_multiple`]; [`sample_indices`] returns their
+//! positions instead.
+//!
+//! We draw a uniform `k`-subset with Floyd's algorithm (Bentley and Floyd, "A sample of
+//! brilliance", CACM 1987), which needs exactly `k` random indices. The subset is kept
+//! in a bitset with one bit per position, which is much faster than a hash set unless
+//! the population is huge compared to the sample; in that case, we fall back to a hash
+//! set. Very small samples are searched linearly. As Floyd's algorithm does not
+//! produce the sample in random order, we finally shuffle it with
+//! [`crate::scatter_shuffle::sequential::seq_scatter_shuffle`].
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::choose::choose_multiple;
+//!
+//! let deck: Vec<u32> = (0..52).collect();
+//! let hand = choose_multiple(&mut rand::thread_rng(), &deck, 5);
+//!
+//! assert_eq!(hand.len(), 5);
+//! assert_eq!(deck, (0..52).collect::<Vec<_>>());
+//! ```
+
+use super::error::ShuffleError;
+use super::scatter_shuffle::sequential::seq_scatter_shuffle;
+use super::uniform_index::gen_index;
+use rand::Rng;
+use std::collections::HashSet;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Samples of at most this many elements are kept in a vector and searched linearly.
+const MAX_LINEAR_SEARCH: usize = 32;
+
+/// Populations with more than this many bitset words per sampled element use a hash
+/// set instead of a bitset.
+const MAX_WORDS_PER_SAMPLE: usize = 64;
+
+/// Returns references to `k` elements of `data` drawn uniformly without replacement,
+/// in uniformly random order. `data` is not modified.
+///
+/// # Panics
+/// Panics if `k > data.len()`; see [`try_choose_multiple`] for a non-panicking variant.
+pub fn choose_multiple<'a, R: Rng, T>(rng: &mut R, data: &'a [T], k: usize) -> Vec<&'a T> {
+    try_choose_multiple(rng, data, k).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`choose_multiple`], but returns [`ShuffleError::SampleTooLarge`] instead of
+/// panicking if `k > data.len()`.
+pub fn try_choose_multiple<'a, R: Rng, T>(
+    rng: &mut R,
+    data: &'a [T],
+    k: usize,
+) -> Result<Vec<&'a T>, ShuffleError> {
+    let indices = try_sample_indices(rng, data.len(), k)?;
+    Ok(indices.into_iter().map(|i| &data[i]).collect())
+}
+
+/// Returns `k` indices drawn uniformly without replacement from `0..n`, in uniformly
+/// random order.
+///
+/// # Panics
+/// Panics if `k > n`; see [`try_sample_indices`] for a non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::choose::sample_indices;
+///
+/// let mut sample = sample_indices(&mut rand::thread_rng(), 1_000_000, 1000);
+///
+/// sample.sort();
+/// sample.dedup();
+/// assert_eq!(sample.len(), 1000);
+/// ```
+pub fn sample_indices<R: Rng>(rng: &mut R, n: usize, k: usize) -> Vec<usize> {
+    try_sample_indices(rng, n, k).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`sample_indices`], but returns [`ShuffleError::SampleTooLarge`] instead of
+/// panicking if `k > n`.
+pub fn try_sample_indices<R: Rng>(
+    rng: &mut R,
+    n: usize,
+    k: usize,
+) -> Result<Vec<usize>, ShuffleError> {
+    if k > n {
+        return Err(ShuffleError::SampleTooLarge);
+    }
+
+    let mut sample = Vec::with_capacity(k);
+    let num_words = n.div_ceil(WORD_BITS);
+
+    if k <= MAX_LINEAR_SEARCH {
+        let mut chosen = Vec::with_capacity(k);
+        floyd(rng, n, k, &mut sample, |i| {
+            let contained = chosen.contains(&i);
+            chosen.push(i);
+            contained
+        });
+    } else if num_words > MAX_WORDS_PER_SAMPLE * k {
+        let mut chosen = HashSet::with_capacity(k);
+        floyd(rng, n, k, &mut sample, |i| !chosen.insert(i));
+    } else {
+        let mut chosen = vec![0u64; num_words];
+        floyd(rng, n, k, &mut sample, |i| {
+            let (word, bit) = (i / WORD_BITS, 1 << (i % WORD_BITS));
+            let contained = chosen[word] & bit != 0;
+            chosen[word] |= bit;
+            contained
+        });
+    }
+
+    seq_scatter_shuffle(rng, &mut sample);
+    Ok(sample)
+}
+
+/// Pushes a uniform `k`-subset of `0..n` to `sample` with Floyd's algorithm;
+/// `insert_contains` adds an index to the subset and returns whether it was already
+/// contained.
+fn floyd<R: Rng>(
+    rng: &mut R,
+    n: usize,
+    k: usize,
+    sample: &mut Vec<usize>,
+    mut insert_contains: impl FnMut(usize) -> bool,
+) {
+    for j in n - k..n {
+        let i = gen_index(rng, j + 1);
+
+        // if `i` was chosen before, `j` was not, since all earlier indices are below `j`
+        let chosen = if insert_contains(i) {
+            insert_contains(j);
+            j
+        } else {
+            i
+        };
+        sample.push(chosen);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn samples_are_distinct() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        for (n, k) in [
+            (0, 0),
+            (1, 1),
+            (10, 0),
+            (10, 10),
+            (1000, 999),
+            (1 << 20, 10),
+            (1 << 20, 100),
+        ] {
+            let mut sample = sample_indices(&mut rng, n, k);
+            assert_eq!(sample.len(), k);
+
+            sample.sort();
+            sample.dedup();
+            assert_eq!(sample.len(), k, "n={n} k={k}");
+            assert!(sample.iter().all(|&i| i < n));
+        }
+    }
+
+    #[test]
+    fn too_many_samples_is_error() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        assert_eq!(
+            try_choose_multiple(&mut rng, &[1, 2, 3], 4),
+            Err(ShuffleError::SampleTooLarge)
+        );
+    }
+
+    #[test]
+    fn ordered_samples_are_uniform() {
+        // the 5 * 4 = 20 ordered pairs of distinct elements of `0..5` are equally likely
+        // with each way of storing the subset
+        const RUNS: usize = 40_000;
+        let mut rng = Pcg64Mcg::seed_from_u64(3);
+
+        for variant in ["linear", "bitset", "hash set"] {
+            let mut counts = [[0usize; 5]; 5];
+            for _ in 0..RUNS {
+                let mut sample = Vec::new();
+                let mut vec = Vec::new();
+                let mut words = [0u64];
+                let mut set = HashSet::new();
+                floyd(&mut rng, 5, 2, &mut sample, |i| match variant {
+                    "linear" => {
+                        let contained = vec.contains(&i);
+                        vec.push(i);
+                        contained
+                    }
+                    "bitset" => {
+                        let contained = words[0] >> i & 1 == 1;
+                        words[0] |= 1 << i;
+                        contained
+                    }
+                    _ => !set.insert(i),
+                });
+                seq_scatter_shuffle(&mut rng, &mut sample);
+                counts[sample[0]][sample[1]] += 1;
+            }
+
+            for (first, row) in counts.iter().enumerate() {
+                for (second, &c) in row.iter().enumerate() {
+                    if first == second {
+                        assert_eq!(c, 0);
+                    } else {
+                        assert!(c.abs_diff(RUNS / 20) < RUNS / 200, "{variant}: {counts:?}");
+                    }
+                }
+            }
+        }
+    }
+}
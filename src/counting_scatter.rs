@@ -0,0 +1,265 @@
+//! Out-of-place parallel shuffle for callers that can provide a scratch buffer.
+//!
+//! The in-place rough shuffle of [`par_scatter_shuffle`] moves each element through
+//! stashes and needs several rounds to balance the buckets. If a scratch buffer as
+//! large as the input is available, the elements can instead be scattered directly to
+//! their final buckets, as in a counting sort:
+//!
+//! 1. The input is split into chunks. Each chunk draws a uniform bucket for each of its
+//!    elements and counts the elements per bucket; this pass only draws random bits.
+//! 2. The prefix sums of the counts (in bucket-major order) assign each chunk a
+//!    contiguous range in each bucket of the scratch buffer.
+//! 3. Each chunk draws the same buckets again and copies its elements to their ranges.
+//! 4. Each bucket is copied back into the input and shuffled with
+//!    [`fisher_yates`] while it is still in cache.
+//!
+//! Every element reaches an independent and uniform bucket, and each bucket is shuffled
+//! uniformly, so the output is a uniform random permutation. Each element is copied
+//! twice before its bucket is shuffled in cache, and the random bits of the buckets are
+//! drawn twice. In contrast to the in-place algorithm, there are no rounds over the
+//! stashes, which is meant for large arrays of plain-old-data on many threads; on a
+//! single thread, the in-place algorithm is faster. Inputs of at most
+//! [`SEQUENTIAL_MAX_BYTES`] bytes are shuffled in place with [`par_scatter_shuffle`]
+//! without touching the scratch buffer.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::counting_scatter::par_counting_scatter_shuffle;
+//! use rand::SeedableRng;
+//!
+//! let mut data: Vec<u64> = (0..1 << 22).collect();
+//! let mut scratch = vec![0; data.len()];
+//! par_counting_scatter_shuffle(&mut rand_pcg::Pcg64Mcg::seed_from_u64(1), &mut data, &mut scratch);
+//!
+//! assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+//! ```
+
+use super::error::ShuffleError;
+use super::fisher_yates::fisher_yates;
+use super::scatter_shuffle::parallel::{par_scatter_shuffle, seed_new_rng, SEQUENTIAL_MAX_BYTES};
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Buckets are chosen to hold about this many bytes, so they fit into the L2/L3 cache
+/// when they are shuffled.
+const TARGET_BUCKET_BYTES: usize = 1 << 20;
+
+/// Upper bound on the binary logarithm of the number of buckets; each chunk writes to
+/// all buckets concurrently, so more buckets would thrash the TLB.
+const MAX_LOG_BUCKETS: usize = 10;
+
+/// Number of chunks per thread of the rayon pool, which balances the load of the
+/// passes over the chunks.
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Shuffles `data` in parallel using `scratch` as temporary storage (see the
+/// [module documentation](self)). The contents of `scratch` are unspecified afterwards.
+///
+/// # Panics
+/// If `scratch` is shorter than `data`; see [`try_par_counting_scatter_shuffle`] for a
+/// non-panicking variant.
+pub fn par_counting_scatter_shuffle<R, T>(rng: &mut R, data: &mut [T], scratch: &mut [T])
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Copy + Send + Sync,
+{
+    try_par_counting_scatter_shuffle(rng, data, scratch).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`par_counting_scatter_shuffle`], but returns
+/// [`ShuffleError::ScratchTooSmall`] instead of panicking (leaving `data` untouched).
+pub fn try_par_counting_scatter_shuffle<R, T>(
+    rng: &mut R,
+    data: &mut [T],
+    scratch: &mut [T],
+) -> Result<(), ShuffleError>
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Copy + Send + Sync,
+{
+    if scratch.len() < data.len() {
+        return Err(ShuffleError::ScratchTooSmall);
+    }
+
+    let num_bytes = std::mem::size_of_val(data);
+    if num_bytes <= SEQUENTIAL_MAX_BYTES || crate::oracle::is_enabled() {
+        par_scatter_shuffle(rng, data);
+        return Ok(());
+    }
+
+    let num_buckets = num_bytes.div_ceil(TARGET_BUCKET_BYTES).next_power_of_two();
+    let log_buckets = (num_buckets.ilog2() as usize).clamp(1, MAX_LOG_BUCKETS);
+    let num_chunks = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+
+    counting_scatter_shuffle_impl(
+        rng,
+        data,
+        &mut scratch[..data.len()],
+        log_buckets,
+        num_chunks,
+    );
+    Ok(())
+}
+
+fn counting_scatter_shuffle_impl<R, T>(
+    rng: &mut R,
+    data: &mut [T],
+    scratch: &mut [T],
+    log_buckets: usize,
+    num_chunks: usize,
+) where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Copy + Send + Sync,
+{
+    let n = data.len();
+    let num_buckets = 1 << log_buckets;
+    let chunk_len = n.div_ceil(num_chunks.max(1)).max(1);
+
+    // both passes of a chunk need to draw the same buckets, so we create two identical
+    // generators for each chunk
+    let mut chunk_rngs: Vec<(R, R)> = (0..n.div_ceil(chunk_len))
+        .map(|_| {
+            let mut seed = R::Seed::default();
+            rng.fill_bytes(seed.as_mut());
+            let mut copy = R::Seed::default();
+            copy.as_mut().copy_from_slice(seed.as_mut());
+            (R::from_seed(seed), R::from_seed(copy))
+        })
+        .collect();
+
+    let bucket_rngs: Vec<R> = (0..num_buckets).map(|_| seed_new_rng(rng)).collect();
+
+    // pass 1: count the elements of each chunk per bucket
+    let counts: Vec<Vec<usize>> = chunk_rngs
+        .par_iter_mut()
+        .zip(data.par_chunks(chunk_len))
+        .map(|((rng, _), chunk)| {
+            let mut counts = vec![0; num_buckets];
+            for_each_bucket(rng, chunk.len(), log_buckets, |b| counts[b] += 1);
+            counts
+        })
+        .collect();
+
+    // assign each chunk a range in each bucket
+    let mut bucket_lens = vec![0; num_buckets];
+    let mut targets: Vec<Vec<&mut [T]>> = (0..counts.len())
+        .map(|_| Vec::with_capacity(num_buckets))
+        .collect();
+
+    let mut rest = &mut *scratch;
+    for (b, len) in bucket_lens.iter_mut().enumerate() {
+        for (counts, targets) in counts.iter().zip(targets.iter_mut()) {
+            let (range, tail) = std::mem::take(&mut rest).split_at_mut(counts[b]);
+            targets.push(range);
+            rest = tail;
+            *len += counts[b];
+        }
+    }
+
+    // pass 2: copy each element into its bucket
+    chunk_rngs
+        .par_iter_mut()
+        .zip(data.par_chunks(chunk_len))
+        .zip(targets.par_iter_mut())
+        .for_each(|(((_, rng), chunk), targets)| {
+            let mut cursors = vec![0; num_buckets];
+            let mut elements = chunk.iter();
+            for_each_bucket(rng, chunk.len(), log_buckets, |b| {
+                targets[b][cursors[b]] = *elements.next().unwrap();
+                cursors[b] += 1;
+            });
+        });
+
+    // pass 3: copy the buckets back and shuffle them
+    let mut data_buckets = Vec::with_capacity(num_buckets);
+    let mut scratch_buckets = Vec::with_capacity(num_buckets);
+    let (mut data_rest, mut scratch_rest) = (data, scratch);
+    for len in bucket_lens {
+        let (bucket, tail) = std::mem::take(&mut data_rest).split_at_mut(len);
+        data_buckets.push(bucket);
+        data_rest = tail;
+
+        let (bucket, tail) = std::mem::take(&mut scratch_rest).split_at_mut(len);
+        scratch_buckets.push(bucket);
+        scratch_rest = tail;
+    }
+
+    data_buckets
+        .into_par_iter()
+        .zip(scratch_buckets)
+        .zip(bucket_rngs)
+        .for_each(|((data, scratch), mut rng)| {
+            data.copy_from_slice(scratch);
+            fisher_yates(&mut rng, data);
+        });
+}
+
+/// Calls `f` with `len` uniform bucket indices below `2^log_buckets`; several indices
+/// are taken from each random word.
+fn for_each_bucket<R: Rng>(rng: &mut R, len: usize, log_buckets: usize, mut f: impl FnMut(usize)) {
+    let per_word = 64 / log_buckets;
+    let mask = (1 << log_buckets) - 1;
+
+    let mut remaining = len;
+    while remaining > 0 {
+        let mut word: u64 = rng.gen();
+        for _ in 0..per_word.min(remaining) {
+            f(word as usize & mask);
+            word >>= log_buckets;
+        }
+        remaining -= per_word.min(remaining);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn shuffles_large_inputs() {
+        let n = 2 * SEQUENTIAL_MAX_BYTES / 8;
+        let mut data: Vec<u64> = (0..n as u64).collect();
+        let mut scratch = vec![0; n + 10];
+
+        par_counting_scatter_shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut data, &mut scratch);
+        assert!(data
+            .iter()
+            .take(100)
+            .enumerate()
+            .any(|(i, &x)| i as u64 != x));
+
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i as u64 == x));
+    }
+
+    #[test]
+    fn rejects_small_scratch() {
+        let mut data = [1, 2, 3];
+        assert_eq!(
+            try_par_counting_scatter_shuffle(
+                &mut Pcg64Mcg::seed_from_u64(1),
+                &mut data,
+                &mut [0; 2]
+            ),
+            Err(ShuffleError::ScratchTooSmall)
+        );
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    mod statistical {
+        use super::*;
+
+        /// Uses four buckets and three chunks regardless of the input size
+        fn counting_scatter<R: Rng + SeedableRng + Send + Sync, T: Copy + Send + Sync>(
+            rng: &mut R,
+            data: &mut [T],
+        ) {
+            let mut scratch = data.to_vec();
+            counting_scatter_shuffle_impl(rng, data, &mut scratch, 2, 3);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(counting_scatter);
+        crate::statistical_tests::test_shuffle_algorithm_deterministic!(counting_scatter);
+    }
+}
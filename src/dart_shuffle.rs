@@ -0,0 +1,267 @@
+//! Parallel shuffling by dart throwing.
+//!
+//! Each element throws darts at random slots of an over-provisioned array of
+//! `OVERPROVISIONING * n` slots until it hits a free one; the order of the occupied
+//! slots then yields the permutation. All elements throw in parallel, and the array is
+//! compacted in parallel. In contrast to the scatter and merge shuffles, there is no
+//! bucket machinery, which makes this algorithm attractive for medium-sized inputs
+//! on many threads. It needs plenty of auxiliary memory, though: while the darts are
+//! thrown, up to `40 * n` bytes (`16 * n` for the slots, `16 * n` for the darts of a
+//! round, and `4 * n` each for the elements throwing in this and the next round).
+//! Afterwards, the resulting order takes `4 * n` bytes; with the unsafe algorithms
+//! (see [`crate::unsafe_algos`]), the elements are additionally moved through a buffer
+//! of `n * size_of::<T>()` bytes.
+//!
+//! The darts are thrown in rounds. If several elements hit the same free slot in one
+//! round, the one with the highest random priority takes it and the others throw
+//! again. As the rule does not depend on the elements, each permutation is equally
+//! likely (up to ties of the 31-bit priorities, which are broken by position). The
+//! output only depends on the random number generator, not on the number of threads.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::dart_shuffle::par_dart_shuffle;
+//! use rand::SeedableRng;
+//!
+//! let mut data: Vec<u64> = (0..1_000_000).collect();
+//! par_dart_shuffle(&mut rand_pcg::Pcg64Mcg::seed_from_u64(1234), &mut data);
+//!
+//! assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+//! ```
+
+use super::fisher_yates::{fisher_yates, naive};
+use super::indirection::apply_in_place;
+use super::scatter_shuffle::parallel::{par_scatter_shuffle, seed_new_rng};
+use super::uniform_index::gen_index;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Inputs of at most this many elements are shuffled sequentially with Fisher-Yates.
+pub const BASE_CASE_SIZE: usize = 1 << 12;
+
+/// Number of slots per element.
+const OVERPROVISIONING: usize = 2;
+
+/// Darts of this many elements are thrown with the same random number generator.
+const CHUNK_SIZE: usize = 1 << 12;
+
+/// Marks a slot that was taken in an earlier round. Priorities only use the 31 bits
+/// below, so a final slot is never overwritten.
+const FINAL: u64 = 1 << 63;
+
+/// Shuffles `data` in parallel by dart throwing (see the [module documentation](self)).
+/// Inputs with `2^32` or more elements are shuffled with
+/// [`crate::scatter_shuffle::parallel::par_scatter_shuffle`] instead.
+pub fn par_dart_shuffle<R, T>(rng: &mut R, data: &mut [T])
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send,
+{
+    if crate::oracle::is_enabled() {
+        return naive::fisher_yates(rng, data);
+    }
+
+    dart_shuffle_impl(rng, data, BASE_CASE_SIZE)
+}
+
+fn dart_shuffle_impl<R, T>(rng: &mut R, data: &mut [T], base_case_size: usize)
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send,
+{
+    if data.len() <= base_case_size.max(1) {
+        return fisher_yates(rng, data);
+    }
+
+    if u32::try_from(data.len()).is_err() {
+        crate::telemetry::record_fallback(crate::telemetry::Fallback::DartToScatter);
+        return par_scatter_shuffle(rng, data);
+    }
+
+    let mut order = throw_darts(rng, data.len());
+
+    #[cfg(feature = "unsafe_algos")]
+    if crate::unsafe_algos::is_enabled() {
+        return unsafe_algos::par_gather(data, &order);
+    }
+
+    apply_in_place(data, &mut order);
+}
+
+/// Returns the positions of the input in the order in which they appear in the output.
+fn throw_darts<R>(rng: &mut R, n: usize) -> Vec<u32>
+where
+    R: Rng + SeedableRng + Send + Sync,
+{
+    let num_slots = OVERPROVISIONING * n;
+    let slots: Vec<AtomicU64> = (0..num_slots)
+        .into_par_iter()
+        .map(|_| AtomicU64::new(0))
+        .collect();
+
+    let mut pending: Vec<u32> = (0..n as u32).collect();
+
+    while !pending.is_empty() {
+        // derive the generators sequentially, so the result does not depend on the threads
+        let mut rngs: Vec<R> = (0..pending.len().div_ceil(CHUNK_SIZE))
+            .map(|_| seed_new_rng(rng))
+            .collect();
+
+        let throws: Vec<(usize, u64)> = pending
+            .par_chunks(CHUNK_SIZE)
+            .zip(rngs.par_iter_mut())
+            .flat_map_iter(|(chunk, rng)| {
+                chunk.iter().map(|&elem| {
+                    let slot = gen_index(rng, num_slots);
+                    let priority = (rng.gen::<u32>() >> 1) as u64;
+                    let dart = (priority << 32) | (elem as u64 + 1);
+                    slots[slot].fetch_max(dart, Ordering::Relaxed);
+                    (slot, dart)
+                })
+            })
+            .collect();
+
+        // all darts of the round have landed; the winners finalize their slots
+        pending = throws
+            .par_iter()
+            .filter_map(|&(slot, dart)| {
+                if slots[slot].load(Ordering::Relaxed) == dart {
+                    slots[slot].store(dart | FINAL, Ordering::Relaxed);
+                    None
+                } else {
+                    Some((dart as u32) - 1)
+                }
+            })
+            .collect();
+    }
+
+    slots
+        .into_par_iter()
+        .filter_map(|slot| {
+            let slot = slot.into_inner();
+            (slot != 0).then(|| slot as u32 - 1)
+        })
+        .collect()
+}
+
+#[cfg(feature = "unsafe_algos")]
+mod unsafe_algos {
+    use rayon::prelude::*;
+
+    struct SharedPtr<T>(*const T);
+
+    // SAFETY: the pointer is only used to read distinct elements from several threads
+    unsafe impl<T: Send> Sync for SharedPtr<T> {}
+
+    /// Same as [`crate::indirection::apply_in_place`], but moves the elements in parallel through
+    /// a buffer.
+    pub(super) fn par_gather<T: Send>(data: &mut [T], order: &[u32]) {
+        const CHUNK_SIZE: usize = 1 << 14;
+
+        let mut buffer: Vec<T> = Vec::with_capacity(data.len());
+        let source = SharedPtr(data.as_ptr());
+
+        buffer.spare_capacity_mut()[..data.len()]
+            .par_iter_mut()
+            .zip(order.par_iter())
+            .for_each(|(target, &i)| {
+                let source = &source;
+                // SAFETY: `order` is a permutation, so each element is read exactly once
+                target.write(unsafe { source.0.add(i as usize).read() });
+            });
+
+        data.par_chunks_mut(CHUNK_SIZE)
+            .zip(buffer.spare_capacity_mut().par_chunks_mut(CHUNK_SIZE))
+            .for_each(|(target, source)| {
+                // SAFETY: all elements of the buffer were initialized above and are moved
+                // back exactly once; the buffer never owns them (its length remains 0)
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        source.as_ptr() as *const T,
+                        target.as_mut_ptr(),
+                        target.len(),
+                    )
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn orders_are_permutations() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        for n in [2, 3, 100, 10_000, 100_000] {
+            let mut order = throw_darts(&mut rng, n);
+            order.sort();
+            assert!(
+                order.iter().enumerate().all(|(i, &x)| i == x as usize),
+                "n={n}"
+            );
+        }
+    }
+
+    #[test]
+    fn apply_in_place_matches_gather() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        let data: Vec<usize> = (0..10_000).map(|x| 7 * x).collect();
+        let order = throw_darts(&mut rng, data.len());
+
+        let expected: Vec<usize> = order.iter().map(|&i| data[i as usize]).collect();
+
+        let mut actual = data.clone();
+        apply_in_place(&mut actual, &mut order.clone());
+        assert_eq!(actual, expected);
+
+        #[cfg(feature = "unsafe_algos")]
+        {
+            let mut actual = data.clone();
+            unsafe_algos::par_gather(&mut actual, &order);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn independent_of_threads() {
+        let mut reference: Vec<u32> = (0..100_000).collect();
+        par_dart_shuffle(&mut Pcg64Mcg::seed_from_u64(3), &mut reference);
+
+        for num_threads in [1, 3] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap();
+
+            let mut data: Vec<u32> = (0..100_000).collect();
+            pool.install(|| par_dart_shuffle(&mut Pcg64Mcg::seed_from_u64(3), &mut data));
+            assert_eq!(data, reference, "num_threads={num_threads}");
+        }
+    }
+
+    #[test]
+    fn drops_each_element_once() {
+        let mut rng = Pcg64Mcg::seed_from_u64(4);
+        let mut data: Vec<String> = (0..20_000).map(|x| x.to_string()).collect();
+        par_dart_shuffle(&mut rng, &mut data);
+
+        data.sort_by_key(|x| x.parse::<usize>().unwrap());
+        assert!(data.iter().enumerate().all(|(i, x)| *x == i.to_string()));
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn shuffle_without_base_case<R: Rng + SeedableRng + Send + Sync, T: Send>(
+            rng: &mut R,
+            data: &mut [T],
+        ) {
+            dart_shuffle_impl(rng, data, 1)
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_without_base_case);
+    }
+}
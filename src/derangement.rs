@@ -0,0 +1,188 @@
+//! Uniformly random derangements, i.e., permutations without fixed points.
+//!
+//! Rejection sampling (shuffling until no element stays at its position) needs `e`
+//! shuffles in expectation, and restarting as soon as a fixed point is placed (as
+//! [`crate::restricted`] does) still discards a constant fraction of the work. We
+//! instead implement the algorithm of Martínez, Panholzer, and Prodinger ("Generating
+//! random derangements", ANALCO 2008), which never restarts: it runs a Fisher-Yates
+//! shuffle from the back that occasionally closes a cycle, with the probability of
+//! doing so chosen such that every derangement is equally likely. It draws about `2n`
+//! random numbers and needs `n` bits of auxiliary memory to mark closed cycles.
+//!
+//! The probabilities are computed with floating-point arithmetic, so the distribution
+//! is uniform up to rounding errors in the order of `2^-53`.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::derangement::seq_derangement;
+//!
+//! // everybody draws somebody else in the gift exchange
+//! let people: Vec<u32> = (0..100).collect();
+//! let mut recipients = people.clone();
+//! seq_derangement(&mut rand::thread_rng(), &mut recipients);
+//!
+//! assert!(people.iter().zip(&recipients).all(|(p, r)| p != r));
+//! ```
+
+use super::error::ShuffleError;
+use super::uniform_index;
+use rand::Rng;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// For `u` of at least this many positions, the probability of closing a cycle equals
+/// `1 / u` up to rounding errors.
+const EXACT_CLOSING_PROBABILITIES: usize = 32;
+
+/// Rearranges `data` uniformly at random such that no element remains at its position.
+/// Inputs of length zero are left untouched.
+///
+/// # Panics
+/// Panics if `data` has exactly one element; see [`try_seq_derangement`] for a
+/// non-panicking variant.
+pub fn seq_derangement<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    try_seq_derangement(rng, data).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`seq_derangement`], but returns [`ShuffleError::Infeasible`] instead of
+/// panicking if `data` has exactly one element.
+pub fn try_seq_derangement<R: Rng, T>(rng: &mut R, data: &mut [T]) -> Result<(), ShuffleError> {
+    if data.len() == 1 {
+        return Err(ShuffleError::Infeasible);
+    }
+
+    let closing_probabilities = closing_probabilities();
+    let closing_probability = |u: usize| {
+        closing_probabilities
+            .get(u)
+            .copied()
+            .unwrap_or(1.0 / u as f64)
+    };
+
+    let mut marked = vec![0u64; data.len().div_ceil(WORD_BITS)];
+    let is_marked = |marked: &[u64], i: usize| marked[i / WORD_BITS] >> (i % WORD_BITS) & 1 == 1;
+
+    // `unmarked` counts the unmarked positions in `0..=i`; the elements at these
+    // positions form a derangement-in-progress of their own
+    let mut unmarked = data.len();
+    let mut i = data.len().saturating_sub(1);
+
+    while unmarked >= 2 {
+        if !is_marked(&marked, i) {
+            let j = loop {
+                let j = uniform_index::gen_index(rng, i);
+                if !is_marked(&marked, j) {
+                    break j;
+                }
+            };
+
+            data.swap(i, j);
+
+            // with the given probability, `j` closes a cycle and is finished
+            if rng.gen::<f64>() < closing_probability(unmarked) {
+                marked[j / WORD_BITS] |= 1 << (j % WORD_BITS);
+                unmarked -= 1;
+            }
+
+            unmarked -= 1;
+        }
+
+        i -= 1;
+    }
+
+    Ok(())
+}
+
+/// Returns the probabilities `(u - 1) * D(u - 2) / D(u)` for `u` below
+/// [`EXACT_CLOSING_PROBABILITIES`], where `D(u)` is the number of derangements of `u`
+/// elements. With `d(u) = D(u) / u!`, they equal `d(u - 2) / (u * d(u))`, where `d(u)`
+/// is the partial sum of the series `1 / e = sum (-1)^k / k!`.
+fn closing_probabilities() -> [f64; EXACT_CLOSING_PROBABILITIES] {
+    let mut d = [0.0; EXACT_CLOSING_PROBABILITIES];
+    let mut term = 1.0;
+    let mut sum = 0.0;
+    for (k, d) in d.iter_mut().enumerate() {
+        if k > 0 {
+            term /= -(k as f64);
+        }
+        sum += term;
+        *d = sum;
+    }
+
+    let mut probabilities = [0.0; EXACT_CLOSING_PROBABILITIES];
+    for u in 2..EXACT_CLOSING_PROBABILITIES {
+        probabilities[u] = d[u - 2] / (u as f64 * d[u]);
+    }
+    probabilities
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+    use std::collections::HashMap;
+
+    #[test]
+    fn has_no_fixed_points() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        for n in [0, 2, 3, 4, 10, 100, 100_000] {
+            let mut data: Vec<usize> = (0..n).collect();
+            seq_derangement(&mut rng, &mut data);
+            assert!(data.iter().enumerate().all(|(i, &x)| i != x), "n={n}");
+
+            data.sort();
+            assert!(data.iter().copied().eq(0..n));
+        }
+    }
+
+    #[test]
+    fn single_element_is_infeasible() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        assert_eq!(
+            try_seq_derangement(&mut rng, &mut [1]),
+            Err(ShuffleError::Infeasible)
+        );
+    }
+
+    #[test]
+    fn closing_probabilities_match_derangement_numbers() {
+        // D(0), D(1), ...
+        let mut num_derangements = vec![1u128, 0];
+        for u in 2..EXACT_CLOSING_PROBABILITIES as u128 {
+            let len = num_derangements.len();
+            num_derangements
+                .push((u - 1) * (num_derangements[len - 1] + num_derangements[len - 2]));
+        }
+
+        let probabilities = closing_probabilities();
+        for u in 2..num_derangements.len() {
+            let expected =
+                (u - 1) as f64 * num_derangements[u - 2] as f64 / num_derangements[u] as f64;
+            assert!((probabilities[u] - expected).abs() < 1e-12, "u={u}");
+        }
+
+        let last = EXACT_CLOSING_PROBABILITIES - 1;
+        assert!((probabilities[last] * last as f64 - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn derangements_are_uniform() {
+        // there are D(5) = 44 derangements of five elements
+        const RUNS: usize = 44_000;
+
+        let mut rng = Pcg64Mcg::seed_from_u64(3);
+        let mut counts: HashMap<Vec<u8>, usize> = HashMap::new();
+        for _ in 0..RUNS {
+            let mut data: Vec<u8> = (0..5).collect();
+            seq_derangement(&mut rng, &mut data);
+            *counts.entry(data).or_default() += 1;
+        }
+
+        assert_eq!(counts.len(), 44);
+        assert!(
+            counts.values().all(|&c| c.abs_diff(1000) < 150),
+            "{counts:?}"
+        );
+    }
+}
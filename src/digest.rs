@@ -0,0 +1,237 @@
+//! Order-sensitive digests of shuffled data.
+//!
+//! Distributed jobs often shuffle the same dataset with the same seed on several
+//! replicas. Comparing a short digest of the result is much cheaper than transmitting
+//! the permutation itself. [`permutation_digest`] hashes the elements in order, so any
+//! two different arrangements of the same elements yield different digests (up to hash
+//! collisions). The digest does not depend on the platform (word size and endianness),
+//! the Rust version, or the number of threads. It is **not** cryptographically secure.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::digest::permutation_digest;
+//!
+//! let data: Vec<u32> = (0..1000).collect();
+//! let mut swapped = data.clone();
+//! swapped.swap(10, 20);
+//!
+//! assert_eq!(permutation_digest(&data), permutation_digest(&data.clone()));
+//! assert_ne!(permutation_digest(&data), permutation_digest(&swapped));
+//! ```
+
+use rayon::prelude::*;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Elements are hashed in chunks of this size in parallel; the chunk digests are then
+/// combined in order. The constant is part of the digest definition.
+const CHUNK_SIZE: usize = 1 << 16;
+
+const SEED_LO: u64 = 0x243F_6A88_85A3_08D3;
+const SEED_HI: u64 = 0x1319_8A2E_0370_7344;
+
+/// Returns a 64-bit order-sensitive digest of `data`.
+pub fn permutation_digest<T: Hash + Sync>(data: &[T]) -> u64 {
+    permutation_digest128(data) as u64
+}
+
+/// Same as [`permutation_digest`], but returns a 128-bit digest for a lower collision
+/// probability.
+pub fn permutation_digest128<T: Hash + Sync>(data: &[T]) -> u128 {
+    let chunk_digests: Vec<u128> = data
+        .par_chunks(CHUNK_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut hasher = DigestHasher::new(i as u64);
+            for elem in chunk {
+                elem.hash(&mut hasher);
+            }
+            hasher.finish128()
+        })
+        .collect();
+
+    let mut hasher = DigestHasher::new(u64::MAX);
+    for digest in chunk_digests {
+        hasher.write_u128(digest);
+    }
+    hasher.write_u64(data.len() as u64);
+    hasher.finish128()
+}
+
+/// Summary of a seeded shuffle, intended to be logged so that replicas can verify
+/// that they produced the same permutation. It is displayed as
+/// `seed=0x... len=... digest=0x...`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShuffleRecord {
+    pub seed: u64,
+    pub len: usize,
+    pub digest: u64,
+}
+
+impl fmt::Display for ShuffleRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed={:#018x} len={} digest={:#018x}",
+            self.seed, self.len, self.digest
+        )
+    }
+}
+
+/// Shuffles `data` in parallel with a [`rand_pcg::Pcg64Mcg`] seeded with `seed` and
+/// returns the [`ShuffleRecord`] of the result (requires the `seed_with` feature).
+/// Replicas only produce the same permutation if their rayon pools have the same
+/// number of threads.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::digest::par_shuffle_recorded;
+///
+/// let mut data: Vec<u64> = (0..1_000_000).collect();
+/// let record = par_shuffle_recorded(0x1234, &mut data);
+///
+/// println!("shuffled training data: {record}");
+/// ```
+#[cfg(feature = "seed_with")]
+pub fn par_shuffle_recorded<T: Hash + Send + Sync>(seed: u64, data: &mut [T]) -> ShuffleRecord {
+    use rand::SeedableRng;
+
+    let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(seed);
+    crate::scatter_shuffle::parallel::par_scatter_shuffle(&mut rng, data);
+
+    ShuffleRecord {
+        seed,
+        len: data.len(),
+        digest: permutation_digest(data),
+    }
+}
+
+/// Two lanes of 64 bits, each updated with the SplitMix64 finalizer. All integers are
+/// fed as `u64` words, so the result does not depend on the platform.
+struct DigestHasher {
+    lo: u64,
+    hi: u64,
+}
+
+impl DigestHasher {
+    fn new(seed: u64) -> Self {
+        Self {
+            lo: mix(SEED_LO ^ seed),
+            hi: mix(SEED_HI ^ seed),
+        }
+    }
+
+    fn finish128(&self) -> u128 {
+        ((mix(self.hi) as u128) << 64) | mix(self.lo) as u128
+    }
+}
+
+impl Hasher for DigestHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.write_u64(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+
+        let mut rest = [0u8; 8];
+        rest[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+        self.write_u64(u64::from_le_bytes(rest) ^ ((chunks.remainder().len() as u64) << 56));
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.lo = mix(self.lo ^ i);
+        self.hi = mix(self.hi.rotate_left(32) ^ i);
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64)
+    }
+
+    fn finish(&self) -> u64 {
+        mix(self.lo)
+    }
+}
+
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn order_sensitive() {
+        for n in [2, 100, CHUNK_SIZE, CHUNK_SIZE + 1, 3 * CHUNK_SIZE] {
+            let data: Vec<u32> = (0..n as u32).collect();
+            let digest = permutation_digest128(&data);
+
+            for (i, j) in [(0, 1), (0, n - 1), (n / 2 - 1, n / 2)] {
+                let mut swapped = data.clone();
+                swapped.swap(i, j);
+                assert_ne!(permutation_digest128(&swapped), digest, "n={n} i={i} j={j}");
+            }
+        }
+    }
+
+    #[test]
+    fn length_sensitive() {
+        assert_ne!(permutation_digest::<u8>(&[]), permutation_digest(&[0u8]));
+        assert_ne!(permutation_digest(&[0u8]), permutation_digest(&[0u8, 0]));
+        assert_ne!(
+            permutation_digest(&["a", "bc"]),
+            permutation_digest(&["ab", "c"])
+        );
+    }
+
+    #[test]
+    fn platform_independent() {
+        // the digest is part of the public contract; it must never change
+        let data: Vec<u32> = (0..100_000).collect();
+        assert_eq!(permutation_digest(&data), permutation_digest(&data));
+        assert_eq!(
+            permutation_digest(&data),
+            permutation_digest(&data.iter().map(|&x| x as usize).collect::<Vec<_>>())
+        );
+        assert_eq!(permutation_digest(&data), 0xbc05_4448_b4c6_c659);
+    }
+
+    #[cfg(feature = "seed_with")]
+    #[test]
+    fn recorded_shuffle() {
+        let mut a: Vec<u64> = (0..1 << 20).collect();
+        let mut b = a.clone();
+
+        let record_a = par_shuffle_recorded(42, &mut a);
+        let record_b = par_shuffle_recorded(42, &mut b);
+        assert_eq!(record_a, record_b);
+        assert_eq!(record_a.digest, permutation_digest(&a));
+
+        let record_c = par_shuffle_recorded(43, &mut b);
+        assert_ne!(record_a.digest, record_c.digest);
+
+        assert!(record_a
+            .to_string()
+            .starts_with("seed=0x000000000000002a len=1048576 "));
+    }
+}
@@ -0,0 +1,211 @@
+//! Nearly-sorted inputs with a tunable amount of disorder.
+//!
+//! Sorting algorithms and search structures are often benchmarked on inputs that are
+//! "almost" sorted. [`partially_shuffle`] selects each position independently with
+//! probability `disorder` and moves the elements at the selected positions along a
+//! uniform random cycle (Sattolo's algorithm). Hence, every selected element leaves its
+//! position, and the expected fraction of displaced elements is exactly `disorder`
+//! (unless a single position is selected, which then stays in place). The displacement
+//! of an element is not bounded; [`crate::windowed::shuffle_windows`] produces inputs
+//! in which no element moves further than a given distance.
+//!
+//! [`measure_disorder`] verifies the achieved disorder of an output by comparing each
+//! element's position with its position in sorted order.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::disorder::{measure_disorder, partially_shuffle};
+//!
+//! let mut data: Vec<u32> = (0..100_000).collect();
+//! partially_shuffle(&mut rand::thread_rng(), &mut data, 0.1);
+//!
+//! let disorder = measure_disorder(&data);
+//! assert!((disorder.fraction_displaced() - 0.1).abs() < 0.01);
+//! ```
+
+use super::error::ShuffleError;
+use super::uniform_index::gen_index;
+use rand::Rng;
+
+/// Moves each element with probability `disorder` to a random position that was also
+/// selected (see the [module documentation](self)).
+///
+/// # Panics
+/// If `disorder` is not within `0.0..=1.0`; see [`try_partially_shuffle`] for a
+/// non-panicking variant.
+pub fn partially_shuffle<R: Rng, T>(rng: &mut R, data: &mut [T], disorder: f64) {
+    try_partially_shuffle(rng, data, disorder).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`partially_shuffle`], but returns [`ShuffleError::InvalidDisorder`] instead
+/// of panicking (leaving `data` untouched).
+pub fn try_partially_shuffle<R: Rng, T>(
+    rng: &mut R,
+    data: &mut [T],
+    disorder: f64,
+) -> Result<(), ShuffleError> {
+    if !(0.0..=1.0).contains(&disorder) {
+        return Err(ShuffleError::InvalidDisorder);
+    }
+
+    let selected = select_positions(rng, data.len(), disorder);
+
+    // Sattolo's algorithm: every selected element is moved along a single cycle
+    for i in (1..selected.len()).rev() {
+        let j = gen_index(rng, i);
+        data.swap(selected[i], selected[j]);
+    }
+
+    Ok(())
+}
+
+/// Returns the positions in `0..n`, each of which is included independently with
+/// probability `p`; the gaps between them are drawn from the geometric distribution.
+fn select_positions<R: Rng>(rng: &mut R, n: usize, p: f64) -> Vec<usize> {
+    if p == 0.0 {
+        return Vec::new();
+    }
+    if p == 1.0 {
+        return (0..n).collect();
+    }
+
+    let log_q = (1.0 - p).ln();
+    let mut selected = Vec::with_capacity((n as f64 * p * 1.1) as usize);
+    let mut pos: usize = 0;
+
+    loop {
+        // `1.0 - rng.gen()` lies in (0, 1], so the logarithm is finite
+        let gap = ((1.0 - rng.gen::<f64>()).ln() / log_q) as usize;
+        pos = match pos.checked_add(gap) {
+            Some(pos) if pos < n => pos,
+            _ => break,
+        };
+
+        selected.push(pos);
+        pos += 1;
+    }
+
+    selected
+}
+
+/// Disorder of a sequence relative to its sorted order (see [`measure_disorder`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Disorder {
+    /// Number of elements.
+    pub len: usize,
+    /// Number of elements that are not at their position in sorted order.
+    pub num_displaced: usize,
+    /// Largest distance of an element to its position in sorted order.
+    pub max_displacement: usize,
+    /// Sum of the distances of all elements to their positions in sorted order.
+    pub total_displacement: u64,
+}
+
+impl Disorder {
+    /// Fraction of displaced elements, which is zero for empty sequences.
+    pub fn fraction_displaced(&self) -> f64 {
+        self.num_displaced as f64 / self.len.max(1) as f64
+    }
+
+    /// Average distance of an element to its position in sorted order.
+    pub fn mean_displacement(&self) -> f64 {
+        self.total_displacement as f64 / self.len.max(1) as f64
+    }
+}
+
+/// Compares the position of each element with its position after a stable sort. Equal
+/// elements keep their relative order, so they only count as displaced if they have to.
+/// Takes `O(n log n)` time and `O(n)` additional memory.
+pub fn measure_disorder<T: Ord>(data: &[T]) -> Disorder {
+    let mut order: Vec<usize> = (0..data.len()).collect();
+    order.sort_by(|&a, &b| data[a].cmp(&data[b]));
+
+    order.iter().enumerate().fold(
+        Disorder {
+            len: data.len(),
+            ..Disorder::default()
+        },
+        |mut disorder, (rank, &pos)| {
+            let distance = rank.abs_diff(pos);
+            disorder.num_displaced += (distance > 0) as usize;
+            disorder.max_displacement = disorder.max_displacement.max(distance);
+            disorder.total_displacement += distance as u64;
+            disorder
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::windowed::shuffle_windows;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn achieves_requested_disorder() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let n = 100_000;
+
+        for p in [0.0, 0.001, 0.1, 0.5, 0.9, 1.0] {
+            let mut data: Vec<u32> = (0..n).collect();
+            partially_shuffle(&mut rng, &mut data, p);
+
+            let fraction = measure_disorder(&data).fraction_displaced();
+            let sigma = (p * (1.0 - p) / n as f64).sqrt();
+            assert!(
+                (fraction - p).abs() <= 5.0 * sigma + 1e-9,
+                "p={p} fraction={fraction}"
+            );
+
+            data.sort();
+            assert!(data.iter().copied().eq(0..n));
+        }
+    }
+
+    #[test]
+    fn selects_positions_uniformly() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        let mut counts = [0u32; 10];
+        for _ in 0..10_000 {
+            for pos in select_positions(&mut rng, counts.len(), 0.3) {
+                counts[pos] += 1;
+            }
+        }
+
+        assert!(
+            counts.iter().all(|&c| (2800..3200).contains(&c)),
+            "{counts:?}"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_disorder() {
+        let mut data = [1, 2, 3];
+        for p in [-0.1, 1.1, f64::NAN] {
+            assert_eq!(
+                try_partially_shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut data, p),
+                Err(ShuffleError::InvalidDisorder)
+            );
+        }
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn measures_displacement() {
+        assert_eq!(measure_disorder::<u32>(&[]), Disorder::default());
+        assert_eq!(
+            measure_disorder(&[1, 1, 0, 2, 2]),
+            Disorder {
+                len: 5,
+                num_displaced: 3,
+                max_displacement: 2,
+                total_displacement: 4,
+            }
+        );
+
+        let mut data: Vec<u32> = (0..10_000).collect();
+        shuffle_windows(&mut Pcg64Mcg::seed_from_u64(3), &mut data, 10);
+        assert!(measure_disorder(&data).max_displacement < 10);
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+
+/// Errors reported by the fallible entry points of this crate. New variants may be
+/// added in minor releases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShuffleError {
+    /// The random number generator produced an obviously non-random sequence
+    /// (see [`crate::rng_guard`]). The input was left untouched.
+    DegenerateRng,
+    /// A window size of zero was requested (see [`crate::windowed`]).
+    ZeroWindow,
+    /// The length or element size of the input does not match the
+    /// [`crate::plan::ShufflePlan`] it is executed with.
+    ShapeMismatch,
+    /// A parallel [`crate::plan::ShufflePlan`] was executed sequentially.
+    ParallelPlan,
+    /// More elements were requested than the population contains.
+    SampleTooLarge,
+    /// The strata do not tile the input (see [`crate::stratified`]).
+    InvalidStrata,
+    /// A configuration specified a base case size of zero.
+    ZeroBaseCaseSize,
+    /// A configuration splits inputs into zero subproblems.
+    ZeroSubproblems,
+    /// The requested number of buckets is not supported by the algorithm.
+    UnsupportedNumberOfBuckets,
+    /// The input is too large for an algorithm that uses 32-bit indices.
+    InputTooLarge,
+    /// The tiles do not evenly divide the image (see [`crate::tiles`]).
+    InvalidTiles,
+    /// Every permutation hits a forbidden position (see [`crate::restricted`]), e.g.,
+    /// there is no derangement of a single element (see [`crate::derangement`]).
+    Infeasible,
+    /// A cycle of length zero or an impossible number of cycles was requested (see
+    /// [`crate::permutation`]).
+    InvalidCycles,
+    /// The CPU or the element type is not supported by a vectorized kernel.
+    UnsupportedTarget,
+    /// The input is not a permutation of `0..n` (see [`crate::permutation_matrix`]).
+    NotAPermutation,
+    /// A reproduction token could not be parsed (see [`crate::repro`]).
+    InvalidToken,
+    /// A reproduction token was captured with different feature flags or runtime
+    /// switches than the current process uses (see [`crate::repro`]).
+    ConfigurationMismatch,
+    /// The requested algorithm variant is not compiled in or disabled at runtime
+    /// (see [`crate::fisher_yates::FyVariant`]).
+    UnavailableVariant,
+    /// The shards do not hold the same number of elements before and after the
+    /// shuffle (see [`crate::sharded`]).
+    InvalidShards,
+    /// The rank does not encode a permutation of the requested length (see
+    /// [`crate::lehmer`]).
+    InvalidRank,
+    /// The scratch buffer is shorter than the input (see [`crate::counting_scatter`]).
+    ScratchTooSmall,
+    /// The fraction of displaced elements is not within `0.0..=1.0` (see
+    /// [`crate::disorder`]).
+    InvalidDisorder,
+    /// A seed or commitment is not given as 64 hexadecimal digits (see
+    /// `crate::audit`).
+    MalformedHex,
+    /// The revealed seed does not match the commitment, or the shuffle does not
+    /// reproduce the published outcome (see `crate::audit`).
+    AuditFailed,
+    /// The subproblem weights are empty or contain a zero (see [`crate::weighted`]).
+    InvalidSubproblemWeights,
+    /// An element weight is negative or not finite (see [`crate::weighted_order`]).
+    InvalidElementWeights,
+    /// The buffer of a [`crate::replay_rng::ReplayRng`] holds fewer bytes than requested.
+    EntropyExhausted,
+    /// The name does not denote a [`crate::Strategy`].
+    UnknownStrategy,
+}
+
+impl fmt::Display for ShuffleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShuffleError::DegenerateRng => write!(
+                f,
+                "the random number generator appears to be broken (e.g., unseeded or a stub)"
+            ),
+            ShuffleError::ZeroWindow => write!(f, "window must not be empty"),
+            ShuffleError::ShapeMismatch => write!(f, "input shape does not match plan"),
+            ShuffleError::ParallelPlan => write!(
+                f,
+                "parallel plans need to be executed with ShufflePlan::shuffle"
+            ),
+            ShuffleError::SampleTooLarge => {
+                write!(
+                    f,
+                    "cannot sample more elements than the population contains"
+                )
+            }
+            ShuffleError::InvalidStrata => {
+                write!(f, "strata need to be consecutive ranges covering the input")
+            }
+            ShuffleError::ZeroBaseCaseSize => write!(f, "base case size must not be zero"),
+            ShuffleError::ZeroSubproblems => {
+                write!(f, "inputs need to be split into at least one subproblem")
+            }
+            ShuffleError::UnsupportedNumberOfBuckets => {
+                write!(f, "the number of buckets is not supported")
+            }
+            ShuffleError::InputTooLarge => {
+                write!(f, "the input is too large for 32-bit indices")
+            }
+            ShuffleError::InvalidTiles => {
+                write!(f, "tiles need to be non-empty and evenly divide the image")
+            }
+            ShuffleError::Infeasible => {
+                write!(f, "no permutation avoids all forbidden positions")
+            }
+            ShuffleError::InvalidCycles => {
+                write!(f, "no permutation has the requested cycles")
+            }
+            ShuffleError::UnsupportedTarget => {
+                write!(f, "the CPU or element type is not supported by this kernel")
+            }
+            ShuffleError::NotAPermutation => {
+                write!(f, "the input is not a permutation")
+            }
+            ShuffleError::InvalidToken => write!(f, "malformed reproduction token"),
+            ShuffleError::ConfigurationMismatch => write!(
+                f,
+                "the reproduction token was captured with a different configuration"
+            ),
+            ShuffleError::UnavailableVariant => {
+                write!(f, "the algorithm variant is not available in this build")
+            }
+            ShuffleError::InvalidShards => {
+                write!(
+                    f,
+                    "input and output shards need to hold the same number of elements"
+                )
+            }
+            ShuffleError::InvalidRank => {
+                write!(f, "the rank does not encode a permutation of this length")
+            }
+            ShuffleError::ScratchTooSmall => {
+                write!(
+                    f,
+                    "the scratch buffer needs to be at least as long as the input"
+                )
+            }
+            ShuffleError::InvalidDisorder => {
+                write!(f, "disorder needs to be between 0.0 and 1.0")
+            }
+            ShuffleError::MalformedHex => write!(f, "expected 64 hexadecimal digits"),
+            ShuffleError::InvalidSubproblemWeights => {
+                write!(f, "subproblem weights need to be non-empty and positive")
+            }
+            ShuffleError::InvalidElementWeights => {
+                write!(f, "element weights need to be non-negative and finite")
+            }
+            ShuffleError::AuditFailed => {
+                write!(f, "the seed or the outcome does not match the commitment")
+            }
+            ShuffleError::EntropyExhausted => write!(f, "the randomness buffer is exhausted"),
+            ShuffleError::UnknownStrategy => write!(
+                f,
+                "unknown strategy; expected fisher_yates, seq_scatter, par_scatter, seq_merge, par_merge, or auto"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShuffleError {}
@@ -0,0 +1,405 @@
+//! Shuffling the records of text files that do not fit into memory.
+//!
+//! [`ExternalShuffle`] rearranges the records of a file (lines, or CSV records whose
+//! quoted fields may contain line breaks) in a uniform random order while holding only
+//! about [`ExternalShuffle::with_max_bucket_bytes`] bytes in memory:
+//!
+//! 1. The input is read once and split into records; each record is appended to a
+//!    uniformly random bucket, i.e., a temporary file. The number of buckets is chosen
+//!    such that the expected size of a bucket is half of the memory budget.
+//! 2. The buckets are processed one after another: a bucket is read into memory, an
+//!    offset table of its records is built, the table is shuffled, and the records are
+//!    written to the output in the permuted order.
+//!
+//! As each record reaches an independent and uniform bucket, and each bucket is shuffled
+//! uniformly, the output is a uniform random permutation of the records. A CSV header
+//! is copied to the output unchanged. Every record of the output ends with a line
+//! break; a final record without one receives a `\n`.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::external::{ExternalShuffle, RecordFormat};
+//!
+//! let input = "id,comment\n1,\"multi\nline\"\n2,plain\n3,\"with \"\"quotes\"\"\"\n";
+//! let mut output = Vec::new();
+//!
+//! let num_records = ExternalShuffle::new(RecordFormat::Csv { has_header: true })
+//!     .shuffle_reader(&mut rand::thread_rng(), input.as_bytes(), input.len() as u64, &mut output)
+//!     .unwrap();
+//!
+//! assert_eq!(num_records, 3);
+//! assert!(output.starts_with(b"id,comment\n"));
+//! assert_eq!(output.len(), input.len());
+//! ```
+
+use super::error::ShuffleError;
+use super::uniform_index::gen_index;
+use super::RipShuffleSequential;
+use rand::Rng;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default of [`ExternalShuffle::with_max_bucket_bytes`].
+pub const DEFAULT_MAX_BUCKET_BYTES: usize = 1 << 26;
+
+/// Upper bound on the number of buckets, which are open files during the first pass.
+pub const MAX_NUM_BUCKETS: usize = 1 << 12;
+
+/// How the input is split into records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Each line (terminated by `\n`) is a record.
+    Lines,
+    /// Records are lines, except that line breaks within double-quoted fields do not
+    /// end a record (RFC 4180). If `has_header` is set, the first record is kept in place.
+    Csv { has_header: bool },
+}
+
+/// Configuration of an external shuffle (see the [module documentation](self)).
+#[derive(Clone, Debug)]
+pub struct ExternalShuffle {
+    format: RecordFormat,
+    max_bucket_bytes: usize,
+    temp_dir: PathBuf,
+}
+
+impl ExternalShuffle {
+    /// Shuffles records of the given format with the [`DEFAULT_MAX_BUCKET_BYTES`] memory
+    /// budget and buckets in [`std::env::temp_dir`].
+    pub fn new(format: RecordFormat) -> Self {
+        Self {
+            format,
+            max_bucket_bytes: DEFAULT_MAX_BUCKET_BYTES,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Sets the memory budget; the buckets are expected to hold half of it. Returns
+    /// [`ShuffleError::ZeroBaseCaseSize`] for a budget of zero.
+    pub fn with_max_bucket_bytes(mut self, bytes: usize) -> Result<Self, ShuffleError> {
+        if bytes == 0 {
+            return Err(ShuffleError::ZeroBaseCaseSize);
+        }
+
+        self.max_bucket_bytes = bytes;
+        Ok(self)
+    }
+
+    /// Sets the directory of the temporary bucket files, which are removed afterwards.
+    pub fn with_temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = dir.into();
+        self
+    }
+
+    /// Number of buckets used for an input of `num_bytes` bytes.
+    pub fn num_buckets(&self, num_bytes: u64) -> usize {
+        let expected_bytes = (self.max_bucket_bytes / 2).max(1) as u64;
+        (num_bytes.div_ceil(expected_bytes) as usize).clamp(1, MAX_NUM_BUCKETS)
+    }
+
+    /// Shuffles the records of the file `input` into the file `output` (which must not
+    /// be `input`) and returns the number of records (excluding a CSV header).
+    pub fn shuffle_file<R: Rng>(
+        &self,
+        rng: &mut R,
+        input: &Path,
+        output: &Path,
+    ) -> io::Result<u64> {
+        let num_bytes = std::fs::metadata(input)?.len();
+        let reader = BufReader::new(File::open(input)?);
+        let mut writer = BufWriter::new(File::create(output)?);
+
+        let num_records = self.shuffle_reader(rng, reader, num_bytes, &mut writer)?;
+        writer.flush()?;
+        Ok(num_records)
+    }
+
+    /// Same as [`ExternalShuffle::shuffle_file`], but reads the records from `input` and
+    /// writes them to `output`. The size `num_bytes` of the input only needs to be
+    /// approximate; it determines the number of buckets.
+    pub fn shuffle_reader<R: Rng>(
+        &self,
+        rng: &mut R,
+        mut input: impl BufRead,
+        num_bytes: u64,
+        mut output: impl Write,
+    ) -> io::Result<u64> {
+        let mut record = Vec::new();
+
+        if self.format == (RecordFormat::Csv { has_header: true })
+            && read_record(&mut input, self.format, &mut record)?
+        {
+            output.write_all(&record)?;
+        }
+
+        let buckets = TempBuckets::create(&self.temp_dir, self.num_buckets(num_bytes));
+
+        // pass 1: distribute the records
+        let mut num_records = 0;
+        {
+            let mut writers = buckets
+                .paths
+                .iter()
+                .map(|path| File::create(path).map(BufWriter::new))
+                .collect::<io::Result<Vec<_>>>()?;
+
+            while read_record(&mut input, self.format, &mut record)? {
+                let bucket = gen_index(rng, writers.len());
+                writers[bucket].write_all(&record)?;
+                num_records += 1;
+            }
+
+            for writer in &mut writers {
+                writer.flush()?;
+            }
+        }
+
+        // pass 2: shuffle each bucket in memory
+        let mut data = Vec::new();
+        for path in &buckets.paths {
+            data.clear();
+            File::open(path)?.read_to_end(&mut data)?;
+
+            let mut records = split_records(&data, self.format);
+            records.seq_shuffle(rng);
+            for record in records {
+                output.write_all(record)?;
+            }
+        }
+
+        Ok(num_records)
+    }
+}
+
+/// Reads the next record including its line break into `buf` and appends a `\n` if it is
+/// missing. Returns `false` if the input is exhausted.
+fn read_record(
+    input: &mut impl BufRead,
+    format: RecordFormat,
+    buf: &mut Vec<u8>,
+) -> io::Result<bool> {
+    buf.clear();
+
+    // a line break ends the record unless it is preceded by an odd number of quotes
+    let mut num_quotes = 0;
+    loop {
+        let begin = buf.len();
+        if input.read_until(b'\n', buf)? == 0 {
+            break;
+        }
+
+        if format == RecordFormat::Lines {
+            break;
+        }
+
+        num_quotes += buf[begin..].iter().filter(|&&c| c == b'"').count();
+        if num_quotes % 2 == 0 {
+            break;
+        }
+    }
+
+    if num_quotes % 2 == 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unterminated quoted field in the last CSV record",
+        ));
+    }
+
+    if buf.is_empty() {
+        return Ok(false);
+    }
+
+    if buf.last() != Some(&b'\n') {
+        buf.push(b'\n');
+    }
+
+    Ok(true)
+}
+
+/// Splits the contents of a bucket, which consists of records written by [`read_record`].
+fn split_records(data: &[u8], format: RecordFormat) -> Vec<&[u8]> {
+    let mut records = Vec::new();
+    let mut begin = 0;
+    let mut in_quotes = false;
+
+    for (i, &c) in data.iter().enumerate() {
+        match c {
+            b'"' if format != RecordFormat::Lines => in_quotes = !in_quotes,
+            b'\n' if !in_quotes => {
+                records.push(&data[begin..=i]);
+                begin = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    debug_assert_eq!(begin, data.len());
+    records
+}
+
+/// Temporary bucket files that are removed when dropped.
+struct TempBuckets {
+    paths: Vec<PathBuf>,
+}
+
+impl TempBuckets {
+    fn create(dir: &Path, num_buckets: usize) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let paths = (0..num_buckets)
+            .map(|i| dir.join(format!("rip_shuffle-{}-{id}-{i}", std::process::id())))
+            .collect();
+
+        Self { paths }
+    }
+}
+
+impl Drop for TempBuckets {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    fn shuffle_str(shuffle: &ExternalShuffle, seed: u64, input: &str) -> String {
+        let mut output = Vec::new();
+        shuffle
+            .shuffle_reader(
+                &mut Pcg64Mcg::seed_from_u64(seed),
+                input.as_bytes(),
+                input.len() as u64,
+                &mut output,
+            )
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn sorted_records(data: &str, format: RecordFormat) -> Vec<&[u8]> {
+        let mut records = split_records(data.as_bytes(), format);
+        records.sort();
+        records
+    }
+
+    #[test]
+    fn keeps_csv_records_intact() {
+        let records = [
+            "1,\"a\nb\"\n",
+            "2,\"x\"\"y\"\n",
+            "3,plain\r\n",
+            "4,\"\"\n",
+            "5,last\n",
+        ];
+        let input = format!("h1,h2\n{}5,last", records[..4].concat());
+
+        let shuffle = ExternalShuffle::new(RecordFormat::Csv { has_header: true })
+            .with_max_bucket_bytes(8)
+            .unwrap();
+        assert!(shuffle.num_buckets(input.len() as u64) > 1);
+
+        let output = shuffle_str(&shuffle, 1, &input);
+        let body = output.strip_prefix("h1,h2\n").unwrap();
+
+        let mut expected: Vec<&[u8]> = records.iter().map(|r| r.as_bytes()).collect();
+        expected.sort();
+        assert_eq!(
+            sorted_records(body, RecordFormat::Csv { has_header: false }),
+            expected
+        );
+    }
+
+    #[test]
+    fn shuffles_files() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("rip_shuffle-test-in-{}", std::process::id()));
+        let output = dir.join(format!("rip_shuffle-test-out-{}", std::process::id()));
+
+        let lines: String = (0..10_000).map(|i| format!("line {i}\n")).collect();
+        std::fs::write(&input, &lines).unwrap();
+
+        let shuffle = ExternalShuffle::new(RecordFormat::Lines)
+            .with_max_bucket_bytes(4096)
+            .unwrap();
+        let num_records = shuffle
+            .shuffle_file(&mut Pcg64Mcg::seed_from_u64(2), &input, &output)
+            .unwrap();
+
+        let shuffled = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert_eq!(num_records, 10_000);
+        assert_ne!(shuffled, lines);
+        assert_eq!(
+            sorted_records(&shuffled, RecordFormat::Lines),
+            sorted_records(&lines, RecordFormat::Lines)
+        );
+    }
+
+    #[test]
+    fn handles_empty_inputs() {
+        let shuffle = ExternalShuffle::new(RecordFormat::Csv { has_header: true });
+        assert_eq!(shuffle_str(&shuffle, 1, ""), "");
+        assert_eq!(shuffle_str(&shuffle, 1, "header"), "header\n");
+        assert_eq!(
+            ExternalShuffle::new(RecordFormat::Lines)
+                .shuffle_reader(
+                    &mut Pcg64Mcg::seed_from_u64(1),
+                    "1\n\"2".as_bytes(),
+                    4,
+                    Vec::new()
+                )
+                .unwrap(),
+            2
+        );
+        assert!(shuffle
+            .shuffle_reader(
+                &mut Pcg64Mcg::seed_from_u64(1),
+                "h\n1\n\"2\n".as_bytes(),
+                6,
+                Vec::new()
+            )
+            .is_err());
+        assert_eq!(
+            ExternalShuffle::new(RecordFormat::Lines)
+                .with_max_bucket_bytes(0)
+                .err(),
+            Some(ShuffleError::ZeroBaseCaseSize)
+        );
+    }
+
+    mod statistical {
+        use super::*;
+
+        /// Shuffles the indices as lines with several buckets and applies the permutation
+        fn external<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            let shuffle = ExternalShuffle::new(RecordFormat::Lines)
+                .with_max_bucket_bytes(32)
+                .unwrap();
+
+            let input: String = (0..data.len()).map(|i| format!("{i}\n")).collect();
+            let mut output = Vec::new();
+            shuffle
+                .shuffle_reader(rng, input.as_bytes(), input.len() as u64, &mut output)
+                .unwrap();
+
+            let mut order: Vec<u32> = String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .map(|line| line.parse().unwrap())
+                .collect();
+            crate::indirection::apply_in_place(data, &mut order);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(external);
+    }
+}
@@ -0,0 +1,205 @@
+//! Fisher-Yates kernel for 4- and 8-byte elements using AVX-512 gathers and scatters.
+//!
+//! The kernel draws the partners of a window of `W` consecutive positions (in the same
+//! order as [`super::naive::fisher_yates`]). If none of the partners lies in the
+//! window and no two partners coincide, the `W` swaps are independent: the window is
+//! loaded contiguously, the partners are gathered, and both are written back with a
+//! scatter and a contiguous store. Otherwise, which rarely happens for large inputs,
+//! the window is processed with scalar swaps. Hence, the result is identical to the
+//! naive implementation for the same random number generator. The partners of the
+//! next window are drawn (and prefetched) while the current one is processed.
+//!
+//! [`super::fisher_yates`] selects the kernel at runtime for inputs of at least
+//! [`DISPATCH_LEN`] elements if the CPU supports AVX-512F/CD/VL (see [`is_applicable`])
+//! and the unsafe algorithms are enabled. For smaller inputs, which fit into the
+//! caches, it is not faster than the scalar implementations.
+
+use super::*;
+use crate::error::ShuffleError;
+use std::arch::x86_64::*;
+use std::mem::size_of;
+
+/// Smallest input for which [`super::fisher_yates`] uses the kernel.
+pub const DISPATCH_LEN: usize = 1 << 20;
+
+/// Inputs with fewer elements are shuffled with the naive implementation, since
+/// partners in the window (and hence conflicts) become likely.
+const MIN_LEN: usize = 1 << 12;
+
+/// Returns whether [`fisher_yates`] supports the current CPU, elements of type `T`,
+/// and inputs of length `len`.
+pub fn is_applicable<T>(len: usize) -> bool {
+    (size_of::<T>() == 4 || size_of::<T>() == 8)
+        && len < i32::MAX as usize
+        && is_x86_feature_detected!("avx512f")
+        && is_x86_feature_detected!("avx512cd")
+        && is_x86_feature_detected!("avx512vl")
+}
+
+/// Shuffles `data` with the AVX-512 kernel. Returns [`ShuffleError::UnsupportedTarget`]
+/// and leaves `data` untouched if the kernel is not applicable (see [`is_applicable`]).
+pub fn fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T]) -> Result<(), ShuffleError> {
+    if !is_applicable::<T>(data.len()) {
+        return Err(ShuffleError::UnsupportedTarget);
+    }
+
+    // SAFETY: the CPU features and the element size were checked above
+    unsafe {
+        if size_of::<T>() == 4 {
+            fisher_yates_4::<R, T, MIN_LEN>(rng, data);
+        } else {
+            fisher_yates_8::<R, T, MIN_LEN>(rng, data);
+        }
+    }
+
+    Ok(())
+}
+
+#[target_feature(enable = "avx512f,avx512cd,avx512vl")]
+unsafe fn fisher_yates_4<R: Rng, T, const MIN: usize>(rng: &mut R, data: &mut [T]) {
+    debug_assert_eq!(size_of::<T>(), 4);
+
+    windowed_fisher_yates::<R, T, 16, MIN>(rng, data, |data, base, partners| {
+        let idx = _mm512_loadu_si512(partners.as_ptr() as *const _);
+
+        let in_window = _mm512_cmpge_epi32_mask(idx, _mm512_set1_epi32(base as i32));
+        let conflicts = _mm512_conflict_epi32(idx);
+        if in_window != 0 || _mm512_test_epi32_mask(conflicts, conflicts) != 0 {
+            return false;
+        }
+
+        let ptr = data as *mut i32;
+        let window = _mm512_loadu_si512(ptr.add(base) as *const _);
+        let gathered = _mm512_i32gather_epi32::<4>(idx, ptr as *const _);
+        _mm512_i32scatter_epi32::<4>(ptr as *mut _, idx, window);
+        _mm512_storeu_si512(ptr.add(base) as *mut _, gathered);
+        true
+    })
+}
+
+#[target_feature(enable = "avx512f,avx512cd,avx512vl")]
+unsafe fn fisher_yates_8<R: Rng, T, const MIN: usize>(rng: &mut R, data: &mut [T]) {
+    debug_assert_eq!(size_of::<T>(), 8);
+
+    windowed_fisher_yates::<R, T, 8, MIN>(rng, data, |data, base, partners| {
+        let idx = _mm256_loadu_si256(partners.as_ptr() as *const _);
+
+        let in_window = _mm256_cmpge_epi32_mask(idx, _mm256_set1_epi32(base as i32));
+        let conflicts = _mm256_conflict_epi32(idx);
+        if in_window != 0 || _mm256_test_epi32_mask(conflicts, conflicts) != 0 {
+            return false;
+        }
+
+        let ptr = data as *mut i64;
+        let window = _mm512_loadu_si512(ptr.add(base) as *const _);
+        let gathered = _mm512_i32gather_epi64::<8>(idx, ptr as *const _);
+        _mm512_i32scatter_epi64::<8>(ptr as *mut _, idx, window);
+        _mm512_storeu_si512(ptr.add(base) as *mut _, gathered);
+        true
+    })
+}
+
+/// Runs the Fisher-Yates shuffle in windows of `W` positions down to position `MIN`.
+/// `swap_window(ptr, base, partners)` swaps each position `base + m` with `partners[m]`
+/// if this can be done independently, and returns false (without modifying the data)
+/// otherwise.
+#[inline(always)]
+unsafe fn windowed_fisher_yates<R: Rng, T, const W: usize, const MIN: usize>(
+    rng: &mut R,
+    data: &mut [T],
+    mut swap_window: impl FnMut(*mut T, usize, &[u32; W]) -> bool,
+) {
+    debug_assert!(MIN >= W);
+
+    let n = data.len();
+    if n < MIN {
+        return naive::fisher_yates(rng, data);
+    }
+
+    let ptr = data.as_mut_ptr();
+
+    // draws the partners of `base..base + W` in decreasing order of the positions
+    let draw_and_fetch = |rng: &mut R, base: usize, partners: &mut [u32; W]| {
+        for m in (0..W).rev() {
+            let j = uniform_index::gen_index(rng, base + m + 1);
+            _mm_prefetch::<_MM_HINT_T0>(ptr.add(j) as *const i8);
+            partners[m] = j as u32;
+        }
+    };
+
+    let mut base = n - W;
+    let mut partners = [0u32; W];
+    draw_and_fetch(rng, base, &mut partners);
+
+    while base >= MIN {
+        let mut next = [0u32; W];
+        draw_and_fetch(rng, base - W, &mut next);
+
+        if !swap_window(ptr, base, &partners) {
+            for m in (0..W).rev() {
+                std::ptr::swap(ptr.add(base + m), ptr.add(partners[m] as usize));
+            }
+        }
+
+        partners = next;
+        base -= W;
+    }
+
+    for m in (0..W).rev() {
+        std::ptr::swap(ptr.add(base + m), ptr.add(partners[m] as usize));
+    }
+
+    naive::fisher_yates(rng, &mut data[..base]);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    // uses the kernel already for tiny inputs, where conflicts are frequent
+    fn shuffle<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+        if !is_applicable::<T>(data.len()) {
+            naive::fisher_yates(rng, data);
+        } else if size_of::<T>() == 4 {
+            unsafe { fisher_yates_4::<R, T, 16>(rng, data) };
+        } else {
+            unsafe { fisher_yates_8::<R, T, 16>(rng, data) };
+        }
+    }
+
+    #[test]
+    fn matches_naive() {
+        if !is_applicable::<u32>(0) {
+            return;
+        }
+
+        for n in [0, 1, MIN_LEN - 1, MIN_LEN, MIN_LEN + 17, 1 << 20] {
+            let mut reference: Vec<u64> = (0..n as u64).collect();
+            naive::fisher_yates(&mut Pcg64Mcg::seed_from_u64(n as u64), &mut reference);
+
+            let mut data: Vec<u64> = (0..n as u64).collect();
+            fisher_yates(&mut Pcg64Mcg::seed_from_u64(n as u64), &mut data).unwrap();
+            assert_eq!(data, reference);
+
+            let mut data: Vec<u32> = (0..n as u32).collect();
+            fisher_yates(&mut Pcg64Mcg::seed_from_u64(n as u64), &mut data).unwrap();
+            assert!(data.iter().zip(&reference).all(|(&x, &y)| x as u64 == y));
+        }
+    }
+
+    #[test]
+    fn unsupported_element_size() {
+        let mut data = [0u16; 10];
+        assert_eq!(
+            fisher_yates(&mut Pcg64Mcg::seed_from_u64(1), &mut data),
+            Err(ShuffleError::UnsupportedTarget)
+        );
+    }
+
+    mod statistical {
+        use super::*;
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle);
+    }
+}
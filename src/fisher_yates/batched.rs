@@ -0,0 +1,68 @@
+//! Fisher-Yates variant that draws the swap partners of two consecutive iterations
+//! at once. The 32-bit version obtains both indices from a single 64-bit random word
+//! (see [`uniform_index::impl_u32::gen_index_pair`]); the 64-bit version merely unrolls
+//! the loop. Neither needs intrinsics, so both are available on stable.
+
+use super::*;
+use crate::swap::swap;
+
+/// Shuffles `data` with the 32-bit or 64-bit version depending on its length
+/// (see [`uniform_index::u32_upper_bound`]).
+pub fn fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    if cfg!(target_pointer_width = "32") || data.len() <= uniform_index::u32_upper_bound() as usize
+    {
+        fisher_yates_u32(rng, data);
+    } else {
+        fisher_yates_u64(rng, data);
+    }
+}
+
+/// # Panics
+/// If `data` has more than [`u32::MAX`] elements.
+pub fn fisher_yates_u32<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    assert!(data.len() <= u32::MAX as usize);
+
+    let mut i = data.len();
+    while i >= 3 {
+        let (j0, j1) = uniform_index::impl_u32::gen_index_pair(rng, (i as u32, i as u32 - 1));
+        swap(data, i - 1, j0 as usize);
+        swap(data, i - 2, j1 as usize);
+        i -= 2;
+    }
+
+    if i == 2 {
+        swap(data, 1, uniform_index::impl_u32::gen_index(rng, 2) as usize);
+    }
+}
+
+pub fn fisher_yates_u64<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    let mut i = data.len();
+    while i >= 3 {
+        let j0 = uniform_index::impl_u64::gen_index(rng, i as u64);
+        let j1 = uniform_index::impl_u64::gen_index(rng, i as u64 - 1);
+        swap(data, i - 1, j0 as usize);
+        swap(data, i - 2, j1 as usize);
+        i -= 2;
+    }
+
+    if i == 2 {
+        swap(data, 1, uniform_index::impl_u64::gen_index(rng, 2) as usize);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod u32 {
+        use super::*;
+        crate::statistical_tests::test_shuffle_algorithm!(fisher_yates_u32);
+        crate::statistical_tests::test_shuffle_algorithm_deterministic!(fisher_yates_u32);
+    }
+
+    mod u64 {
+        use super::*;
+        crate::statistical_tests::test_shuffle_algorithm!(fisher_yates_u64);
+        crate::statistical_tests::test_shuffle_algorithm_deterministic!(fisher_yates_u64);
+    }
+}
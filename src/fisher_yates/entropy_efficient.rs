@@ -0,0 +1,53 @@
+use super::*;
+use uniform_index::EntropyPool;
+
+/// A Fisher-Yates shuffle that draws its indices from an [`EntropyPool`] and hence
+/// consumes only about `log2(n!)` random bits, i.e. close to the information-theoretic
+/// minimum. Use it only if the random number generator dominates the running time;
+/// otherwise the default implementations are considerably faster.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::fisher_yates::entropy_efficient::fisher_yates;
+///
+/// let mut data: Vec<_> = (0..1000).collect();
+/// fisher_yates(&mut rand::thread_rng(), &mut data);
+/// ```
+pub fn fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    fisher_yates_with_pool(rng, &mut EntropyPool::new(), data)
+}
+
+/// Same as [`fisher_yates`], but draws from a pool provided by the caller, which
+/// allows to reuse left-over entropy across multiple shuffles.
+pub fn fisher_yates_with_pool<R: Rng, T>(rng: &mut R, pool: &mut EntropyPool, data: &mut [T]) {
+    for i in (1..data.len()).rev() {
+        let j = pool.gen_index(rng, i + 1);
+        data.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    crate::statistical_tests::test_shuffle_algorithm!(fisher_yates);
+    crate::statistical_tests::test_shuffle_algorithm_deterministic!(fisher_yates);
+
+    #[test]
+    fn close_to_entropy_bound() {
+        let mut rng = Pcg64Mcg::seed_from_u64(123);
+
+        for n in [10usize, 100, 1000, 10_000] {
+            let mut pool = EntropyPool::new();
+            let mut data: Vec<_> = (0..n).collect();
+            fisher_yates_with_pool(&mut rng, &mut pool, &mut data);
+
+            let log2_factorial: f64 = (2..=n).map(|i| (i as f64).log2()).sum();
+            let consumed = pool.bits_consumed() as f64;
+            assert!(
+                consumed < 1.01 * log2_factorial + 128.0,
+                "n={n} consumed={consumed} bound={log2_factorial}"
+            );
+        }
+    }
+}
@@ -1,9 +1,19 @@
 use super::uniform_index;
+use crate::error::ShuffleError;
 use rand::Rng;
 
+#[cfg(all(target_arch = "x86_64", feature = "unsafe_algos"))]
+pub mod avx512;
+pub mod batched;
+pub mod entropy_efficient;
 pub mod naive;
+#[cfg(all(target_arch = "aarch64", feature = "unsafe_algos"))]
+pub mod neon;
 pub(crate) mod noncontiguous;
 
+#[cfg(feature = "prefetch")]
+pub mod prefetch_width;
+
 #[cfg(feature = "prefetch")]
 pub mod with_prefetch;
 
@@ -13,14 +23,179 @@ pub mod with_unsafe_algos;
 
 #[allow(unreachable_code)]
 pub fn fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    if crate::oracle::is_enabled() {
+        return naive::fisher_yates(rng, data);
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "unsafe_algos"))]
+    if data.len() >= avx512::DISPATCH_LEN
+        && crate::unsafe_algos::is_enabled()
+        && avx512::fisher_yates(rng, data).is_ok()
+    {
+        return;
+    }
+
+    #[cfg(all(target_arch = "aarch64", feature = "unsafe_algos"))]
+    if crate::unsafe_algos::is_enabled() && neon::fisher_yates(rng, data).is_ok() {
+        return;
+    }
+
     #[cfg(feature = "prefetch")]
     #[cfg(feature = "unsafe_algos")]
-    if data.len() < uniform_index::U32_MAX_UPPER_BOUND as usize {
-        return with_unsafe_algos::fisher_yates_u32(rng, data);
+    if data.len() < uniform_index::u32_upper_bound() as usize
+        && crate::unsafe_algos::is_enabled()
+        && with_unsafe_algos::fisher_yates_u32(rng, data).is_ok()
+    {
+        return;
     }
 
     #[cfg(feature = "prefetch")]
     return with_prefetch::fisher_yates(rng, data);
 
-    naive::fisher_yates(rng, data);
+    batched::fisher_yates(rng, data);
+}
+
+/// Fisher-Yates implementations that can be selected at runtime with
+/// [`fisher_yates_with`], e.g., for benchmarks or to rule out a specific
+/// implementation during bug triage. All variants exist in all builds; the ones that
+/// are not compiled in are reported by [`FyVariant::is_available`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FyVariant {
+    /// The implementation chosen by [`fisher_yates`]
+    Auto,
+    /// The textbook implementation in [`naive`]
+    Naive,
+    /// Two iterations per random word, see [`batched`]
+    Batched,
+    /// Explicit prefetching of the swap partners (requires the `prefetch` feature)
+    Prefetch,
+    /// Prefetching with unchecked accesses (requires the `prefetch` and
+    /// `unsafe_algos` features)
+    Unsafe,
+    /// The AVX-512 kernel (requires `unsafe_algos` on x86-64 and a supporting CPU)
+    Avx512,
+    /// The NEON kernel (requires `unsafe_algos` on aarch64)
+    Neon,
+}
+
+impl FyVariant {
+    pub const ALL: [FyVariant; 7] = [
+        FyVariant::Auto,
+        FyVariant::Naive,
+        FyVariant::Batched,
+        FyVariant::Prefetch,
+        FyVariant::Unsafe,
+        FyVariant::Avx512,
+        FyVariant::Neon,
+    ];
+
+    /// Returns whether the variant is compiled in and, for the unsafe variants, enabled
+    /// at runtime (see [`crate::unsafe_algos`]). The AVX-512 kernel may still reject
+    /// an input if the CPU or the element type is not supported.
+    pub fn is_available(self) -> bool {
+        match self {
+            FyVariant::Auto | FyVariant::Naive | FyVariant::Batched => true,
+            FyVariant::Prefetch => cfg!(feature = "prefetch"),
+            FyVariant::Unsafe => {
+                cfg!(all(feature = "prefetch", feature = "unsafe_algos"))
+                    && crate::unsafe_algos::is_enabled()
+            }
+            FyVariant::Avx512 => {
+                cfg!(all(target_arch = "x86_64", feature = "unsafe_algos"))
+                    && crate::unsafe_algos::is_enabled()
+            }
+            FyVariant::Neon => {
+                cfg!(all(target_arch = "aarch64", feature = "unsafe_algos"))
+                    && crate::unsafe_algos::is_enabled()
+            }
+        }
+    }
+}
+
+/// Shuffles `data` with the given Fisher-Yates `variant`. In contrast to
+/// [`fisher_yates`], there is no fallback: if the variant is not available
+/// (see [`FyVariant::is_available`]) or does not support the input, an error is
+/// returned and `data` is left untouched.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::fisher_yates::{fisher_yates_with, FyVariant};
+///
+/// let mut data: Vec<_> = (0..1000).collect();
+/// fisher_yates_with(FyVariant::Naive, &mut rand::thread_rng(), &mut data).unwrap();
+/// ```
+#[allow(unreachable_code)]
+pub fn fisher_yates_with<R: Rng, T>(
+    variant: FyVariant,
+    rng: &mut R,
+    data: &mut [T],
+) -> Result<(), ShuffleError> {
+    if !variant.is_available() {
+        return Err(ShuffleError::UnavailableVariant);
+    }
+
+    match variant {
+        FyVariant::Auto => fisher_yates(rng, data),
+        FyVariant::Naive => naive::fisher_yates(rng, data),
+        FyVariant::Batched => batched::fisher_yates(rng, data),
+        FyVariant::Prefetch => {
+            #[cfg(feature = "prefetch")]
+            with_prefetch::fisher_yates(rng, data);
+        }
+        FyVariant::Unsafe => {
+            #[cfg(all(feature = "prefetch", feature = "unsafe_algos"))]
+            return with_unsafe_algos::fisher_yates_u32(rng, data);
+        }
+        FyVariant::Avx512 => {
+            #[cfg(all(target_arch = "x86_64", feature = "unsafe_algos"))]
+            return avx512::fisher_yates(rng, data);
+        }
+        FyVariant::Neon => {
+            #[cfg(all(target_arch = "aarch64", feature = "unsafe_algos"))]
+            return neon::fisher_yates(rng, data);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn fisher_yates_with_all_variants() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for variant in FyVariant::ALL {
+            let mut data: Vec<u64> = (0..10_000).collect();
+
+            match fisher_yates_with(variant, &mut rng, &mut data) {
+                Ok(()) => {
+                    assert!(variant.is_available());
+                    assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+                    data.sort();
+                }
+                Err(ShuffleError::UnavailableVariant) => assert!(!variant.is_available()),
+                Err(e) => assert_eq!(
+                    (variant, e),
+                    (FyVariant::Avx512, ShuffleError::UnsupportedTarget)
+                ),
+            }
+
+            assert!(data.iter().enumerate().all(|(i, &x)| i as u64 == x));
+        }
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn naive_variant<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            fisher_yates_with(FyVariant::Naive, rng, data).unwrap()
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(naive_variant);
+    }
 }
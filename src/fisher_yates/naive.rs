@@ -1,9 +1,23 @@
 use super::*;
+use crate::observer::{NoObserver, SwapObserver};
+use crate::swap::swap;
 
 pub fn fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    fisher_yates_observed(rng, data, &mut NoObserver)
+}
+
+/// Same as [`fisher_yates`], but reports each swap to `observer` (see [`crate::observer`]).
+pub fn fisher_yates_observed<R: Rng, T, O: SwapObserver>(
+    rng: &mut R,
+    data: &mut [T],
+    observer: &mut O,
+) {
     for i in (1..data.len()).rev() {
         let j = uniform_index::gen_index(rng, i + 1);
-        data.swap(i, j);
+        swap(data, i, j);
+        if i != j {
+            observer.swapped(i, j);
+        }
     }
 }
 
@@ -0,0 +1,130 @@
+//! Fisher-Yates kernel for aarch64 using NEON and `prfm` prefetches.
+//!
+//! The prefetching Fisher-Yates variants require the nightly-only `prefetch` feature;
+//! on aarch64, prefetches are issued on the stable channel via inline assembly (see
+//! `crate::prefetch`). The random indices are drawn four at a time: a single NEON
+//! widening multiplication maps four random words to indices, and only if one of them
+//! could be biased, the exact (scalar) rejection test is run. As in the prefetching
+//! variants, indices are drawn [`PREFETCH_WIDTH`] iterations ahead and their elements
+//! are prefetched. The rough shuffle benefits from the `prfm` prefetches as well.
+
+use super::*;
+use crate::error::ShuffleError;
+use crate::prefetch::prefetch_write_data;
+use std::arch::aarch64::*;
+
+/// Number of iterations between drawing an index and swapping with it; a multiple of 4.
+const PREFETCH_WIDTH: usize = 16;
+
+/// Shuffles `data`. Returns [`ShuffleError::InputTooLarge`] and leaves `data`
+/// untouched if it has [`uniform_index::u32_upper_bound`] or more elements.
+pub fn fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T]) -> Result<(), ShuffleError> {
+    if data.len() >= uniform_index::u32_upper_bound() as usize {
+        return Err(ShuffleError::InputTooLarge);
+    }
+
+    // SAFETY: NEON is part of the aarch64 baseline
+    unsafe { fisher_yates_impl(rng, data) };
+    Ok(())
+}
+
+#[target_feature(enable = "neon")]
+unsafe fn fisher_yates_impl<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    let n = data.len();
+    if n <= 2 * PREFETCH_WIDTH {
+        return naive::fisher_yates(rng, data);
+    }
+
+    let ptr = data.as_mut_ptr();
+
+    // the partner of position `p` is stored in `partners[p % PREFETCH_WIDTH]`
+    let mut partners = [0u32; PREFETCH_WIDTH];
+
+    // draws the partners of positions `top - 3..=top` and prefetches them
+    let draw_and_fetch = |rng: &mut R, top: usize, partners: &mut [u32; PREFETCH_WIDTH]| {
+        let ub = top as u32 + 1;
+        let indices = gen_index_quad(rng, [ub, ub - 1, ub - 2, ub - 3]);
+
+        for (k, &j) in indices.iter().enumerate() {
+            partners[(top - k) % PREFETCH_WIDTH] = j;
+            prefetch_write_data(&mut *ptr.add(j as usize));
+        }
+    };
+
+    for top in (n - PREFETCH_WIDTH..n).rev().step_by(4) {
+        draw_and_fetch(rng, top, &mut partners);
+    }
+
+    // invariant: `partners` holds the partners of `p - PREFETCH_WIDTH + 1..=p`
+    let mut p = n - 1;
+    while p >= PREFETCH_WIDTH + 4 {
+        for k in 0..4 {
+            let j = partners[(p - k) % PREFETCH_WIDTH] as usize;
+            std::ptr::swap(ptr.add(p - k), ptr.add(j));
+        }
+
+        draw_and_fetch(rng, p - PREFETCH_WIDTH, &mut partners);
+        p -= 4;
+    }
+
+    let rest = p + 1 - PREFETCH_WIDTH;
+    for q in (rest..=p).rev() {
+        let j = partners[q % PREFETCH_WIDTH] as usize;
+        std::ptr::swap(ptr.add(q), ptr.add(j));
+    }
+
+    naive::fisher_yates(rng, &mut data[..rest]);
+}
+
+/// Returns an index from `0..exclusive_ub[k]` for each `k`, uniformly and
+/// independently (generalizes [`uniform_index::impl_u32::gen_index_pair`]).
+#[target_feature(enable = "neon")]
+unsafe fn gen_index_quad<R: Rng>(rng: &mut R, exclusive_ub: [u32; 4]) -> [u32; 4] {
+    let rand: [u32; 4] = {
+        let (a, b): (u64, u64) = (rng.gen(), rng.gen());
+        [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32]
+    };
+
+    let r = vld1q_u32(rand.as_ptr());
+    let ub = vld1q_u32(exclusive_ub.as_ptr());
+
+    let prod_lo = vmull_u32(vget_low_u32(r), vget_low_u32(ub));
+    let prod_hi = vmull_high_u32(r, ub);
+
+    let indices = vshrn_high_n_u64::<32>(vshrn_n_u64::<32>(prod_lo), prod_hi);
+    let fractions = vmovn_high_u64(vmovn_u64(prod_lo), prod_hi);
+
+    let mut result = [0u32; 4];
+    vst1q_u32(result.as_mut_ptr(), indices);
+
+    // a fraction below the bound is necessary for the index to be biased
+    if vmaxvq_u32(vcltq_u32(fractions, ub)) != 0 {
+        for (idx, (&r, &ub)) in result.iter_mut().zip(rand.iter().zip(&exclusive_ub)) {
+            *idx = uniform_index::impl_u32::gen_index_impl(rng, r, ub);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shuffle<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+        fisher_yates(rng, data).unwrap()
+    }
+
+    crate::statistical_tests::test_shuffle_algorithm!(shuffle);
+    crate::statistical_tests::test_shuffle_algorithm_deterministic!(shuffle);
+
+    #[test]
+    fn index_quad_in_range() {
+        let mut rng = rand::thread_rng();
+        for ub in [1u32, 2, 3, 1000, u32::MAX / 16] {
+            let bounds = [ub, ub + 1, ub + 2, ub + 3];
+            let indices = unsafe { gen_index_quad(&mut rng, bounds) };
+            assert!(indices.iter().zip(&bounds).all(|(i, b)| i < b));
+        }
+    }
+}
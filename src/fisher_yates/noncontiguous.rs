@@ -11,7 +11,11 @@ pub fn noncontiguous_fisher_yates<R: Rng, T>(rng: &mut R, ranges: &mut [&mut [T]
         return;
     }
 
-    let mut max_len = ranges.iter().map(|r| r.len()).max().unwrap();
+    let mut max_len = ranges
+        .iter()
+        .map(|r| r.len())
+        .max()
+        .expect("ranges is not empty");
     let mut max_len_tol = ranges.len() * max_len / 2;
 
     for i_range in (0..ranges.len()).rev() {
@@ -28,7 +32,7 @@ pub fn noncontiguous_fisher_yates<R: Rng, T>(rng: &mut R, ranges: &mut [&mut [T]
                             .take(i_range + 1)
                             .map(|r| r.len())
                             .max()
-                            .unwrap();
+                            .expect("takes at least one range");
                         max_len_tol = (i_range + 1) * max_len / 2;
                     } else {
                         max_len_tol -= 1;
@@ -39,7 +43,14 @@ pub fn noncontiguous_fisher_yates<R: Rng, T>(rng: &mut R, ranges: &mut [&mut [T]
                 let j_range = uniform_index::gen_index(rng, i_range + 1);
                 let j = uniform_index::gen_index(rng, ub + 1);
 
-                if j < ranges[j_range].len() {
+                // positions behind `i` in the current range are already final
+                let is_candidate = if j_range == i_range {
+                    j <= i
+                } else {
+                    j < ranges[j_range].len()
+                };
+
+                if is_candidate {
                     unsafe {
                         let i_ptr = ranges[i_range].as_mut_ptr().add(i);
                         let j_ptr = ranges[j_range].as_mut_ptr().add(j);
@@ -84,4 +95,35 @@ mod test {
     }
 
     test_split!(reject, noncontiguous_fisher_yates);
+
+    #[test]
+    fn long_ranges_are_uniform() {
+        use rand::SeedableRng;
+        use rand_pcg::Pcg64Mcg;
+
+        const N: usize = 8;
+        const RUNS: usize = 80_000;
+
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+        let mut counts = [[0usize; N]; N];
+        for _ in 0..RUNS {
+            let mut data: Vec<usize> = (0..N).collect();
+            let (left, right) = data.split_at_mut(2);
+            noncontiguous_fisher_yates(&mut rng, &mut [left, right]);
+
+            for (pos, &x) in data.iter().enumerate() {
+                counts[x][pos] += 1;
+            }
+        }
+
+        // the expected count is 10000 with a standard deviation of about 94
+        for (x, positions) in counts.iter().enumerate() {
+            for (pos, &count) in positions.iter().enumerate() {
+                assert!(
+                    count.abs_diff(RUNS / N) < RUNS / N / 20,
+                    "x={x} pos={pos} count={count}"
+                );
+            }
+        }
+    }
 }
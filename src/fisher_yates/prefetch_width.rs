@@ -0,0 +1,137 @@
+//! Calibration of the prefetch distance used by the Fisher-Yates implementations.
+//!
+//! The prefetching Fisher-Yates variants draw their random indices ahead of time and
+//! prefetch the corresponding elements; the number of indices in flight needs to cover
+//! the memory latency. Since the latency differs by a factor of 2 to 4 between desktop
+//! DDR4, server DDR5 and HBM machines, no single constant is optimal. Instead, the
+//! latency can be measured once per process with a short pointer chase by calling
+//! [`calibrate`] (e.g., at startup), and the distance is derived from it.
+//!
+//! The calibration allocates a buffer and takes some tens of milliseconds; as the
+//! shuffles themselves are free of heap allocations, it is never started implicitly.
+//! Without calibration, and for inputs shorter than [`MIN_CALIBRATION_LEN`] (which
+//! mostly hit the cache), [`DEFAULT_PREFETCH_WIDTH`] is used.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::fisher_yates::prefetch_width;
+//!
+//! let width = prefetch_width::calibrate();
+//! assert_eq!(prefetch_width::calibrated_prefetch_width(), Some(width));
+//! ```
+
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Prefetch distance used for small inputs (and the value the crate was tuned with).
+pub const DEFAULT_PREFETCH_WIDTH: usize = 16;
+
+/// Smallest and largest prefetch distance that may be selected by the calibration.
+pub const MIN_PREFETCH_WIDTH: usize = 8;
+pub const MAX_PREFETCH_WIDTH: usize = 64;
+
+/// Inputs with fewer elements do not use the calibrated prefetch distance.
+pub const MIN_CALIBRATION_LEN: usize = 1 << 20;
+
+/// Approximate time of one Fisher-Yates iteration if the element is already cached.
+const NS_PER_ITERATION: f64 = 5.0;
+
+static CALIBRATED_WIDTH: OnceLock<usize> = OnceLock::new();
+
+/// Returns the prefetch distance for an input of `len` elements.
+pub fn prefetch_width_for(len: usize) -> usize {
+    match calibrated_prefetch_width() {
+        Some(width) if len >= MIN_CALIBRATION_LEN => width,
+        _ => DEFAULT_PREFETCH_WIDTH,
+    }
+}
+
+/// Measures the memory latency (only in the first call) and returns the prefetch
+/// distance derived from it, a power of two between [`MIN_PREFETCH_WIDTH`] and
+/// [`MAX_PREFETCH_WIDTH`]. The distance is used by all subsequent shuffles.
+pub fn calibrate() -> usize {
+    *CALIBRATED_WIDTH.get_or_init(|| width_for_latency(measure_memory_latency_ns()))
+}
+
+/// Returns the prefetch distance determined by [`calibrate`], if it was called before.
+pub fn calibrated_prefetch_width() -> Option<usize> {
+    CALIBRATED_WIDTH.get().copied()
+}
+
+fn width_for_latency(latency_ns: f64) -> usize {
+    let iterations = (latency_ns / NS_PER_ITERATION).ceil();
+    if !iterations.is_finite() || iterations <= MIN_PREFETCH_WIDTH as f64 {
+        return MIN_PREFETCH_WIDTH;
+    }
+
+    (iterations as usize)
+        .next_power_of_two()
+        .min(MAX_PREFETCH_WIDTH)
+}
+
+/// Estimates the latency of a random access to main memory via a chain of dependent
+/// loads into a buffer that exceeds common last level caches.
+fn measure_memory_latency_ns() -> f64 {
+    const LOG_LEN: usize = 23;
+    const MASK: usize = (1 << LOG_LEN) - 1;
+    const STEPS: usize = 1 << 16;
+
+    // the buffer needs to be written, otherwise all pages may map to the zero page
+    let buffer: Vec<u32> = (0..1u64 << LOG_LEN).map(|i| splitmix(i) as u32).collect();
+
+    // adding the step counter prevents the chain from running into a short cycle
+    let mut idx = 0usize;
+    let start = Instant::now();
+    for step in 0..STEPS {
+        idx = (buffer[idx] as usize).wrapping_add(step) & MASK;
+    }
+    std::hint::black_box(idx);
+
+    start.elapsed().as_nanos() as f64 / STEPS as f64
+}
+
+fn splitmix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Calls `$func::<W>($args)` with `W` being the prefetch distance for an input of `$len`
+/// elements.
+macro_rules! dispatch_prefetch_width {
+    ($len : expr, $func : ident :: < $($gen : ty),* > ( $($args : expr),* )) => {
+        match crate::fisher_yates::prefetch_width::prefetch_width_for($len) {
+            8 => $func::<$($gen,)* 8>($($args),*),
+            32 => $func::<$($gen,)* 32>($($args),*),
+            64 => $func::<$($gen,)* 64>($($args),*),
+            _ => $func::<$($gen,)* 16>($($args),*),
+        }
+    };
+}
+
+pub(crate) use dispatch_prefetch_width;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn width_for_latency() {
+        assert_eq!(super::width_for_latency(0.0), MIN_PREFETCH_WIDTH);
+        assert_eq!(super::width_for_latency(f64::NAN), MIN_PREFETCH_WIDTH);
+        assert_eq!(super::width_for_latency(80.0), 16);
+        assert_eq!(super::width_for_latency(120.0), 32);
+        assert_eq!(super::width_for_latency(1e6), MAX_PREFETCH_WIDTH);
+    }
+
+    #[test]
+    fn calibration() {
+        let width = calibrate();
+        assert_eq!(calibrated_prefetch_width(), Some(width));
+        assert!(width.is_power_of_two());
+        assert!((MIN_PREFETCH_WIDTH..=MAX_PREFETCH_WIDTH).contains(&width));
+        assert_eq!(prefetch_width_for(MIN_CALIBRATION_LEN), width);
+        assert_eq!(prefetch_width_for(1000), DEFAULT_PREFETCH_WIDTH);
+    }
+}
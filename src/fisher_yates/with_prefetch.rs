@@ -1,10 +1,10 @@
+use super::prefetch_width::dispatch_prefetch_width;
 use super::*;
+use crate::swap::swap;
 use std::intrinsics::prefetch_write_data;
 
-const DEFAULT_PREFETCH_WIDTH: usize = 16;
-
 pub fn fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T]) {
-    if data.len() < uniform_index::U32_MAX_UPPER_BOUND as usize {
+    if data.len() < uniform_index::u32_upper_bound() as usize {
         fisher_yates_u32(rng, data);
     } else {
         fisher_yates_u64(rng, data);
@@ -12,19 +12,15 @@ pub fn fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T]) {
 }
 
 pub fn fisher_yates_u32<R: Rng, T>(rng: &mut R, data: &mut [T]) {
-    fisher_yates_impl::<R, T, _, DEFAULT_PREFETCH_WIDTH>(
-        rng,
-        |rng: &mut R, ub: usize| uniform_index::impl_u32::gen_index(rng, ub as u32) as usize,
-        data,
-    );
+    let distr =
+        |rng: &mut R, ub: usize| uniform_index::impl_u32::gen_index(rng, ub as u32) as usize;
+    dispatch_prefetch_width!(data.len(), fisher_yates_impl::<R, T, _>(rng, distr, data));
 }
 
 pub fn fisher_yates_u64<R: Rng, T>(rng: &mut R, data: &mut [T]) {
-    fisher_yates_impl::<R, T, _, DEFAULT_PREFETCH_WIDTH>(
-        rng,
-        |rng: &mut R, ub: usize| uniform_index::impl_u64::gen_index(rng, ub as u64) as usize,
-        data,
-    );
+    let distr =
+        |rng: &mut R, ub: usize| uniform_index::impl_u64::gen_index(rng, ub as u64) as usize;
+    dispatch_prefetch_width!(data.len(), fisher_yates_impl::<R, T, _>(rng, distr, data));
 }
 
 fn fisher_yates_impl<R: Rng, T, D: Fn(&mut R, usize) -> usize, const PREFETCH_WIDTH: usize>(
@@ -54,7 +50,7 @@ fn fisher_yates_impl<R: Rng, T, D: Fn(&mut R, usize) -> usize, const PREFETCH_WI
     let mut draw_and_fetch = |data: &[T], ub: usize| -> usize {
         let new_idx = distr(rng, ub);
         const LOCALITY: i32 = 1;
-        unsafe { prefetch_write_data(data.as_ptr().add(new_idx), LOCALITY) };
+        unsafe { prefetch_write_data::<_, LOCALITY>(data.as_ptr().add(new_idx)) };
         new_idx
     };
 
@@ -64,12 +60,12 @@ fn fisher_yates_impl<R: Rng, T, D: Fn(&mut R, usize) -> usize, const PREFETCH_WI
 
     for i in (PREFETCH_WIDTH + 1..n).rev() {
         let j = enqueue(draw_and_fetch(data, i - PREFETCH_WIDTH + 1));
-        data.swap(i, j);
+        swap(data, i, j);
     }
 
     for i in (1..PREFETCH_WIDTH + 1).rev() {
         let j = enqueue(0);
-        data.swap(i, j);
+        swap(data, i, j);
     }
 }
 
@@ -1,12 +1,18 @@
+use super::prefetch_width::dispatch_prefetch_width;
 use super::*;
+use crate::error::ShuffleError;
 use std::intrinsics::prefetch_write_data;
-
-const DEFAULT_PREFETCH_WIDTH: usize = 16;
 const LOCALITY: i32 = 1;
 
-pub fn fisher_yates_u32<R: Rng, T>(rng: &mut R, data: &mut [T]) {
-    assert!(data.len() < u32::MAX as usize);
-    fisher_yates_impl::<R, T, DEFAULT_PREFETCH_WIDTH>(rng, data)
+/// Fisher-Yates variant using 32-bit indices. Returns [`ShuffleError::InputTooLarge`]
+/// and leaves `data` untouched if it has `u32::MAX` or more elements.
+pub fn fisher_yates_u32<R: Rng, T>(rng: &mut R, data: &mut [T]) -> Result<(), ShuffleError> {
+    if data.len() >= u32::MAX as usize {
+        return Err(ShuffleError::InputTooLarge);
+    }
+
+    dispatch_prefetch_width!(data.len(), fisher_yates_impl::<R, T>(rng, data));
+    Ok(())
 }
 
 pub fn fisher_yates_impl<R: Rng, T, const PREFETCH_WIDTH: usize>(rng: &mut R, data: &mut [T]) {
@@ -37,7 +43,7 @@ pub fn fisher_yates_impl<R: Rng, T, const PREFETCH_WIDTH: usize>(rng: &mut R, da
     // generate new random index and prefetch its address
     let draw_and_fetch_init = |rng: &mut R, data: &[T], initial: u32, ub: usize| -> usize {
         let new_idx = uniform_index::impl_u32::gen_index_impl(rng, initial, ub as u32) as usize;
-        unsafe { prefetch_write_data(data.as_ptr().add(new_idx), LOCALITY) };
+        unsafe { prefetch_write_data::<_, LOCALITY>(data.as_ptr().add(new_idx)) };
         new_idx
     };
 
@@ -82,8 +88,12 @@ pub fn fisher_yates_impl<R: Rng, T, const PREFETCH_WIDTH: usize>(rng: &mut R, da
 
 #[cfg(test)]
 mod test {
-    use super::fisher_yates_u32;
+    use super::*;
+
+    fn shuffle_u32<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+        fisher_yates_u32(rng, data).unwrap()
+    }
 
-    crate::statistical_tests::test_shuffle_algorithm!(fisher_yates_u32);
-    crate::statistical_tests::test_shuffle_algorithm_deterministic!(fisher_yates_u32);
+    crate::statistical_tests::test_shuffle_algorithm!(shuffle_u32);
+    crate::statistical_tests::test_shuffle_algorithm_deterministic!(shuffle_u32);
 }
@@ -0,0 +1,199 @@
+//! [`arbitrary`] integration for fuzzing the configuration space.
+//!
+//! The configuration types ([`Preset`], [`FyVariant`], and [`MergeConfiguration`])
+//! implement [`arbitrary::Arbitrary`]. A [`FuzzCase`] combines them with an input
+//! length and a seed, and [`shuffle_arbitrary`] executes the case and panics if the
+//! output is not a permutation of the input. Settings that the builders reject (e.g.,
+//! an unsupported number of buckets) are skipped, as are variants that are not
+//! available in the current build.
+//!
+//! # Example
+//! A [`cargo fuzz`](https://github.com/rust-fuzz/cargo-fuzz) target reads:
+//! ```ignore
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|bytes: &[u8]| rip_shuffle::fuzzing::shuffle_arbitrary(bytes));
+//! ```
+
+use super::error::ShuffleError;
+use super::fisher_yates::{fisher_yates_with, FyVariant};
+use super::merge_shuffle::MergeConfiguration;
+use super::plan::{Preset, ShufflePlan};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Largest input length of a [`FuzzCase`]. The elements have 8 bytes, so the longest
+/// inputs exceed [`crate::scatter_shuffle::parallel::SEQUENTIAL_MAX_BYTES`] and reach
+/// the parallel scatter shuffle.
+pub const MAX_LEN: usize = 1 << 21;
+
+impl<'a> Arbitrary<'a> for Preset {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[
+            Preset::Throughput,
+            Preset::LowMemory,
+            Preset::LowLatency,
+            Preset::Deterministic,
+        ])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for FyVariant {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&FyVariant::ALL)?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for MergeConfiguration {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let base_case_bytes = u.int_in_range(1..=1 << 22)?;
+        Ok(MergeConfiguration::default()
+            .with_base_case_bytes(base_case_bytes)
+            .unwrap())
+    }
+}
+
+/// The algorithm and settings executed by a [`FuzzCase`].
+#[derive(Clone, Copy, Debug, Arbitrary)]
+pub enum FuzzAlgorithm {
+    /// A [`ShufflePlan`] with the given overrides, which are applied in this order.
+    Plan {
+        parallel: bool,
+        preset: Preset,
+        base_case_size: Option<u32>,
+        num_buckets: Option<u16>,
+        max_subproblems: Option<u16>,
+    },
+    FisherYates(FyVariant),
+    Merge {
+        config: MergeConfiguration,
+        parallel: bool,
+    },
+    Dart,
+}
+
+/// An input length, a seed, and an algorithm to shuffle with; see [`shuffle_arbitrary`].
+#[derive(Clone, Copy, Debug)]
+pub struct FuzzCase {
+    pub len: usize,
+    pub seed: u64,
+    pub algorithm: FuzzAlgorithm,
+}
+
+impl<'a> Arbitrary<'a> for FuzzCase {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            len: u.int_in_range(0..=MAX_LEN)?,
+            seed: u.arbitrary()?,
+            algorithm: u.arbitrary()?,
+        })
+    }
+}
+
+impl FuzzCase {
+    /// Shuffles `0..len` as configured and returns the output, or the error reported by
+    /// a builder or by [`fisher_yates_with`].
+    pub fn run(&self) -> std::result::Result<Vec<u64>, ShuffleError> {
+        let mut data: Vec<u64> = (0..self.len as u64).collect();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        match self.algorithm {
+            FuzzAlgorithm::Plan {
+                parallel,
+                preset,
+                base_case_size,
+                num_buckets,
+                max_subproblems,
+            } => {
+                let mut plan = if parallel {
+                    ShufflePlan::parallel::<u64>(self.len)
+                } else {
+                    ShufflePlan::sequential::<u64>(self.len)
+                }
+                .with_preset(preset);
+
+                if let Some(size) = base_case_size {
+                    plan = plan.with_base_case_size(size as usize)?;
+                }
+                if let Some(num) = num_buckets {
+                    plan = plan.with_num_buckets(num as usize)?;
+                }
+                if let Some(num) = max_subproblems {
+                    plan = plan.with_max_subproblems(num as usize)?;
+                }
+
+                plan.shuffle(&mut rng, &mut data);
+            }
+            FuzzAlgorithm::FisherYates(variant) => fisher_yates_with(variant, &mut rng, &mut data)?,
+            FuzzAlgorithm::Merge { config, parallel } => {
+                if parallel {
+                    config.par_shuffle(&mut rng, &mut data);
+                } else {
+                    config.seq_shuffle(&mut rng, &mut data);
+                }
+            }
+            FuzzAlgorithm::Dart => crate::dart_shuffle::par_dart_shuffle(&mut rng, &mut data),
+        }
+
+        Ok(data)
+    }
+}
+
+/// Fuzzing entry point: decodes a [`FuzzCase`] from `bytes`, executes it, and panics
+/// if the output is not a permutation of the input. Cases that cannot be decoded or
+/// that are rejected by the builders are ignored.
+pub fn shuffle_arbitrary(bytes: &[u8]) {
+    let Ok(case) = FuzzCase::arbitrary_take_rest(Unstructured::new(bytes)) else {
+        return;
+    };
+
+    let Ok(output) = case.run() else {
+        return;
+    };
+
+    let mut seen = vec![false; case.len];
+    for &x in &output {
+        assert!(
+            !std::mem::replace(&mut seen[x as usize], true),
+            "{case:?} duplicated {x}"
+        );
+    }
+    assert_eq!(output.len(), case.len, "{case:?}");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn runs_arbitrary_cases() {
+        let mut bytes = [0u8; 64];
+        for seed in 0..200u64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            rand::RngCore::fill_bytes(&mut rng, &mut bytes);
+
+            // keep the inputs short to save time
+            bytes[..2].fill(0);
+            shuffle_arbitrary(&bytes);
+        }
+
+        shuffle_arbitrary(&[]);
+    }
+
+    #[test]
+    fn reports_rejected_settings() {
+        let case = FuzzCase {
+            len: 100,
+            seed: 1,
+            algorithm: FuzzAlgorithm::Plan {
+                parallel: false,
+                preset: Preset::Throughput,
+                base_case_size: Some(0),
+                num_buckets: None,
+                max_subproblems: None,
+            },
+        };
+
+        assert_eq!(case.run().err(), Some(ShuffleError::ZeroBaseCaseSize));
+    }
+}
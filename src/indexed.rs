@@ -0,0 +1,357 @@
+//! Shuffling containers that do not expose a contiguous slice.
+//!
+//! Memory-mapped structures, chunked arenas, or columnar stores often cannot hand out
+//! a `&mut [T]` of their elements. Such containers only need to implement
+//! [`IndexedSwap`] to be shuffled with [`fisher_yates_indexed`] or
+//! [`merge_shuffle_indexed`]. Both only access the container through its
+//! [`IndexedSwap::swap`] method, which may for instance swap the `i`-th entries of
+//! several columns at once.
+//!
+//! The generic shuffles are sequential and considerably slower than their slice-based
+//! counterparts, which exploit the contiguous layout for prefetching and parallelism.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::indexed::{merge_shuffle_indexed, IndexedSwap};
+//!
+//! // two columns that need to be shuffled consistently
+//! struct Columns {
+//!     ids: Vec<u32>,
+//!     names: Vec<String>,
+//! }
+//!
+//! impl IndexedSwap for Columns {
+//!     fn len(&self) -> usize {
+//!         self.ids.len()
+//!     }
+//!
+//!     fn swap(&mut self, i: usize, j: usize) {
+//!         self.ids.swap(i, j);
+//!         self.names.swap(i, j);
+//!     }
+//! }
+//!
+//! let mut columns = Columns {
+//!     ids: (0..100).collect(),
+//!     names: (0..100).map(|i| format!("name{i}")).collect(),
+//! };
+//!
+//! merge_shuffle_indexed(&mut rand::thread_rng(), &mut columns);
+//!
+//! for (id, name) in columns.ids.iter().zip(&columns.names) {
+//!     assert_eq!(format!("name{id}"), *name);
+//! }
+//! ```
+
+use super::merge_shuffle::FY_BASE_CASE;
+use super::random_bits::RandomBitsSource;
+use super::uniform_index;
+use rand::Rng;
+use std::collections::VecDeque;
+
+/// Number of random indices that [`fisher_yates_indexed`] draws ahead of time and
+/// passes to [`IndexedSwap::prefetch`].
+const PREFETCH_WIDTH: usize = 16;
+
+/// A random-access container whose elements can be swapped by index.
+pub trait IndexedSwap {
+    /// Returns the number of elements.
+    fn len(&self) -> usize;
+
+    /// Returns true if the container has no elements.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Swaps the elements at positions `i` and `j`; both are smaller than
+    /// [`IndexedSwap::len`], but may be equal.
+    fn swap(&mut self, i: usize, j: usize);
+
+    /// Hints that the element at position `i` will be swapped soon. The default
+    /// implementation does nothing.
+    fn prefetch(&self, _i: usize) {}
+}
+
+impl<T> IndexedSwap for [T] {
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        <[T]>::swap(self, i, j)
+    }
+}
+
+impl<T> IndexedSwap for Vec<T> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.as_mut_slice().swap(i, j)
+    }
+}
+
+impl<T> IndexedSwap for VecDeque<T> {
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        VecDeque::swap(self, i, j)
+    }
+}
+
+impl<C: IndexedSwap + ?Sized> IndexedSwap for &mut C {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        (**self).swap(i, j)
+    }
+
+    fn prefetch(&self, i: usize) {
+        (**self).prefetch(i)
+    }
+}
+
+/// Shuffles `data` with the Fisher-Yates algorithm. The random indices are drawn
+/// some iterations ahead and announced via [`IndexedSwap::prefetch`]. For slices, the
+/// result is identical to [`crate::fisher_yates::naive::fisher_yates`] with the same
+/// random number generator.
+pub fn fisher_yates_indexed<R: Rng, C: IndexedSwap + ?Sized>(rng: &mut R, data: &mut C) {
+    fisher_yates_range(rng, data, 0, data.len());
+}
+
+/// Shuffles `data` with the merge shuffle, i.e. both halves are shuffled recursively
+/// and then randomly merged. In contrast to [`fisher_yates_indexed`], most swaps
+/// happen between nearby positions, which benefits containers with expensive random
+/// accesses.
+pub fn merge_shuffle_indexed<R: Rng, C: IndexedSwap + ?Sized>(rng: &mut R, data: &mut C) {
+    if crate::oracle::is_enabled() {
+        return fisher_yates_indexed(rng, data);
+    }
+
+    merge_shuffle_range::<R, C, FY_BASE_CASE>(rng, data, 0, data.len());
+}
+
+fn merge_shuffle_range<R: Rng, C: IndexedSwap + ?Sized, const BASE_CASE: usize>(
+    rng: &mut R,
+    data: &mut C,
+    begin: usize,
+    end: usize,
+) {
+    debug_assert!(BASE_CASE >= 2);
+
+    if end - begin < BASE_CASE {
+        return fisher_yates_range(rng, data, begin, end);
+    }
+
+    let mid = begin + (end - begin) / 2;
+    merge_shuffle_range::<R, C, BASE_CASE>(rng, data, begin, mid);
+    merge_shuffle_range::<R, C, BASE_CASE>(rng, data, mid, end);
+
+    let num_merged = rough_random_merge(rng, data, begin, mid, end);
+    for left in num_merged..end {
+        let partner = begin + uniform_index::gen_index(rng, end - left);
+        data.swap(left, partner);
+    }
+}
+
+/// Index-based counterpart of the safe rough merge of [`crate::merge_shuffle`]; returns
+/// the position up to which `begin..end` is merged.
+fn rough_random_merge<R: Rng, C: IndexedSwap + ?Sized>(
+    rng: &mut R,
+    data: &mut C,
+    mut begin: usize,
+    mut mid: usize,
+    end: usize,
+) -> usize {
+    let mut rbs = RandomBitsSource::default();
+
+    loop {
+        if rbs.gen_bool(rng) {
+            if mid == end {
+                break;
+            }
+
+            data.swap(begin, mid);
+            mid += 1;
+        } else if begin == mid {
+            break;
+        }
+
+        begin += 1;
+    }
+
+    begin
+}
+
+fn fisher_yates_range<R: Rng, C: IndexedSwap + ?Sized>(
+    rng: &mut R,
+    data: &mut C,
+    begin: usize,
+    end: usize,
+) {
+    let n = end - begin;
+    if n < 2 {
+        return;
+    }
+
+    let mut draw_and_fetch = |data: &C, i: usize| -> usize {
+        let j = begin + uniform_index::gen_index(rng, i + 1);
+        data.prefetch(j);
+        j
+    };
+
+    // the partner of `begin + i` is stored in `partners[i % PREFETCH_WIDTH]`
+    let mut partners = [0usize; PREFETCH_WIDTH];
+    for i in (n.saturating_sub(PREFETCH_WIDTH).max(1)..n).rev() {
+        partners[i % PREFETCH_WIDTH] = draw_and_fetch(data, i);
+    }
+
+    for i in (1..n).rev() {
+        let j = partners[i % PREFETCH_WIDTH];
+        if i > PREFETCH_WIDTH {
+            partners[i % PREFETCH_WIDTH] = draw_and_fetch(data, i - PREFETCH_WIDTH);
+        }
+        data.swap(begin + i, j);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+    use std::cell::Cell;
+
+    #[test]
+    fn fisher_yates_matches_naive() {
+        for n in [0, 1, 2, PREFETCH_WIDTH, PREFETCH_WIDTH + 1, 1000] {
+            let mut data: Vec<usize> = (0..n).collect();
+            fisher_yates_indexed(&mut Pcg64Mcg::seed_from_u64(n as u64), &mut data);
+
+            let mut reference: Vec<usize> = (0..n).collect();
+            crate::fisher_yates::naive::fisher_yates(
+                &mut Pcg64Mcg::seed_from_u64(n as u64),
+                &mut reference,
+            );
+
+            assert_eq!(data, reference);
+        }
+    }
+
+    #[test]
+    fn prefetch_is_called() {
+        struct Counting<'a> {
+            data: Vec<u32>,
+            prefetches: &'a Cell<usize>,
+        }
+
+        impl IndexedSwap for Counting<'_> {
+            fn len(&self) -> usize {
+                self.data.len()
+            }
+
+            fn swap(&mut self, i: usize, j: usize) {
+                self.data.swap(i, j)
+            }
+
+            fn prefetch(&self, i: usize) {
+                assert!(i < self.data.len());
+                self.prefetches.set(self.prefetches.get() + 1);
+            }
+        }
+
+        let prefetches = Cell::new(0);
+        let mut data = Counting {
+            data: (0..100).collect(),
+            prefetches: &prefetches,
+        };
+
+        fisher_yates_indexed(&mut Pcg64Mcg::seed_from_u64(1), &mut data);
+        assert_eq!(prefetches.get(), 99);
+    }
+
+    /// Stores the elements in chunks of (at most) three elements.
+    struct Chunked<T> {
+        chunks: Vec<Vec<T>>,
+    }
+
+    impl<T> Chunked<T> {
+        fn new(data: &mut [T]) -> Self
+        where
+            T: Clone,
+        {
+            Self {
+                chunks: data.chunks(3).map(|c| c.to_vec()).collect(),
+            }
+        }
+
+        fn write_back(self, data: &mut [T]) {
+            for (x, y) in data.iter_mut().zip(self.chunks.into_iter().flatten()) {
+                *x = y;
+            }
+        }
+    }
+
+    impl<T> IndexedSwap for Chunked<T> {
+        fn len(&self) -> usize {
+            self.chunks.iter().map(|c| c.len()).sum()
+        }
+
+        fn swap(&mut self, i: usize, j: usize) {
+            let (i, j) = (i.min(j), i.max(j));
+            if i / 3 == j / 3 {
+                self.chunks[i / 3].swap(i % 3, j % 3);
+            } else {
+                let (lower, upper) = self.chunks.split_at_mut(j / 3);
+                std::mem::swap(&mut lower[i / 3][i % 3], &mut upper[0][j % 3]);
+            }
+        }
+    }
+
+    mod fisher_yates {
+        use super::*;
+
+        fn shuffle<R: Rng, T: Clone>(rng: &mut R, data: &mut [T]) {
+            let mut chunked = Chunked::new(data);
+            fisher_yates_indexed(rng, &mut chunked);
+            chunked.write_back(data);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle);
+    }
+
+    mod merge_shuffle {
+        use super::*;
+
+        fn shuffle<R: Rng, T: Clone>(rng: &mut R, data: &mut [T]) {
+            let mut chunked = Chunked::new(data);
+            let n = chunked.len();
+            merge_shuffle_range::<R, _, 4>(rng, &mut chunked, 0, n);
+            chunked.write_back(data);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle);
+    }
+
+    mod vec_deque {
+        use super::*;
+
+        fn shuffle<R: Rng, T: Clone>(rng: &mut R, data: &mut [T]) {
+            // rotating the deque makes its storage wrap around
+            let mut deque: VecDeque<T> = data.iter().cloned().collect();
+            deque.rotate_left(data.len() / 3);
+            merge_shuffle_range::<R, _, 2>(rng, &mut deque, 0, data.len());
+
+            for (x, y) in data.iter_mut().zip(deque) {
+                *x = y;
+            }
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle);
+    }
+}
@@ -0,0 +1,183 @@
+//! Shuffling of large elements via an index array.
+//!
+//! The scatter shuffle moves each element several times (into its bucket, within the
+//! stashes, and in the base case). For large elements, it can be faster to shuffle an
+//! array of indices instead and to move each element once into its final position
+//! by following the cycles of the permutation. This allocates `4n` bytes for the
+//! indices, hence [`crate::RipShuffleSequential::seq_shuffle`] only does so if the
+//! strategy was switched on with [`enable`]: then, elements of at least
+//! [`MIN_ELEMENT_SIZE`] bytes are shuffled via an index array if the unsafe
+//! algorithms are enabled. Fixed-capacity containers (e.g., `ArrayVec`) never use it.
+//! Run `cargo bench --bench indirection` to compare both strategies on your machine.
+//!
+//! The output is a uniform random permutation either way, but not the same one:
+//! the output for a given seed changes when switching.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::indirection;
+//! use rand::SeedableRng;
+//!
+//! let mut data: Vec<[u64; 64]> = (0..10_000).map(|i| [i; 64]).collect();
+//! indirection::seq_shuffle_indirect(&mut rand_pcg::Pcg64::seed_from_u64(1), &mut data);
+//!
+//! assert_ne!(data[0][0], 0); // might fail with probability 1 / 10000
+//! ```
+
+use super::scatter_shuffle::sequential::seq_scatter_shuffle;
+use rand::Rng;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Elements of at least this many bytes are shuffled via an index array, if the
+/// automatic indirection is enabled.
+pub const MIN_ELEMENT_SIZE: usize = 128;
+
+/// Inputs with fewer elements are shuffled directly, since they are cheap anyway.
+pub const MIN_LEN: usize = 1 << 10;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enables the automatic indirection for all subsequent calls (in all threads).
+/// Then, [`crate::RipShuffleSequential::seq_shuffle`] allocates an index array for
+/// large elements.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Disables the automatic indirection (the default). Then,
+/// [`crate::RipShuffleSequential::seq_shuffle`] performs no heap allocations for any
+/// element type.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Returns whether the automatic indirection is enabled.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns whether [`crate::RipShuffleSequential::seq_shuffle`] shuffles `len` elements
+/// of type `T` via an index array. This requires the unsafe algorithms (see
+/// [`crate::unsafe_algos`]).
+pub fn is_applicable<T>(len: usize) -> bool {
+    size_of::<T>() >= MIN_ELEMENT_SIZE
+        && len >= MIN_LEN
+        && u32::try_from(len).is_ok()
+        && is_enabled()
+        && crate::unsafe_algos::is_enabled()
+        && !crate::oracle::is_enabled()
+}
+
+/// Shuffles `data` by shuffling an index array with [`seq_scatter_shuffle`] and
+/// moving each element into its final position afterwards (independently of the
+/// element size and of [`is_enabled`]). Without the unsafe algorithms (see
+/// [`crate::unsafe_algos`]), the elements are swapped rather than moved, which is
+/// typically slower than shuffling them directly.
+///
+/// # Panics
+/// If `data` has `2^32` or more elements.
+pub fn seq_shuffle_indirect<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    let n = u32::try_from(data.len()).expect("indirection supports less than 2^32 elements");
+
+    let mut order: Vec<u32> = (0..n).collect();
+    seq_scatter_shuffle(rng, &mut order);
+
+    #[cfg(feature = "unsafe_algos")]
+    if crate::unsafe_algos::is_enabled() {
+        return unsafe_algos::move_in_place(data, &mut order);
+    }
+
+    apply_in_place(data, &mut order);
+}
+
+/// Rearranges `data` such that position `i` receives the element at `order[i]` by
+/// following the cycles of the permutation. `order` is overwritten.
+pub(crate) fn apply_in_place<T>(data: &mut [T], order: &mut [u32]) {
+    for start in 0..order.len() {
+        let mut k = start;
+        while order[k] as usize != start {
+            let source = order[k] as usize;
+            data.swap(k, source);
+            order[k] = k as u32;
+            k = source;
+        }
+        order[k] = k as u32;
+    }
+}
+
+#[cfg(feature = "unsafe_algos")]
+mod unsafe_algos {
+    /// Same as [`super::apply_in_place`], but moves each element only once instead of
+    /// swapping it. `order` needs to be a permutation of `0..data.len()`.
+    pub(super) fn move_in_place<T>(data: &mut [T], order: &mut [u32]) {
+        assert_eq!(data.len(), order.len());
+        let ptr = data.as_mut_ptr();
+
+        for start in 0..order.len() {
+            if order[start] as usize == start {
+                continue;
+            }
+
+            // SAFETY: all indices are in bounds; each cycle is traversed once, the hole
+            // at `start` moves along the cycle and is finally filled with `tmp`. Nothing
+            // in between can panic, so no element is dropped twice.
+            unsafe {
+                let tmp = std::ptr::read(ptr.add(start));
+                let mut k = start;
+                loop {
+                    let source = *order.get_unchecked(k) as usize;
+                    *order.get_unchecked_mut(k) = k as u32;
+                    if source == start {
+                        std::ptr::write(ptr.add(k), tmp);
+                        break;
+                    }
+                    std::ptr::copy_nonoverlapping(ptr.add(source), ptr.add(k), 1);
+                    k = source;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn apply_in_place_matches_gather() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        let data: Vec<usize> = (0..10_000).map(|x| 7 * x).collect();
+        let mut order: Vec<u32> = (0..data.len() as u32).collect();
+        seq_scatter_shuffle(&mut rng, &mut order);
+
+        let expected: Vec<usize> = order.iter().map(|&i| data[i as usize]).collect();
+
+        let mut actual = data.clone();
+        apply_in_place(&mut actual, &mut order.clone());
+        assert_eq!(actual, expected);
+
+        #[cfg(feature = "unsafe_algos")]
+        {
+            let mut actual: Vec<String> = data.iter().map(|x| x.to_string()).collect();
+            unsafe_algos::move_in_place(&mut actual, &mut order);
+            assert!(actual
+                .iter()
+                .zip(&expected)
+                .all(|(a, e)| *a == e.to_string()));
+        }
+    }
+
+    #[test]
+    fn applicability() {
+        assert!(!is_applicable::<u64>(1 << 20));
+        assert!(!is_applicable::<[u8; 1024]>(MIN_LEN - 1));
+    }
+
+    mod statistical {
+        use super::super::seq_shuffle_indirect;
+        crate::statistical_tests::test_shuffle_algorithm!(seq_shuffle_indirect);
+    }
+}
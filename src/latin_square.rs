@@ -0,0 +1,384 @@
+//! Random Latin squares and balanced grid shuffles.
+//!
+//! Experimental designs often assign treatments to a grid of, e.g., subjects and
+//! periods such that every treatment appears exactly once in each row and column. A
+//! random such assignment is a random Latin square, which [`random_latin_square`]
+//! samples approximately uniformly with the Markov chain of Jacobson and Matthews
+//! ("Generating uniformly distributed random Latin squares", J. Comb. Des. 1996).
+//! The chain starts from a cyclic square with shuffled rows, columns and symbols.
+//!
+//! [`shuffle_grid`] rearranges an existing grid by a random permutation of its rows
+//! and an independent one of its columns. This keeps the contents of each row and of
+//! each column (as multisets), so a balanced design remains balanced.
+
+use super::error::ShuffleError;
+use super::fisher_yates::fisher_yates;
+use super::uniform_index;
+use rand::Rng;
+
+/// Marks an unused entry of a [`Line`].
+const NONE: u32 = u32::MAX;
+
+/// Returns a random Latin square of order `n` in row-major order, i.e. the entry in
+/// row `r` and column `c` is stored at index `r * n + c`. Each of the symbols `0..n`
+/// appears exactly once in each row and column.
+///
+/// The Markov chain runs until it visited `n^3` (proper) Latin squares, which is a
+/// common choice in practice; rigorous mixing time bounds are unknown. See
+/// [`random_latin_square_with_steps`] to choose a different number of steps.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::latin_square::random_latin_square;
+///
+/// let n = 5;
+/// let square = random_latin_square(&mut rand::thread_rng(), n);
+///
+/// for r in 0..n {
+///     let mut row: Vec<_> = square[r * n..][..n].to_vec();
+///     row.sort();
+///     assert_eq!(row, vec![0, 1, 2, 3, 4]);
+/// }
+/// ```
+pub fn random_latin_square<R: Rng>(rng: &mut R, n: usize) -> Vec<usize> {
+    random_latin_square_with_steps(rng, n, n.saturating_mul(n).saturating_mul(n))
+}
+
+/// Same as [`random_latin_square`], but runs the Markov chain until it visited `steps`
+/// Latin squares. With zero steps, the result is a uniformly random isotope of the cyclic
+/// square, i.e. an addition table of `Z_n` with shuffled rows, columns and symbols.
+pub fn random_latin_square_with_steps<R: Rng>(rng: &mut R, n: usize, steps: usize) -> Vec<usize> {
+    let mut rows: Vec<usize> = (0..n).collect();
+    let mut cols: Vec<usize> = (0..n).collect();
+    let mut syms: Vec<usize> = (0..n).collect();
+    fisher_yates(rng, &mut rows);
+    fisher_yates(rng, &mut cols);
+    fisher_yates(rng, &mut syms);
+
+    let mut cube = IncidenceCube::new(n);
+    for r in 0..n {
+        for c in 0..n {
+            cube.set_one(rows[r], cols[c], syms[(r + c) % n]);
+        }
+    }
+
+    // every square of order two or less is an isotope of the cyclic one
+    if n > 2 {
+        // Only steps that end in a proper square are counted: the chain observed at
+        // these steps has the uniform stationary distribution. Stopping at the first
+        // proper square after a fixed number of steps would favor squares that are
+        // entered from improper ones more often.
+        let mut proper_steps = 0;
+        while proper_steps < steps {
+            cube.step(rng);
+            proper_steps += cube.improper.is_none() as usize;
+        }
+    }
+
+    (0..n * n).map(|i| cube.by_row_col[i][0] as usize).collect()
+}
+
+/// Randomly permutes the rows and, independently, the columns of the `width`-column
+/// grid stored in row-major order in `grid`. All arrangements reachable this way are
+/// equally likely.
+///
+/// # Panics
+/// Panics if `grid.len()` is not a multiple of `width`; see [`try_shuffle_grid`] for a
+/// non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::latin_square::shuffle_grid;
+///
+/// let mut grid = vec![
+///     0, 1, 2,
+///     1, 2, 0,
+///     2, 0, 1,
+/// ];
+///
+/// shuffle_grid(&mut rand::thread_rng(), &mut grid, 3);
+///
+/// // still a Latin square
+/// for i in 0..3 {
+///     let mut row: Vec<_> = grid[3 * i..][..3].to_vec();
+///     let mut col: Vec<_> = grid.iter().skip(i).step_by(3).copied().collect();
+///     row.sort();
+///     col.sort();
+///     assert_eq!(row, vec![0, 1, 2]);
+///     assert_eq!(col, vec![0, 1, 2]);
+/// }
+/// ```
+pub fn shuffle_grid<R: Rng, T>(rng: &mut R, grid: &mut [T], width: usize) {
+    try_shuffle_grid(rng, grid, width).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`shuffle_grid`], but returns [`ShuffleError::ShapeMismatch`] and leaves
+/// `grid` untouched if its length is not a multiple of `width`.
+pub fn try_shuffle_grid<R: Rng, T>(
+    rng: &mut R,
+    grid: &mut [T],
+    width: usize,
+) -> Result<(), ShuffleError> {
+    if grid.is_empty() {
+        return Ok(());
+    }
+
+    if width == 0 || !grid.len().is_multiple_of(width) {
+        return Err(ShuffleError::ShapeMismatch);
+    }
+
+    let height = grid.len() / width;
+    for i in (1..height).rev() {
+        let j = uniform_index::gen_index(rng, i + 1);
+        if i != j {
+            let (upper, lower) = grid.split_at_mut(i * width);
+            upper[j * width..][..width].swap_with_slice(&mut lower[..width]);
+        }
+    }
+
+    let col_swaps: Vec<(usize, usize)> = (1..width)
+        .rev()
+        .map(|i| (i, uniform_index::gen_index(rng, i + 1)))
+        .collect();
+
+    for row in grid.chunks_exact_mut(width) {
+        for &(i, j) in &col_swaps {
+            row.swap(i, j);
+        }
+    }
+
+    Ok(())
+}
+
+/// Positions of the (at most two) ones on a line of the incidence cube.
+type Line = [u32; 2];
+
+/// The incidence cube of a (possibly improper) Latin square: the entry `(r, c, s)` is
+/// one if symbol `s` is in row `r` and column `c`. In an improper square, a single
+/// entry is minus one and the three lines through it contain two ones each; all other
+/// lines contain exactly one.
+///
+/// Instead of the `n^3` entries, only the positions of the ones are stored for each
+/// line, and the entry with minus one separately.
+struct IncidenceCube {
+    n: usize,
+    by_row_col: Vec<Line>,
+    by_row_sym: Vec<Line>,
+    by_col_sym: Vec<Line>,
+    improper: Option<(usize, usize, usize)>,
+}
+
+impl IncidenceCube {
+    fn new(n: usize) -> Self {
+        Self {
+            n,
+            by_row_col: vec![[NONE; 2]; n * n],
+            by_row_sym: vec![[NONE; 2]; n * n],
+            by_col_sym: vec![[NONE; 2]; n * n],
+            improper: None,
+        }
+    }
+
+    fn get(&self, r: usize, c: usize, s: usize) -> i8 {
+        if self.improper == Some((r, c, s)) {
+            -1
+        } else if self.by_row_col[r * self.n + c].contains(&(s as u32)) {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn set_one(&mut self, r: usize, c: usize, s: usize) {
+        let n = self.n;
+        insert(&mut self.by_row_col[r * n + c], s);
+        insert(&mut self.by_row_sym[r * n + s], c);
+        insert(&mut self.by_col_sym[c * n + s], r);
+    }
+
+    fn clear_one(&mut self, r: usize, c: usize, s: usize) {
+        let n = self.n;
+        remove(&mut self.by_row_col[r * n + c], s);
+        remove(&mut self.by_row_sym[r * n + s], c);
+        remove(&mut self.by_col_sym[c * n + s], r);
+    }
+
+    fn add(&mut self, r: usize, c: usize, s: usize, delta: i8) {
+        match (self.get(r, c, s), delta) {
+            (0, 1) => self.set_one(r, c, s),
+            (1, -1) => self.clear_one(r, c, s),
+            (-1, 1) => self.improper = None,
+            (0, -1) => {
+                debug_assert!(self.improper.is_none());
+                self.improper = Some((r, c, s));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Performs one step of the Jacobson-Matthews chain.
+    fn step<R: Rng>(&mut self, rng: &mut R) {
+        let n = self.n;
+
+        let ((r, c, s), pick): (_, fn(&mut R, Line) -> usize) = match self.improper {
+            Some(cell) => (cell, |rng, line| line[rng.gen::<bool>() as usize] as usize),
+            None => loop {
+                let cell = (
+                    uniform_index::gen_index(rng, n),
+                    uniform_index::gen_index(rng, n),
+                    uniform_index::gen_index(rng, n),
+                );
+
+                if self.get(cell.0, cell.1, cell.2) == 0 {
+                    break (cell, |_, line| line[0] as usize);
+                }
+            },
+        };
+
+        let r2 = pick(rng, self.by_col_sym[c * n + s]);
+        let c2 = pick(rng, self.by_row_sym[r * n + s]);
+        let s2 = pick(rng, self.by_row_col[r * n + c]);
+
+        // increments first, so that at most one entry is minus one at any time
+        self.add(r, c, s, 1);
+        self.add(r, c2, s2, 1);
+        self.add(r2, c, s2, 1);
+        self.add(r2, c2, s, 1);
+
+        self.add(r, c2, s, -1);
+        self.add(r2, c, s, -1);
+        self.add(r, c, s2, -1);
+        self.add(r2, c2, s2, -1);
+    }
+}
+
+fn insert(line: &mut Line, x: usize) {
+    let slot = if line[0] == NONE { 0 } else { 1 };
+    debug_assert_eq!(line[slot], NONE);
+    line[slot] = x as u32;
+}
+
+fn remove(line: &mut Line, x: usize) {
+    if line[0] == x as u32 {
+        line[0] = line[1];
+    } else {
+        debug_assert_eq!(line[1], x as u32);
+    }
+    line[1] = NONE;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+    use std::collections::HashMap;
+
+    fn is_latin_square(square: &[usize], n: usize) -> bool {
+        let expected: Vec<usize> = (0..n).collect();
+        (0..n).all(|i| {
+            let mut row = square[i * n..][..n].to_vec();
+            let mut col: Vec<_> = square.iter().skip(i).step_by(n).copied().collect();
+            row.sort();
+            col.sort();
+            row == expected && col == expected
+        })
+    }
+
+    #[test]
+    fn is_latin() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for n in [0, 1, 2, 3, 10, 31] {
+            let square = random_latin_square(&mut rng, n);
+            assert_eq!(square.len(), n * n);
+            assert!(is_latin_square(&square, n));
+        }
+    }
+
+    #[test]
+    fn uniform() {
+        // there are 576 Latin squares of order 4 in two isotopy classes, so all of
+        // them are only reached if the chain leaves the class of the cyclic square
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+        const PER_SQUARE: usize = 100;
+
+        let mut counts = HashMap::new();
+        for _ in 0..576 * PER_SQUARE {
+            *counts
+                .entry(random_latin_square(&mut rng, 4))
+                .or_insert(0usize) += 1;
+        }
+
+        assert_eq!(counts.len(), 576);
+        assert!(counts
+            .values()
+            .all(|&c| c > PER_SQUARE * 6 / 10 && c < PER_SQUARE * 14 / 10));
+    }
+
+    #[test]
+    fn grid_stays_balanced() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let (width, height) = (7, 5);
+
+        // each entry stores its row and column
+        let mut grid: Vec<(usize, usize)> = (0..height)
+            .flat_map(|r| (0..width).map(move |c| (r, c)))
+            .collect();
+
+        shuffle_grid(&mut rng, &mut grid, width);
+
+        for row in grid.chunks(width) {
+            assert!(row.iter().all(|&(r, _)| r == row[0].0));
+        }
+        for c in 0..width {
+            assert!(grid[c..]
+                .iter()
+                .step_by(width)
+                .all(|&(_, x)| x == grid[c].1));
+        }
+
+        grid.sort();
+        assert!(grid
+            .iter()
+            .enumerate()
+            .all(|(i, &(r, c))| (r, c) == (i / width, i % width)));
+    }
+
+    #[test]
+    fn grid_shape_mismatch() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let mut grid: Vec<usize> = (0..12).collect();
+
+        for width in [0, 5, 13] {
+            assert_eq!(
+                try_shuffle_grid(&mut rng, &mut grid, width),
+                Err(ShuffleError::ShapeMismatch)
+            );
+        }
+
+        assert!(grid.iter().enumerate().all(|(i, &x)| i == x));
+        assert!(try_shuffle_grid(&mut rng, &mut grid[..0], 0).is_ok());
+    }
+
+    mod columns {
+        use super::*;
+
+        fn shuffle_as_row<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            let n = data.len();
+            shuffle_grid(rng, data, n);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_as_row);
+    }
+
+    mod rows {
+        use super::*;
+
+        fn shuffle_as_column<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            shuffle_grid(rng, data, 1);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_as_column);
+    }
+}
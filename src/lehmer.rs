@@ -0,0 +1,298 @@
+//! Encoding of permutations as their rank in the factorial number system.
+//!
+//! The Lehmer code of a permutation `perm` of `0..n` has the digits
+//! `code[i] = |{j > i : perm[j] < perm[i]}|`, where `code[i] < n - i`. Read as a
+//! number in the factorial number system, it is the rank of `perm` among all `n!`
+//! permutations in lexicographic order. Hence, an exact permutation (e.g., the one
+//! drawn for an experiment) can be stored or transmitted in `log2(n!)` bits plus a small
+//! overhead, instead of `n` indices.
+//!
+//! [`encode_rank`] packs the digits into 64-bit words: each word holds as many
+//! consecutive digits as fit (at least one for `n <= 2^32`), so the overhead is less
+//! than one digit per word. The words are the digits of the rank in a mixed radix
+//! whose bases are the products of the digit bases of each word; the first word is the
+//! most significant one. Both directions take `O(n log n)` time.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::lehmer::{decode_rank, encode_rank, shuffle_by_rank};
+//! use rip_shuffle::permutation::par_random_permutation;
+//! use rand::SeedableRng;
+//!
+//! let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+//! let perm = par_random_permutation(&mut rng, 1000);
+//!
+//! let rank = encode_rank(&perm).unwrap();
+//! assert!(rank.len() < perm.len() / 3);
+//! assert_eq!(decode_rank(1000, &rank).unwrap(), perm);
+//!
+//! // shuffle data with the same permutation: position `i` receives `data[perm[i]]`
+//! let mut data: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+//! shuffle_by_rank(&mut data, &rank).unwrap();
+//! assert!(data.iter().zip(&perm).all(|(d, &p)| *d == p.to_string()));
+//! ```
+
+use super::error::ShuffleError;
+use std::ops::Range;
+
+/// Returns the Lehmer code of `perm` (see the [module documentation](self)), or
+/// [`ShuffleError::NotAPermutation`] if `perm` is not a permutation of `0..perm.len()`.
+pub fn lehmer_code(perm: &[usize]) -> Result<Vec<usize>, ShuffleError> {
+    let n = perm.len();
+    let mut seen = Fenwick::new(n);
+    let mut code = vec![0; n];
+
+    for (digit, &x) in code.iter_mut().zip(perm).rev() {
+        if x >= n || seen.prefix_sum(x + 1) != seen.prefix_sum(x) {
+            return Err(ShuffleError::NotAPermutation);
+        }
+
+        *digit = seen.prefix_sum(x);
+        seen.add(x);
+    }
+
+    Ok(code)
+}
+
+/// Returns the permutation with the Lehmer code `code`, or [`ShuffleError::InvalidRank`]
+/// if a digit `code[i]` is not smaller than `code.len() - i`.
+pub fn from_lehmer_code(code: &[usize]) -> Result<Vec<usize>, ShuffleError> {
+    let n = code.len();
+    let mut remaining = Fenwick::with_all(n);
+
+    code.iter()
+        .enumerate()
+        .map(|(i, &digit)| {
+            if digit >= n - i {
+                return Err(ShuffleError::InvalidRank);
+            }
+
+            let x = remaining.find(digit);
+            remaining.remove(x);
+            Ok(x)
+        })
+        .collect()
+}
+
+/// Number of words of the rank of a permutation of `0..n` (see [`encode_rank`]).
+pub fn num_rank_words(n: usize) -> usize {
+    word_digits(n).count()
+}
+
+/// Returns the rank of `perm` packed into 64-bit words (see the
+/// [module documentation](self)), or [`ShuffleError::NotAPermutation`] if `perm` is
+/// not a permutation of `0..perm.len()`.
+pub fn encode_rank(perm: &[usize]) -> Result<Vec<u64>, ShuffleError> {
+    let n = perm.len();
+    let code = lehmer_code(perm)?;
+
+    Ok(word_digits(n)
+        .map(|(digits, _)| digits.fold(0u64, |word, i| word * (n - i) as u64 + code[i] as u64))
+        .collect())
+}
+
+/// Returns the permutation of `0..n` with the given rank (see [`encode_rank`]). Returns
+/// [`ShuffleError::InvalidRank`] if `rank` does not have [`num_rank_words`]`(n)` words
+/// or one of them exceeds its range (i.e., `rank` does not stem from [`encode_rank`]).
+pub fn decode_rank(n: usize, rank: &[u64]) -> Result<Vec<usize>, ShuffleError> {
+    let mut code = vec![0; n];
+
+    let mut words = rank.iter();
+    for (digits, range) in word_digits(n) {
+        let mut word = *words.next().ok_or(ShuffleError::InvalidRank)? as u128;
+        if word >= range {
+            return Err(ShuffleError::InvalidRank);
+        }
+
+        for i in digits.rev() {
+            let base = (n - i) as u128;
+            code[i] = (word % base) as usize;
+            word /= base;
+        }
+    }
+
+    if words.next().is_some() {
+        return Err(ShuffleError::InvalidRank);
+    }
+
+    from_lehmer_code(&code)
+}
+
+/// Rearranges `data` according to the permutation `perm` with the given rank, i.e.,
+/// position `i` receives the element at `perm[i]` (see [`decode_rank`]). Returns
+/// [`ShuffleError::InputTooLarge`] for `u32::MAX` or more elements, and leaves `data`
+/// untouched on errors.
+pub fn shuffle_by_rank<T>(data: &mut [T], rank: &[u64]) -> Result<(), ShuffleError> {
+    if data.len() >= u32::MAX as usize {
+        return Err(ShuffleError::InputTooLarge);
+    }
+
+    let mut order: Vec<u32> = decode_rank(data.len(), rank)?
+        .into_iter()
+        .map(|x| x as u32)
+        .collect();
+
+    crate::indirection::apply_in_place(data, &mut order);
+    Ok(())
+}
+
+/// Returns the indices of the digits packed into each word together with the number of
+/// values the word can take. The last digit is always zero and is not stored.
+fn word_digits(n: usize) -> impl Iterator<Item = (Range<usize>, u128)> {
+    let num_digits = n.saturating_sub(1);
+    let mut begin = 0;
+
+    std::iter::from_fn(move || {
+        if begin == num_digits {
+            return None;
+        }
+
+        let mut end = begin;
+        let mut range = 1u128;
+        while end < num_digits && range * (n - end) as u128 <= 1 << 64 {
+            range *= (n - end) as u128;
+            end += 1;
+        }
+
+        // a single base exceeding 2^64 cannot be represented
+        assert!(end > begin, "permutation too large");
+
+        let digits = begin..end;
+        begin = end;
+        Some((digits, range))
+    })
+}
+
+/// Binary indexed tree over `0..n` storing the multiplicity of each value.
+struct Fenwick {
+    tree: Vec<usize>,
+}
+
+impl Fenwick {
+    fn new(n: usize) -> Self {
+        Self {
+            tree: vec![0; n + 1],
+        }
+    }
+
+    /// Every value is contained once.
+    fn with_all(n: usize) -> Self {
+        // node `i` covers the `i & i.wrapping_neg()` values ending at `i`
+        Self {
+            tree: (0..=n).map(|i| i & i.wrapping_neg()).collect(),
+        }
+    }
+
+    fn add(&mut self, x: usize) {
+        let mut i = x + 1;
+        while i < self.tree.len() {
+            self.tree[i] += 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn remove(&mut self, x: usize) {
+        let mut i = x + 1;
+        while i < self.tree.len() {
+            self.tree[i] -= 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Number of contained values smaller than `end`.
+    fn prefix_sum(&self, end: usize) -> usize {
+        let mut sum = 0;
+        let mut i = end;
+        while i > 0 {
+            sum += self.tree[i];
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /// Returns the `k`-th smallest contained value (starting at zero).
+    fn find(&self, mut k: usize) -> usize {
+        let n = self.tree.len() - 1;
+        let mut pos = 0;
+        let mut step = if n == 0 { 0 } else { 1 << n.ilog2() };
+
+        while step > 0 {
+            if pos + step <= n && self.tree[pos + step] <= k {
+                pos += step;
+                k -= self.tree[pos];
+            }
+            step >>= 1;
+        }
+
+        pos
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::permutation::par_random_permutation;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn ranks_are_lexicographic() {
+        let n = 5;
+        assert_eq!(num_rank_words(n), 1);
+
+        let perms: Vec<Vec<usize>> = (0..120)
+            .map(|rank| decode_rank(n, &[rank]).unwrap())
+            .collect();
+
+        assert_eq!(perms[0], [0, 1, 2, 3, 4]);
+        assert_eq!(perms[119], [4, 3, 2, 1, 0]);
+        assert!(perms.windows(2).all(|w| w[0] < w[1]));
+
+        for (rank, perm) in perms.iter().enumerate() {
+            assert_eq!(encode_rank(perm).unwrap(), [rank as u64]);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        for n in [0, 1, 2, 3, 20, 21, 22, 1000, 100_000] {
+            let perm = par_random_permutation(&mut rng, n);
+            let rank = encode_rank(&perm).unwrap();
+            assert_eq!(rank.len(), num_rank_words(n), "n={n}");
+            assert_eq!(decode_rank(n, &rank).unwrap(), perm, "n={n}");
+        }
+
+        // 20! < 2^64 < 21!
+        assert_eq!(num_rank_words(20), 1);
+        assert_eq!(num_rank_words(21), 2);
+    }
+
+    #[test]
+    fn shuffles_by_rank() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        let perm = par_random_permutation(&mut rng, 5000);
+
+        let mut data: Vec<u64> = (0..5000).map(|x| x * 3).collect();
+        shuffle_by_rank(&mut data, &encode_rank(&perm).unwrap()).unwrap();
+        assert!(data.iter().zip(&perm).all(|(&d, &p)| d == 3 * p as u64));
+    }
+
+    #[test]
+    fn rejects_invalid_inputs() {
+        assert_eq!(encode_rank(&[0, 2]), Err(ShuffleError::NotAPermutation));
+        assert_eq!(encode_rank(&[1, 1]), Err(ShuffleError::NotAPermutation));
+        assert_eq!(from_lehmer_code(&[0, 1]), Err(ShuffleError::InvalidRank));
+
+        assert_eq!(decode_rank(5, &[120]), Err(ShuffleError::InvalidRank));
+        assert_eq!(decode_rank(5, &[]), Err(ShuffleError::InvalidRank));
+        assert_eq!(decode_rank(5, &[0, 0]), Err(ShuffleError::InvalidRank));
+
+        let mut data = [1, 2, 3];
+        assert_eq!(
+            shuffle_by_rank(&mut data, &[6]),
+            Err(ShuffleError::InvalidRank)
+        );
+        assert_eq!(data, [1, 2, 3]);
+    }
+}
@@ -1,29 +1,92 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(feature = "prefetch", feature(core_intrinsics))]
 
+pub mod adaptive;
 pub mod api;
+pub mod approximate_shuffle;
+#[cfg(feature = "async")]
+pub mod async_shuffle;
+#[cfg(feature = "audit")]
+pub mod audit;
+pub mod background;
+pub mod batch_rng;
+#[cfg(feature = "bitvec")]
+pub mod bit_slice;
+pub mod bitset;
+pub mod bucketing;
+pub mod choose;
+pub mod counting_scatter;
+pub mod dart_shuffle;
+pub mod derangement;
+pub mod digest;
+pub mod disorder;
+pub mod error;
+pub mod external;
 pub mod fisher_yates;
+#[cfg(feature = "arbitrary")]
+pub mod fuzzing;
+pub mod indexed;
+pub mod indirection;
+pub mod latin_square;
+pub mod lehmer;
 pub mod merge_shuffle;
+pub mod observer;
+pub mod oracle;
+pub mod partial_shuffle;
+pub mod permutation;
+pub mod permutation_matrix;
+pub mod permutation_test;
+#[cfg(feature = "pinning")]
+pub mod pinning;
+pub mod plan;
 pub mod profiler;
+#[cfg(feature = "proptest")]
+pub mod proptest_strategies;
 pub mod random_bits;
+pub mod replay_rng;
+#[cfg(feature = "seed_with")]
+pub mod repro;
+pub mod restricted;
+pub mod rng_guard;
+pub mod rng_strategy;
 pub mod rough_shuffle;
 pub mod scatter_shuffle;
+pub mod session;
+pub mod sharded;
+pub mod sorted_sample;
+pub mod stratified;
+pub mod telemetry;
+#[cfg(any(test, feature = "test_util"))]
+pub mod test_util;
+pub mod text;
+pub mod tiles;
 pub mod uniform_index;
+pub mod unsafe_algos;
+pub mod varied;
+pub mod weighted;
+pub mod weighted_order;
+pub mod windowed;
 
 pub mod prelude {
+    pub use super::approximate_shuffle::rough_shuffle_only;
+    pub use super::dart_shuffle::par_dart_shuffle;
     pub use super::fisher_yates::fisher_yates;
     pub use super::merge_shuffle::par_merge_shuffle;
     pub use super::merge_shuffle::seq_merge_shuffle;
+    pub use super::plan::{Preset, ShufflePlan};
     pub use super::rough_shuffle::{IsPowerOfTwo, NumberOfBuckets};
-    pub use super::scatter_shuffle::parallel::par_scatter_shuffle;
+    pub use super::scatter_shuffle::parallel::{
+        par_scatter_shuffle, par_scatter_shuffle_in_scope, par_shuffled_chunks,
+    };
     pub use super::scatter_shuffle::sequential::seq_scatter_shuffle;
     pub use super::scatter_shuffle::{ParConfiguration, SeqConfiguration};
+    pub use super::windowed::{par_shuffle_each, shuffle_windows, shuffle_windows_overlapping};
 }
 
 pub use api::*;
 
-mod bucketing;
 mod prefetch;
+mod swap;
 
 #[cfg(test)]
 mod statistical_tests;
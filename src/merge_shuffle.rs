@@ -1,32 +1,131 @@
 use crate::{
-    bucketing::slicing::Slicing, random_bits::RandomBitsSource,
-    scatter_shuffle::parallel::seed_new_rng,
+    bitset::sample_ones_in_chunk, bucketing::slicing::Slicing, error::ShuffleError,
+    random_bits::RandomBitsSource, scatter_shuffle::parallel::seed_new_rng,
 };
 
-use super::{fisher_yates::fisher_yates, uniform_index};
+use super::{
+    fisher_yates::{fisher_yates, naive},
+    uniform_index,
+};
 use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Base case size in elements of [`crate::indexed::merge_shuffle_indexed`], whose
+/// element size is unknown.
+pub(crate) const FY_BASE_CASE: usize = 1 << 18;
+
+/// Inputs of less than this many bytes are shuffled with Fisher-Yates by default;
+/// this matches [`FY_BASE_CASE`] for elements of 8 bytes.
+pub const BASE_CASE_BYTES: usize = 1 << 21;
+
+/// Settings of [`seq_merge_shuffle`] and [`par_merge_shuffle`]. The base case is
+/// given in bytes, so that the recursion depth depends on the memory footprint of
+/// the input, as for the scatter shuffle.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::merge_shuffle::MergeConfiguration;
+/// use rand::SeedableRng;
+///
+/// let config = MergeConfiguration::default().with_base_case_bytes(1 << 16).unwrap();
+/// assert_eq!(config.base_case_len::<u64>(), 1 << 13);
+/// assert_eq!(config.base_case_len::<[u8; 1 << 20]>(), 1);
+///
+/// let mut data: Vec<u64> = (0..100_000).collect();
+/// config.par_shuffle(&mut rand_pcg::Pcg64Mcg::seed_from_u64(1234), &mut data);
+/// assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MergeConfiguration {
+    base_case_bytes: usize,
+}
+
+impl Default for MergeConfiguration {
+    fn default() -> Self {
+        Self {
+            base_case_bytes: BASE_CASE_BYTES,
+        }
+    }
+}
+
+impl MergeConfiguration {
+    /// Overrides the base case size (default: [`BASE_CASE_BYTES`]). Returns
+    /// [`ShuffleError::ZeroBaseCaseSize`] if `base_case_bytes` is zero.
+    pub fn with_base_case_bytes(mut self, base_case_bytes: usize) -> Result<Self, ShuffleError> {
+        if base_case_bytes == 0 {
+            return Err(ShuffleError::ZeroBaseCaseSize);
+        }
 
-const FY_BASE_CASE: usize = 1 << 18;
+        self.base_case_bytes = base_case_bytes;
+        Ok(self)
+    }
+
+    pub fn base_case_bytes(&self) -> usize {
+        self.base_case_bytes
+    }
+
+    /// Returns the number of elements of type `T` below which inputs are shuffled
+    /// with Fisher-Yates; at least one.
+    pub fn base_case_len<T>(&self) -> usize {
+        (self.base_case_bytes / std::mem::size_of::<T>().max(1)).max(1)
+    }
+
+    pub fn seq_shuffle<R: Rng, T>(&self, rng: &mut R, data: &mut [T]) {
+        if crate::oracle::is_enabled() {
+            return naive::fisher_yates(rng, data);
+        }
 
+        seq_merge_shuffle_impl(rng, data, self.base_case_len::<T>())
+    }
+
+    pub fn par_shuffle<R: Rng + SeedableRng + Send + Sync, T: Send>(
+        &self,
+        rng: &mut R,
+        data: &mut [T],
+    ) {
+        if crate::oracle::is_enabled() {
+            return naive::fisher_yates(rng, data);
+        }
+
+        par_merge_shuffle_impl(rng, data, self.base_case_len::<T>())
+    }
+}
+
+/// Shuffles `data` with the default [`MergeConfiguration`].
 pub fn seq_merge_shuffle<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    MergeConfiguration::default().seq_shuffle(rng, data)
+}
+
+/// Shuffles `data` in parallel with the default [`MergeConfiguration`]. The merge
+/// steps are split into up to one part per thread of the current rayon pool, so the
+/// permutation depends on the number of threads.
+pub fn par_merge_shuffle<R: Rng + SeedableRng + Send + Sync, T: std::marker::Send>(
+    rng: &mut R,
+    data: &mut [T],
+) {
+    MergeConfiguration::default().par_shuffle(rng, data)
+}
+
+fn seq_merge_shuffle_impl<R: Rng, T>(rng: &mut R, data: &mut [T], base_case_len: usize) {
     let n = data.len();
-    if n < FY_BASE_CASE {
+    if n < base_case_len.max(2) {
         return fisher_yates(rng, data);
     }
 
     let (left, right) = data.split_at_mut(n / 2);
 
-    seq_merge_shuffle(rng, left);
-    seq_merge_shuffle(rng, right);
+    seq_merge_shuffle_impl(rng, left, base_case_len);
+    seq_merge_shuffle_impl(rng, right, base_case_len);
     random_merge(rng, left, right);
 }
 
-pub fn par_merge_shuffle<R: Rng + SeedableRng + Send + Sync, T: std::marker::Send>(
+fn par_merge_shuffle_impl<R: Rng + SeedableRng + Send + Sync, T: Send>(
     rng: &mut R,
     data: &mut [T],
+    base_case_len: usize,
 ) {
     let n = data.len();
-    if n < FY_BASE_CASE {
+    if n < base_case_len.max(2) {
         return fisher_yates(rng, data);
     }
 
@@ -35,11 +134,71 @@ pub fn par_merge_shuffle<R: Rng + SeedableRng + Send + Sync, T: std::marker::Sen
     let mut right_rng: R = seed_new_rng(rng);
 
     rayon::join(
-        || par_merge_shuffle(rng, left),
-        || par_merge_shuffle(&mut right_rng, right),
+        || par_merge_shuffle_impl(rng, left, base_case_len),
+        || par_merge_shuffle_impl(&mut right_rng, right, base_case_len),
     );
 
-    random_merge(rng, left, right);
+    let num_parts = rayon::current_num_threads();
+    par_random_merge(rng, data, n / 2, num_parts, base_case_len);
+}
+
+/// Same as [`random_merge`] for `data[..num_left]` and `data[num_left..]`, but in
+/// parallel. The number of elements of the left part that end up in the first half of
+/// the output follows the hypergeometric distribution. We draw it, swap the surplus
+/// elements of one part with elements of the other part, and merge both halves
+/// independently. As both parts are uniformly shuffled, it does not matter which
+/// elements are swapped. The swaps cost extra work, so the input is only split until
+/// there are `num_parts` halves, or until they have at most `seq_merge_len` elements.
+fn par_random_merge<R: Rng + SeedableRng + Send + Sync, T: Send>(
+    rng: &mut R,
+    data: &mut [T],
+    num_left: usize,
+    num_parts: usize,
+    seq_merge_len: usize,
+) {
+    let n = data.len();
+    if num_parts <= 1 || n <= seq_merge_len.max(2) || num_left == 0 || num_left == n {
+        let (left, right) = data.split_at_mut(num_left);
+        return random_merge(rng, left, right);
+    }
+
+    let half = n / 2;
+    let left_in_first_half = sample_ones_in_chunk(rng, n, num_left, half);
+
+    // the first half currently contains `num_left.min(half)` elements of the left part;
+    // the surplus is swapped with the first elements of the right part in the second half
+    let surplus_begin = left_in_first_half;
+    let surplus_end = num_left.min(half);
+    let right_begin = num_left.max(half);
+    {
+        let (head, tail) = data.split_at_mut(right_begin);
+        let len = surplus_end - surplus_begin;
+        par_swap(&mut head[surplus_begin..surplus_end], &mut tail[..len]);
+    }
+
+    let (first, second) = data.split_at_mut(half);
+    let mut second_rng: R = seed_new_rng(rng);
+
+    rayon::join(
+        || par_random_merge(rng, first, left_in_first_half, num_parts / 2, seq_merge_len),
+        || {
+            par_random_merge(
+                &mut second_rng,
+                second,
+                num_left - left_in_first_half,
+                num_parts - num_parts / 2,
+                seq_merge_len,
+            )
+        },
+    );
+}
+
+fn par_swap<T: Send>(a: &mut [T], b: &mut [T]) {
+    const CHUNK_SIZE: usize = 1 << 14;
+
+    a.par_chunks_mut(CHUNK_SIZE)
+        .zip(b.par_chunks_mut(CHUNK_SIZE))
+        .for_each(|(a, b)| a.swap_with_slice(b));
 }
 
 fn random_merge<R: Rng, T>(rng: &mut R, left: &mut [T], right: &mut [T]) {
@@ -47,8 +206,10 @@ fn random_merge<R: Rng, T>(rng: &mut R, left: &mut [T], right: &mut [T]) {
 
     let num_rough_merged = {
         #[cfg(feature = "unsafe_algos")]
-        unsafe {
-            unsafe_rough_random_merge(rng, left, right)
+        if crate::unsafe_algos::is_enabled() {
+            unsafe { unsafe_rough_random_merge(rng, left, right) }
+        } else {
+            safe_rough_random_merge(rng, left, right)
         }
 
         #[cfg(not(feature = "unsafe_algos"))]
@@ -99,7 +260,7 @@ unsafe fn unsafe_rough_random_merge<R: Rng, T>(
         let rand: u64 = rng.gen();
 
         const STEPS: usize = 16;
-        for i in (0..64).into_iter().step_by(STEPS) {
+        for i in (0..64).step_by(STEPS) {
             (begin, mid) = unsafe_uncheck_iterations::<T, 16>((rand >> i) as usize, begin, mid);
         }
     }
@@ -176,6 +337,22 @@ mod test {
 
     impl_merge_test!(safe_rough_random_merge);
     impl_merge_test!(unsafe_rough_random_merge);
+
+    mod par_random_merge {
+        use super::*;
+        use rand::seq::SliceRandom;
+
+        fn shuffle<R: Rng + SeedableRng + Send + Sync, T: Send>(rng: &mut R, data: &mut [T]) {
+            let n = data.len();
+            let num_left = n / 3;
+            let (left, right) = data.split_at_mut(num_left);
+            left.shuffle(rng);
+            right.shuffle(rng);
+            super::super::par_random_merge(rng, data, num_left, n, 2);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle);
+    }
 }
 
 #[cfg(test)]
@@ -191,4 +368,28 @@ mod integration_test {
         use super::*;
         crate::statistical_tests::test_shuffle_algorithm!(par_merge_shuffle);
     }
+
+    mod small_base_case {
+        use super::*;
+
+        fn shuffle<R: Rng + SeedableRng + Send + Sync, T: Send>(rng: &mut R, data: &mut [T]) {
+            par_merge_shuffle_impl(rng, data, 2)
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle);
+    }
+
+    #[test]
+    fn base_case_len_depends_on_element_size() {
+        let config = MergeConfiguration::default();
+        assert_eq!(config.base_case_len::<u64>(), FY_BASE_CASE);
+        assert_eq!(config.base_case_len::<u8>(), 8 * FY_BASE_CASE);
+        assert_eq!(config.base_case_len::<[u64; 8]>(), FY_BASE_CASE / 8);
+        assert_eq!(config.base_case_len::<()>(), BASE_CASE_BYTES);
+
+        assert_eq!(
+            config.with_base_case_bytes(0),
+            Err(ShuffleError::ZeroBaseCaseSize)
+        );
+    }
 }
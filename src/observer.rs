@@ -0,0 +1,91 @@
+//! Observing the swaps performed by a shuffle.
+//!
+//! Some auxiliary structures cannot be shuffled alongside the data as a parallel slice,
+//! e.g., an external index map or a mirror of the data residing on a GPU. A
+//! [`SwapObserver`] is informed about every swap of [`shuffle_observed`] and can replay
+//! it on such structures. Closures `FnMut(usize, usize)` are observers.
+//!
+//! The observed shuffle is a Fisher-Yates shuffle, since the scatter shuffle moves
+//! elements through buckets and stashes and does not express its work as swaps. The
+//! unobserved shuffles are not affected: their swap loops are instantiated with
+//! [`NoObserver`], which compiles to nothing.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::observer::shuffle_observed;
+//!
+//! let mut data: Vec<_> = (0..1000).collect();
+//! let mut mirror: Vec<String> = data.iter().map(|x| x.to_string()).collect();
+//!
+//! shuffle_observed(&mut rand::thread_rng(), &mut data, &mut |i, j| mirror.swap(i, j));
+//!
+//! assert!(data.iter().zip(&mirror).all(|(x, m)| x.to_string() == *m));
+//! ```
+
+use super::fisher_yates::naive::fisher_yates_observed;
+use rand::Rng;
+
+/// Receives the swaps performed by a shuffle.
+pub trait SwapObserver {
+    /// Called after the elements at positions `i` and `j` (with `i != j`) were swapped.
+    fn swapped(&mut self, i: usize, j: usize);
+}
+
+/// Observer that ignores all swaps.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoObserver;
+
+impl SwapObserver for NoObserver {
+    #[inline(always)]
+    fn swapped(&mut self, _i: usize, _j: usize) {}
+}
+
+impl<F: FnMut(usize, usize)> SwapObserver for F {
+    #[inline(always)]
+    fn swapped(&mut self, i: usize, j: usize) {
+        self(i, j)
+    }
+}
+
+/// Shuffles `data` uniformly at random and reports each swap to `observer` (in the
+/// order in which they are performed).
+pub fn shuffle_observed<R: Rng, T, O: SwapObserver>(rng: &mut R, data: &mut [T], observer: &mut O) {
+    fisher_yates_observed(rng, data, observer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn replay_swaps() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for n in [0, 1, 2, 100, 10_000] {
+            let mut data: Vec<usize> = (0..n).collect();
+            let mut swaps = Vec::new();
+            shuffle_observed(&mut rng, &mut data, &mut |i, j| swaps.push((i, j)));
+
+            assert!(swaps.iter().all(|&(i, j)| i != j && i < n && j < n));
+
+            let mut replayed: Vec<usize> = (0..n).collect();
+            for (i, j) in swaps {
+                replayed.swap(i, j);
+            }
+            assert_eq!(replayed, data);
+        }
+    }
+
+    #[test]
+    fn same_result_as_unobserved() {
+        let mut data: Vec<usize> = (0..1000).collect();
+        shuffle_observed(&mut Pcg64Mcg::seed_from_u64(1), &mut data, &mut NoObserver);
+
+        let mut expected: Vec<usize> = (0..1000).collect();
+        crate::fisher_yates::naive::fisher_yates(&mut Pcg64Mcg::seed_from_u64(1), &mut expected);
+
+        assert_eq!(data, expected);
+    }
+}
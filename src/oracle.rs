@@ -0,0 +1,46 @@
+//! The oracle mode forces all shuffling entry points to use the simple reference
+//! implementation, i.e. the naive Fisher-Yates shuffle without prefetching, unsafe
+//! code, or parallelism. The output then only depends on the random number generator
+//! (and not on the build configuration or the number of threads), which allows to
+//! differential-test pipelines, to reproduce results obtained on constrained targets,
+//! and to bisect whether a bug originates in the fast paths of this crate.
+//!
+//! The oracle mode is enabled by the `oracle` feature, or at runtime via [`enable`].
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::{fisher_yates::naive, oracle, RipShuffleSequential};
+//! use rand::SeedableRng;
+//! use rand_pcg::Pcg64;
+//!
+//! oracle::enable();
+//!
+//! let mut data: Vec<_> = (0..1000).collect();
+//! data.seq_shuffle(&mut Pcg64::seed_from_u64(1234));
+//!
+//! let mut reference: Vec<_> = (0..1000).collect();
+//! naive::fisher_yates(&mut Pcg64::seed_from_u64(1234), &mut reference);
+//!
+//! assert_eq!(data, reference);
+//! # oracle::disable();
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enables the oracle mode for all subsequent calls (in all threads).
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Disables the oracle mode, unless it is enforced by the `oracle` feature.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Returns whether the shuffling entry points currently use the reference implementation.
+#[inline]
+pub fn is_enabled() -> bool {
+    cfg!(feature = "oracle") || ENABLED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,204 @@
+//! Shuffling only a prefix of the input.
+//!
+//! [`seq_partial_shuffle`] moves `k` elements drawn uniformly without replacement to the
+//! front of the input, in uniformly random order, i.e., the prefix equals the prefix of
+//! a full shuffle in distribution. The remaining elements are kept in an unspecified
+//! order. This mirrors `rand`'s `partial_shuffle`, but uses the front of the slice.
+//!
+//! For large inputs, a truncated Fisher-Yates shuffle performs `k` swaps with random
+//! positions, i.e., it touches `k` random cache lines. This is hard to beat for small
+//! prefixes, but for larger ones it pays off to access memory in a more structured way:
+//!
+//! - If `k` is at least [`FULL_SHUFFLE_FRACTION`] of the input, we shuffle the whole
+//!   input with the scatter shuffle, which is roughly twice as fast as Fisher-Yates.
+//! - If `k` is at least [`CHUNKED_FRACTION`] of the input, we split the input into
+//!   chunks of [`CHUNK_BYTES`] bytes and draw how many of the `k` elements each chunk
+//!   contributes (from the hypergeometric distribution, as in [`crate::bitset`]). Each
+//!   chunk then selects its elements with a truncated Fisher-Yates shuffle within the
+//!   cache, and they are moved to the front in a single sweep. Since the elements of
+//!   earlier chunks precede those of later ones, the prefix is finally shuffled with
+//!   the scatter shuffle.
+//! - Otherwise, and for inputs of at most [`CHUNK_BYTES`] bytes, we use the truncated
+//!   Fisher-Yates shuffle.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::partial_shuffle::seq_partial_shuffle;
+//!
+//! let mut data: Vec<_> = (0..1_000_000).collect();
+//! seq_partial_shuffle(&mut rand::thread_rng(), &mut data, 10);
+//!
+//! let winners = &data[..10];
+//! assert!(winners.iter().all(|&x| x < 1_000_000));
+//! ```
+
+use super::bitset::sample_ones_in_chunk;
+use super::scatter_shuffle::parallel::par_scatter_shuffle;
+use super::scatter_shuffle::sequential::seq_scatter_shuffle;
+use super::uniform_index;
+use rand::{Rng, SeedableRng};
+
+/// Size of the chunks from which the elements of the prefix are selected; each chunk
+/// should fit into the cache.
+pub const CHUNK_BYTES: usize = 1 << 18;
+
+/// Prefixes covering at least this fraction of the input use the chunked selection.
+pub const CHUNKED_FRACTION: f64 = 0.25;
+
+/// Prefixes covering at least this fraction of the input are obtained by a full
+/// shuffle; the parallel variant divides the fraction by the number of threads.
+pub const FULL_SHUFFLE_FRACTION: f64 = 0.66;
+
+/// Moves `k` uniformly drawn elements of `data` to its front in random order (see the
+/// [module documentation](self)). If `k` exceeds the length, the whole input is
+/// shuffled.
+pub fn seq_partial_shuffle<R: Rng, T>(rng: &mut R, data: &mut [T], k: usize) {
+    let k = k.min(data.len());
+    let fraction = k as f64 / data.len() as f64;
+
+    if std::mem::size_of_val(data) <= CHUNK_BYTES
+        || fraction < CHUNKED_FRACTION
+        || crate::oracle::is_enabled()
+    {
+        return truncated_fisher_yates(rng, data, k);
+    }
+
+    if fraction >= FULL_SHUFFLE_FRACTION {
+        return seq_scatter_shuffle(rng, data);
+    }
+
+    select_prefix(rng, data, k, chunk_len::<T>());
+    seq_scatter_shuffle(rng, &mut data[..k]);
+}
+
+/// Same as [`seq_partial_shuffle`], but shuffles the whole input in parallel if `k` is
+/// at least [`FULL_SHUFFLE_FRACTION`] of the input divided by the number of threads of
+/// the current rayon pool.
+pub fn par_partial_shuffle<R, T>(rng: &mut R, data: &mut [T], k: usize)
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send,
+{
+    let num_threads = rayon::current_num_threads();
+    let fraction = k.min(data.len()) as f64 / data.len() as f64;
+
+    if num_threads > 1
+        && std::mem::size_of_val(data) > CHUNK_BYTES
+        && fraction >= FULL_SHUFFLE_FRACTION / num_threads as f64
+        && !crate::oracle::is_enabled()
+    {
+        return par_scatter_shuffle(rng, data);
+    }
+
+    seq_partial_shuffle(rng, data, k)
+}
+
+fn chunk_len<T>() -> usize {
+    (CHUNK_BYTES / std::mem::size_of::<T>().max(1)).max(1)
+}
+
+/// The first `k` iterations of a Fisher-Yates shuffle running from the front.
+fn truncated_fisher_yates<R: Rng, T>(rng: &mut R, data: &mut [T], k: usize) {
+    let n = data.len();
+    for i in 0..k.min(n.saturating_sub(1)) {
+        let j = i + uniform_index::gen_index(rng, n - i);
+        data.swap(i, j);
+    }
+}
+
+/// Moves a uniformly drawn `k`-subset of `data` to the front. The elements of each
+/// chunk appear in random order, but precede those of later chunks.
+fn select_prefix<R: Rng, T>(rng: &mut R, data: &mut [T], k: usize, chunk_len: usize) {
+    let mut remaining_len = data.len();
+    let mut remaining_k = k;
+    let mut num_selected = 0;
+    let mut chunk_begin = 0;
+
+    while remaining_k > 0 {
+        let len = chunk_len.min(remaining_len);
+        let chunk_k = sample_ones_in_chunk(rng, remaining_len, remaining_k, len);
+
+        let chunk = &mut data[chunk_begin..chunk_begin + len];
+        truncated_fisher_yates(rng, chunk, chunk_k);
+
+        // the target range may overlap the chunk; each swap only displaces elements
+        // that were not selected or were already moved
+        for i in 0..chunk_k {
+            data.swap(num_selected + i, chunk_begin + i);
+        }
+
+        num_selected += chunk_k;
+        remaining_k -= chunk_k;
+        remaining_len -= len;
+        chunk_begin += len;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    fn assert_partial_shuffle(data: &[u64], k: usize) {
+        let mut sorted = data.to_vec();
+        sorted.sort();
+        assert!(sorted.iter().copied().eq(0..data.len() as u64));
+
+        if k > 100 {
+            assert!(!data[..k].windows(2).all(|w| w[0] < w[1]));
+        }
+    }
+
+    #[test]
+    fn moves_sample_to_front() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+
+        for n in [0, 1, 2, 1000, 1 << 18, 1 << 21] {
+            for k in [0, 1, 2, 50, 1000, n / 3, n - n.min(1), n, n + 1] {
+                let mut data: Vec<u64> = (0..n as u64).collect();
+                seq_partial_shuffle(&mut rng, &mut data, k);
+                assert_partial_shuffle(&data, k.min(n));
+
+                let mut data: Vec<u64> = (0..n as u64).collect();
+                par_partial_shuffle(&mut rng, &mut data, k);
+                assert_partial_shuffle(&data, k.min(n));
+            }
+        }
+    }
+
+    #[test]
+    fn selects_uniformly() {
+        // each element reaches each position of the prefix with probability 1/n
+        const N: usize = 8;
+        const K: usize = 4;
+        const ROUNDS: usize = 80_000;
+
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        let mut counts = [[0usize; N]; K];
+        for _ in 0..ROUNDS {
+            let mut data: Vec<usize> = (0..N).collect();
+            select_prefix(&mut rng, &mut data, K, 3);
+            seq_scatter_shuffle(&mut rng, &mut data[..K]);
+            for (pos, &x) in data[..K].iter().enumerate() {
+                counts[pos][x] += 1;
+            }
+        }
+
+        let expected = ROUNDS / N;
+        for count in counts.iter().flatten() {
+            assert!(count.abs_diff(expected) < expected / 10, "{counts:?}");
+        }
+    }
+
+    mod statistical {
+        use super::*;
+
+        // the truncated shuffle of all positions is a full Fisher-Yates shuffle
+        fn truncated<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            let k = data.len();
+            truncated_fisher_yates(rng, data, k);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(truncated);
+    }
+}
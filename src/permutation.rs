@@ -0,0 +1,382 @@
+//! Random permutations of index ranges.
+//!
+//! Often the output of a shuffle is not a rearranged dataset but a permutation of the
+//! indices `0..n`, e.g., to access several arrays in the same random order. The
+//! functions in this module produce such permutations directly: the identity is
+//! written in parallel into a freshly allocated vector, which is then shuffled with
+//! [`par_scatter_shuffle`]. [`par_random_permutation_u32`] uses 32-bit indices, which
+//! halves the memory traffic compared to `usize` on 64-bit targets.
+//!
+//! Structured random inputs, e.g. for benchmarking algorithms on permutations, can be
+//! generated with [`random_permutation_with_cycle_type`] and
+//! [`random_permutation_with_num_cycles`], which sample uniformly among the
+//! permutations with prescribed cycle lengths or a prescribed number of cycles.
+
+use super::error::ShuffleError;
+use super::fisher_yates::fisher_yates;
+use super::scatter_shuffle::parallel::par_scatter_shuffle;
+use super::uniform_index;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Returns a uniform random permutation of `0..n`.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::permutation::par_random_permutation;
+/// use rand::SeedableRng;
+///
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+/// let mut perm = par_random_permutation(&mut rng, 1000);
+///
+/// perm.sort();
+/// assert!(perm.iter().enumerate().all(|(i, &x)| i == x));
+/// ```
+pub fn par_random_permutation<R>(rng: &mut R, n: usize) -> Vec<usize>
+where
+    R: Rng + SeedableRng + Send + Sync,
+{
+    let mut perm = Vec::with_capacity(n);
+    perm.par_extend((0..n).into_par_iter());
+    par_scatter_shuffle(rng, &mut perm);
+    perm
+}
+
+/// Returns a uniform random permutation of `0..n` with 32-bit entries, or
+/// [`ShuffleError::InputTooLarge`] if `n` is `2^32` or more.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::permutation::par_random_permutation_u32;
+/// use rand::SeedableRng;
+///
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+/// let perm: Vec<u32> = par_random_permutation_u32(&mut rng, 1000).unwrap();
+/// assert_eq!(perm.len(), 1000);
+/// ```
+pub fn par_random_permutation_u32<R>(rng: &mut R, n: u64) -> Result<Vec<u32>, ShuffleError>
+where
+    R: Rng + SeedableRng + Send + Sync,
+{
+    let n = u32::try_from(n).map_err(|_| ShuffleError::InputTooLarge)?;
+
+    let mut perm = Vec::with_capacity(n as usize);
+    perm.par_extend((0..n).into_par_iter());
+    par_scatter_shuffle(rng, &mut perm);
+    Ok(perm)
+}
+
+/// Returns a permutation `perm` of `0..n` (mapping `i` to `perm[i]`), where `n` is the
+/// sum of `cycle_lengths`, drawn uniformly among all permutations whose cycles have
+/// exactly these lengths. The order of `cycle_lengths` does not matter.
+///
+/// # Panics
+/// Panics if a cycle length is zero; see [`try_random_permutation_with_cycle_type`]
+/// for a non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::permutation::{cycle_type, random_permutation_with_cycle_type};
+///
+/// let perm = random_permutation_with_cycle_type(&mut rand::thread_rng(), &[3, 1, 3, 5]);
+/// assert_eq!(perm.len(), 12);
+/// assert_eq!(cycle_type(&perm), vec![1, 3, 3, 5]);
+/// ```
+pub fn random_permutation_with_cycle_type<R: Rng>(
+    rng: &mut R,
+    cycle_lengths: &[usize],
+) -> Vec<usize> {
+    try_random_permutation_with_cycle_type(rng, cycle_lengths).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`random_permutation_with_cycle_type`], but returns
+/// [`ShuffleError::InvalidCycles`] instead of panicking if a cycle length is zero.
+pub fn try_random_permutation_with_cycle_type<R: Rng>(
+    rng: &mut R,
+    cycle_lengths: &[usize],
+) -> Result<Vec<usize>, ShuffleError> {
+    if cycle_lengths.contains(&0) {
+        return Err(ShuffleError::InvalidCycles);
+    }
+
+    // Cutting a uniformly shuffled sequence into consecutive blocks of the given lengths
+    // and closing each block into a cycle yields every permutation of the cycle type
+    // equally often.
+    let n = cycle_lengths.iter().sum();
+    let mut order: Vec<usize> = (0..n).collect();
+    fisher_yates(rng, &mut order);
+
+    let mut perm = vec![0; n];
+    let mut blocks = order.as_slice();
+    for &len in cycle_lengths {
+        let block;
+        (block, blocks) = blocks.split_at(len);
+
+        for (&from, &to) in block.iter().zip(block.iter().cycle().skip(1)) {
+            perm[from] = to;
+        }
+    }
+
+    Ok(perm)
+}
+
+/// Returns a permutation `perm` of `0..n` (mapping `i` to `perm[i]`) drawn uniformly
+/// among all permutations with exactly `num_cycles` cycles.
+///
+/// The sampler follows the recurrence of the (unsigned) Stirling numbers of the first
+/// kind and tabulates their logarithms, so it takes `O(n * num_cycles)` time and
+/// memory.
+///
+/// # Panics
+/// Panics if no such permutation exists, i.e. `num_cycles > n` or `num_cycles == 0 < n`;
+/// see [`try_random_permutation_with_num_cycles`] for a non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::permutation::{cycle_type, random_permutation_with_num_cycles};
+///
+/// let perm = random_permutation_with_num_cycles(&mut rand::thread_rng(), 1000, 3);
+/// assert_eq!(cycle_type(&perm).len(), 3);
+/// ```
+pub fn random_permutation_with_num_cycles<R: Rng>(
+    rng: &mut R,
+    n: usize,
+    num_cycles: usize,
+) -> Vec<usize> {
+    try_random_permutation_with_num_cycles(rng, n, num_cycles).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`random_permutation_with_num_cycles`], but returns
+/// [`ShuffleError::InvalidCycles`] instead of panicking if no permutation of `0..n` has
+/// `num_cycles` cycles.
+pub fn try_random_permutation_with_num_cycles<R: Rng>(
+    rng: &mut R,
+    n: usize,
+    num_cycles: usize,
+) -> Result<Vec<usize>, ShuffleError> {
+    if num_cycles > n || (num_cycles == 0 && n > 0) {
+        return Err(ShuffleError::InvalidCycles);
+    }
+
+    // `ln_stirling[m][j]` is the logarithm of the number of permutations of `m` elements
+    // with `j` cycles. They satisfy `c(m, j) = c(m - 1, j - 1) + (m - 1) c(m - 1, j)`:
+    // element `m - 1` either forms a cycle on its own, or follows one of the other
+    // elements in a permutation of `m - 1` elements with `j` cycles.
+    let mut ln_stirling = vec![vec![f64::NEG_INFINITY; num_cycles + 1]; n + 1];
+    ln_stirling[0][0] = 0.0;
+    for m in 1..=n {
+        for j in 1..=num_cycles.min(m) {
+            let alone = ln_stirling[m - 1][j - 1];
+            let follows = ln_stirling[m - 1][j] + ((m - 1) as f64).ln();
+            ln_stirling[m][j] = ln_add_exp(alone, follows);
+        }
+    }
+
+    // decide top-down whether each element forms a cycle on its own ...
+    let mut is_alone = vec![false; n];
+    let mut cycles = num_cycles;
+    for m in (1..=n).rev() {
+        let p_alone = (ln_stirling[m - 1][cycles - 1] - ln_stirling[m][cycles]).exp();
+        if cycles == m || rng.gen_bool(p_alone.min(1.0)) {
+            is_alone[m - 1] = true;
+            cycles -= 1;
+        }
+    }
+
+    // ... and build the permutation bottom-up, inserting the others behind a uniformly
+    // random element
+    let mut perm: Vec<usize> = (0..n).collect();
+    for (elem, alone) in is_alone.into_iter().enumerate() {
+        if !alone {
+            let pred = uniform_index::gen_index(rng, elem);
+            perm[elem] = perm[pred];
+            perm[pred] = elem;
+        }
+    }
+
+    Ok(perm)
+}
+
+/// Returns the lengths of the cycles of the permutation `perm` (mapping `i` to
+/// `perm[i]`) in increasing order.
+///
+/// # Panics
+/// Panics if `perm` is not a permutation of `0..perm.len()`.
+pub fn cycle_type(perm: &[usize]) -> Vec<usize> {
+    let mut visited = vec![false; perm.len()];
+    let mut lengths = Vec::new();
+
+    for start in 0..perm.len() {
+        let mut len = 0;
+        let mut i = start;
+        while !visited[i] {
+            visited[i] = true;
+            i = perm[i];
+            len += 1;
+        }
+
+        if len > 0 {
+            assert_eq!(i, start, "input is not a permutation");
+            lengths.push(len);
+        }
+    }
+
+    lengths.sort_unstable();
+    lengths
+}
+
+fn ln_add_exp(a: f64, b: f64) -> f64 {
+    let (hi, lo) = if a > b { (a, b) } else { (b, a) };
+    if lo == f64::NEG_INFINITY {
+        hi
+    } else {
+        hi + (lo - hi).exp().ln_1p()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn is_permutation() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for n in [0, 1, 2, 100, 1 << 22] {
+            let mut perm = par_random_permutation_u32(&mut rng, n as u64).unwrap();
+            assert_eq!(perm.len(), n);
+            perm.sort();
+            assert!(perm.iter().enumerate().all(|(i, &x)| i as u32 == x));
+
+            let mut perm = par_random_permutation(&mut rng, n);
+            perm.sort();
+            assert!(perm.iter().enumerate().all(|(i, &x)| i == x));
+        }
+    }
+
+    #[test]
+    fn matches_shuffled_identity() {
+        let n = 1 << 21;
+        let perm = par_random_permutation_u32(&mut Pcg64Mcg::seed_from_u64(1), n).unwrap();
+
+        let mut expected: Vec<u32> = (0..n as u32).collect();
+        par_scatter_shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut expected);
+
+        assert_eq!(perm, expected);
+    }
+
+    #[test]
+    fn too_large() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        assert_eq!(
+            par_random_permutation_u32(&mut rng, 1 << 32),
+            Err(ShuffleError::InputTooLarge)
+        );
+    }
+
+    #[test]
+    fn uniform() {
+        // all 6 permutations of three elements appear equally often
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+        const RUNS: usize = 12_000;
+        let mut counts = std::collections::HashMap::new();
+
+        for _ in 0..RUNS {
+            let perm = par_random_permutation_u32(&mut rng, 3).unwrap();
+            *counts.entry(perm).or_insert(0usize) += 1;
+        }
+
+        assert_eq!(counts.len(), 6);
+        assert!(counts
+            .values()
+            .all(|&c| c > RUNS / 6 * 9 / 10 && c < RUNS / 6 * 11 / 10));
+    }
+
+    fn assert_uniform<F: FnMut(&mut Pcg64Mcg) -> Vec<usize>>(
+        num_perms: usize,
+        mut sample: F,
+    ) -> Vec<Vec<usize>> {
+        let mut rng = Pcg64Mcg::seed_from_u64(num_perms as u64);
+        let runs = 2000 * num_perms;
+        let mut counts = std::collections::HashMap::new();
+
+        for _ in 0..runs {
+            *counts.entry(sample(&mut rng)).or_insert(0usize) += 1;
+        }
+
+        assert_eq!(counts.len(), num_perms);
+        assert!(counts.values().all(|&c| c > 1800 && c < 2200));
+        counts.into_keys().collect()
+    }
+
+    #[test]
+    fn cycle_type_uniform() {
+        for (lengths, num_perms) in [(vec![2, 2], 3), (vec![1, 3], 8), (vec![4], 6)] {
+            for perm in assert_uniform(num_perms, |rng| {
+                random_permutation_with_cycle_type(rng, &lengths)
+            }) {
+                let mut expected = lengths.clone();
+                expected.sort();
+                assert_eq!(cycle_type(&perm), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn num_cycles_uniform() {
+        // the unsigned Stirling numbers of the first kind for n = 4
+        for (num_cycles, num_perms) in [(1, 6), (2, 11), (3, 6), (4, 1)] {
+            for perm in assert_uniform(num_perms, |rng| {
+                random_permutation_with_num_cycles(rng, 4, num_cycles)
+            }) {
+                assert_eq!(cycle_type(&perm).len(), num_cycles);
+            }
+        }
+    }
+
+    #[test]
+    fn large_cycles() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+
+        let lengths = [1, 1000, 1, 50_000, 7];
+        let perm = random_permutation_with_cycle_type(&mut rng, &lengths);
+        assert_eq!(cycle_type(&perm), vec![1, 1, 7, 1000, 50_000]);
+
+        for num_cycles in [1, 10, 2000] {
+            let perm = random_permutation_with_num_cycles(&mut rng, 2000, num_cycles);
+            assert_eq!(cycle_type(&perm).len(), num_cycles);
+        }
+    }
+
+    #[test]
+    fn invalid_cycles() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+
+        assert_eq!(
+            try_random_permutation_with_cycle_type(&mut rng, &[2, 0, 1]),
+            Err(ShuffleError::InvalidCycles)
+        );
+        assert_eq!(
+            try_random_permutation_with_cycle_type(&mut rng, &[]),
+            Ok(vec![])
+        );
+
+        for (n, num_cycles) in [(5, 0), (3, 4)] {
+            assert_eq!(
+                try_random_permutation_with_num_cycles(&mut rng, n, num_cycles),
+                Err(ShuffleError::InvalidCycles)
+            );
+        }
+        assert_eq!(
+            try_random_permutation_with_num_cycles(&mut rng, 0, 0),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn cycle_type_of_non_permutation() {
+        cycle_type(&[1, 1, 0]);
+    }
+}
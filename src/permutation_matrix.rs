@@ -0,0 +1,264 @@
+//! Permutations as sparse permutation matrices.
+//!
+//! Numerical linear algebra codes typically consume permutations as matrices, e.g., to
+//! reorder the rows of a system before a factorization, or to randomize the order of
+//! unknowns. A [`PermutationMatrix`] wraps a permutation `perm` of `0..n` and
+//! represents the `n x n` matrix `P` with a one in row `i` and column `perm[i]` (and
+//! zeros elsewhere). Hence, `P x` gathers `x[perm[i]]` into position `i`, and the
+//! transpose (which is the inverse) scatters `x[i]` to position `perm[i]`.
+//!
+//! The matrix can be exported in compressed sparse row (CSR) or column (CSC) format,
+//! or applied to vectors directly without materializing it.
+
+use super::error::ShuffleError;
+use super::fisher_yates::fisher_yates;
+use rand::Rng;
+
+/// The permutation matrix of a permutation of `0..n`. See the [module documentation](self).
+///
+/// # Example
+/// ```
+/// use rip_shuffle::permutation_matrix::PermutationMatrix;
+///
+/// let p = PermutationMatrix::from_permutation(vec![2, 0, 1]);
+///
+/// let (row_offsets, col_indices) = p.to_csr();
+/// assert_eq!(row_offsets, vec![0, 1, 2, 3]);
+/// assert_eq!(col_indices, vec![2, 0, 1]);
+///
+/// assert_eq!(p.mul_vec(&[10, 20, 30]), vec![30, 10, 20]);
+/// assert_eq!(p.transpose().mul_vec(&[30, 10, 20]), vec![10, 20, 30]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PermutationMatrix {
+    perm: Vec<usize>,
+}
+
+impl PermutationMatrix {
+    /// Returns a uniformly random permutation matrix of dimension `n`.
+    pub fn random<R: Rng>(rng: &mut R, n: usize) -> Self {
+        let mut perm: Vec<usize> = (0..n).collect();
+        fisher_yates(rng, &mut perm);
+        Self { perm }
+    }
+
+    /// Returns the identity matrix of dimension `n`.
+    pub fn identity(n: usize) -> Self {
+        Self {
+            perm: (0..n).collect(),
+        }
+    }
+
+    /// Returns the matrix with a one in row `i` and column `perm[i]` for each `i`.
+    ///
+    /// # Panics
+    /// Panics if `perm` is not a permutation of `0..perm.len()`; see
+    /// [`PermutationMatrix::try_from_permutation`] for a non-panicking variant.
+    pub fn from_permutation(perm: Vec<usize>) -> Self {
+        Self::try_from_permutation(perm).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`PermutationMatrix::from_permutation`], but returns
+    /// [`ShuffleError::NotAPermutation`] instead of panicking.
+    pub fn try_from_permutation(perm: Vec<usize>) -> Result<Self, ShuffleError> {
+        let mut seen = vec![false; perm.len()];
+        for &x in &perm {
+            match seen.get_mut(x) {
+                Some(s) if !*s => *s = true,
+                _ => return Err(ShuffleError::NotAPermutation),
+            }
+        }
+
+        Ok(Self { perm })
+    }
+
+    /// Number of rows (and columns) of the matrix.
+    pub fn dim(&self) -> usize {
+        self.perm.len()
+    }
+
+    /// The underlying permutation, i.e. the column of the one in each row.
+    pub fn permutation(&self) -> &[usize] {
+        &self.perm
+    }
+
+    /// Returns the underlying permutation without copying it.
+    pub fn into_permutation(self) -> Vec<usize> {
+        self.perm
+    }
+
+    /// Returns the transpose of the matrix, which is also its inverse.
+    pub fn transpose(&self) -> Self {
+        let mut inverse = vec![0; self.dim()];
+        for (i, &j) in self.perm.iter().enumerate() {
+            inverse[j] = i;
+        }
+
+        Self { perm: inverse }
+    }
+
+    /// Returns the row offsets (of length `dim() + 1`) and the column indices (of length
+    /// `dim()`) of the matrix in compressed sparse row format. All stored values are one.
+    pub fn to_csr(&self) -> (Vec<usize>, Vec<usize>) {
+        ((0..=self.dim()).collect(), self.perm.clone())
+    }
+
+    /// Returns the column offsets (of length `dim() + 1`) and the row indices (of length
+    /// `dim()`) of the matrix in compressed sparse column format. All stored values are
+    /// one.
+    pub fn to_csc(&self) -> (Vec<usize>, Vec<usize>) {
+        self.transpose().to_csr()
+    }
+
+    /// Returns the product `P x`, i.e. the vector with `x[perm[i]]` at position `i`.
+    ///
+    /// # Panics
+    /// Panics if `x.len() != self.dim()`; see [`PermutationMatrix::try_mul_vec`] for a
+    /// non-panicking variant.
+    pub fn mul_vec<T: Clone>(&self, x: &[T]) -> Vec<T> {
+        self.try_mul_vec(x).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`PermutationMatrix::mul_vec`], but returns [`ShuffleError::ShapeMismatch`]
+    /// instead of panicking.
+    pub fn try_mul_vec<T: Clone>(&self, x: &[T]) -> Result<Vec<T>, ShuffleError> {
+        if x.len() != self.dim() {
+            return Err(ShuffleError::ShapeMismatch);
+        }
+
+        Ok(self.perm.iter().map(|&j| x[j].clone()).collect())
+    }
+
+    /// Replaces `x` by the product `P x` in-place, following the cycles of the
+    /// permutation. In contrast to [`PermutationMatrix::mul_vec`], the elements need not
+    /// implement [`Clone`].
+    ///
+    /// # Panics
+    /// Panics if `x.len() != self.dim()`; see [`PermutationMatrix::try_mul_vec_in_place`]
+    /// for a non-panicking variant.
+    pub fn mul_vec_in_place<T>(&self, x: &mut [T]) {
+        self.try_mul_vec_in_place(x)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`PermutationMatrix::mul_vec_in_place`], but returns
+    /// [`ShuffleError::ShapeMismatch`] and leaves `x` untouched instead of panicking.
+    pub fn try_mul_vec_in_place<T>(&self, x: &mut [T]) -> Result<(), ShuffleError> {
+        if x.len() != self.dim() {
+            return Err(ShuffleError::ShapeMismatch);
+        }
+
+        let mut done = vec![false; self.dim()];
+        for start in 0..self.dim() {
+            // pulls the element of the successor into each position of the cycle; the
+            // element of `start` travels along until it reaches the last position
+            let mut i = start;
+            while !done[i] {
+                done[i] = true;
+                let j = self.perm[i];
+                if j != start {
+                    x.swap(i, j);
+                }
+                i = j;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    /// Dense reference product of the matrix with `x`.
+    fn dense_mul_vec(p: &PermutationMatrix, x: &[i64]) -> Vec<i64> {
+        let n = p.dim();
+        let (row_offsets, col_indices) = p.to_csr();
+        let mut dense = vec![vec![0; n]; n];
+        for i in 0..n {
+            for &j in &col_indices[row_offsets[i]..row_offsets[i + 1]] {
+                dense[i][j] = 1;
+            }
+        }
+
+        dense
+            .iter()
+            .map(|row| row.iter().zip(x).map(|(a, b)| a * b).sum())
+            .collect()
+    }
+
+    #[test]
+    fn matches_dense_product() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for n in [0, 1, 2, 17, 100] {
+            let p = PermutationMatrix::random(&mut rng, n);
+            let x: Vec<i64> = (0..n as i64).map(|i| 3 * i + 1).collect();
+
+            assert_eq!(p.mul_vec(&x), dense_mul_vec(&p, &x));
+            assert_eq!(p.transpose().mul_vec(&x), dense_mul_vec(&p.transpose(), &x));
+            assert_eq!(p.transpose().mul_vec(&p.mul_vec(&x)), x);
+        }
+    }
+
+    #[test]
+    fn csc_is_transposed_csr() {
+        let p = PermutationMatrix::random(&mut Pcg64Mcg::seed_from_u64(1), 50);
+        let (col_offsets, row_indices) = p.to_csc();
+
+        assert_eq!(col_offsets, (0..=50).collect::<Vec<_>>());
+        for (i, &j) in p.permutation().iter().enumerate() {
+            assert_eq!(row_indices[j], i);
+        }
+
+        assert_eq!(p.transpose().transpose(), p);
+        assert_eq!(
+            PermutationMatrix::identity(50).transpose(),
+            PermutationMatrix::identity(50)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        for perm in [vec![0, 0], vec![1, 2], vec![2, 0, 0]] {
+            assert_eq!(
+                PermutationMatrix::try_from_permutation(perm),
+                Err(ShuffleError::NotAPermutation)
+            );
+        }
+
+        let p = PermutationMatrix::identity(3);
+        assert_eq!(p.try_mul_vec(&[1, 2]), Err(ShuffleError::ShapeMismatch));
+        assert_eq!(
+            p.try_mul_vec_in_place(&mut [1, 2, 3, 4]),
+            Err(ShuffleError::ShapeMismatch)
+        );
+    }
+
+    #[test]
+    fn in_place_matches_mul_vec() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+
+        for n in [0, 1, 2, 17, 1000] {
+            let p = PermutationMatrix::random(&mut rng, n);
+            let x: Vec<usize> = (0..n).collect();
+
+            let mut y = x.clone();
+            p.mul_vec_in_place(&mut y);
+            assert_eq!(y, p.mul_vec(&x));
+        }
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn shuffle<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            PermutationMatrix::random(rng, data.len()).mul_vec_in_place(data)
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle);
+    }
+}
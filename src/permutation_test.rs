@@ -0,0 +1,173 @@
+//! Monte-Carlo permutation tests.
+//!
+//! A permutation test compares a statistic of the observed data against its
+//! distribution over random permutations of the data. This requires shuffling the
+//! same input many times, which [`permutation_test`] does in parallel: the iterations
+//! are split into blocks with independently seeded random number generators, and
+//! each worker thread shuffles a single reused copy of the input. Apart from one
+//! buffer per thread and the result vector, no memory is allocated.
+
+use super::scatter_shuffle::parallel::seed_new_rng;
+use super::scatter_shuffle::sequential::seq_scatter_shuffle;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Number of iterations that share a random number generator. The result does not
+/// depend on the number of threads, since each block is seeded in sequence.
+const ITERATIONS_PER_BLOCK: usize = 64;
+
+/// Result of [`permutation_test`].
+#[derive(Clone, Debug)]
+pub struct PermutationTest {
+    /// The statistic evaluated on the input in its original order.
+    pub observed: f64,
+    /// The statistic evaluated on each random permutation of the input.
+    pub statistics: Vec<f64>,
+}
+
+impl PermutationTest {
+    /// Returns the (one-sided) p-value of the observation, i.e. the estimated
+    /// probability that a random permutation yields a statistic at least as large as
+    /// the observed one. The estimate `(1 + #{s >= observed}) / (1 + iterations)`
+    /// never returns zero. For a two-sided test, use the absolute value as statistic.
+    pub fn p_value(&self) -> f64 {
+        let at_least_as_extreme = self
+            .statistics
+            .iter()
+            .filter(|&&s| s >= self.observed)
+            .count();
+
+        (1 + at_least_as_extreme) as f64 / (1 + self.statistics.len()) as f64
+    }
+}
+
+/// Evaluates `statistic` on `data` and on `iterations` independent random permutations
+/// of `data`. The iterations are processed in parallel; the result only depends on
+/// `rng` (and not on the number of threads).
+///
+/// # Example
+/// ```
+/// use rip_shuffle::permutation_test::permutation_test;
+/// use rand::SeedableRng;
+///
+/// // is the mean of the first group larger than the one of the second group?
+/// let data: Vec<f64> = (0..20).map(|i| if i < 10 { 5.0 + i as f64 } else { i as f64 - 10.0 }).collect();
+/// let mean_difference = |d: &[f64]| d[..10].iter().sum::<f64>() / 10.0 - d[10..].iter().sum::<f64>() / 10.0;
+///
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+/// let result = permutation_test(&mut rng, &data, 10_000, mean_difference);
+///
+/// assert_eq!(result.observed, 5.0);
+/// assert!(result.p_value() < 0.01);
+/// ```
+pub fn permutation_test<R, T, S>(
+    rng: &mut R,
+    data: &[T],
+    iterations: usize,
+    statistic: S,
+) -> PermutationTest
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Clone + Send + Sync,
+    S: Fn(&[T]) -> f64 + Sync,
+{
+    let observed = statistic(data);
+
+    let mut statistics = vec![0.0; iterations];
+    let rngs: Vec<R> = (0..iterations.div_ceil(ITERATIONS_PER_BLOCK))
+        .map(|_| seed_new_rng(rng))
+        .collect();
+
+    statistics
+        .par_chunks_mut(ITERATIONS_PER_BLOCK)
+        .zip(rngs)
+        .for_each_init(
+            || data.to_vec(),
+            |buffer, (block, mut rng)| {
+                for result in block {
+                    // restoring the input keeps the result independent of the scheduling
+                    buffer.clone_from_slice(data);
+                    seq_scatter_shuffle(&mut rng, buffer);
+                    *result = statistic(buffer);
+                }
+            },
+        );
+
+    PermutationTest {
+        observed,
+        statistics,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    fn first_minus_second_half(data: &[f64]) -> f64 {
+        let (left, right) = data.split_at(data.len() / 2);
+        left.iter().sum::<f64>() - right.iter().sum::<f64>()
+    }
+
+    #[test]
+    fn no_effect_on_constant_data() {
+        let mut rng = Pcg64Mcg::seed_from_u64(123);
+        let data = vec![1.0; 100];
+
+        let result = permutation_test(&mut rng, &data, 1000, first_minus_second_half);
+
+        assert_eq!(result.statistics.len(), 1000);
+        assert_eq!(result.p_value(), 1.0);
+    }
+
+    #[test]
+    fn detects_effect() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+        let data: Vec<f64> = (0..100).map(|i| (100 - i) as f64).collect();
+
+        let result = permutation_test(&mut rng, &data, 1000, first_minus_second_half);
+        assert!(result.p_value() < 0.01);
+
+        // the reversed input shows no effect in this direction
+        let data: Vec<f64> = data.into_iter().rev().collect();
+        let result = permutation_test(&mut rng, &data, 1000, first_minus_second_half);
+        assert!(result.p_value() > 0.99);
+    }
+
+    #[test]
+    fn p_values_uniform_under_null() {
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+        const TESTS: usize = 200;
+
+        let below_tenth = (0..TESTS)
+            .filter(|_| {
+                let data: Vec<f64> = (0..30).map(|_| rng.gen()).collect();
+                permutation_test(&mut rng, &data, 200, first_minus_second_half).p_value() <= 0.1
+            })
+            .count();
+
+        assert!(below_tenth > TESTS / 30 && below_tenth < TESTS / 5);
+    }
+
+    #[test]
+    fn deterministic() {
+        let data: Vec<f64> = (0..50).map(|i| (i * i % 17) as f64).collect();
+
+        let results: Vec<_> = (0..2)
+            .map(|_| {
+                let mut rng = Pcg64Mcg::seed_from_u64(23456);
+                permutation_test(&mut rng, &data, 500, first_minus_second_half).statistics
+            })
+            .collect();
+
+        assert_eq!(results[0], results[1]);
+    }
+
+    #[test]
+    fn no_iterations() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let result = permutation_test(&mut rng, &[1.0, 2.0], 0, first_minus_second_half);
+        assert!(result.statistics.is_empty());
+        assert_eq!(result.p_value(), 1.0);
+    }
+}
@@ -0,0 +1,99 @@
+//! Pinning the workers of parallel shuffles to cores (requires the `pinning` feature).
+//!
+//! Parallel shuffles are bound by memory bandwidth; on large multi-socket machines,
+//! workers migrating between cores (and NUMA nodes) measurably slow them down. The
+//! functions in this module run a shuffle in a dedicated rayon thread pool whose
+//! workers are pinned to given cores via [`core_affinity`].
+//!
+//! Pinning is best-effort: if the operating system rejects it (e.g., because the core
+//! is not part of the process' cpuset) the worker keeps running unpinned.
+
+use super::scatter_shuffle::parallel::par_scatter_shuffle;
+use rand::{Rng, SeedableRng};
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+
+pub use core_affinity::CoreId;
+
+/// Returns the cores available to this process, or an empty vector if they cannot be
+/// determined on this platform.
+pub fn available_cores() -> Vec<CoreId> {
+    core_affinity::get_core_ids().unwrap_or_default()
+}
+
+/// Builds a rayon thread pool with one worker per entry of `cores`; the `i`-th worker is
+/// pinned to `cores[i]`. If `cores` is empty, all [`available_cores`] are used.
+///
+/// Building a pool spawns threads, so it should be reused for repeated shuffles:
+///
+/// # Example
+/// ```
+/// use rip_shuffle::pinning::*;
+/// use rip_shuffle::RipShuffleParallel;
+/// use rand::SeedableRng;
+///
+/// let pool = pinned_thread_pool(&available_cores()).unwrap();
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+///
+/// let mut data: Vec<_> = (0..1_000_000).collect();
+/// pool.install(|| data.par_shuffle(&mut rng));
+/// ```
+pub fn pinned_thread_pool(cores: &[CoreId]) -> Result<ThreadPool, ThreadPoolBuildError> {
+    let mut cores = cores.to_vec();
+    if cores.is_empty() {
+        cores = available_cores();
+    }
+
+    ThreadPoolBuilder::new()
+        .num_threads(cores.len())
+        .thread_name(|i| format!("rip_shuffle-pinned-{i}"))
+        .start_handler(move |i| {
+            if let Some(&core) = cores.get(i) {
+                core_affinity::set_for_current(core);
+            }
+        })
+        .build()
+}
+
+/// Shuffles `data` with [`par_scatter_shuffle`] on workers pinned to `cores` (see
+/// [`pinned_thread_pool`]). This builds a new thread pool for each call.
+pub fn par_shuffle_pinned<R, T>(
+    rng: &mut R,
+    data: &mut [T],
+    cores: &[CoreId],
+) -> Result<(), ThreadPoolBuildError>
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send,
+{
+    let pool = pinned_thread_pool(cores)?;
+    pool.install(|| par_scatter_shuffle(rng, data));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn pool_size() {
+        let cores = available_cores();
+        let pool = pinned_thread_pool(&cores[..1]).unwrap();
+        assert_eq!(pool.current_num_threads(), 1);
+
+        let pool = pinned_thread_pool(&[]).unwrap();
+        assert_eq!(pool.current_num_threads(), cores.len().max(1));
+    }
+
+    #[test]
+    fn shuffle_pinned() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+        let mut data: Vec<usize> = (0..1 << 20).collect();
+
+        par_shuffle_pinned(&mut rng, &mut data, &available_cores()).unwrap();
+
+        assert_ne!(data[..10], [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+    }
+}
@@ -0,0 +1,803 @@
+//! Reusable shuffle plans.
+//!
+//! The entry points such as [`crate::RipShuffleParallel::par_shuffle`] select an
+//! algorithm and its tuning constants (number of buckets, base case size, number of
+//! subproblems) on each call based on the input size. A [`ShufflePlan`] makes these
+//! decisions once for a given length and element size and can then be executed on
+//! any number of same-shaped inputs, e.g., the training set in every epoch. It also
+//! exposes the decisions, which is useful to reason about performance.
+//!
+//! The defaults can be overridden with [`ShufflePlan::with_base_case_size`],
+//! [`ShufflePlan::with_num_buckets`], and [`ShufflePlan::with_max_subproblems`].
+//! These validate the setting and report invalid ones as [`ShuffleError`] when the plan
+//! is set up, rather than failing deep inside the algorithm. Alternatively,
+//! [`ShufflePlan::with_preset`] selects a [`Preset`] that bundles the settings for a
+//! common goal, e.g., low latency or reproducibility.
+//!
+//! Executing a plan emits the same permutation as the corresponding entry point.
+//! Note that all algorithms work in-place and do not allocate scratch buffers, so
+//! there is no memory to be retained between invocations.
+
+use super::error::ShuffleError;
+use super::fisher_yates::{fisher_yates, naive};
+use super::scatter_shuffle::parallel::{self, ParScatterShuffleImpl};
+use super::scatter_shuffle::sequential::{self, SeqScatterShuffleImpl};
+use super::scatter_shuffle::{ParConfiguration, SeqConfiguration};
+use rand::{Rng, SeedableRng};
+
+/// Numbers of buckets accepted by [`ShufflePlan::with_num_buckets`].
+pub const SUPPORTED_NUM_BUCKETS: [usize; 5] = [64, 128, 256, 512, 1024];
+
+/// Base case size of the parallel scatter shuffle in the [`Preset::LowLatency`] preset.
+const LOW_LATENCY_BASE_CASE_SIZE: usize = 1 << 16;
+
+/// Named combinations of settings for a [`ShufflePlan`], selected with
+/// [`ShufflePlan::with_preset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// The settings of the entry points, tuned for the throughput on large inputs.
+    Throughput,
+    /// Keeps the auxiliary state small: 64 buckets and at most one subproblem per
+    /// thread of the current rayon pool, which also bounds the number of random
+    /// number generators seeded for the parallel scatter shuffle.
+    LowMemory,
+    /// Splits parallel shuffles into smaller subproblems (of about `2^16` elements),
+    /// which are balanced better across threads, so the last thread finishes earlier.
+    /// Sequential plans use the default settings.
+    LowLatency,
+    /// Executes the naive Fisher-Yates shuffle, so the output only depends on the random
+    /// number generator, and not on the build configuration, the CPU, the runtime
+    /// switches, or the number of threads (see also [`crate::oracle`]). This sacrifices
+    /// performance and parallelism.
+    Deterministic,
+}
+
+/// The algorithm executed by a [`ShufflePlan`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// A single Fisher-Yates pass; used for small inputs.
+    FisherYates,
+    /// The sequential scatter shuffle.
+    SeqScatter { num_buckets: usize },
+    /// The parallel scatter shuffle.
+    ParScatter { num_buckets: usize },
+}
+
+/// Precomputed algorithm selection and tuning constants for shuffling inputs of a
+/// fixed length and element size. See the [module documentation](self).
+///
+/// # Example
+/// ```
+/// use rip_shuffle::plan::ShufflePlan;
+/// use rand::SeedableRng;
+///
+/// let mut data: Vec<u64> = (0..1_000_000).collect();
+/// let plan = ShufflePlan::parallel::<u64>(data.len());
+///
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+/// for _epoch in 0..3 {
+///     plan.shuffle(&mut rng, &mut data);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShufflePlan {
+    len: usize,
+    element_size: usize,
+    parallel: bool,
+    num_buckets: usize,
+    algorithm: Algorithm,
+    config: PlanConfiguration,
+}
+
+impl ShufflePlan {
+    /// Plans a sequential shuffle as executed by
+    /// [`crate::RipShuffleSequential::seq_shuffle`].
+    pub fn sequential<T>(len: usize) -> Self {
+        Self::new::<T>(len, false)
+    }
+
+    /// Plans a parallel shuffle as executed by [`crate::RipShuffleParallel::par_shuffle`].
+    pub fn parallel<T>(len: usize) -> Self {
+        Self::new::<T>(len, true)
+    }
+
+    fn new<T>(len: usize, parallel: bool) -> Self {
+        Self::with_shape(len, std::mem::size_of::<T>(), parallel)
+    }
+
+    pub(crate) fn with_shape(len: usize, element_size: usize, parallel: bool) -> Self {
+        let mut plan = Self {
+            len,
+            element_size,
+            parallel,
+            num_buckets: 0,
+            algorithm: Algorithm::FisherYates,
+            config: PlanConfiguration::default(),
+        };
+        plan.num_buckets = plan.default_num_buckets();
+        plan.select_algorithm();
+        plan
+    }
+
+    fn default_num_buckets(&self) -> usize {
+        if !self.parallel {
+            return sequential::NUM_BUCKETS;
+        }
+
+        parallel::num_buckets_for_bytes(self.len.saturating_mul(self.element_size)).unwrap_or(64)
+    }
+
+    fn select_algorithm(&mut self) {
+        let num_buckets = self.num_buckets;
+
+        self.algorithm = if self.config.reference {
+            Algorithm::FisherYates
+        } else if self.parallel {
+            let num_bytes = self.len.saturating_mul(self.element_size);
+            if num_bytes <= parallel::SEQUENTIAL_MAX_BYTES {
+                Algorithm::FisherYates
+            } else {
+                Algorithm::ParScatter { num_buckets }
+            }
+        } else if self.len <= self.config.seq_base_case_size {
+            Algorithm::FisherYates
+        } else {
+            Algorithm::SeqScatter { num_buckets }
+        };
+    }
+
+    /// Replaces all settings by the ones of `preset`. Settings overridden afterwards
+    /// (e.g., with [`ShufflePlan::with_num_buckets`]) take precedence over the preset.
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::plan::{Algorithm, Preset, ShufflePlan};
+    ///
+    /// let plan = ShufflePlan::parallel::<u64>(1 << 24).with_preset(Preset::LowMemory);
+    /// assert_eq!(plan.algorithm(), Algorithm::ParScatter { num_buckets: 64 });
+    ///
+    /// let plan = ShufflePlan::parallel::<u64>(1 << 24).with_preset(Preset::Deterministic);
+    /// assert_eq!(plan.algorithm(), Algorithm::FisherYates);
+    /// ```
+    pub fn with_preset(mut self, preset: Preset) -> Self {
+        self.config = PlanConfiguration::default();
+        self.num_buckets = self.default_num_buckets();
+
+        match preset {
+            Preset::Throughput => {}
+            Preset::LowMemory => {
+                self.num_buckets = 64;
+                if self.parallel {
+                    self.config.par_max_subproblems = rayon::current_num_threads();
+                }
+            }
+            Preset::LowLatency => {
+                if self.parallel {
+                    self.config.par_base_case_size = LOW_LATENCY_BASE_CASE_SIZE;
+                }
+            }
+            Preset::Deterministic => self.config.reference = true,
+        }
+
+        self.select_algorithm();
+        self
+    }
+
+    /// Overrides the size below which subproblems are shuffled with Fisher-Yates.
+    /// Returns [`ShuffleError::ZeroBaseCaseSize`] if `base_case_size` is zero.
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::{error::ShuffleError, plan::{Algorithm, ShufflePlan}};
+    ///
+    /// let plan = ShufflePlan::sequential::<u32>(1000).with_base_case_size(100).unwrap();
+    /// assert_eq!(plan.algorithm(), Algorithm::SeqScatter { num_buckets: 128 });
+    ///
+    /// let plan = ShufflePlan::sequential::<u32>(1000).with_base_case_size(0);
+    /// assert_eq!(plan, Err(ShuffleError::ZeroBaseCaseSize));
+    /// ```
+    pub fn with_base_case_size(mut self, base_case_size: usize) -> Result<Self, ShuffleError> {
+        if self.parallel {
+            self.config.par_base_case_size = base_case_size;
+        } else {
+            self.config.seq_base_case_size = base_case_size;
+        }
+
+        self.config.seq_validate()?;
+        self.config.par_validate()?;
+        self.select_algorithm();
+        Ok(self)
+    }
+
+    /// Overrides the number of buckets of the scatter shuffle. Returns
+    /// [`ShuffleError::UnsupportedNumberOfBuckets`] unless `num_buckets` is
+    /// contained in [`SUPPORTED_NUM_BUCKETS`]. Has no effect if the input is small
+    /// enough for Fisher-Yates.
+    pub fn with_num_buckets(mut self, num_buckets: usize) -> Result<Self, ShuffleError> {
+        if !SUPPORTED_NUM_BUCKETS.contains(&num_buckets) {
+            return Err(ShuffleError::UnsupportedNumberOfBuckets);
+        }
+
+        self.num_buckets = num_buckets;
+        self.select_algorithm();
+        Ok(self)
+    }
+
+    /// Overrides the maximum number of subproblems the parallel scatter shuffle
+    /// splits its input into. Returns [`ShuffleError::ZeroSubproblems`] if
+    /// `max_subproblems` is zero.
+    pub fn with_max_subproblems(mut self, max_subproblems: usize) -> Result<Self, ShuffleError> {
+        if max_subproblems == 0 {
+            return Err(ShuffleError::ZeroSubproblems);
+        }
+
+        self.config.par_max_subproblems = max_subproblems;
+        Ok(self)
+    }
+
+    /// Number of elements of the inputs this plan applies to.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the plan applies to empty inputs.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size in bytes of the elements of the inputs this plan applies to.
+    pub fn element_size(&self) -> usize {
+        self.element_size
+    }
+
+    /// The algorithm selected for the input shape.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Size below which subproblems are shuffled with Fisher-Yates.
+    pub fn base_case_size(&self) -> usize {
+        if self.parallel {
+            self.config.par_base_case_size
+        } else {
+            self.config.seq_base_case_size
+        }
+    }
+
+    /// Number of buckets of the scatter shuffle, even if it is not selected.
+    #[cfg(feature = "seed_with")]
+    pub(crate) fn num_buckets(&self) -> usize {
+        self.num_buckets
+    }
+
+    /// Maximum number of subproblems of the parallel scatter shuffle.
+    #[cfg(feature = "seed_with")]
+    pub(crate) fn max_subproblems(&self) -> usize {
+        self.config.par_max_subproblems
+    }
+
+    /// Returns `true` if the plan was created for a parallel shuffle.
+    #[cfg(feature = "seed_with")]
+    pub(crate) fn is_parallel_plan(&self) -> bool {
+        self.parallel
+    }
+
+    /// Returns `true` if the plan executes the naive Fisher-Yates shuffle.
+    #[cfg(feature = "seed_with")]
+    pub(crate) fn is_deterministic(&self) -> bool {
+        self.config.reference
+    }
+
+    /// Returns `true` if executing the plan uses the rayon worker pool.
+    pub fn is_parallel(&self) -> bool {
+        matches!(self.algorithm, Algorithm::ParScatter { .. })
+    }
+
+    /// Reports the decisions of this plan together with the code paths selected by
+    /// the build configuration and the runtime switches, without running the shuffle.
+    /// The result implements [`std::fmt::Display`] and is intended for performance
+    /// debugging and to be logged alongside results for reproducibility.
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::plan::{Algorithm, ShufflePlan};
+    ///
+    /// let explanation = ShufflePlan::parallel::<u64>(1 << 24).explain();
+    /// if explanation.oracle {
+    ///     // the `oracle` mode replaces every plan by the naive Fisher-Yates shuffle
+    ///     assert_eq!(explanation.algorithm, Algorithm::FisherYates);
+    /// } else {
+    ///     assert_eq!(explanation.algorithm, Algorithm::ParScatter { num_buckets: 256 });
+    ///     assert!(explanation.parallel);
+    /// }
+    ///
+    /// println!("{explanation}");
+    /// ```
+    pub fn explain(&self) -> Explanation {
+        let oracle = crate::oracle::is_enabled();
+        let reference = oracle || self.config.reference;
+        let algorithm = if oracle {
+            Algorithm::FisherYates
+        } else {
+            self.algorithm
+        };
+        let parallel = matches!(algorithm, Algorithm::ParScatter { .. });
+
+        Explanation {
+            len: self.len,
+            element_size: self.element_size,
+            algorithm,
+            base_case_size: self.base_case_size(),
+            max_subproblems: parallel.then_some(self.config.par_max_subproblems),
+            parallel,
+            unsafe_algos: !reference && crate::unsafe_algos::is_enabled(),
+            prefetch: !reference && cfg!(feature = "prefetch"),
+            oracle,
+        }
+    }
+
+    /// Executes the plan, i.e. rearranges `data` in a uniform random permutation.
+    ///
+    /// # Panics
+    /// Panics if the shape of `data` does not match the plan.
+    pub fn shuffle<R, T>(&self, rng: &mut R, data: &mut [T])
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        T: Send,
+    {
+        match self.algorithm {
+            Algorithm::ParScatter { num_buckets } => {
+                self.assert_shape(data);
+                if crate::oracle::is_enabled() {
+                    return naive::fisher_yates(rng, data);
+                }
+
+                let config = self.config;
+                match num_buckets {
+                    64 => ParScatterShuffleImpl::<R, T, _, 64>::new(config).shuffle(rng, data),
+                    128 => ParScatterShuffleImpl::<R, T, _, 128>::new(config).shuffle(rng, data),
+                    256 => ParScatterShuffleImpl::<R, T, _, 256>::new(config).shuffle(rng, data),
+                    512 => ParScatterShuffleImpl::<R, T, _, 512>::new(config).shuffle(rng, data),
+                    1024 => ParScatterShuffleImpl::<R, T, _, 1024>::new(config).shuffle(rng, data),
+                    _ => unreachable!("plans only use supported numbers of buckets"),
+                }
+            }
+            _ => self.seq_shuffle(rng, data),
+        }
+    }
+
+    /// Executes a sequential plan. In contrast to [`ShufflePlan::shuffle`], this
+    /// imposes no constraints on the random number generator and the elements.
+    ///
+    /// # Panics
+    /// Panics if the shape of `data` does not match the plan, or if the plan is parallel.
+    /// See [`ShufflePlan::try_seq_shuffle`] for a non-panicking variant.
+    pub fn seq_shuffle<R: Rng, T>(&self, rng: &mut R, data: &mut [T]) {
+        self.try_seq_shuffle(rng, data)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`ShufflePlan::seq_shuffle`], but returns [`ShuffleError::ShapeMismatch`]
+    /// or [`ShuffleError::ParallelPlan`] instead of panicking. In this case, the input
+    /// remains untouched.
+    pub fn try_seq_shuffle<R: Rng, T>(
+        &self,
+        rng: &mut R,
+        data: &mut [T],
+    ) -> Result<(), ShuffleError> {
+        self.check_shape(data)?;
+        if self.is_parallel() {
+            return Err(ShuffleError::ParallelPlan);
+        }
+
+        if crate::oracle::is_enabled() || self.config.reference {
+            naive::fisher_yates(rng, data);
+            return Ok(());
+        }
+
+        let config = self.config;
+        match self.algorithm {
+            Algorithm::FisherYates => fisher_yates(rng, data),
+            Algorithm::SeqScatter { num_buckets } => match num_buckets {
+                64 => SeqScatterShuffleImpl::<R, T, _, 64>::new(config).shuffle(rng, data),
+                128 => SeqScatterShuffleImpl::<R, T, _, 128>::new(config).shuffle(rng, data),
+                256 => SeqScatterShuffleImpl::<R, T, _, 256>::new(config).shuffle(rng, data),
+                512 => SeqScatterShuffleImpl::<R, T, _, 512>::new(config).shuffle(rng, data),
+                1024 => SeqScatterShuffleImpl::<R, T, _, 1024>::new(config).shuffle(rng, data),
+                _ => return Err(ShuffleError::UnsupportedNumberOfBuckets),
+            },
+            Algorithm::ParScatter { .. } => unreachable!("parallel plans are rejected above"),
+        }
+
+        Ok(())
+    }
+
+    fn assert_shape<T>(&self, data: &[T]) {
+        self.check_shape(data).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn check_shape<T>(&self, data: &[T]) -> Result<(), ShuffleError> {
+        if data.len() == self.len && std::mem::size_of::<T>() == self.element_size {
+            Ok(())
+        } else {
+            Err(ShuffleError::ShapeMismatch)
+        }
+    }
+}
+
+/// Report returned by [`ShufflePlan::explain`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    /// Number of elements of the input.
+    pub len: usize,
+    /// Size of each element in bytes.
+    pub element_size: usize,
+    /// The algorithm that is executed; always [`Algorithm::FisherYates`] in oracle mode.
+    pub algorithm: Algorithm,
+    /// Size below which subproblems are shuffled with Fisher-Yates.
+    pub base_case_size: usize,
+    /// Maximum number of subproblems (only for parallel algorithms).
+    pub max_subproblems: Option<usize>,
+    /// Whether the rayon worker pool is used.
+    pub parallel: bool,
+    /// Whether the unsafe fast paths are used (see [`crate::unsafe_algos`]).
+    pub unsafe_algos: bool,
+    /// Whether explicit prefetching is used.
+    pub prefetch: bool,
+    /// Whether the oracle mode forces the reference implementation (see [`crate::oracle`]).
+    pub oracle: bool,
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "n={} element_size={} ", self.len, self.element_size)?;
+
+        match self.algorithm {
+            Algorithm::FisherYates => write!(f, "algorithm=fisher_yates")?,
+            Algorithm::SeqScatter { num_buckets } => {
+                write!(f, "algorithm=seq_scatter buckets={num_buckets}")?
+            }
+            Algorithm::ParScatter { num_buckets } => {
+                write!(f, "algorithm=par_scatter buckets={num_buckets}")?
+            }
+        }
+
+        write!(f, " base_case={}", self.base_case_size)?;
+        if let Some(max_subproblems) = self.max_subproblems {
+            write!(f, " max_subproblems={max_subproblems}")?;
+        }
+
+        write!(
+            f,
+            " parallel={} unsafe_algos={} prefetch={} oracle={}",
+            self.parallel, self.unsafe_algos, self.prefetch, self.oracle
+        )
+    }
+}
+
+/// Runtime counterpart of the default configurations of the scatter shuffles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct PlanConfiguration {
+    seq_base_case_size: usize,
+    par_base_case_size: usize,
+    par_max_subproblems: usize,
+    /// Use the naive Fisher-Yates shuffle (see [`Preset::Deterministic`]).
+    reference: bool,
+}
+
+impl Default for PlanConfiguration {
+    fn default() -> Self {
+        Self {
+            seq_base_case_size: sequential::DEFAULT_BASE_CASE_SIZE,
+            par_base_case_size: parallel::DEFAULT_BASE_CASE_SIZE,
+            par_max_subproblems: parallel::DEFAULT_MAX_SUBPROBLEMS,
+            reference: false,
+        }
+    }
+}
+
+impl SeqConfiguration for PlanConfiguration {
+    fn seq_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+        fisher_yates(rng, data)
+    }
+
+    fn seq_base_case_size(&self) -> usize {
+        self.seq_base_case_size
+    }
+}
+
+impl ParConfiguration for PlanConfiguration {
+    fn par_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+        fisher_yates(rng, data)
+    }
+
+    fn par_base_case_size(&self) -> usize {
+        self.par_base_case_size
+    }
+
+    fn par_number_of_subproblems(&self, n: usize) -> usize {
+        parallel::default_number_of_subproblems(
+            n,
+            self.par_base_case_size,
+            self.par_max_subproblems,
+        )
+    }
+
+    crate::implement_no_profiler!();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn selects_algorithms() {
+        assert_eq!(
+            ShufflePlan::sequential::<u64>(1000).algorithm(),
+            Algorithm::FisherYates
+        );
+        assert_eq!(
+            ShufflePlan::sequential::<u64>(1 << 20).algorithm(),
+            Algorithm::SeqScatter { num_buckets: 128 }
+        );
+        assert_eq!(
+            ShufflePlan::parallel::<u64>(1 << 20).algorithm(),
+            Algorithm::FisherYates
+        );
+        assert_eq!(
+            ShufflePlan::parallel::<u64>(1 << 22).algorithm(),
+            Algorithm::ParScatter { num_buckets: 64 }
+        );
+        assert_eq!(
+            ShufflePlan::parallel::<u64>(1 << 24).algorithm(),
+            Algorithm::ParScatter { num_buckets: 256 }
+        );
+        assert!(!ShufflePlan::sequential::<u8>(1 << 30).is_parallel());
+    }
+
+    #[test]
+    fn matches_entry_points() {
+        for n in [0, 10, 1 << 19, (1 << 19) + 1, 1 << 21] {
+            let seq_plan = ShufflePlan::sequential::<u32>(n);
+            let par_plan = ShufflePlan::parallel::<u32>(n);
+
+            let mut expected: Vec<u32> = (0..n as u32).collect();
+            let mut actual = expected.clone();
+            seq_scatter_shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut expected);
+            seq_plan.seq_shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut actual);
+            assert_eq!(actual, expected, "n={n}");
+
+            let mut expected: Vec<u32> = (0..n as u32).collect();
+            let mut actual = expected.clone();
+            par_scatter_shuffle(&mut Pcg64Mcg::seed_from_u64(2), &mut expected);
+            par_plan.shuffle(&mut Pcg64Mcg::seed_from_u64(2), &mut actual);
+            assert_eq!(actual, expected, "n={n}");
+        }
+    }
+
+    #[test]
+    fn reusable() {
+        let mut rng = Pcg64Mcg::seed_from_u64(123);
+        let plan = ShufflePlan::parallel::<usize>(1 << 21);
+        let mut data: Vec<usize> = (0..plan.len()).collect();
+
+        for _ in 0..3 {
+            plan.shuffle(&mut rng, &mut data);
+        }
+
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+    }
+
+    #[test]
+    #[should_panic]
+    fn shape_mismatch() {
+        let plan = ShufflePlan::sequential::<u32>(10);
+        plan.seq_shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut [0u64; 10]);
+    }
+
+    #[test]
+    fn try_seq_shuffle_reports_errors() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let plan = ShufflePlan::sequential::<u32>(10);
+
+        assert_eq!(
+            plan.try_seq_shuffle(&mut rng, &mut [0u64; 10]),
+            Err(ShuffleError::ShapeMismatch)
+        );
+        assert_eq!(
+            plan.try_seq_shuffle(&mut rng, &mut [0u32; 11]),
+            Err(ShuffleError::ShapeMismatch)
+        );
+        assert_eq!(plan.try_seq_shuffle(&mut rng, &mut [0u32; 10]), Ok(()));
+
+        let plan = ShufflePlan::parallel::<u8>(1 << 24);
+        let mut data = vec![0u8; plan.len()];
+        assert_eq!(
+            plan.try_seq_shuffle(&mut rng, &mut data),
+            Err(ShuffleError::ParallelPlan)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        for plan in [
+            ShufflePlan::sequential::<u32>(1 << 20),
+            ShufflePlan::parallel::<u32>(1 << 24),
+        ] {
+            assert_eq!(
+                plan.with_base_case_size(0),
+                Err(ShuffleError::ZeroBaseCaseSize)
+            );
+            assert_eq!(
+                plan.with_num_buckets(100),
+                Err(ShuffleError::UnsupportedNumberOfBuckets)
+            );
+            assert_eq!(
+                plan.with_max_subproblems(0),
+                Err(ShuffleError::ZeroSubproblems)
+            );
+        }
+    }
+
+    #[test]
+    fn custom_settings() {
+        let plan = ShufflePlan::sequential::<u32>(1 << 12)
+            .with_base_case_size(1 << 8)
+            .unwrap()
+            .with_num_buckets(64)
+            .unwrap();
+        assert_eq!(plan.base_case_size(), 1 << 8);
+        assert_eq!(plan.algorithm(), Algorithm::SeqScatter { num_buckets: 64 });
+
+        // raising the base case size switches back to Fisher-Yates, but keeps the buckets
+        let plan = plan.with_base_case_size(1 << 12).unwrap();
+        assert_eq!(plan.algorithm(), Algorithm::FisherYates);
+        let plan = plan.with_base_case_size(1 << 10).unwrap();
+        assert_eq!(plan.algorithm(), Algorithm::SeqScatter { num_buckets: 64 });
+
+        let plan = ShufflePlan::parallel::<u64>(1 << 22)
+            .with_num_buckets(128)
+            .unwrap()
+            .with_max_subproblems(4)
+            .unwrap();
+        assert_eq!(plan.algorithm(), Algorithm::ParScatter { num_buckets: 128 });
+
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+        let mut data: Vec<u64> = (0..plan.len() as u64).collect();
+        plan.shuffle(&mut rng, &mut data);
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i as u64 == x));
+    }
+
+    #[test]
+    fn presets() {
+        let plan = ShufflePlan::parallel::<u64>(1 << 24)
+            .with_num_buckets(128)
+            .unwrap()
+            .with_preset(Preset::Throughput);
+        assert_eq!(plan, ShufflePlan::parallel::<u64>(1 << 24));
+
+        let plan = ShufflePlan::parallel::<u64>(1 << 24).with_preset(Preset::LowMemory);
+        assert_eq!(plan.algorithm(), Algorithm::ParScatter { num_buckets: 64 });
+
+        let plan = ShufflePlan::parallel::<u64>(1 << 24).with_preset(Preset::LowLatency);
+        assert_eq!(plan.base_case_size(), LOW_LATENCY_BASE_CASE_SIZE);
+        assert_eq!(
+            ShufflePlan::sequential::<u64>(1 << 24).with_preset(Preset::LowLatency),
+            ShufflePlan::sequential::<u64>(1 << 24)
+        );
+
+        // settings overridden after the preset take precedence
+        let plan = ShufflePlan::sequential::<u64>(1 << 24)
+            .with_preset(Preset::LowMemory)
+            .with_num_buckets(256)
+            .unwrap();
+        assert_eq!(plan.algorithm(), Algorithm::SeqScatter { num_buckets: 256 });
+    }
+
+    #[test]
+    fn deterministic_preset_matches_reference() {
+        for n in [0, 10, 1 << 21] {
+            let plan = ShufflePlan::parallel::<u32>(n).with_preset(Preset::Deterministic);
+            assert_eq!(plan.algorithm(), Algorithm::FisherYates);
+            assert!(!plan.explain().unsafe_algos);
+
+            let mut expected: Vec<u32> = (0..n as u32).collect();
+            let mut actual = expected.clone();
+            naive::fisher_yates(&mut Pcg64Mcg::seed_from_u64(1), &mut expected);
+            plan.shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut actual);
+            assert_eq!(actual, expected, "n={n}");
+        }
+    }
+
+    #[test]
+    fn explain() {
+        let explanation = ShufflePlan::sequential::<u32>(1 << 20)
+            .with_num_buckets(64)
+            .unwrap()
+            .explain();
+        // the `oracle` feature forces the reference implementation
+        if !explanation.oracle {
+            assert_eq!(
+                explanation.algorithm,
+                Algorithm::SeqScatter { num_buckets: 64 }
+            );
+            assert!(!explanation.parallel);
+            assert_eq!(explanation.max_subproblems, None);
+            assert!(explanation
+                .to_string()
+                .contains("algorithm=seq_scatter buckets=64"));
+        }
+        assert_eq!(explanation.len, 1 << 20);
+        assert_eq!(explanation.element_size, 4);
+        assert_eq!(
+            explanation.base_case_size,
+            sequential::DEFAULT_BASE_CASE_SIZE
+        );
+
+        let explanation = ShufflePlan::parallel::<u64>(1 << 22)
+            .with_max_subproblems(7)
+            .unwrap()
+            .explain();
+        if !explanation.oracle {
+            assert!(explanation.parallel);
+            assert_eq!(explanation.max_subproblems, Some(7));
+        }
+    }
+
+    mod sequential_plan {
+        use super::*;
+
+        fn shuffle_with_plan<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            ShufflePlan::sequential::<T>(data.len()).seq_shuffle(rng, data)
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_with_plan);
+    }
+
+    mod presets {
+        use super::*;
+
+        fn low_memory<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            ShufflePlan::sequential::<T>(data.len())
+                .with_preset(Preset::LowMemory)
+                .with_base_case_size(2)
+                .unwrap()
+                .seq_shuffle(rng, data)
+        }
+
+        fn deterministic<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            ShufflePlan::sequential::<T>(data.len())
+                .with_preset(Preset::Deterministic)
+                .seq_shuffle(rng, data)
+        }
+
+        mod low_memory {
+            use super::*;
+            crate::statistical_tests::test_shuffle_algorithm!(low_memory);
+        }
+
+        mod deterministic {
+            use super::*;
+            crate::statistical_tests::test_shuffle_algorithm!(deterministic);
+        }
+    }
+
+    mod small_base_case_plan {
+        use super::*;
+
+        fn shuffle_with_plan<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            ShufflePlan::sequential::<T>(data.len())
+                .with_base_case_size(2)
+                .unwrap()
+                .with_num_buckets(256)
+                .unwrap()
+                .seq_shuffle(rng, data)
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_with_plan);
+    }
+}
@@ -6,21 +6,40 @@ pub mod avail {
 
     #[inline(always)]
     pub fn prefetch_write_data<T>(item: &mut T) {
-        unsafe {
-            std::intrinsics::prefetch_write_data(item as *mut T, 1);
-        }
+        std::intrinsics::prefetch_write_data::<_, 1>(item as *mut T);
     }
 }
 
 #[cfg(feature = "prefetch")]
 pub use avail::*;
 
-#[cfg(not(feature = "prefetch"))]
+// On aarch64, prefetching is available on the stable channel via inline assembly.
+#[cfg(all(not(feature = "prefetch"), target_arch = "aarch64"))]
+mod prfm {
+    pub const SUPPORTED: bool = true;
+
+    #[inline(always)]
+    pub fn prefetch_write_data<T>(item: &mut T) {
+        // SAFETY: a prefetch is only a hint; it neither faults nor modifies memory
+        unsafe {
+            std::arch::asm!(
+                "prfm pstl1keep, [{0}]",
+                in(reg) item as *mut T,
+                options(nostack, readonly, preserves_flags)
+            );
+        }
+    }
+}
+
+#[cfg(all(not(feature = "prefetch"), target_arch = "aarch64"))]
+pub use prfm::*;
+
+#[cfg(all(not(feature = "prefetch"), not(target_arch = "aarch64")))]
 mod mock {
     pub const SUPPORTED: bool = false;
 
     pub fn prefetch_write_data<T>(_item: &mut T) {}
 }
 
-#[cfg(not(feature = "prefetch"))]
+#[cfg(all(not(feature = "prefetch"), not(target_arch = "aarch64")))]
 pub use mock::*;
@@ -2,6 +2,24 @@ pub trait Profiler {
     type Frame: ProfilerFrame;
 
     fn start(&self, region: &'static str) -> Self::Frame;
+
+    /// Same as [`Profiler::start`], but additionally reports the recursion depth of the
+    /// caller (`0` for the outermost call). Ignored by default.
+    fn start_at_depth(&self, region: &'static str, _depth: usize) -> Self::Frame {
+        self.start(region)
+    }
+
+    /// Executes `shuffle`, a complete parallel shuffle of `num_bytes` bytes in a pool of
+    /// `num_threads` threads. By default, nothing is measured.
+    fn measure_throughput(&self, _num_threads: usize, _num_bytes: usize, shuffle: impl FnOnce()) {
+        shuffle()
+    }
+
+    /// Receives the layout of the buckets after `phase` of a scatter step at recursion
+    /// `depth`; `dump` renders it as an ASCII diagram (see
+    /// [`crate::bucketing::BucketSlice::dump`]). Ignored by default, so no diagram is
+    /// rendered.
+    fn bucket_layout(&self, _phase: &'static str, _depth: usize, _dump: impl FnOnce() -> String) {}
 }
 
 pub trait ProfilerFrame {
@@ -31,4 +49,544 @@ pub mod no_profiler {
     }
 }
 
-pub mod par_profile {}
+/// A profiler for the parallel algorithms that aggregates the time spent per region
+/// and recursion depth over all threads.
+///
+/// Each thread records into its own buffer (created on its first record), so the
+/// threads do not contend while shuffling, independently of the pool they run in; the
+/// buffers are only merged by [`ParProfiler::report`].
+/// Frames are cheap to clone handles to the profiler, which makes [`ParProfiler`]
+/// usable as [`crate::scatter_shuffle::ParConfiguration::Profiler`].
+///
+/// Additionally, the throughput of complete shuffles is aggregated per pool size. If
+/// shuffles were profiled in pools of different sizes,
+/// [`par_profile::ProfileReport::saturation_point`] estimates the number of threads beyond which
+/// memory bandwidth is saturated (see also [`crate::adaptive`]).
+///
+/// A profiler built with [`ParProfiler::with_bucket_layouts`] also keeps the bucket
+/// layout after each phase of each scatter step (see [`Profiler::bucket_layout`]),
+/// which helps to investigate imbalanced buckets of custom configurations.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::profiler::par_profile::ParProfiler;
+/// use rip_shuffle::profiler::{Profiler, ProfilerFrame};
+///
+/// let profiler = ParProfiler::new();
+/// {
+///     let mut frame = profiler.start_at_depth("Outer", 0);
+///     frame.new_region("Phase");
+/// }
+///
+/// let report = profiler.report();
+/// assert_eq!(report.entries().len(), 2);
+/// println!("{report}");
+/// ```
+pub mod par_profile {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, HashMap};
+    use std::fmt;
+    use std::sync::{Arc, Mutex, Weak};
+    use std::time::{Duration, Instant};
+
+    type Key = (usize, &'static str);
+    type Buffer = Arc<Mutex<HashMap<Key, Totals>>>;
+    type Buffers = Mutex<Vec<Buffer>>;
+
+    thread_local! {
+        // the buffers of this thread, one per profiler it recorded into
+        static THREAD_BUFFERS: RefCell<Vec<(Weak<Buffers>, Buffer)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[derive(Clone, Copy, Default)]
+    struct Totals {
+        calls: u64,
+        time: Duration,
+    }
+
+    /// See the [module documentation](self).
+    #[derive(Clone)]
+    pub struct ParProfiler {
+        // one buffer per recording thread; only `report` and `reset` lock the buffer of
+        // another thread, so recording does not contend
+        buffers: Arc<Buffers>,
+        // recorded once per shuffle, so a single buffer does not cause contention
+        throughput: Arc<Mutex<BTreeMap<usize, ThroughputEntry>>>,
+        // only recorded if requested, since rendering the diagrams is expensive
+        layouts: Option<Arc<Mutex<Vec<BucketLayout>>>>,
+    }
+
+    impl Default for ParProfiler {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl ParProfiler {
+        /// Creates a profiler without any buffers; each thread adds its own buffer once it
+        /// records.
+        pub fn new() -> Self {
+            Self {
+                buffers: Arc::default(),
+                throughput: Arc::default(),
+                layouts: None,
+            }
+        }
+
+        /// Additionally records the bucket layouts (see [`Profiler::bucket_layout`]).
+        pub fn with_bucket_layouts(mut self) -> Self {
+            self.layouts = Some(Arc::default());
+            self
+        }
+
+        fn record(&self, depth: usize, region: &'static str, time: Duration) {
+            let buffer = self.thread_buffer();
+            let mut buffer = buffer.lock().unwrap();
+            let totals = buffer.entry((depth, region)).or_default();
+            totals.calls += 1;
+            totals.time += time;
+        }
+
+        /// Returns the buffer of the calling thread and registers a new one on first use.
+        fn thread_buffer(&self) -> Buffer {
+            THREAD_BUFFERS.with_borrow_mut(|local| {
+                // the weak references keep the allocation alive, so the address of a
+                // dropped profiler is not reused while it is in the list
+                let owner = Arc::as_ptr(&self.buffers);
+                if let Some((_, buffer)) = local.iter().find(|(o, _)| o.as_ptr() == owner) {
+                    return buffer.clone();
+                }
+
+                local.retain(|(o, _)| o.strong_count() > 0);
+
+                let buffer = Buffer::default();
+                self.buffers.lock().unwrap().push(buffer.clone());
+                local.push((Arc::downgrade(&self.buffers), buffer.clone()));
+                buffer
+            })
+        }
+
+        /// Merges the buffers of all threads.
+        pub fn report(&self) -> ProfileReport {
+            let mut merged: HashMap<Key, Totals> = HashMap::new();
+            for buffer in self.buffers.lock().unwrap().iter() {
+                for (&key, totals) in buffer.lock().unwrap().iter() {
+                    let entry = merged.entry(key).or_default();
+                    entry.calls += totals.calls;
+                    entry.time += totals.time;
+                }
+            }
+
+            let mut entries: Vec<ProfileEntry> = merged
+                .into_iter()
+                .map(|((depth, region), totals)| ProfileEntry {
+                    region,
+                    depth,
+                    calls: totals.calls,
+                    total_time: totals.time,
+                })
+                .collect();
+            entries.sort_by_key(|e| (e.depth, e.region));
+
+            let throughput = self.throughput.lock().unwrap().values().cloned().collect();
+
+            let layouts = self
+                .layouts
+                .as_ref()
+                .map_or_else(Vec::new, |layouts| layouts.lock().unwrap().clone());
+
+            ProfileReport {
+                entries,
+                throughput,
+                layouts,
+            }
+        }
+
+        /// Discards all recorded frames and throughputs.
+        pub fn reset(&self) {
+            for buffer in self.buffers.lock().unwrap().iter() {
+                buffer.lock().unwrap().clear();
+            }
+            self.throughput.lock().unwrap().clear();
+            if let Some(layouts) = &self.layouts {
+                layouts.lock().unwrap().clear();
+            }
+        }
+    }
+
+    impl Profiler for ParProfiler {
+        type Frame = ParProfileFrame;
+
+        fn start(&self, region: &'static str) -> Self::Frame {
+            self.start_at_depth(region, 0)
+        }
+
+        fn start_at_depth(&self, region: &'static str, depth: usize) -> Self::Frame {
+            let now = Instant::now();
+            ParProfileFrame {
+                profiler: self.clone(),
+                depth,
+                frame: (region, now),
+                region: None,
+            }
+        }
+
+        fn measure_throughput(&self, num_threads: usize, num_bytes: usize, shuffle: impl FnOnce()) {
+            let start = Instant::now();
+            shuffle();
+            self.record_throughput(num_threads, num_bytes, start.elapsed());
+        }
+
+        fn bucket_layout(&self, phase: &'static str, depth: usize, dump: impl FnOnce() -> String) {
+            if let Some(layouts) = &self.layouts {
+                let diagram = dump();
+                layouts.lock().unwrap().push(BucketLayout {
+                    phase,
+                    depth,
+                    diagram,
+                });
+            }
+        }
+    }
+
+    impl ParProfiler {
+        /// Records that a shuffle of `num_bytes` bytes in a pool of `num_threads` threads
+        /// took `elapsed`; called by [`Profiler::measure_throughput`].
+        pub fn record_throughput(&self, num_threads: usize, num_bytes: usize, elapsed: Duration) {
+            let mut throughput = self.throughput.lock().unwrap();
+            let entry = throughput
+                .entry(num_threads)
+                .or_insert_with(|| ThroughputEntry {
+                    num_threads,
+                    ..Default::default()
+                });
+
+            entry.shuffles += 1;
+            entry.bytes += num_bytes as u64;
+            entry.total_time += elapsed;
+        }
+    }
+
+    /// Frame of a [`ParProfiler`]; the frame and its current region are recorded when
+    /// the next region starts or the frame is dropped.
+    pub struct ParProfileFrame {
+        profiler: ParProfiler,
+        depth: usize,
+        frame: (&'static str, Instant),
+        region: Option<(&'static str, Instant)>,
+    }
+
+    impl ParProfileFrame {
+        fn finish_region(&mut self, now: Instant) {
+            if let Some((name, start)) = self.region.take() {
+                self.profiler.record(self.depth, name, now - start);
+            }
+        }
+    }
+
+    impl ProfilerFrame for ParProfileFrame {
+        fn new_region(&mut self, name: &'static str) {
+            let now = Instant::now();
+            self.finish_region(now);
+            self.region = Some((name, now));
+        }
+    }
+
+    impl Drop for ParProfileFrame {
+        fn drop(&mut self) {
+            let now = Instant::now();
+            self.finish_region(now);
+
+            let (name, start) = self.frame;
+            self.profiler.record(self.depth, name, now - start);
+        }
+    }
+
+    /// Aggregated time of one region at one recursion depth.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct ProfileEntry {
+        pub region: &'static str,
+        pub depth: usize,
+        /// Number of times the region was executed
+        pub calls: u64,
+        /// Time summed over all calls (and hence all threads)
+        pub total_time: Duration,
+    }
+
+    /// Aggregated throughput of the shuffles executed in pools of one size.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct ThroughputEntry {
+        pub num_threads: usize,
+        /// Number of shuffles
+        pub shuffles: u64,
+        /// Bytes summed over all shuffles
+        pub bytes: u64,
+        /// Time summed over all shuffles
+        pub total_time: Duration,
+    }
+
+    impl ThroughputEntry {
+        pub fn bytes_per_second(&self) -> f64 {
+            self.bytes as f64 / self.total_time.as_secs_f64().max(f64::MIN_POSITIVE)
+        }
+    }
+
+    /// Bucket layout after one phase of a scatter step, see [`Profiler::bucket_layout`].
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct BucketLayout {
+        pub phase: &'static str,
+        pub depth: usize,
+        pub diagram: String,
+    }
+
+    /// Result of [`ParProfiler::report`], sorted by depth and region.
+    #[derive(Clone, Debug)]
+    pub struct ProfileReport {
+        entries: Vec<ProfileEntry>,
+        throughput: Vec<ThroughputEntry>,
+        layouts: Vec<BucketLayout>,
+    }
+
+    impl ProfileReport {
+        pub fn entries(&self) -> &[ProfileEntry] {
+            &self.entries
+        }
+
+        /// Throughput per pool size, sorted by the number of threads.
+        pub fn throughput(&self) -> &[ThroughputEntry] {
+            &self.throughput
+        }
+
+        /// Bucket layouts in the order they were recorded (which depends on the
+        /// scheduling of the threads); empty unless the profiler was built with
+        /// [`ParProfiler::with_bucket_layouts`].
+        pub fn bucket_layouts(&self) -> &[BucketLayout] {
+            &self.layouts
+        }
+
+        /// Returns the smallest number of threads whose throughput is within a factor of
+        /// `1 + min_gain` of the best throughput of any measured pool size, i.e., more
+        /// threads gained less than `min_gain` (e.g., `0.1` for 10%). Returns `None`
+        /// unless at least two pool sizes were measured.
+        pub fn saturation_point(&self, min_gain: f64) -> Option<usize> {
+            if self.throughput.len() < 2 {
+                return None;
+            }
+
+            let best = self
+                .throughput
+                .iter()
+                .map(ThroughputEntry::bytes_per_second)
+                .fold(0.0, f64::max);
+
+            self.throughput
+                .iter()
+                .find(|e| e.bytes_per_second() * (1.0 + min_gain) >= best)
+                .map(|e| e.num_threads)
+        }
+
+        /// Returns the entry of `region` at recursion `depth`, if it was recorded.
+        pub fn get(&self, region: &str, depth: usize) -> Option<&ProfileEntry> {
+            self.entries
+                .iter()
+                .find(|e| e.depth == depth && e.region == region)
+        }
+    }
+
+    impl fmt::Display for ProfileReport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(
+                f,
+                "{:>5} {:<24} {:>8} {:>12}",
+                "depth", "region", "calls", "total [ms]"
+            )?;
+            for e in &self.entries {
+                writeln!(
+                    f,
+                    "{:>5} {:<24} {:>8} {:>12.3}",
+                    e.depth,
+                    e.region,
+                    e.calls,
+                    e.total_time.as_secs_f64() * 1e3
+                )?;
+            }
+
+            if !self.throughput.is_empty() {
+                writeln!(f, "{:>7} {:>8} {:>12}", "threads", "shuffles", "GB/s")?;
+            }
+            for e in &self.throughput {
+                writeln!(
+                    f,
+                    "{:>7} {:>8} {:>12.3}",
+                    e.num_threads,
+                    e.shuffles,
+                    e.bytes_per_second() * 1e-9
+                )?;
+            }
+
+            for layout in &self.layouts {
+                writeln!(f, "depth {} after {}:", layout.depth, layout.phase)?;
+                write!(f, "{}", layout.diagram)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::scatter_shuffle::parallel::ParScatterShuffleImpl;
+        use crate::scatter_shuffle::{ParConfiguration, SeqConfiguration};
+        use rand::{Rng, SeedableRng};
+        use rand_pcg::Pcg64Mcg;
+
+        #[derive(Clone, Default)]
+        struct ProfiledConfig {
+            profiler: ParProfiler,
+        }
+
+        impl SeqConfiguration for ProfiledConfig {
+            fn seq_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+                crate::fisher_yates::fisher_yates(rng, data)
+            }
+
+            fn seq_base_case_size(&self) -> usize {
+                1 << 10
+            }
+        }
+
+        impl ParConfiguration for ProfiledConfig {
+            fn par_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+                crate::fisher_yates::fisher_yates(rng, data)
+            }
+
+            fn par_base_case_size(&self) -> usize {
+                1 << 10
+            }
+
+            fn par_number_of_subproblems(&self, _n: usize) -> usize {
+                4
+            }
+
+            type Profiler = ParProfiler;
+            fn get_profiler(&self) -> &ParProfiler {
+                &self.profiler
+            }
+        }
+
+        #[test]
+        fn records_regions_per_depth() {
+            let config = ProfiledConfig::default();
+            let mut data: Vec<u32> = (0..1 << 16).collect();
+
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(3)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                ParScatterShuffleImpl::<Pcg64Mcg, _, _, 16>::new(config.clone())
+                    .shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut data)
+            });
+
+            let report = config.profiler.report();
+            assert_eq!(report.get("ParScatter", 0).unwrap().calls, 1);
+            assert_eq!(report.get("RoughScatter", 0).unwrap().calls, 1);
+            assert!(report.get("ParScatter", 1).unwrap().calls >= 16);
+            assert!(report.get("TwoSweep", 1).is_some());
+
+            let throughput = report.throughput();
+            assert_eq!(throughput.len(), 1);
+            assert_eq!(throughput[0].num_threads, 3);
+            assert_eq!(throughput[0].bytes, 4 << 16);
+
+            config.profiler.reset();
+            assert!(config.profiler.report().entries().is_empty());
+            assert!(config.profiler.report().throughput().is_empty());
+        }
+
+        #[test]
+        fn records_bucket_layouts() {
+            let mut data: Vec<u32> = (0..1 << 12).collect();
+            let shuffle = |config: ProfiledConfig, data: &mut [u32]| {
+                ParScatterShuffleImpl::<Pcg64Mcg, _, _, 4>::new(config.clone())
+                    .shuffle(&mut Pcg64Mcg::seed_from_u64(1), data);
+                config.profiler.report()
+            };
+
+            let report = shuffle(ProfiledConfig::default(), &mut data);
+            assert!(report.bucket_layouts().is_empty());
+
+            let config = ProfiledConfig {
+                profiler: ParProfiler::new().with_bucket_layouts(),
+            };
+            let report = shuffle(config.clone(), &mut data);
+            let layouts: Vec<_> = report
+                .bucket_layouts()
+                .iter()
+                .filter(|l| l.depth == 0)
+                .collect();
+            assert_eq!(layouts.len(), 2);
+            assert_eq!((layouts[0].phase, layouts[0].depth), ("RoughScatter", 0));
+            assert_eq!((layouts[1].phase, layouts[1].depth), ("TwoSweep", 0));
+
+            // one line per bucket after the diagram and the header
+            let diagram = &layouts[1].diagram;
+            assert_eq!(diagram.lines().count(), 2 + 4);
+            assert!(diagram.lines().next().unwrap().starts_with("|#"));
+            let total_len: usize = diagram
+                .lines()
+                .skip(2)
+                .map(|line| {
+                    line.split_whitespace()
+                        .nth(2)
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap()
+                })
+                .sum();
+            assert_eq!(total_len, data.len());
+            assert!(report.to_string().contains("depth 0 after TwoSweep:"));
+
+            config.profiler.reset();
+            assert!(config.profiler.report().bucket_layouts().is_empty());
+        }
+
+        #[test]
+        fn one_buffer_per_thread_of_any_pool() {
+            // created in the global pool, but used in a larger one
+            let profiler = ParProfiler::new();
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(rayon::current_num_threads() + 3)
+                .build()
+                .unwrap();
+
+            pool.broadcast(|_| drop(profiler.start("Region")));
+            pool.broadcast(|_| drop(profiler.start("Region")));
+
+            let num_threads = pool.current_num_threads();
+            assert_eq!(profiler.buffers.lock().unwrap().len(), num_threads);
+            let calls = profiler.report().get("Region", 0).unwrap().calls;
+            assert_eq!(calls, 2 * num_threads as u64);
+        }
+
+        #[test]
+        fn finds_saturation_point() {
+            let profiler = ParProfiler::new();
+            let ms = Duration::from_millis;
+
+            profiler.record_throughput(1, 1 << 30, ms(1000));
+            assert_eq!(profiler.report().saturation_point(0.1), None);
+
+            profiler.record_throughput(2, 1 << 30, ms(520));
+            profiler.record_throughput(4, 1 << 30, ms(500));
+            profiler.record_throughput(8, 1 << 30, ms(550));
+            assert_eq!(profiler.report().saturation_point(0.1), Some(2));
+            assert_eq!(profiler.report().saturation_point(0.01), Some(4));
+
+            // more threads only help if the gain is large enough
+            profiler.record_throughput(16, 1 << 30, ms(250));
+            assert_eq!(profiler.report().saturation_point(0.1), Some(16));
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! [`proptest`] strategies producing random permutations and shuffled vectors.
+//!
+//! The generated values are uniformly random permutations. When a test fails,
+//! shrinking moves the permutation towards the identity: each simplification
+//! step puts one more element back into its original position (by a single
+//! transposition), so a minimal counterexample is as close to the unshuffled
+//! input as the failure permits.
+//!
+//! # Example
+//! ```
+//! use proptest::prelude::*;
+//! use proptest::test_runner::TestRunner;
+//! use rip_shuffle::proptest_strategies::shuffled;
+//!
+//! let mut runner = TestRunner::default();
+//! runner
+//!     .run(&shuffled((0..100).collect()), |data| {
+//!         let mut sorted = data.clone();
+//!         sorted.sort();
+//!         prop_assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! ```
+
+use crate::RipShuffleSequential;
+use proptest::prelude::*;
+use proptest::strategy::{NewTree, ValueTree};
+use proptest::test_runner::TestRunner;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fmt::Debug;
+
+/// Strategy producing uniformly random permutations of `0..len`.
+/// Use [`proptest::strategy::Strategy::prop_flat_map`] for variable lengths.
+pub fn permutation(len: usize) -> PermutationStrategy {
+    PermutationStrategy { len }
+}
+
+/// Strategy producing uniformly random rearrangements of `values`; shrinks towards
+/// the order in which `values` are provided.
+pub fn shuffled<T: Clone + Debug>(values: Vec<T>) -> ShuffledStrategy<T> {
+    ShuffledStrategy { values }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PermutationStrategy {
+    len: usize,
+}
+
+impl Strategy for PermutationStrategy {
+    type Tree = PermutationValueTree;
+    type Value = Vec<usize>;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        // seeding through a proptest strategy keeps us independent of the rand
+        // version used by proptest's own test runner
+        let seed = any::<u64>().new_tree(runner)?.current();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut permutation: Vec<usize> = (0..self.len).collect();
+        permutation.seq_shuffle(&mut rng);
+
+        Ok(PermutationValueTree::new(permutation))
+    }
+}
+
+/// Value tree of [`PermutationStrategy`]; see the module documentation for its
+/// shrinking behavior.
+#[derive(Clone, Debug)]
+pub struct PermutationValueTree {
+    current: Vec<usize>,
+    first_candidate: usize,
+    last_swap: Option<(usize, usize)>,
+}
+
+impl PermutationValueTree {
+    fn new(permutation: Vec<usize>) -> Self {
+        Self {
+            current: permutation,
+            first_candidate: 0,
+            last_swap: None,
+        }
+    }
+}
+
+impl ValueTree for PermutationValueTree {
+    type Value = Vec<usize>;
+
+    fn current(&self) -> Self::Value {
+        self.current.clone()
+    }
+
+    fn simplify(&mut self) -> bool {
+        let n = self.current.len();
+        let Some(i) = (self.first_candidate..n).find(|&i| self.current[i] != i) else {
+            self.last_swap = None;
+            return false;
+        };
+
+        // a single transposition puts element `i` back into its original position
+        let j = self.current.iter().position(|&x| x == i).unwrap();
+        self.current.swap(i, j);
+
+        self.first_candidate = i + 1;
+        self.last_swap = Some((i, j));
+        true
+    }
+
+    fn complicate(&mut self) -> bool {
+        match self.last_swap.take() {
+            Some((i, j)) => {
+                self.current.swap(i, j);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ShuffledStrategy<T> {
+    values: Vec<T>,
+}
+
+impl<T: Clone + Debug> Strategy for ShuffledStrategy<T> {
+    type Tree = ShuffledValueTree<T>;
+    type Value = Vec<T>;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        Ok(ShuffledValueTree {
+            values: self.values.clone(),
+            permutation: permutation(self.values.len()).new_tree(runner)?,
+        })
+    }
+}
+
+/// Value tree of [`ShuffledStrategy`]; shrinks its underlying permutation.
+#[derive(Clone, Debug)]
+pub struct ShuffledValueTree<T> {
+    values: Vec<T>,
+    permutation: PermutationValueTree,
+}
+
+impl<T: Clone + Debug> ValueTree for ShuffledValueTree<T> {
+    type Value = Vec<T>;
+
+    fn current(&self) -> Self::Value {
+        self.permutation
+            .current
+            .iter()
+            .map(|&i| self.values[i].clone())
+            .collect()
+    }
+
+    fn simplify(&mut self) -> bool {
+        self.permutation.simplify()
+    }
+
+    fn complicate(&mut self) -> bool {
+        self.permutation.complicate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn is_permutation(perm: &[usize]) -> bool {
+        let mut sorted = perm.to_vec();
+        sorted.sort();
+        sorted.iter().enumerate().all(|(i, &x)| i == x)
+    }
+
+    #[test]
+    fn generates_permutations() {
+        let mut runner = TestRunner::deterministic();
+        for n in [0, 1, 2, 10, 100] {
+            let tree = permutation(n).new_tree(&mut runner).unwrap();
+            assert_eq!(tree.current().len(), n);
+            assert!(is_permutation(&tree.current()));
+        }
+    }
+
+    #[test]
+    fn shrinks_to_identity() {
+        let mut runner = TestRunner::deterministic();
+        let mut tree = permutation(50).new_tree(&mut runner).unwrap();
+
+        // a test that always fails never complicates
+        while tree.simplify() {
+            assert!(is_permutation(&tree.current()));
+        }
+
+        assert_eq!(tree.current(), (0..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn complicate_keeps_required_displacement() {
+        let mut runner = TestRunner::deterministic();
+        let mut tree = permutation(30).new_tree(&mut runner).unwrap();
+
+        // the "failure" requires that element 7 is not at its original position
+        let fails = |perm: &[usize]| perm[7] != 7;
+        while !fails(&tree.current()) {
+            tree = permutation(30).new_tree(&mut runner).unwrap();
+        }
+
+        while tree.simplify() {
+            if !fails(&tree.current()) {
+                assert!(tree.complicate());
+                assert!(fails(&tree.current()));
+            }
+        }
+
+        let shrunk = tree.current();
+        assert!(fails(&shrunk));
+        assert!(is_permutation(&shrunk));
+        assert!(shrunk.iter().enumerate().filter(|(i, &x)| *i != x).count() <= 2);
+    }
+
+    #[test]
+    fn shuffled_values() {
+        let mut runner = TestRunner::deterministic();
+        let values: Vec<_> = (0..20).map(|x| x * 3).collect();
+        let mut tree = shuffled(values.clone()).new_tree(&mut runner).unwrap();
+
+        let mut current = tree.current();
+        current.sort();
+        assert_eq!(current, values);
+
+        while tree.simplify() {}
+        assert_eq!(tree.current(), values);
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_integration(perm in (0..200usize).prop_flat_map(permutation)) {
+            prop_assert!(is_permutation(&perm));
+        }
+    }
+}
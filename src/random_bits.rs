@@ -68,10 +68,158 @@ impl RandomBitsSource {
     pub fn gen_bool(&mut self, rng: &mut impl Rng) -> bool {
         self.gen_const_bits::<1>(rng) == 0
     }
+
+    /// Samples a uniform number from `0..ub` by rejection from `ceil(log2 ub)` cached
+    /// random bits per attempt (fewer than two attempts are expected). This is cheaper
+    /// than [`crate::uniform_index::gen_index`] for small bounds that are not powers
+    /// of two, as most calls do not query `rng` at all.
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::random_bits::RandomBitsSource;
+    ///
+    /// let mut rbs = RandomBitsSource::new();
+    /// let mut rng = rand::thread_rng();
+    ///
+    /// assert!((0..100).all(|_| rbs.gen_below(&mut rng, 6) < 6));
+    /// ```
+    #[inline]
+    pub fn gen_below(&mut self, rng: &mut impl Rng, ub: usize) -> usize {
+        debug_assert!(ub > 0);
+        let num_bits = (usize::BITS - (ub - 1).leading_zeros()) as usize;
+
+        loop {
+            let x = self.gen_bits(rng, num_bits) as usize;
+            if x < ub {
+                return x;
+            }
+        }
+    }
+
+    /// Returns an iterator over `count` values of `N <= 32` random bits each, as
+    /// produced by [`RandomBitsSource::gen_const_bits`]. Unused bits remain cached in
+    /// `self` once the iterator is dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use rip_shuffle::random_bits::RandomBitsSource;
+    ///
+    /// let mut rbs = RandomBitsSource::new();
+    /// let digits: Vec<u32> = rbs.iter_bits::<3, _>(&mut rand::thread_rng(), 100).collect();
+    ///
+    /// assert_eq!(digits.len(), 100);
+    /// assert!(digits.iter().all(|&d| d < 8));
+    /// ```
+    pub fn iter_bits<'a, const N: usize, R: Rng>(
+        &'a mut self,
+        rng: &'a mut R,
+        count: usize,
+    ) -> BitsIter<'a, R, N> {
+        BitsIter {
+            source: self,
+            rng,
+            remaining: count,
+        }
+    }
 }
 
+/// Iterator returned by [`RandomBitsSource::iter_bits`].
+pub struct BitsIter<'a, R, const N: usize> {
+    source: &'a mut RandomBitsSource,
+    rng: &'a mut R,
+    remaining: usize,
+}
+
+impl<R: Rng, const N: usize> Iterator for BitsIter<'_, R, N> {
+    type Item = u32;
+
+    #[inline]
+    fn next(&mut self) -> Option<u32> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        self.remaining -= 1;
+        Some(self.source.gen_const_bits::<N>(self.rng))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<R: Rng, const N: usize> ExactSizeIterator for BitsIter<'_, R, N> {}
+
 pub type FairCoin = RandomBitsSource;
 
+/// Same as [`RandomBitsSource`], but caches 32-bit words and only uses 32-bit
+/// operations. Prefer it on targets without native 64-bit arithmetic (e.g.,
+/// microcontrollers or wasm32), where the 64-bit shifts dominate the cost of a coin flip.
+#[derive(Default)]
+pub struct RandomBitsSource32 {
+    random_bits: u32,
+    num_available: u32,
+}
+
+impl RandomBitsSource32 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Produce up to `num_bits <= 32` random bits and return them in the
+    /// `num_bits` least significant positions of the returned value. The
+    /// unused bits are cached and may speed up subsequent calls.
+    #[inline]
+    pub fn gen_bits(&mut self, rng: &mut impl Rng, num_bits: u32) -> u32 {
+        if num_bits == 32 {
+            return rng.next_u32();
+        }
+
+        let mask = (1u32 << num_bits) - 1;
+
+        let random = if num_bits > self.num_available {
+            let rand = rng.next_u32();
+            self.random_bits |= (rand >> num_bits) << self.num_available;
+            self.num_available = (self.num_available + 32 - num_bits).min(32);
+
+            rand
+        } else {
+            let rand = self.random_bits;
+            self.random_bits >>= num_bits;
+            self.num_available -= num_bits;
+            rand
+        };
+
+        random & mask
+    }
+
+    /// Produce up to `N < 32` random bits and return them in the
+    /// `N` least significant positions of the returned value. The
+    /// unused bits are cached and may speed up subsequent calls.
+    /// See [`RandomBitsSource::gen_const_bits`].
+    #[inline]
+    pub fn gen_const_bits<const N: u32>(&mut self, rng: &mut impl Rng) -> u32 {
+        if self.num_available < N {
+            self.random_bits = rng.next_u32();
+            self.num_available = 32;
+        }
+
+        let mask = (1u32 << N) - 1;
+        let rand = self.random_bits & mask;
+        self.random_bits >>= N;
+        self.num_available -= N;
+
+        rand
+    }
+
+    #[inline]
+    pub fn gen_bool(&mut self, rng: &mut impl Rng) -> bool {
+        self.gen_const_bits::<1>(rng) == 0
+    }
+}
+
+pub type FairCoin32 = RandomBitsSource32;
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -101,7 +249,6 @@ mod test {
 
         for num_bits in [1u64, 2, 5, 10, 20, 50, 63] {
             let bit_sum: u64 = (0..NUM_ITERATIONS)
-                .into_iter()
                 .map(|_| rbs.gen_bits(&mut rng, num_bits as usize).count_ones() as u64)
                 .sum();
 
@@ -147,7 +294,6 @@ mod test {
         let mut rbs = RandomBitsSource::new();
 
         let bit_sum: u64 = (0..NUM_ITERATIONS)
-            .into_iter()
             .map(|_| rbs.gen_const_bits::<N>(&mut rng).count_ones() as u64)
             .sum();
 
@@ -155,6 +301,94 @@ mod test {
         assert!(4 * bit_sum < 3 * NUM_ITERATIONS * N as u64);
     }
 
+    #[test]
+    fn gen_below_is_uniform() {
+        const NUM_SAMPLES: usize = 60_000;
+        let mut rng = Pcg64::seed_from_u64(4567);
+        let mut rbs = RandomBitsSource::new();
+
+        assert_eq!(rbs.gen_below(&mut rng, 1), 0);
+
+        for ub in [2, 3, 5, 6, 7, 12] {
+            let mut counts = vec![0usize; ub];
+            for _ in 0..NUM_SAMPLES {
+                counts[rbs.gen_below(&mut rng, ub)] += 1;
+            }
+
+            let expected = NUM_SAMPLES / ub;
+            assert!(
+                counts.iter().all(|&c| c.abs_diff(expected) < expected / 10),
+                "ub={ub} counts={counts:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn iter_bits_matches_gen_const_bits() {
+        let mut rbs = RandomBitsSource::new();
+        let mut rng = Pcg64::seed_from_u64(3456);
+        let mut iter = rbs.iter_bits::<5, _>(&mut rng, 100);
+        assert_eq!(iter.len(), 100);
+        iter.next();
+        assert_eq!(iter.len(), 99);
+        let from_iter: Vec<u32> = iter.collect();
+
+        let mut rbs = RandomBitsSource::new();
+        let mut rng = Pcg64::seed_from_u64(3456);
+        let expected: Vec<u32> = (0..100)
+            .map(|_| rbs.gen_const_bits::<5>(&mut rng))
+            .skip(1)
+            .collect();
+
+        assert_eq!(from_iter, expected);
+        assert_eq!(rbs.iter_bits::<5, _>(&mut rng, 0).count(), 0);
+    }
+
+    #[test]
+    fn source32_gen_bits_expected_num_bits() {
+        const NUM_ITERATIONS: u64 = 10_000;
+        let mut rng = Pcg64::seed_from_u64(987234);
+        let mut rbs = RandomBitsSource32::new();
+
+        for num_bits in [1u32, 2, 5, 10, 20, 31, 32] {
+            let bit_sum: u64 = (0..NUM_ITERATIONS)
+                .map(|_| {
+                    let bits = rbs.gen_bits(&mut rng, num_bits);
+                    assert!(num_bits == 32 || bits < (1 << num_bits));
+                    bits.count_ones() as u64
+                })
+                .sum();
+
+            assert!(4 * bit_sum > NUM_ITERATIONS * num_bits as u64);
+            assert!(4 * bit_sum < 3 * NUM_ITERATIONS * num_bits as u64);
+        }
+    }
+
+    #[test]
+    fn source32_gen_const_bits_expected_num_bits() {
+        fn test<const N: u32>() {
+            const NUM_ITERATIONS: u64 = 10_000;
+            let mut rng = Pcg64::seed_from_u64(8734 * N as u64 + 1);
+            let mut rbs = RandomBitsSource32::new();
+
+            let bit_sum: u64 = (0..NUM_ITERATIONS)
+                .map(|_| {
+                    let bits = rbs.gen_const_bits::<N>(&mut rng);
+                    assert!(bits < (1 << N));
+                    bits.count_ones() as u64
+                })
+                .sum();
+
+            assert!(4 * bit_sum > NUM_ITERATIONS * N as u64);
+            assert!(4 * bit_sum < 3 * NUM_ITERATIONS * N as u64);
+        }
+
+        test::<1>();
+        test::<3>();
+        test::<16>();
+        test::<31>();
+    }
+
     #[test]
     fn gen_bool_expected_num_bits() {
         const NUM_ITERATIONS: u64 = 10_000;
@@ -162,7 +396,6 @@ mod test {
         let mut rbs = RandomBitsSource::new();
 
         let bit_sum: u64 = (0..NUM_ITERATIONS)
-            .into_iter()
             .map(|_| rbs.gen_bool(&mut rng) as u64)
             .sum();
 
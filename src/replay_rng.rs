@@ -0,0 +1,227 @@
+//! Shuffling with pre-generated randomness.
+//!
+//! Air-gapped systems often receive their entropy from an external source (e.g., a
+//! hardware generator or a file produced elsewhere), and audits want to replay a shuffle
+//! from archived randomness. [`ReplayRng`] serves the random words from a caller-supplied
+//! byte buffer, in order and without any post-processing: `next_u32` and `next_u64`
+//! consume four and eight little-endian bytes, respectively. [`SliceRng`] is a short-hand
+//! for a generator borrowing the buffer.
+//!
+//! [`ReplayRng`] implements [`rand::SeedableRng`], so it is accepted by the parallel
+//! entry points as well. These seed one generator per task from their parent via
+//! [`rand::SeedableRng::from_seed`]; for a [`ReplayRng`], such a task generator is a
+//! ChaCha12 stream ([`rand::rngs::StdRng`]) keyed with 32 bytes of the buffer. Hence,
+//! the output is fully determined by the buffer in any case.
+//!
+//! If the buffer is exhausted, [`rand::RngCore::try_fill_bytes`] returns
+//! [`ShuffleError::EntropyExhausted`] (wrapped into a [`rand::Error`]) without consuming
+//! any bytes, while the infallible methods of [`rand::RngCore`] panic. The shuffle
+//! is then interrupted, and the input holds an arbitrary permutation of its elements.
+//! Fisher-Yates draws 64 bits per element (and few additional words for rejected
+//! samples), so a buffer of `8 * n + 1024` bytes suffices with overwhelming probability;
+//! this also covers the parallel shuffles, whose task generators do not read the buffer.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::replay_rng::SliceRng;
+//! use rip_shuffle::RipShuffleSequential;
+//!
+//! let archived: Vec<u8> = (0..10_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+//!
+//! let mut first: Vec<u32> = (0..1000).collect();
+//! let mut rng = SliceRng::new(&archived);
+//! first.seq_shuffle(&mut rng);
+//! println!("consumed {} bytes", rng.position());
+//!
+//! // replaying the same buffer yields the same permutation
+//! let mut second: Vec<u32> = (0..1000).collect();
+//! second.seq_shuffle(&mut SliceRng::new(&archived));
+//! assert_eq!(first, second);
+//! ```
+
+use super::error::ShuffleError;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+/// Serves randomness from the buffer `B` (see the [module documentation](self)).
+#[derive(Clone, Debug)]
+pub struct ReplayRng<B> {
+    source: Source<B>,
+}
+
+/// A [`ReplayRng`] that borrows its buffer.
+pub type SliceRng<'a> = ReplayRng<&'a [u8]>;
+
+#[derive(Clone, Debug)]
+enum Source<B> {
+    Buffer { bytes: B, pos: usize },
+    Task(Box<StdRng>),
+}
+
+impl<B: AsRef<[u8]>> ReplayRng<B> {
+    /// Serves the bytes of `bytes` from the front.
+    pub fn new(bytes: B) -> Self {
+        Self {
+            source: Source::Buffer { bytes, pos: 0 },
+        }
+    }
+
+    /// Number of bytes consumed so far; zero for task generators (which do not read
+    /// from a buffer).
+    pub fn position(&self) -> usize {
+        match &self.source {
+            Source::Buffer { pos, .. } => *pos,
+            Source::Task(_) => 0,
+        }
+    }
+
+    /// Number of bytes left in the buffer, or `None` for task generators.
+    pub fn remaining(&self) -> Option<usize> {
+        match &self.source {
+            Source::Buffer { bytes, pos } => Some(bytes.as_ref().len() - pos),
+            Source::Task(_) => None,
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> RngCore for ReplayRng<B> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// # Panics
+    /// If the buffer holds fewer than `dest.len()` bytes.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match &mut self.source {
+            Source::Buffer { bytes, pos } => {
+                let src = bytes
+                    .as_ref()
+                    .get(*pos..*pos + dest.len())
+                    .ok_or_else(|| rand::Error::new(ShuffleError::EntropyExhausted))?;
+                dest.copy_from_slice(src);
+                *pos += dest.len();
+                Ok(())
+            }
+            Source::Task(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> SeedableRng for ReplayRng<B> {
+    type Seed = <StdRng as SeedableRng>::Seed;
+
+    /// Returns a task generator (see the [module documentation](self)).
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self {
+            source: Source::Task(Box::new(StdRng::from_seed(seed))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{RipShuffleParallel, RipShuffleSequential};
+    use rand::Rng;
+    use rand_pcg::Pcg64Mcg;
+    use std::sync::Arc;
+
+    fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        Pcg64Mcg::seed_from_u64(seed).fill_bytes(&mut bytes);
+        bytes
+    }
+
+    #[test]
+    fn serves_buffer_in_order() {
+        let bytes: Vec<u8> = (1..=16).collect();
+        let mut rng = SliceRng::new(&bytes);
+
+        assert_eq!(rng.next_u32(), 0x04030201);
+        assert_eq!(rng.next_u64(), 0x0c0b0a0908070605);
+        assert_eq!(rng.position(), 12);
+        assert_eq!(rng.remaining(), Some(4));
+
+        let mut dest = [0; 5];
+        let err = rng.try_fill_bytes(&mut dest).unwrap_err();
+        assert_eq!(
+            err.take_inner().downcast_ref::<ShuffleError>(),
+            Some(&ShuffleError::EntropyExhausted)
+        );
+        assert_eq!(rng.position(), 12);
+
+        rng.fill_bytes(&mut dest[..4]);
+        assert_eq!(dest[..4], [13, 14, 15, 16]);
+        assert_eq!(rng.remaining(), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "exhausted")]
+    fn panics_when_exhausted() {
+        let mut data: Vec<u32> = (0..100).collect();
+        data.seq_shuffle(&mut ReplayRng::new([0u8; 16]));
+    }
+
+    #[test]
+    fn replays_sequential_shuffles() {
+        let bytes = random_bytes(1, 8 * 10_000 + 1024);
+
+        let shuffle = |bytes: &[u8]| {
+            let mut data: Vec<u32> = (0..10_000).collect();
+            data.seq_shuffle(&mut SliceRng::new(bytes));
+            data
+        };
+
+        assert_eq!(shuffle(&bytes), shuffle(&bytes));
+        assert_ne!(shuffle(&bytes), shuffle(&random_bytes(2, bytes.len())));
+    }
+
+    #[test]
+    fn replays_parallel_shuffles() {
+        let n = 1 << 21;
+        let bytes: Arc<[u8]> = random_bytes(3, 8 * n + 1024).into();
+
+        let shuffle = || {
+            let mut rng = ReplayRng::new(bytes.clone());
+            let mut data: Vec<u64> = (0..n as u64).collect();
+            data.par_shuffle(&mut rng);
+            assert!(rng.position() > 0);
+            data
+        };
+
+        let data = shuffle();
+        assert_eq!(data, shuffle());
+
+        let mut sorted = data.clone();
+        sorted.sort();
+        assert!(sorted.iter().enumerate().all(|(i, &x)| i as u64 == x));
+        assert_ne!(data, sorted);
+
+        let task: ReplayRng<Vec<u8>> = ReplayRng::from_seed([7; 32]);
+        assert_eq!(task.remaining(), None);
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn replay<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            let mut bytes = vec![0u8; 8 * data.len() + 1024];
+            rng.fill_bytes(&mut bytes);
+            data.seq_shuffle(&mut ReplayRng::new(bytes));
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(replay);
+    }
+}
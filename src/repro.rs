@@ -0,0 +1,332 @@
+//! Reproduction tokens for seeded shuffles.
+//!
+//! Rare failures (e.g., in the unsafe fast paths) are only reproducible with the exact
+//! seed, plan settings, feature flags, runtime switches, and input shape. A
+//! [`ReproToken`] captures all of them in a short string such as
+//! `rip1-2a-1000000-8-100-40000-7f8-8-5`, which can be pasted into a bug report or a
+//! test. [`shuffle_with_repro`] prints the token to stderr if the shuffle panics, and
+//! [`replay`] executes the same shuffle again.
+//!
+//! The token uses the [`rand_pcg::Pcg64Mcg`] generator seeded with the recorded seed
+//! (requires the `seed_with` feature). Replaying only reproduces the permutation with
+//! the same version of this crate on the same CPU; [`replay`] rejects tokens whose
+//! feature flags or runtime switches differ from the current process.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::{plan::ShufflePlan, repro};
+//!
+//! let mut data: Vec<u64> = (0..1_000_000).collect();
+//! let plan = ShufflePlan::parallel::<u64>(data.len());
+//! let token = repro::shuffle_with_repro(1234, &plan, &mut data);
+//!
+//! let mut replayed: Vec<u64> = (0..1_000_000).collect();
+//! repro::replay(&token.to_string(), &mut replayed).unwrap();
+//! assert_eq!(data, replayed);
+//! ```
+
+use super::error::ShuffleError;
+use super::plan::{Preset, ShufflePlan};
+use rand::SeedableRng;
+use std::fmt;
+use std::str::FromStr;
+
+/// Prefix of the string representation; bumped if the format changes.
+const TOKEN_PREFIX: &str = "rip1";
+
+const FLAG_PARALLEL: u64 = 1;
+const FLAG_DETERMINISTIC: u64 = 2;
+const FLAG_UNSAFE_ALGOS: u64 = 4;
+const FLAG_PREFETCH: u64 = 8;
+const FLAG_ORACLE: u64 = 16;
+
+/// Everything needed to repeat a seeded shuffle. It is displayed as (and parsed from)
+/// `rip1-<seed>-<len>-<element size>-<buckets>-<base case>-<max subproblems>-<threads>-<flags>`
+/// with all numbers in lower-case hexadecimal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReproToken {
+    pub seed: u64,
+    pub len: usize,
+    pub element_size: usize,
+    /// Whether the plan was created with [`ShufflePlan::parallel`].
+    pub parallel: bool,
+    /// Whether the plan uses [`Preset::Deterministic`].
+    pub deterministic: bool,
+    pub num_buckets: usize,
+    pub base_case_size: usize,
+    pub max_subproblems: usize,
+    /// Number of threads of the rayon pool; the parallel scatter shuffle splits its work
+    /// accordingly.
+    pub num_threads: usize,
+    /// Whether the unsafe fast paths were enabled (see [`crate::unsafe_algos`]).
+    pub unsafe_algos: bool,
+    /// Whether the crate was compiled with the `prefetch` feature.
+    pub prefetch: bool,
+    /// Whether the oracle mode was enabled (see [`crate::oracle`]).
+    pub oracle: bool,
+}
+
+impl ReproToken {
+    /// Captures the token of executing `plan` with a generator seeded with `seed` in the
+    /// current process.
+    pub fn capture(seed: u64, plan: &ShufflePlan) -> Self {
+        Self {
+            seed,
+            len: plan.len(),
+            element_size: plan.element_size(),
+            parallel: plan.is_parallel_plan(),
+            deterministic: plan.is_deterministic(),
+            num_buckets: plan.num_buckets(),
+            base_case_size: plan.base_case_size(),
+            max_subproblems: plan.max_subproblems(),
+            num_threads: rayon::current_num_threads(),
+            unsafe_algos: crate::unsafe_algos::is_enabled(),
+            prefetch: cfg!(feature = "prefetch"),
+            oracle: crate::oracle::is_enabled(),
+        }
+    }
+
+    /// Reconstructs the plan the token was captured from. Returns an error if one of
+    /// the recorded settings is invalid.
+    pub fn plan(&self) -> Result<ShufflePlan, ShuffleError> {
+        let mut plan = ShufflePlan::with_shape(self.len, self.element_size, self.parallel);
+        if self.deterministic {
+            plan = plan.with_preset(Preset::Deterministic);
+        }
+
+        plan.with_num_buckets(self.num_buckets)?
+            .with_base_case_size(self.base_case_size)?
+            .with_max_subproblems(self.max_subproblems)
+    }
+
+    /// Returns `true` if the current process uses the feature flags and runtime switches
+    /// recorded in the token, and (for parallel plans) a rayon pool of the same size.
+    pub fn matches_configuration(&self) -> bool {
+        (!self.parallel || self.num_threads == rayon::current_num_threads())
+            && self.unsafe_algos == crate::unsafe_algos::is_enabled()
+            && self.prefetch == cfg!(feature = "prefetch")
+            && self.oracle == crate::oracle::is_enabled()
+    }
+
+    fn flags(&self) -> u64 {
+        [
+            (self.parallel, FLAG_PARALLEL),
+            (self.deterministic, FLAG_DETERMINISTIC),
+            (self.unsafe_algos, FLAG_UNSAFE_ALGOS),
+            (self.prefetch, FLAG_PREFETCH),
+            (self.oracle, FLAG_ORACLE),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag)
+    }
+}
+
+impl fmt::Display for ReproToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{TOKEN_PREFIX}-{:x}-{:x}-{:x}-{:x}-{:x}-{:x}-{:x}-{:x}",
+            self.seed,
+            self.len,
+            self.element_size,
+            self.num_buckets,
+            self.base_case_size,
+            self.max_subproblems,
+            self.num_threads,
+            self.flags()
+        )
+    }
+}
+
+impl FromStr for ReproToken {
+    type Err = ShuffleError;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        let mut parts = token.trim().split('-');
+        if parts.next() != Some(TOKEN_PREFIX) {
+            return Err(ShuffleError::InvalidToken);
+        }
+
+        let mut fields = [0u64; 8];
+        for field in &mut fields {
+            let part = parts.next().ok_or(ShuffleError::InvalidToken)?;
+            *field = u64::from_str_radix(part, 16).map_err(|_| ShuffleError::InvalidToken)?;
+        }
+        if parts.next().is_some() {
+            return Err(ShuffleError::InvalidToken);
+        }
+
+        let [seed, len, element_size, num_buckets, base_case_size, max_subproblems, num_threads, flags] =
+            fields;
+        let to_usize = |x: u64| usize::try_from(x).map_err(|_| ShuffleError::InvalidToken);
+
+        Ok(Self {
+            seed,
+            len: to_usize(len)?,
+            element_size: to_usize(element_size)?,
+            parallel: flags & FLAG_PARALLEL != 0,
+            deterministic: flags & FLAG_DETERMINISTIC != 0,
+            num_buckets: to_usize(num_buckets)?,
+            base_case_size: to_usize(base_case_size)?,
+            max_subproblems: to_usize(max_subproblems)?,
+            num_threads: to_usize(num_threads)?,
+            unsafe_algos: flags & FLAG_UNSAFE_ALGOS != 0,
+            prefetch: flags & FLAG_PREFETCH != 0,
+            oracle: flags & FLAG_ORACLE != 0,
+        })
+    }
+}
+
+/// Executes `plan` on `data` with a [`rand_pcg::Pcg64Mcg`] seeded with `seed` and
+/// returns the [`ReproToken`] of the shuffle. If the shuffle panics, the token is
+/// printed to stderr before the panic is propagated.
+///
+/// # Panics
+/// Panics if the shape of `data` does not match the plan.
+pub fn shuffle_with_repro<T: Send>(seed: u64, plan: &ShufflePlan, data: &mut [T]) -> ReproToken {
+    let token = ReproToken::capture(seed, plan);
+    let _reporter = PanicReporter(token);
+
+    let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(seed);
+    plan.shuffle(&mut rng, data);
+
+    token
+}
+
+/// Repeats the shuffle described by `token` on `data`, which needs to hold the original
+/// input. Returns [`ShuffleError::InvalidToken`] if the token is malformed,
+/// [`ShuffleError::ShapeMismatch`] if `data` does not match the recorded shape, and
+/// [`ShuffleError::ConfigurationMismatch`] if the current process would not produce the
+/// same permutation. In these cases, `data` remains untouched.
+pub fn replay<T: Send>(token: &str, data: &mut [T]) -> Result<(), ShuffleError> {
+    let token: ReproToken = token.parse()?;
+    let plan = token.plan()?;
+
+    if data.len() != token.len || std::mem::size_of::<T>() != token.element_size {
+        return Err(ShuffleError::ShapeMismatch);
+    }
+    if !token.matches_configuration() {
+        return Err(ShuffleError::ConfigurationMismatch);
+    }
+
+    let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(token.seed);
+    plan.shuffle(&mut rng, data);
+    Ok(())
+}
+
+/// Prints the token if dropped while unwinding.
+struct PanicReporter(ReproToken);
+
+impl Drop for PanicReporter {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!(
+                "rip_shuffle: shuffle panicked; reproduce with token {}",
+                self.0
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let plans = [
+            ShufflePlan::sequential::<u32>(1000),
+            ShufflePlan::parallel::<u64>(1 << 24)
+                .with_max_subproblems(3)
+                .unwrap(),
+            ShufflePlan::parallel::<u8>(1 << 20).with_preset(Preset::Deterministic),
+        ];
+
+        for plan in plans {
+            let token = ReproToken::capture(u64::MAX, &plan);
+            let string = token.to_string();
+            assert!(string.starts_with("rip1-ffffffffffffffff-"), "{string}");
+            assert_eq!(string.parse(), Ok(token));
+            assert_eq!(token.plan(), Ok(plan));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        let token = ReproToken::capture(1, &ShufflePlan::sequential::<u32>(10)).to_string();
+        assert!(token.parse::<ReproToken>().is_ok());
+
+        let truncated = &token[..token.rfind('-').unwrap()];
+        for invalid in [
+            "",
+            "rip0-1-a-4-80-100-100-1-4",
+            truncated,
+            &format!("{token}-0"),
+            &token.replace("rip1-1-", "rip1-x-"),
+        ] {
+            assert_eq!(
+                invalid.parse::<ReproToken>(),
+                Err(ShuffleError::InvalidToken),
+                "{invalid}"
+            );
+        }
+
+        let mut data = [0u32; 10];
+        assert_eq!(
+            replay("rip1-1-a-4-3-100-100-1-0", &mut data),
+            Err(ShuffleError::UnsupportedNumberOfBuckets)
+        );
+    }
+
+    #[test]
+    fn replays_shuffle() {
+        for (n, parallel) in [(100, false), (1 << 20, false), (1 << 22, true)] {
+            let plan = if parallel {
+                ShufflePlan::parallel::<u64>(n)
+            } else {
+                ShufflePlan::sequential::<u64>(n)
+            };
+
+            let mut data: Vec<u64> = (0..n as u64).collect();
+            let token = shuffle_with_repro(n as u64, &plan, &mut data);
+
+            let mut replayed: Vec<u64> = (0..n as u64).collect();
+            replay(&token.to_string(), &mut replayed).unwrap();
+            assert_eq!(replayed, data, "n={n}");
+        }
+    }
+
+    #[test]
+    fn replay_checks_shape_and_configuration() {
+        let plan = ShufflePlan::sequential::<u32>(10);
+        let token = ReproToken::capture(1, &plan);
+
+        let mut data = [0u64; 10];
+        assert_eq!(
+            replay(&token.to_string(), &mut data),
+            Err(ShuffleError::ShapeMismatch)
+        );
+
+        let mut data = [7u32; 10];
+        let foreign = ReproToken {
+            prefetch: !token.prefetch,
+            ..token
+        };
+        assert_eq!(
+            replay(&foreign.to_string(), &mut data),
+            Err(ShuffleError::ConfigurationMismatch)
+        );
+        assert_eq!(data, [7u32; 10]);
+
+        let plan = ShufflePlan::parallel::<u32>(10);
+        let other_pool = ReproToken {
+            num_threads: rayon::current_num_threads() + 1,
+            ..ReproToken::capture(1, &plan)
+        };
+        assert_eq!(
+            replay(&other_pool.to_string(), &mut data),
+            Err(ShuffleError::ConfigurationMismatch)
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Random permutations with restricted positions.
+//!
+//! Scheduling and assignment problems often need a random permutation in which
+//! certain elements must not end up at certain positions (e.g., nobody reviews their
+//! own paper); derangements are the special case of forbidding each element at its
+//! own position. [`random_restricted_permutation`] samples uniformly among all
+//! permutations that avoid a given set of forbidden `(element, position)` pairs.
+//!
+//! The sampler first checks via a bipartite matching that at least one such
+//! permutation exists. It then runs Fisher-Yates shuffles from the front and restarts
+//! as soon as a forbidden pair is placed; since the decision to restart only depends
+//! on the prefix drawn so far, this is equivalent to rejection sampling of complete
+//! permutations, and the result is uniform.
+//!
+//! # Warning
+//! The expected number of restarts is the inverse of the fraction of valid
+//! permutations. For sparse constraints (few forbidden positions per element) this is
+//! a small constant, e.g. about `e` for derangements, but it grows exponentially for
+//! dense constraints.
+
+use super::error::ShuffleError;
+use super::uniform_index;
+use rand::Rng;
+use std::collections::{HashSet, VecDeque};
+
+const UNMATCHED: usize = usize::MAX;
+
+/// Returns a uniformly random permutation `perm` of `0..n` such that no
+/// `(perm[pos], pos)` is contained in `forbidden`. Pairs with an element or position
+/// of at least `n` are ignored.
+///
+/// # Panics
+/// Panics if no such permutation exists; see [`try_random_restricted_permutation`]
+/// for a non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::restricted::random_restricted_permutation;
+///
+/// // assign reviewers 0..4 to papers 0..4; nobody may review their own paper,
+/// // and reviewer 0 must not review paper 1
+/// let forbidden: Vec<_> = (0..4).map(|i| (i, i)).chain([(0, 1)]).collect();
+/// let reviewer_of = random_restricted_permutation(&mut rand::thread_rng(), 4, &forbidden);
+///
+/// assert!(reviewer_of.iter().enumerate().all(|(paper, &r)| r != paper));
+/// assert_ne!(reviewer_of[1], 0);
+/// ```
+pub fn random_restricted_permutation<R: Rng>(
+    rng: &mut R,
+    n: usize,
+    forbidden: &[(usize, usize)],
+) -> Vec<usize> {
+    try_random_restricted_permutation(rng, n, forbidden).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`random_restricted_permutation`], but returns [`ShuffleError::Infeasible`]
+/// instead of panicking if every permutation of `0..n` contains a forbidden pair.
+pub fn try_random_restricted_permutation<R: Rng>(
+    rng: &mut R,
+    n: usize,
+    forbidden: &[(usize, usize)],
+) -> Result<Vec<usize>, ShuffleError> {
+    let forbidden = Forbidden::new(n, forbidden);
+    if !forbidden.is_feasible() {
+        return Err(ShuffleError::Infeasible);
+    }
+
+    // each attempt shuffles the outcome of the previous one, which is as good as
+    // starting from the identity
+    let mut perm: Vec<usize> = (0..n).collect();
+
+    'attempt: loop {
+        for pos in 0..n {
+            let partner = pos + uniform_index::gen_index(rng, n - pos);
+            perm.swap(pos, partner);
+
+            if forbidden.contains(perm[pos], pos) {
+                continue 'attempt;
+            }
+        }
+
+        return Ok(perm);
+    }
+}
+
+struct Forbidden {
+    n: usize,
+    pairs: HashSet<(usize, usize)>,
+    num_per_element: Vec<usize>,
+}
+
+impl Forbidden {
+    fn new(n: usize, forbidden: &[(usize, usize)]) -> Self {
+        let pairs: HashSet<_> = forbidden
+            .iter()
+            .copied()
+            .filter(|&(elem, pos)| elem < n && pos < n)
+            .collect();
+
+        let mut num_per_element = vec![0; if pairs.is_empty() { 0 } else { n }];
+        for &(elem, _) in &pairs {
+            num_per_element[elem] += 1;
+        }
+
+        Self {
+            n,
+            pairs,
+            num_per_element,
+        }
+    }
+
+    fn contains(&self, elem: usize, pos: usize) -> bool {
+        !self.pairs.is_empty() && self.pairs.contains(&(elem, pos))
+    }
+
+    /// Returns true if a perfect matching of elements and positions avoids all
+    /// forbidden pairs. Most elements are matched greedily; for each remaining one, an
+    /// augmenting path is searched in the (implicit) complement of the forbidden pairs.
+    fn is_feasible(&self) -> bool {
+        if self.pairs.is_empty() {
+            return true;
+        }
+
+        let mut matching = Matching {
+            position_of: vec![UNMATCHED; self.n],
+            element_at: vec![UNMATCHED; self.n],
+        };
+
+        // among any `num_per_element[elem] + 1` free positions, one is allowed for `elem`
+        let mut free: Vec<usize> = (0..self.n).collect();
+        let mut unmatched = Vec::new();
+        for elem in 0..self.n {
+            let candidates = (self.num_per_element[elem] + 1).min(free.len());
+            match (free.len() - candidates..free.len())
+                .rev()
+                .find(|&k| !self.contains(elem, free[k]))
+            {
+                Some(k) => matching.assign(elem, free.swap_remove(k)),
+                None => unmatched.push(elem),
+            }
+        }
+
+        unmatched
+            .into_iter()
+            .all(|elem| self.augment(&mut matching, elem))
+    }
+
+    /// Breadth-first search for an augmenting path starting at the unmatched `root`.
+    /// Each position is reached at most once, and scanning the unreached positions from
+    /// an element only skips positions forbidden for it, so a search takes time linear
+    /// in `n` and the number of forbidden pairs.
+    fn augment(&self, matching: &mut Matching, root: usize) -> bool {
+        let mut unreached: Vec<usize> = (0..self.n).collect();
+        let mut reached_from = vec![UNMATCHED; self.n];
+        let mut queue = VecDeque::from([root]);
+
+        while let Some(elem) = queue.pop_front() {
+            let mut k = 0;
+            while k < unreached.len() {
+                let pos = unreached[k];
+                if self.contains(elem, pos) {
+                    k += 1;
+                    continue;
+                }
+
+                unreached.swap_remove(k);
+                reached_from[pos] = elem;
+
+                match matching.element_at[pos] {
+                    UNMATCHED => {
+                        // flip the path back to the root
+                        let mut pos = pos;
+                        loop {
+                            let elem = reached_from[pos];
+                            let prev = matching.position_of[elem];
+                            matching.assign(elem, pos);
+                            if prev == UNMATCHED {
+                                return true;
+                            }
+                            pos = prev;
+                        }
+                    }
+                    other => queue.push_back(other),
+                }
+            }
+        }
+
+        false
+    }
+}
+
+struct Matching {
+    position_of: Vec<usize>,
+    element_at: Vec<usize>,
+}
+
+impl Matching {
+    fn assign(&mut self, elem: usize, pos: usize) {
+        self.position_of[elem] = pos;
+        self.element_at[pos] = elem;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+    use std::collections::HashMap;
+
+    fn avoids(perm: &[usize], forbidden: &[(usize, usize)]) -> bool {
+        forbidden
+            .iter()
+            .all(|&(elem, pos)| perm.get(pos) != Some(&elem))
+    }
+
+    #[test]
+    fn is_permutation() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for n in [0, 3, 10, 100_000] {
+            let forbidden: Vec<_> = (0..n).map(|i| (i, i)).chain([(0, 1)]).collect();
+            let mut perm = random_restricted_permutation(&mut rng, n, &forbidden);
+            assert!(avoids(&perm, &forbidden));
+
+            perm.sort();
+            assert!(perm.iter().enumerate().all(|(i, &x)| i == x));
+        }
+    }
+
+    #[test]
+    fn infeasible() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+
+        // element 0 may not be placed anywhere
+        let all_positions: Vec<_> = (0..3).map(|pos| (0, pos)).collect();
+        // no element may be placed at position 1
+        let all_elements: Vec<_> = (0..3).map(|elem| (elem, 1)).collect();
+        // elements 0 and 1 may only be placed at position 0
+        let hall_violation = [(0, 1), (0, 2), (1, 1), (1, 2)];
+        // a derangement of a single element
+        let single = [(0, 0)];
+
+        for (n, forbidden) in [
+            (3, &all_positions[..]),
+            (3, &all_elements[..]),
+            (3, &hall_violation[..]),
+            (1, &single[..]),
+        ] {
+            assert_eq!(
+                try_random_restricted_permutation(&mut rng, n, forbidden),
+                Err(ShuffleError::Infeasible)
+            );
+        }
+    }
+
+    #[test]
+    fn single_valid_permutation() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        let n = 6;
+        let only: Vec<usize> = vec![3, 5, 0, 4, 1, 2];
+
+        let forbidden: Vec<_> = (0..n)
+            .flat_map(|elem| (0..n).map(move |pos| (elem, pos)))
+            .filter(|&(elem, pos)| only[pos] != elem)
+            .chain([(n, 0), (0, n)])
+            .collect();
+
+        assert_eq!(random_restricted_permutation(&mut rng, n, &forbidden), only);
+    }
+
+    #[test]
+    fn uniform() {
+        // all 9 derangements of four elements appear equally often
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+        let forbidden: Vec<_> = (0..4).map(|i| (i, i)).collect();
+        const RUNS: usize = 18_000;
+
+        let mut counts = HashMap::new();
+        for _ in 0..RUNS {
+            let perm = random_restricted_permutation(&mut rng, 4, &forbidden);
+            *counts.entry(perm).or_insert(0usize) += 1;
+        }
+
+        assert_eq!(counts.len(), 9);
+        assert!(counts
+            .values()
+            .all(|&c| c > RUNS / 9 * 9 / 10 && c < RUNS / 9 * 11 / 10));
+    }
+}
@@ -0,0 +1,91 @@
+//! Sanity check detecting obviously broken random number generators.
+//!
+//! A mis-seeded generator or a stub left in production code (e.g., one that always
+//! returns zero) does not crash the shuffling algorithms; they silently produce a
+//! fixed permutation instead. [`check_rng`] detects such generators by inspecting
+//! the first few words: if all of them are identical, the generator is rejected.
+//! For a working generator, this happens with probability `2^-448`.
+//!
+//! The check is opt-in via [`crate::RipShuffleSequential::checked_seq_shuffle`] and
+//! [`crate::RipShuffleParallel::checked_par_shuffle`], which probe a clone of the
+//! generator so that the emitted permutation does not change.
+
+use super::error::ShuffleError;
+use rand::Rng;
+
+/// Number of words inspected by [`check_rng`].
+pub const NUM_PROBES: usize = 8;
+
+/// Draws [`NUM_PROBES`] words from `rng` and returns [`ShuffleError::DegenerateRng`]
+/// if they are all identical (which includes the all-zero stream).
+///
+/// # Example
+/// ```
+/// use rip_shuffle::{error::ShuffleError, rng_guard::check_rng};
+/// use rand::rngs::mock::StepRng;
+///
+/// assert!(check_rng(&mut rand::thread_rng()).is_ok());
+/// assert_eq!(check_rng(&mut StepRng::new(0, 0)), Err(ShuffleError::DegenerateRng));
+/// ```
+pub fn check_rng<R: Rng>(rng: &mut R) -> Result<(), ShuffleError> {
+    let first: u64 = rng.gen();
+
+    if (1..NUM_PROBES).all(|_| rng.gen::<u64>() == first) {
+        return Err(ShuffleError::DegenerateRng);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{CountingRng, SequenceRng};
+    use crate::{RipShuffleParallel, RipShuffleSequential};
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn accepts_proper_rngs() {
+        for seed in 0..100 {
+            assert!(check_rng(&mut Pcg64Mcg::seed_from_u64(seed)).is_ok());
+        }
+
+        // a counter is a poor generator, but not a degenerate one
+        assert!(check_rng(&mut CountingRng::new(0)).is_ok());
+    }
+
+    #[test]
+    fn rejects_constant_rngs() {
+        for value in [0, 1, u64::MAX] {
+            assert_eq!(
+                check_rng(&mut SequenceRng::new([value])),
+                Err(ShuffleError::DegenerateRng)
+            );
+        }
+    }
+
+    #[test]
+    fn checked_shuffles() {
+        let org: Vec<usize> = (0..1000).collect();
+
+        let mut data = org.clone();
+        let result = data.checked_seq_shuffle(&mut SequenceRng::new([0]));
+        assert_eq!(result, Err(ShuffleError::DegenerateRng));
+        assert_eq!(data, org);
+
+        // the check does not alter the permutation
+        let mut reference = org.clone();
+        reference.seq_shuffle(&mut Pcg64Mcg::seed_from_u64(1));
+        data.checked_seq_shuffle(&mut Pcg64Mcg::seed_from_u64(1))
+            .unwrap();
+        assert_eq!(data, reference);
+
+        let mut reference = org.clone();
+        reference.par_shuffle(&mut Pcg64Mcg::seed_from_u64(2));
+        let mut data = org.clone();
+        data.checked_par_shuffle(&mut Pcg64Mcg::seed_from_u64(2))
+            .unwrap();
+        assert_eq!(data, reference);
+    }
+}
@@ -0,0 +1,252 @@
+//! Different random number generators for different phases of the parallel scatter shuffle.
+//!
+//! Most random bits of [`crate::scatter_shuffle::parallel::par_scatter_shuffle`] are
+//! drawn per element: in the leaves of the rough shuffle and in the base cases. The
+//! remaining decisions (the seeds of subproblems, the shuffling of the stashes, and the
+//! sizes of the buckets) only need few random bits. By default, the generator passed
+//! by the caller is used for everything. A [`RngStrategy`] may instead run the
+//! per-element phases with a different generator derived from it.
+//!
+//! [`Hybrid`] seeds a fast generator (e.g., [`rand_pcg::Pcg64Mcg`]) from the caller's
+//! generator for each per-element phase. With a cryptographically secure generator
+//! (e.g., [`rand::rngs::StdRng`]), this is a middle ground between the two extremes:
+//! all seeds and partitioning decisions come from the secure generator and a fresh
+//! fast generator is used for at most one base case or rough-shuffle leaf. The output
+//! is, however, only as unpredictable as the fast generator, so this is no substitute
+//! for [`crate::RipShuffleParallel::secure_par_shuffle`] if an adversary must not be
+//! able to predict the permutation.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::rng_strategy::par_shuffle_hybrid;
+//! use rand::{rngs::StdRng, SeedableRng};
+//!
+//! let mut data: Vec<u64> = (0..1_000_000).collect();
+//! par_shuffle_hybrid::<rand_pcg::Pcg64Mcg, _, _>(&mut StdRng::seed_from_u64(1), &mut data);
+//!
+//! assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+//! ```
+
+use crate::scatter_shuffle::parallel::{num_buckets_for_bytes, seed_new_rng};
+use crate::scatter_shuffle::parallel::{DefaultConfiguration, ParScatterShuffleImpl};
+use crate::scatter_shuffle::{ParConfiguration, SeqConfiguration};
+use rand::{Rng, SeedableRng};
+use std::marker::PhantomData;
+
+/// A phase of the shuffle that draws random bits per element. It is generic over the
+/// generator, so a [`RngStrategy`] can choose the generator's type.
+pub trait IndexRngTask {
+    type Output;
+    fn run<G: Rng>(self, rng: &mut G) -> Self::Output;
+}
+
+/// Selects the generator for the per-element phases (see the [module documentation](self)).
+pub trait RngStrategy: Clone + Send + Sync {
+    /// Runs `task` with a generator derived from `rng`.
+    fn run_per_element<R, Task>(&self, rng: &mut R, task: Task) -> Task::Output
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        Task: IndexRngTask;
+}
+
+/// Uses the caller's generator for all phases; the default of [`ParConfiguration`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SameRng;
+
+impl RngStrategy for SameRng {
+    fn run_per_element<R, Task>(&self, rng: &mut R, task: Task) -> Task::Output
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        Task: IndexRngTask,
+    {
+        task.run(rng)
+    }
+}
+
+/// Runs each per-element phase with a generator of type `F` seeded from the caller's
+/// generator (see [`seed_new_rng`]).
+pub struct Hybrid<F>(PhantomData<fn() -> F>);
+
+impl<F> Default for Hybrid<F> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<F> Clone for Hybrid<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for Hybrid<F> {}
+
+impl<F: Rng + SeedableRng> RngStrategy for Hybrid<F> {
+    fn run_per_element<R, Task>(&self, rng: &mut R, task: Task) -> Task::Output
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        Task: IndexRngTask,
+    {
+        let mut fast: F = seed_new_rng(rng);
+        task.run(&mut fast)
+    }
+}
+
+/// Configuration that behaves like `C`, but runs the per-element phases with the
+/// generators selected by `S` (see [`ParConfiguration::run_per_element`]).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WithRngStrategy<C, S> {
+    pub config: C,
+    pub strategy: S,
+}
+
+impl<C, S> WithRngStrategy<C, S> {
+    pub fn new(config: C, strategy: S) -> Self {
+        Self { config, strategy }
+    }
+}
+
+impl<C: SeqConfiguration, S: Clone> SeqConfiguration for WithRngStrategy<C, S> {
+    fn seq_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+        self.config.seq_base_case_shuffle(rng, data)
+    }
+
+    fn seq_base_case_size(&self) -> usize {
+        self.config.seq_base_case_size()
+    }
+
+    fn seq_disable_recursion(&self) -> bool {
+        self.config.seq_disable_recursion()
+    }
+
+    fn rough_shuffle_handover_rounds(&self) -> usize {
+        self.config.rough_shuffle_handover_rounds()
+    }
+}
+
+impl<C: ParConfiguration, S: RngStrategy> ParConfiguration for WithRngStrategy<C, S> {
+    fn par_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+        self.config.par_base_case_shuffle(rng, data)
+    }
+
+    fn par_base_case_size(&self) -> usize {
+        self.config.par_base_case_size()
+    }
+
+    fn par_number_of_subproblems(&self, n: usize) -> usize {
+        self.config.par_number_of_subproblems(n)
+    }
+
+    fn par_disable_recursion(&self) -> bool {
+        self.config.par_disable_recursion()
+    }
+
+    fn par_subproblem_weight(&self, index: usize, num_subproblems: usize) -> usize {
+        self.config.par_subproblem_weight(index, num_subproblems)
+    }
+
+    fn run_per_element<R, Task>(&self, rng: &mut R, task: Task) -> Task::Output
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        Task: IndexRngTask,
+    {
+        self.strategy.run_per_element(rng, task)
+    }
+
+    type Profiler = C::Profiler;
+    fn get_profiler(&self) -> &Self::Profiler {
+        self.config.get_profiler()
+    }
+}
+
+/// Same as [`crate::scatter_shuffle::parallel::par_scatter_shuffle`], but runs the
+/// per-element phases with generators of type `F` seeded from `rng` (see [`Hybrid`]).
+/// Small inputs are shuffled sequentially with a single generator of type `F`.
+pub fn par_shuffle_hybrid<F, R, T>(rng: &mut R, data: &mut [T])
+where
+    F: Rng + SeedableRng,
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send + Sized,
+{
+    if crate::oracle::is_enabled() {
+        return crate::fisher_yates::naive::fisher_yates(rng, data);
+    }
+
+    let config = WithRngStrategy::new(DefaultConfiguration::default(), Hybrid::<F>::default());
+    match num_buckets_for_bytes(std::mem::size_of_val(data)) {
+        None => crate::fisher_yates::fisher_yates(&mut seed_new_rng::<R, F>(rng), data),
+        Some(64) => ParScatterShuffleImpl::<R, T, _, 64>::new(config).shuffle(rng, data),
+        Some(256) => ParScatterShuffleImpl::<R, T, _, 256>::new(config).shuffle(rng, data),
+        Some(512) => ParScatterShuffleImpl::<R, T, _, 512>::new(config).shuffle(rng, data),
+        Some(_) => ParScatterShuffleImpl::<R, T, _, 1024>::new(config).shuffle(rng, data),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand_pcg::Pcg64Mcg;
+
+    /// Counts the generators derived for per-element phases
+    #[derive(Clone, Default)]
+    struct Counting(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl RngStrategy for Counting {
+        fn run_per_element<R, Task>(&self, rng: &mut R, task: Task) -> Task::Output
+        where
+            R: Rng + SeedableRng + Send + Sync,
+            Task: IndexRngTask,
+        {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Hybrid::<Pcg64Mcg>::default().run_per_element(rng, task)
+        }
+    }
+
+    #[test]
+    fn strategy_runs_per_element_phases() {
+        let strategy = Counting::default();
+        let config = WithRngStrategy::new(DefaultConfiguration::default(), strategy.clone());
+
+        let mut data: Vec<u32> = (0..1 << 20).collect();
+        ParScatterShuffleImpl::<StdRng, u32, _, 64>::new(config)
+            .shuffle(&mut StdRng::seed_from_u64(1), &mut data);
+
+        // at least one rough-shuffle leaf and several base cases
+        assert!(strategy.0.load(std::sync::atomic::Ordering::Relaxed) > 2);
+
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i == x as usize));
+    }
+
+    #[test]
+    fn same_rng_is_default() {
+        let config = WithRngStrategy::new(DefaultConfiguration::default(), SameRng);
+
+        let mut expected: Vec<u32> = (0..1 << 20).collect();
+        let mut actual = expected.clone();
+        ParScatterShuffleImpl::<Pcg64Mcg, u32, _, 64>::new(DefaultConfiguration::default())
+            .shuffle(&mut Pcg64Mcg::seed_from_u64(2), &mut expected);
+        ParScatterShuffleImpl::<Pcg64Mcg, u32, _, 64>::new(config)
+            .shuffle(&mut Pcg64Mcg::seed_from_u64(2), &mut actual);
+
+        assert_eq!(actual, expected);
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn hybrid<R: Rng + SeedableRng + Send + Sync, T: Send>(rng: &mut R, data: &mut [T]) {
+            let config = WithRngStrategy::new(SmallBaseCases, Hybrid::<Pcg64Mcg>::default());
+            ParScatterShuffleImpl::<R, T, _, 4>::new(config).shuffle(rng, data)
+        }
+
+        #[derive(Clone, Copy, Default)]
+        struct SmallBaseCases;
+
+        crate::implement_seq_config!(SmallBaseCases, crate::fisher_yates::fisher_yates, 2);
+        crate::implement_par_config!(SmallBaseCases, crate::fisher_yates::fisher_yates, 2, |_| 2);
+
+        crate::statistical_tests::test_shuffle_algorithm!(hybrid);
+    }
+}
@@ -5,15 +5,48 @@ use rand::Rng;
 mod common_tests;
 
 mod naive;
+mod safe;
 
 #[cfg(feature = "unsafe_algos")]
 pub mod with_unsafe_algos;
 
+#[cfg(all(feature = "unsafe_algos", any(test, feature = "write_combining")))]
+mod write_combining;
+
+/// Default of [`SeqConfiguration::rough_shuffle_handover_rounds`]: the batched (unsafe
+/// or safe) rough shuffle hands over to the naive loop once a batch would execute at
+/// most this many rounds. Each batch resynchronizes all buckets, so tiny batches do
+/// not pay off; on x86-64, handing over at 8 rather than 1 round was slightly faster
+/// for inputs of `2^20` to `2^22` elements.
+///
+/// [`SeqConfiguration::rough_shuffle_handover_rounds`]: crate::scatter_shuffle::SeqConfiguration::rough_shuffle_handover_rounds
+pub const DEFAULT_HANDOVER_ROUNDS: usize = 8;
+
 pub struct NumberOfBuckets<const N: usize> {}
 
 pub trait IsPowerOfTwo {
     const N: usize;
     const LOG2: usize;
+
+    /// Executes the rough shuffle specialized for `N` buckets. This is an
+    /// implementation detail of [`rough_shuffle`]; dispatching through the trait
+    /// selects the specialization at compile time, so there is no fallible
+    /// runtime dispatch on the number of buckets.
+    #[doc(hidden)]
+    fn rough_shuffle<R: Rng, T, const M: usize>(
+        rng: &mut R,
+        buckets: &mut Buckets<T, M>,
+        handover_rounds: usize,
+    );
+
+    /// Executes the write-combining rough shuffle specialized for `N` buckets, if it
+    /// is applicable to `T`; see [`seq_rough_shuffle_with_handover`].
+    #[cfg(feature = "write_combining")]
+    #[doc(hidden)]
+    fn write_combining_rough_shuffle<R: Rng, T, const M: usize>(
+        rng: &mut R,
+        buckets: &mut Buckets<T, M>,
+    );
 }
 
 macro_rules! impl_index_bits_trait {
@@ -21,6 +54,49 @@ macro_rules! impl_index_bits_trait {
         impl IsPowerOfTwo for NumberOfBuckets<$n> {
             const N: usize = $n;
             const LOG2: usize = $log_n;
+
+            fn rough_shuffle<R: Rng, T, const M: usize>(
+                rng: &mut R,
+                buckets: &mut Buckets<T, M>,
+                handover_rounds: usize,
+            ) {
+                const LOG_N: usize = $log_n;
+                const SWAPS_PER_ROUND: usize = 64 / LOG_N;
+                const { assert!(M == $n) };
+
+                // the pointer arithmetic of the unsafe algos does not support zero-sized types
+                if crate::unsafe_algos::is_enabled() && std::mem::size_of::<T>() != 0 {
+                    #[cfg(feature = "unsafe_algos")]
+                    with_unsafe_algos::rough_shuffle::<R, T, LOG_N, M, SWAPS_PER_ROUND>(
+                        rng,
+                        buckets,
+                        handover_rounds,
+                    );
+                } else {
+                    safe::rough_shuffle::<R, T, LOG_N, M, SWAPS_PER_ROUND>(
+                        rng,
+                        buckets,
+                        handover_rounds,
+                    );
+                }
+
+                // the batched algos terminate early. then the naive algo takes over.
+                naive::rough_shuffle::<R, T, LOG_N, M, SWAPS_PER_ROUND>(rng, buckets);
+            }
+
+            #[cfg(feature = "write_combining")]
+            fn write_combining_rough_shuffle<R: Rng, T, const M: usize>(
+                rng: &mut R,
+                buckets: &mut Buckets<T, M>,
+            ) {
+                const LOG_N: usize = $log_n;
+                const SWAPS_PER_ROUND: usize = 64 / LOG_N;
+                const { assert!(M == $n) };
+
+                if crate::unsafe_algos::is_enabled() {
+                    write_combining::rough_shuffle::<R, T, LOG_N, M, SWAPS_PER_ROUND>(rng, buckets);
+                }
+            }
         }
     };
     ($log_n : expr) => {
@@ -43,30 +119,35 @@ pub fn rough_shuffle<R: Rng, T, const N: usize>(rng: &mut R, buckets: &mut Bucke
 where
     NumberOfBuckets<N>: IsPowerOfTwo,
 {
-    macro_rules! entry {
-        ($log_n : literal) => {{
-            const LOG_N: usize = $log_n;
-            const SWAPS_PER_ROUND: usize = 64 / $log_n;
-
-            #[cfg(feature = "unsafe_algos")]
-            with_unsafe_algos::rough_shuffle::<R, T, LOG_N, N, SWAPS_PER_ROUND>(rng, buckets);
-
-            // the unsafe algo may terminate early. then the naive algo takes over.
-            naive::rough_shuffle::<R, T, LOG_N, N, SWAPS_PER_ROUND>(rng, buckets);
-        }};
-    }
-
-    match N {
-        2 => entry!(1),
-        4 => entry!(2),
-        8 => entry!(3),
-        16 => entry!(4),
-        32 => entry!(5),
-        64 => entry!(6),
-        128 => entry!(7),
-        256 => entry!(8),
-        512 => entry!(9),
-        1024 => entry!(10),
-        _ => panic!(), // cannot be reached due to IsPowerOfTwo trait bounds
-    }
+    rough_shuffle_with_handover(rng, buckets, DEFAULT_HANDOVER_ROUNDS)
+}
+
+/// Same as [`rough_shuffle`], but the batched implementation hands over to the naive one
+/// once a batch would execute at most `handover_rounds` rounds (see
+/// [`DEFAULT_HANDOVER_ROUNDS`]); values below 1 are treated as 1. The threshold only
+/// affects the performance, not the distribution of the output.
+pub fn rough_shuffle_with_handover<R: Rng, T, const N: usize>(
+    rng: &mut R,
+    buckets: &mut Buckets<T, N>,
+    handover_rounds: usize,
+) where
+    NumberOfBuckets<N>: IsPowerOfTwo,
+{
+    <NumberOfBuckets<N> as IsPowerOfTwo>::rough_shuffle(rng, buckets, handover_rounds)
+}
+
+/// Same as [`rough_shuffle_with_handover`], but with the `write_combining` feature, the
+/// write-combining rough shuffle processes most elements first. As documented for the
+/// feature, only the sequential scatter shuffle uses it.
+pub fn seq_rough_shuffle_with_handover<R: Rng, T, const N: usize>(
+    rng: &mut R,
+    buckets: &mut Buckets<T, N>,
+    handover_rounds: usize,
+) where
+    NumberOfBuckets<N>: IsPowerOfTwo,
+{
+    #[cfg(feature = "write_combining")]
+    <NumberOfBuckets<N> as IsPowerOfTwo>::write_combining_rough_shuffle(rng, buckets);
+
+    rough_shuffle_with_handover(rng, buckets, handover_rounds)
 }
@@ -1,5 +1,6 @@
 use super::*;
 use crate::random_bits::RandomBitsSource;
+use crate::swap::swap_refs;
 
 pub(super) fn rough_shuffle<
     R: Rng,
@@ -15,7 +16,7 @@ pub(super) fn rough_shuffle<
         return;
     }
 
-    assert_eq!(1 << LOG_NUM_BUCKETS, NUM_BUCKETS);
+    const { assert!(1 << LOG_NUM_BUCKETS == NUM_BUCKETS) };
 
     rough_shuffle_impl::<R, T, LOG_NUM_BUCKETS, NUM_BUCKETS>(rng, buckets);
 }
@@ -25,21 +26,21 @@ fn rough_shuffle_impl<R: Rng, T, const LOG_NUM_BUCKETS: usize, const NUM_BUCKETS
     buckets: &mut Buckets<T, NUM_BUCKETS>,
 ) -> Option<()> {
     let mut rbs = RandomBitsSource::new();
-    let (active_bucket, partners) = buckets.as_mut_slice().split_first_mut().unwrap();
+    let (active_bucket, partners) = buckets.as_mut_slice().split_first_mut()?;
 
-    let mut active_element = active_bucket.first_unprocessed().unwrap();
+    let mut active_element = active_bucket.first_unprocessed()?;
 
     loop {
         let partner_bucket_idx = rbs.gen_const_bits::<LOG_NUM_BUCKETS>(rng) as usize;
 
         if let Some(partner_bucket) = partners.get_mut(partner_bucket_idx) {
-            let partner_element = partner_bucket.first_unprocessed().unwrap();
+            let partner_element = partner_bucket.first_unprocessed()?;
 
-            std::mem::swap(active_element, partner_element);
+            swap_refs(active_element, partner_element);
 
             partner_bucket.process_element()?;
         } else {
-            assert_eq!(partner_bucket_idx, NUM_BUCKETS - 1);
+            debug_assert_eq!(partner_bucket_idx, NUM_BUCKETS - 1);
             active_element = active_bucket.process_element()?;
         }
     }
@@ -0,0 +1,150 @@
+//! Safe counterpart of [`super::with_unsafe_algos`], which is used if the unsafe
+//! algorithms are not compiled in or switched off (see [`crate::unsafe_algos`]).
+//!
+//! The algorithmic structure is the same: each batch executes as many rounds as the
+//! shortest bucket allows, so no swap needs to check whether its bucket is exhausted;
+//! each round obtains the target buckets of several swaps from a single random word and
+//! prefetches them; and the stash has two lanes that alternate between the swaps, which
+//! breaks the dependency between consecutive swaps. Instead of raw pointers, the
+//! positions within the buckets are kept as indices, and the lanes of the stash are
+//! the first two unprocessed elements of the first bucket.
+
+use super::*;
+use crate::prefetch::prefetch_write_data;
+use crate::swap::swap_refs;
+
+/// Number of lanes of the stash.
+const LANES: usize = 2;
+
+pub(super) fn rough_shuffle<R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>(
+    rng: &mut R,
+    buckets: &mut Buckets<T, N>,
+    handover_rounds: usize,
+) {
+    if buckets.len() != N {
+        return;
+    }
+
+    loop {
+        let rounds = length_of_shortest_bucket(buckets) / LANES / SWAPS;
+        if rounds <= handover_rounds.max(1) {
+            break;
+        }
+
+        let staged = shuffle_batch::<R, T, LOG_N, N, SWAPS>(rng, buckets, rounds);
+        for (bucket, num) in buckets.iter_mut().zip(staged) {
+            bucket.set_num_processed(bucket.num_processed() + num);
+        }
+    }
+}
+
+/// Returns the number of unprocessed elements of the shortest bucket, not counting the
+/// lanes of the stash in the first bucket.
+fn length_of_shortest_bucket<T, const N: usize>(buckets: &Buckets<T, N>) -> usize {
+    let first = buckets[0].num_unprocessed().saturating_sub(LANES);
+    buckets[1..]
+        .iter()
+        .map(|bucket| bucket.num_unprocessed())
+        .fold(first, usize::min)
+}
+
+/// Executes `rounds` rounds and returns the number of elements processed in each bucket.
+/// The elements of the stash are left unprocessed in the first bucket.
+fn shuffle_batch<R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>(
+    rng: &mut R,
+    buckets: &mut Buckets<T, N>,
+    rounds: usize,
+) -> [usize; N] {
+    let mut staged = [0usize; N];
+
+    {
+        let mut iter = buckets.iter_mut();
+        let (lanes, first) = iter
+            .next()
+            .expect("there are N > 0 buckets")
+            .data_unprocessed_mut()
+            .split_at_mut(LANES);
+
+        let mut stash = Stash { lanes };
+        let mut first = Some(first);
+        let mut targets: [&mut [T]; N] = std::array::from_fn(|_| match first.take() {
+            Some(first) => first,
+            None => iter
+                .next()
+                .expect("one target per bucket")
+                .data_unprocessed_mut(),
+        });
+
+        for _ in 0..rounds {
+            let indices0 =
+                draw_and_prefetch::<R, T, LOG_N, N, SWAPS>(rng, &mut staged, &mut targets);
+            let indices1 =
+                draw_and_prefetch::<R, T, LOG_N, N, SWAPS>(rng, &mut staged, &mut targets);
+
+            for k in 0..SWAPS {
+                let (bucket, pos) = indices0[k];
+                stash.swap::<0>(&mut targets[bucket][pos]);
+                let (bucket, pos) = indices1[k];
+                stash.swap::<1>(&mut targets[bucket][pos]);
+            }
+        }
+    }
+
+    // the first bucket holds the lanes followed by its processed elements; move the
+    // lanes behind the processed elements
+    let num_first = staged[0];
+    let data = buckets[0].data_unprocessed_mut();
+    for k in 0..LANES.min(num_first) {
+        data.swap(k, LANES + num_first - 1 - k);
+    }
+
+    staged
+}
+
+/// Draws the target positions of `SWAPS` swaps from a single random word and prefetches them.
+fn draw_and_prefetch<R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>(
+    rng: &mut R,
+    staged: &mut [usize; N],
+    targets: &mut [&mut [T]; N],
+) -> [(usize, usize); SWAPS] {
+    let mask = (1usize << LOG_N) - 1;
+    let rand: u64 = rng.gen();
+
+    std::array::from_fn(|k| {
+        let bucket = (rand >> (k * LOG_N)) as usize & mask;
+        let pos = staged[bucket];
+        staged[bucket] += 1;
+
+        prefetch_write_data(&mut targets[bucket][pos]);
+        (bucket, pos)
+    })
+}
+
+/// Two elements that are exchanged alternately with the targets of the swaps.
+struct Stash<'a, T> {
+    lanes: &'a mut [T],
+}
+
+impl<T> Stash<'_, T> {
+    fn swap<const L: usize>(&mut self, elem: &mut T) {
+        swap_refs(&mut self.lanes[L], elem);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::rough_shuffle;
+    use crate::bucketing::Buckets;
+    use crate::rough_shuffle::{common_tests, naive, DEFAULT_HANDOVER_ROUNDS};
+    use rand::Rng;
+
+    fn safe<R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>(
+        rng: &mut R,
+        buckets: &mut Buckets<T, N>,
+    ) {
+        rough_shuffle::<R, T, LOG_N, N, SWAPS>(rng, buckets, DEFAULT_HANDOVER_ROUNDS);
+        naive::rough_shuffle::<R, T, LOG_N, N, SWAPS>(rng, buckets);
+    }
+
+    common_tests::rough_shuffle_tests!(safe);
+}
@@ -12,8 +12,9 @@ use crate::prefetch::*;
 pub(super) fn rough_shuffle<R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>(
     rng: &mut R,
     buckets: &mut Buckets<T, N>,
+    handover_rounds: usize,
 ) {
-    RoughShuffle::<R, T, LOG_N, N, SWAPS>::new(buckets).rough_shuffle(rng)
+    RoughShuffle::<R, T, LOG_N, N, SWAPS>::new(buckets).rough_shuffle(rng, handover_rounds)
 }
 
 pub struct RoughShuffle<'a, 'b, R, T, const LOG_N: usize, const N: usize, const SWAPS: usize> {
@@ -34,10 +35,10 @@ impl<'a, 'b, R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>
         }
     }
 
-    fn rough_shuffle(&mut self, rng: &mut R) {
+    fn rough_shuffle(&mut self, rng: &mut R, handover_rounds: usize) {
         loop {
             let rounds = self.first_staged.length_of_shortest_bucket() / 2 / SWAPS;
-            if rounds <= 1 {
+            if rounds <= handover_rounds.max(1) {
                 break;
             }
 
@@ -117,6 +118,8 @@ impl<T> Stash<T> {
         self.read_idx = write_idx;
     }
 
+    // We do not use non-temporal stores for the write into `elem`: it was just read, so
+    // its cache line is present anyway, and a streaming store would only force its eviction.
     fn swap_assume_read_from<const N: usize>(&mut self, elem: &mut T) {
         debug_assert_eq!(self.read_idx, N);
         unsafe {
@@ -185,7 +188,32 @@ impl<T, const NUM_BUCKETS: usize> BlockBasePointers<T, NUM_BUCKETS> {
 
 #[cfg(test)]
 mod test {
-    use super::{common_tests, rough_shuffle};
+    use super::rough_shuffle;
+    use crate::bucketing::Buckets;
+    use crate::rough_shuffle::{common_tests, DEFAULT_HANDOVER_ROUNDS};
+    use rand::Rng;
+
+    fn default_handover<R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>(
+        rng: &mut R,
+        buckets: &mut Buckets<T, N>,
+    ) {
+        rough_shuffle::<R, T, LOG_N, N, SWAPS>(rng, buckets, DEFAULT_HANDOVER_ROUNDS)
+    }
+
+    fn early_handover<R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>(
+        rng: &mut R,
+        buckets: &mut Buckets<T, N>,
+    ) {
+        rough_shuffle::<R, T, LOG_N, N, SWAPS>(rng, buckets, 16)
+    }
 
-    common_tests::rough_shuffle_tests!(rough_shuffle);
+    mod default {
+        use super::{common_tests, default_handover};
+        common_tests::rough_shuffle_tests!(default_handover);
+    }
+
+    mod early {
+        use super::{common_tests, early_handover};
+        common_tests::rough_shuffle_tests!(early_handover);
+    }
 }
@@ -0,0 +1,339 @@
+//! Rough shuffle with software write-combining (similar to the block buffers of IPS4o).
+//!
+//! Instead of swapping every element into the first unprocessed slot of a random bucket,
+//! the elements are staged in small per-bucket buffers that stay in the L1 cache. Once a
+//! buffer is full, it is flushed as a single block, so each bucket is written in
+//! contiguous chunks rather than with one random cache line access per element. The
+//! slots overwritten by a flush still contain unclassified elements; they are moved onto
+//! a pending stack beforehand and are the next ones to be classified.
+//!
+//! Each classified element is assigned to an independent and uniform bucket, and the
+//! algorithm stops as soon as an element is assigned to a bucket without free slots.
+//! Hence, as in the other implementations, the processed elements are the prefix of an
+//! i.i.d. labelled sequence and the remaining elements are left unprocessed.
+
+use std::mem::MaybeUninit;
+use std::ptr::copy_nonoverlapping;
+
+use super::*;
+
+/// Number of elements staged per bucket before they are written back.
+const BLOCK_SIZE: usize = 16;
+
+/// Upper bound on the stack space used for the buffers and the pending stack.
+const MAX_BUFFER_BYTES: usize = 1 << 16;
+
+/// Returns whether the staging buffers for `N` buckets of `T` fit into [`MAX_BUFFER_BYTES`].
+pub(super) fn is_applicable<T, const N: usize>() -> bool {
+    let size = std::mem::size_of::<T>();
+    size > 0 && 2 * N * BLOCK_SIZE * size <= MAX_BUFFER_BYTES
+}
+
+pub(super) fn rough_shuffle<R: Rng, T, const LOG_N: usize, const N: usize, const SWAPS: usize>(
+    rng: &mut R,
+    buckets: &mut Buckets<T, N>,
+) {
+    if !is_applicable::<T, N>() {
+        return;
+    }
+
+    WriteCombining::<T, N, BLOCK_SIZE>::new(buckets).rough_shuffle::<R, LOG_N>(rng)
+}
+
+struct WriteCombining<'a, 'b, T, const N: usize, const K: usize> {
+    buckets: &'a mut Buckets<'b, T, N>,
+
+    // slots in `begin..written` are processed, `written..read` are holes whose elements
+    // are either pending or staged, and `read..end` are untouched
+    begin: [*mut T; N],
+    written: [*mut T; N],
+    read: [*mut T; N],
+    end: [*mut T; N],
+
+    // number of elements that can still be assigned to a bucket
+    room: [usize; N],
+
+    staged: [[MaybeUninit<T>; K]; N],
+    num_staged: [usize; N],
+
+    // at most `N * K` elements are in flight, i.e. pending or staged
+    pending: [[MaybeUninit<T>; K]; N],
+    num_pending: usize,
+
+    next_refill: usize,
+}
+
+impl<'a, 'b, T, const N: usize, const K: usize> WriteCombining<'a, 'b, T, N, K> {
+    fn new(buckets: &'a mut Buckets<'b, T, N>) -> Self {
+        let mut begin = [std::ptr::null_mut(); N];
+        let mut written = [std::ptr::null_mut(); N];
+        let mut end = [std::ptr::null_mut(); N];
+        let mut room = [0; N];
+
+        for (i, bucket) in buckets.iter_mut().enumerate() {
+            let num_processed = bucket.num_processed();
+            room[i] = bucket.num_unprocessed();
+
+            let range = bucket.data_mut().as_mut_ptr_range();
+            begin[i] = range.start;
+            written[i] = unsafe { range.start.add(num_processed) };
+            end[i] = range.end;
+        }
+
+        Self {
+            buckets,
+            begin,
+            written,
+            read: written,
+            end,
+            room,
+            staged: [const { [const { MaybeUninit::uninit() }; K] }; N],
+            num_staged: [0; N],
+            pending: [const { [const { MaybeUninit::uninit() }; K] }; N],
+            num_pending: 0,
+            next_refill: 0,
+        }
+    }
+
+    fn rough_shuffle<R: Rng, const LOG_N: usize>(mut self, rng: &mut R) {
+        const { assert!(1 << LOG_N == N) };
+
+        // like the other rough shuffles, stop as soon as a bucket is full. Drawing a
+        // bucket for another element and discarding it if the bucket is full would make
+        // the elements of the stashes less likely to end up in full buckets.
+        if self.room.contains(&0) {
+            return;
+        }
+
+        let mut rand = 0u64;
+        let mut bits_left = 0;
+
+        loop {
+            if self.num_pending == 0 && !self.refill() {
+                return;
+            }
+
+            // the element is only moved once its bucket is known, so if `rng` panics,
+            // dropping `self` restores a permutation of the input
+            if bits_left < LOG_N {
+                rand = rng.gen();
+                bits_left = 64;
+            }
+            let bucket = (rand as usize) & (N - 1);
+            rand >>= LOG_N;
+            bits_left -= LOG_N;
+
+            self.room[bucket] -= 1;
+
+            self.num_pending -= 1;
+            let num_staged = self.num_staged[bucket];
+            unsafe {
+                copy_nonoverlapping(
+                    self.pending_ptr().add(self.num_pending),
+                    self.staged[bucket][num_staged].as_mut_ptr(),
+                    1,
+                );
+            }
+            self.num_staged[bucket] = num_staged + 1;
+
+            if self.room[bucket] == 0 {
+                return;
+            }
+
+            if num_staged + 1 == K {
+                self.flush(bucket);
+            }
+        }
+    }
+
+    fn pending_ptr(&mut self) -> *mut T {
+        self.pending.as_mut_ptr().cast()
+    }
+
+    /// Moves up to `K` untouched elements onto the pending stack; returns false if there
+    /// are none left.
+    fn refill(&mut self) -> bool {
+        debug_assert_eq!(self.num_pending, 0);
+
+        for _ in 0..N {
+            let bucket = self.next_refill;
+            self.next_refill = (self.next_refill + 1) % N;
+
+            let num = (unsafe { self.end[bucket].offset_from(self.read[bucket]) } as usize).min(K);
+            if num == 0 {
+                continue;
+            }
+
+            unsafe {
+                copy_nonoverlapping(self.read[bucket], self.pending_ptr(), num);
+                self.read[bucket] = self.read[bucket].add(num);
+            }
+            self.num_pending = num;
+            return true;
+        }
+
+        false
+    }
+
+    /// Writes the staged elements of `bucket` into its next slots. Untouched elements in
+    /// these slots are moved onto the pending stack first.
+    fn flush(&mut self, bucket: usize) {
+        let num = self.num_staged[bucket];
+        let target = self.written[bucket];
+
+        if num == K && target == self.read[bucket] && self.num_pending + K <= N * K {
+            // common case: swap a full block with untouched elements; constant-size copies
+            // are inlined by the compiler
+            unsafe {
+                copy_nonoverlapping(target, self.pending_ptr().add(self.num_pending), K);
+                copy_nonoverlapping(self.staged[bucket].as_ptr().cast(), target, K);
+                self.written[bucket] = target.add(K);
+            }
+            self.read[bucket] = self.written[bucket];
+            self.num_pending += K;
+            self.num_staged[bucket] = 0;
+            return;
+        }
+
+        unsafe {
+            let target_end = target.add(num);
+            debug_assert!(target_end <= self.end[bucket]);
+
+            if target_end > self.read[bucket] {
+                let first_untouched = target.max(self.read[bucket]);
+                let num_untouched = target_end.offset_from(first_untouched) as usize;
+                debug_assert!(self.num_pending + num_untouched <= N * K);
+
+                copy_nonoverlapping(
+                    first_untouched,
+                    self.pending_ptr().add(self.num_pending),
+                    num_untouched,
+                );
+                self.num_pending += num_untouched;
+                self.read[bucket] = target_end;
+            }
+
+            copy_nonoverlapping(self.staged[bucket].as_ptr().cast(), target, num);
+            self.written[bucket] = target_end;
+        }
+
+        self.num_staged[bucket] = 0;
+    }
+}
+
+impl<T, const N: usize, const K: usize> Drop for WriteCombining<'_, '_, T, N, K> {
+    fn drop(&mut self) {
+        for bucket in 0..N {
+            if self.num_staged[bucket] > 0 {
+                self.flush(bucket);
+            }
+        }
+
+        // every pending element was read from a hole; put them back as unprocessed elements
+        for bucket in 0..N {
+            let num_holes = unsafe { self.read[bucket].offset_from(self.written[bucket]) } as usize;
+            debug_assert!(num_holes <= self.num_pending);
+
+            self.num_pending -= num_holes;
+            unsafe {
+                copy_nonoverlapping(
+                    self.pending_ptr().add(self.num_pending),
+                    self.written[bucket],
+                    num_holes,
+                );
+            }
+            self.read[bucket] = self.written[bucket];
+        }
+        debug_assert_eq!(self.num_pending, 0);
+
+        for (i, bucket) in self.buckets.iter_mut().enumerate() {
+            let num_processed = unsafe { self.written[i].offset_from(self.begin[i]) } as usize;
+            bucket.set_num_processed(num_processed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{common_tests, rough_shuffle};
+
+    common_tests::rough_shuffle_tests!(rough_shuffle);
+
+    mod scatter_shuffle {
+        use crate::bucketing::*;
+        use crate::fisher_yates::naive::fisher_yates;
+        use crate::scatter_shuffle::sequential::*;
+        use rand::Rng;
+
+        // the elements within a bucket are not in uniform order after the rough shuffle,
+        // so uniformity is tested after the remaining steps of the scatter shuffle
+        fn scatter_shuffle_with<
+            R: Rng,
+            T,
+            const LOG_NUM_BUCKETS: usize,
+            const NUM_BUCKETS: usize,
+        >(
+            rng: &mut R,
+            data: &mut [T],
+        ) {
+            if data.len() < 4 * NUM_BUCKETS {
+                return fisher_yates(rng, data);
+            }
+
+            let mut buckets = split_slice_into_equally_sized_buckets::<T, NUM_BUCKETS>(data);
+            super::rough_shuffle::<R, T, LOG_NUM_BUCKETS, NUM_BUCKETS, 32>(rng, &mut buckets);
+
+            let num_unprocessed = buckets.total_unprocessed();
+            let target_lengths = sample_final_bucket_size(rng, num_unprocessed, &buckets);
+            move_buckets_to_fit_target_len(&mut buckets, &target_lengths);
+            shuffle_stashes(
+                rng,
+                &mut buckets,
+                scatter_shuffle_with::<R, T, LOG_NUM_BUCKETS, NUM_BUCKETS>,
+            );
+
+            for bucket in &mut buckets {
+                fisher_yates(rng, bucket.data_mut());
+            }
+        }
+
+        fn write_combining_scatter_shuffle<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            scatter_shuffle_with::<R, T, 2, 4>(rng, data)
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(write_combining_scatter_shuffle);
+
+        /// The inputs above are too small to fill a whole block of [`super::super::BLOCK_SIZE`]
+        /// elements. Here, each of the eight buckets holds three blocks; we count how often
+        /// each element ends up in each eighth of the output.
+        #[test]
+        fn full_blocks_are_uniform() {
+            use rand::SeedableRng;
+            use rand_pcg::Pcg64Mcg;
+
+            const NUM_BINS: usize = 8;
+            const N: usize = 8 * 3 * super::super::BLOCK_SIZE;
+            const RUNS: usize = 20_000;
+
+            let mut rng = Pcg64Mcg::seed_from_u64(1234);
+            let mut counts = vec![[0usize; NUM_BINS]; N];
+            for _ in 0..RUNS {
+                let mut data: Vec<usize> = (0..N).collect();
+                scatter_shuffle_with::<_, _, 3, 8>(&mut rng, &mut data);
+                for (pos, &x) in data.iter().enumerate() {
+                    counts[x][pos * NUM_BINS / N] += 1;
+                }
+            }
+
+            // the expected count is 2500 with a standard deviation of about 47
+            for (x, bins) in counts.iter().enumerate() {
+                for (bin, &count) in bins.iter().enumerate() {
+                    assert!(
+                        count.abs_diff(RUNS / NUM_BINS) < RUNS / NUM_BINS / 10,
+                        "x={x} bin={bin} count={count}"
+                    );
+                }
+            }
+        }
+    }
+}
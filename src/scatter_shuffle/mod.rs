@@ -1,4 +1,7 @@
-use rand::Rng;
+use crate::error::ShuffleError;
+use crate::rng_strategy::{IndexRngTask, RngStrategy, SameRng};
+use crate::rough_shuffle::DEFAULT_HANDOVER_ROUNDS;
+use rand::{Rng, SeedableRng};
 
 pub mod parallel;
 pub mod sequential;
@@ -9,6 +12,22 @@ pub trait SeqConfiguration: Clone {
     fn seq_disable_recursion(&self) -> bool {
         false
     }
+
+    /// The unsafe rough shuffle hands over to the naive one once a batch would execute
+    /// at most this many rounds. Also applies to the parallel scatter shuffle.
+    fn rough_shuffle_handover_rounds(&self) -> usize {
+        DEFAULT_HANDOVER_ROUNDS
+    }
+
+    /// Checks the configuration for settings that cannot work, e.g., a base case
+    /// size of zero, which would make the recursion diverge.
+    fn seq_validate(&self) -> Result<(), ShuffleError> {
+        if self.seq_base_case_size() == 0 {
+            return Err(ShuffleError::ZeroBaseCaseSize);
+        }
+
+        Ok(())
+    }
 }
 
 pub trait ParConfiguration: Send + Sync + SeqConfiguration {
@@ -19,6 +38,42 @@ pub trait ParConfiguration: Send + Sync + SeqConfiguration {
         false
     }
 
+    /// Relative amount of work assigned to the `index`-th of `num_subproblems` tasks
+    /// (see [`ParConfiguration::par_number_of_subproblems`]). The rough shuffle and the
+    /// recursion split their input in proportion to these weights, so heterogeneous
+    /// cores (see [`crate::weighted`]) can receive subproblems of different sizes. Weights
+    /// need to be positive; by default, all tasks receive the same share.
+    fn par_subproblem_weight(&self, index: usize, num_subproblems: usize) -> usize {
+        let _ = (index, num_subproblems);
+        1
+    }
+
+    /// Parallel counterpart of [`SeqConfiguration::seq_validate`]. Additionally
+    /// requires that inputs beyond the base case are split into at least one subproblem.
+    fn par_validate(&self) -> Result<(), ShuffleError> {
+        let base_case_size = self.par_base_case_size();
+        if base_case_size == 0 {
+            return Err(ShuffleError::ZeroBaseCaseSize);
+        }
+
+        if self.par_number_of_subproblems(base_case_size.saturating_add(1)) == 0 {
+            return Err(ShuffleError::ZeroSubproblems);
+        }
+
+        Ok(())
+    }
+
+    /// Runs a per-element phase (a leaf of the rough shuffle or a base case) of the
+    /// parallel scatter shuffle. By default, `rng` is used directly; see
+    /// [`crate::rng_strategy`] for deriving a different generator.
+    fn run_per_element<R, Task>(&self, rng: &mut R, task: Task) -> Task::Output
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        Task: IndexRngTask,
+    {
+        SameRng.run_per_element(rng, task)
+    }
+
     type Profiler: Profiler;
     fn get_profiler(&self) -> &Self::Profiler;
 }
@@ -52,4 +107,122 @@ macro_rules! implement_seq_config {
 
 pub use implement_seq_config;
 
+/// Implements [`ParConfiguration`] for `$config` (which also needs to implement
+/// [`SeqConfiguration`], e.g., via [`implement_seq_config!`]). The base cases of
+/// (at most) `$size` elements are shuffled with `$base_algo`, and the number of
+/// subproblems is either `default` (see
+/// [`parallel::default_number_of_subproblems`]) or given by a function or a
+/// non-capturing closure mapping the number of elements to the number of
+/// subproblems. An optional trailing `field: Type` selects a profiler stored in a
+/// field of `$config`; otherwise, no profiler is used.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::scatter_shuffle::parallel::ParScatterShuffleImpl;
+/// use rip_shuffle::{implement_par_config, implement_seq_config};
+/// use rip_shuffle::prelude::{fisher_yates, ParConfiguration, SeqConfiguration};
+/// use rand::{Rng, SeedableRng};
+///
+/// #[derive(Clone, Copy, Default)]
+/// struct SmallBaseCases;
+///
+/// implement_seq_config!(SmallBaseCases, fisher_yates, 1 << 10);
+/// implement_par_config!(SmallBaseCases, fisher_yates, 1 << 12, |n| (n >> 12).clamp(1, 64));
+///
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1);
+/// let mut data: Vec<u64> = (0..1 << 16).collect();
+/// ParScatterShuffleImpl::<_, _, SmallBaseCases, 64>::default().shuffle(&mut rng, &mut data);
+/// ```
+#[macro_export]
+macro_rules! implement_par_config {
+    (@profiler) => {
+        $crate::implement_no_profiler!();
+    };
+
+    (@profiler $field : ident : $profiler : ty) => {
+        type Profiler = $profiler;
+        fn get_profiler(&self) -> &Self::Profiler {
+            &self.$field
+        }
+    };
+
+    (@impl $config : ty, $base_algo : path, $size : expr, $subproblems : expr $(, $field : ident : $profiler : ty)?) => {
+        impl ParConfiguration for $config {
+            fn par_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+                $base_algo(rng, data)
+            }
+
+            fn par_base_case_size(&self) -> usize {
+                $size
+            }
+
+            fn par_number_of_subproblems(&self, n: usize) -> usize {
+                let subproblems: fn(usize) -> usize = $subproblems;
+                subproblems(n)
+            }
+
+            $crate::implement_par_config!(@profiler $($field : $profiler)?);
+        }
+    };
+
+    ($config : ty, $base_algo : path, $size : expr, default $(, $field : ident : $profiler : ty)?) => {
+        $crate::implement_par_config!(@impl $config, $base_algo, $size, |n| {
+            $crate::scatter_shuffle::parallel::default_number_of_subproblems(
+                n,
+                $size,
+                $crate::scatter_shuffle::parallel::DEFAULT_MAX_SUBPROBLEMS,
+            )
+        } $(, $field : $profiler)?);
+    };
+
+    ($config : ty, $base_algo : path, $size : expr, $subproblems : expr $(, $field : ident : $profiler : ty)?) => {
+        $crate::implement_par_config!(@impl $config, $base_algo, $size, $subproblems $(, $field : $profiler)?);
+    };
+}
+
+pub use implement_par_config;
+
 use crate::profiler::Profiler;
+
+#[cfg(test)]
+mod test {
+    use super::parallel::{default_number_of_subproblems, DEFAULT_MAX_SUBPROBLEMS};
+    use super::*;
+    use crate::fisher_yates::fisher_yates;
+    use crate::profiler::par_profile::ParProfiler;
+
+    #[derive(Clone, Default)]
+    struct Closure;
+
+    implement_seq_config!(Closure, fisher_yates, 2);
+    implement_par_config!(Closure, fisher_yates, 3, |n| n / 10);
+
+    #[derive(Clone, Default)]
+    struct Profiled {
+        profiler: ParProfiler,
+    }
+
+    implement_seq_config!(Profiled, fisher_yates, 2);
+    implement_par_config!(Profiled, fisher_yates, 16, default, profiler: ParProfiler);
+
+    #[test]
+    fn implements_par_config() {
+        assert_eq!(Closure.par_base_case_size(), 3);
+        assert_eq!(Closure.par_number_of_subproblems(100), 10);
+
+        let config = Profiled::default();
+        assert_eq!(config.par_base_case_size(), 16);
+        for n in [0, 100, 1 << 20] {
+            assert_eq!(
+                config.par_number_of_subproblems(n),
+                default_number_of_subproblems(n, 16, DEFAULT_MAX_SUBPROBLEMS)
+            );
+        }
+        assert!(std::ptr::eq(config.get_profiler(), &config.profiler));
+
+        let mut data: Vec<u32> = (0..10).collect();
+        config.par_base_case_shuffle(&mut rand_pcg::Pcg64Mcg::seed_from_u64(1), &mut data);
+        data.sort();
+        assert!(data.iter().copied().eq(0..10));
+    }
+}
@@ -2,15 +2,39 @@ use std::marker::PhantomData;
 
 use super::*;
 use crate::bucketing::*;
+use crate::error::ShuffleError;
 use crate::prelude::fisher_yates;
-use crate::profiler::ProfilerFrame;
+use crate::profiler::{Profiler, ProfilerFrame};
+use crate::rng_strategy::IndexRngTask;
 use crate::rough_shuffle::*;
 
 use rand::Rng;
 use rand::SeedableRng;
+use rayon::prelude::*;
+
+/// Inputs of at most this many bytes are shuffled sequentially by [`par_scatter_shuffle`]
+pub const SEQUENTIAL_MAX_BYTES: usize = 1 << 23;
+
+/// Number of buckets used by [`par_scatter_shuffle`] depending on the input size: an
+/// input of `num_bytes` bytes uses the buckets of the first tier `(max_bytes, buckets)`
+/// with `num_bytes < max_bytes`. More buckets reduce the number of recursion levels
+/// (and hence passes over memory) for very large inputs. See [`num_buckets_for_bytes`].
+pub const BUCKET_TIERS: [(u64, usize); 4] = [
+    (1 << 27, 64),    // up to 128 MiB
+    (1 << 34, 256),   // up to 16 GiB
+    (1 << 36, 512),   // up to 64 GiB
+    (u64::MAX, 1024), // beyond
+];
+
+pub(crate) const DEFAULT_BASE_CASE_SIZE: usize = 1 << 18;
+
+/// Width of the bucket diagrams passed to [`crate::profiler::Profiler::bucket_layout`].
+const DUMP_WIDTH: usize = 64;
+/// Default upper bound of [`default_number_of_subproblems`].
+pub const DEFAULT_MAX_SUBPROBLEMS: usize = 2040;
 
 #[derive(Clone, Copy, Default)]
-struct DefaultConfiguration {}
+pub(crate) struct DefaultConfiguration {}
 
 implement_seq_config!(DefaultConfiguration, fisher_yates, 1 << 16); // not relevant, as we do not use SeqScatterShuffle
 
@@ -20,41 +44,233 @@ impl ParConfiguration for DefaultConfiguration {
     }
 
     fn par_base_case_size(&self) -> usize {
-        1 << 18
+        DEFAULT_BASE_CASE_SIZE
     }
 
     fn par_number_of_subproblems(&self, n: usize) -> usize {
-        (n / self.par_base_case_size()).clamp(1, 2040)
+        default_number_of_subproblems(n, self.par_base_case_size(), DEFAULT_MAX_SUBPROBLEMS)
     }
 
+    #[cfg(not(feature = "metrics"))]
     implement_no_profiler!();
+
+    #[cfg(feature = "metrics")]
+    type Profiler = crate::telemetry::MetricsProfiler;
+
+    #[cfg(feature = "metrics")]
+    fn get_profiler(&self) -> &Self::Profiler {
+        &crate::telemetry::MetricsProfiler
+    }
 }
 
-pub fn par_scatter_shuffle<R: Rng + SeedableRng + Send + Sync, T: Send + Sync + Sized>(
+/// Default of [`ParConfiguration::par_number_of_subproblems`]: one subproblem per
+/// thread of the current rayon pool, but at most `max_subproblems` and only as many
+/// as there are base cases in an input of `n` elements. Small pools hence do not pay
+/// for splitting that cannot be exploited, while large pools keep all threads busy.
+pub fn default_number_of_subproblems(
+    n: usize,
+    base_case_size: usize,
+    max_subproblems: usize,
+) -> usize {
+    (n / base_case_size)
+        .min(rayon::current_num_threads())
+        .clamp(1, max_subproblems.max(1))
+}
+
+/// Returns the number of buckets [`par_scatter_shuffle`] uses for an input of
+/// `num_bytes` bytes according to [`BUCKET_TIERS`], or `None` if the input is small
+/// enough to be shuffled sequentially (see [`SEQUENTIAL_MAX_BYTES`]).
+///
+/// # Example
+/// ```
+/// use rip_shuffle::scatter_shuffle::parallel::num_buckets_for_bytes;
+///
+/// assert_eq!(num_buckets_for_bytes(1 << 20), None);
+/// assert_eq!(num_buckets_for_bytes(1 << 26), Some(64));
+/// assert_eq!(num_buckets_for_bytes(1 << 30), Some(256));
+/// ```
+pub fn num_buckets_for_bytes(num_bytes: usize) -> Option<usize> {
+    if num_bytes <= SEQUENTIAL_MAX_BYTES {
+        return None;
+    }
+
+    let (_, num_buckets) = BUCKET_TIERS
+        .iter()
+        .find(|&&(max_bytes, _)| (num_bytes as u64) < max_bytes)
+        .unwrap_or(&BUCKET_TIERS[BUCKET_TIERS.len() - 1]);
+
+    Some(*num_buckets)
+}
+
+pub fn par_scatter_shuffle<R: Rng + SeedableRng + Send + Sync, T: Send + Sized>(
     rng: &mut R,
     data: &mut [T],
 ) {
-    let num_bytes = data.len() * std::mem::size_of::<T>();
+    if crate::oracle::is_enabled() {
+        return crate::fisher_yates::naive::fisher_yates(rng, data);
+    }
 
-    if num_bytes <= (1 << 23) {
-        return fisher_yates(rng, data);
+    let config = DefaultConfiguration::default();
+    match num_buckets_for_bytes(std::mem::size_of_val(data)) {
+        None => fisher_yates(rng, data),
+        Some(64) => ParScatterShuffleImpl::<R, T, _, 64>::new(config).shuffle(rng, data),
+        Some(256) => ParScatterShuffleImpl::<R, T, _, 256>::new(config).shuffle(rng, data),
+        Some(512) => ParScatterShuffleImpl::<R, T, _, 512>::new(config).shuffle(rng, data),
+        Some(_) => ParScatterShuffleImpl::<R, T, _, 1024>::new(config).shuffle(rng, data),
     }
+}
 
-    if num_bytes < (1 << 27) {
-        const NUM_BUCKETS: usize = 64;
-        let algo = ParScatterShuffleImpl::<R, T, DefaultConfiguration, NUM_BUCKETS>::default();
-        algo.shuffle(rng, data);
-    } else {
-        const NUM_BUCKETS: usize = 256;
-        let algo = ParScatterShuffleImpl::<R, T, DefaultConfiguration, NUM_BUCKETS>::default();
-        algo.shuffle(rng, data);
+/// Shuffles `data` in parallel and yields the shuffled chunks as a parallel iterator.
+/// The input is first partitioned into random subproblems of roughly base-case size
+/// (touching each element a few times); each chunk is then shuffled only when the
+/// iterator reaches it. Hence, downstream processing of a chunk is fused with its
+/// final shuffle and finds the chunk still in cache, instead of requiring a second
+/// pass over memory. The concatenation of all chunks (in the order of
+/// [`rayon::iter::IndexedParallelIterator`]) is a uniform random permutation of the input.
+///
+/// The iterator needs to be driven to completion for the whole input to be shuffled.
+/// The permutation differs from the one of [`par_scatter_shuffle`] for the same `rng`.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::scatter_shuffle::parallel::par_shuffled_chunks;
+/// use rayon::prelude::*;
+/// use rand::SeedableRng;
+///
+/// let mut data: Vec<u64> = (0..1_000_000).collect();
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+///
+/// let sum: u64 = par_shuffled_chunks(&mut rng, &mut data)
+///     .map(|chunk| chunk.iter().sum::<u64>())
+///     .sum();
+///
+/// assert_eq!(sum, 999_999 * 1_000_000 / 2);
+/// ```
+pub fn par_shuffled_chunks<'a, R, T>(
+    rng: &mut R,
+    data: &'a mut [T],
+) -> impl IndexedParallelIterator<Item = &'a mut [T]>
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send + Sized,
+{
+    let config = DefaultConfiguration::default();
+
+    default_base_cases(rng, data)
+        .into_par_iter()
+        .map(move |(chunk, rng)| {
+            if let Some(mut rng) = rng {
+                config.par_base_case_shuffle(&mut rng, chunk);
+            }
+            chunk
+        })
+}
+
+/// Shuffles `data` in parallel and invokes `callback` on each part of the output as
+/// soon as it reached its final position and order. Hence, downstream processing (e.g.,
+/// compression or writing to disk) overlaps with the remainder of the shuffle. The
+/// parts cover `data` without overlap, but are passed in unspecified order; the
+/// callback is never invoked concurrently.
+///
+/// The shuffle coincides with the one of [`par_shuffled_chunks`] for the same `rng`.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::scatter_shuffle::parallel::par_scatter_shuffle_with_callback;
+/// use rand::SeedableRng;
+///
+/// let mut data: Vec<u64> = (0..1_000_000).collect();
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+///
+/// let mut num_processed = 0;
+/// par_scatter_shuffle_with_callback(&mut rng, &mut data, |part| num_processed += part.len());
+///
+/// assert_eq!(num_processed, 1_000_000);
+/// ```
+pub fn par_scatter_shuffle_with_callback<R, T, F>(rng: &mut R, data: &mut [T], callback: F)
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send + Sized,
+    F: FnMut(&mut [T]) + Send,
+{
+    let callback = std::sync::Mutex::new(callback);
+    par_shuffled_chunks(rng, data).for_each(|chunk| (callback.lock().unwrap())(chunk));
+}
+
+/// Shuffles `data` as part of the caller's [`rayon::Scope`]. The calling thread
+/// partitions the input into random subproblems (which may use [`rayon::join`] on the
+/// thread pool the scope belongs to); the final shuffle of each subproblem is then
+/// spawned into `scope`. Hence, the input is only shuffled once the scope ends, and
+/// the shuffle composes with other tasks of the scope instead of blocking on them.
+/// The result is a uniform random permutation, but differs from the one of
+/// [`par_scatter_shuffle`] for the same `rng`.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::scatter_shuffle::parallel::par_scatter_shuffle_in_scope;
+/// use rand::SeedableRng;
+///
+/// let mut left: Vec<u64> = (0..1_000_000).collect();
+/// let mut right = left.clone();
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+///
+/// let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+/// pool.in_place_scope(|scope| {
+///     par_scatter_shuffle_in_scope(scope, &mut rng, &mut left);
+///     par_scatter_shuffle_in_scope(scope, &mut rng, &mut right);
+/// });
+///
+/// assert_ne!(left, right);
+/// ```
+pub fn par_scatter_shuffle_in_scope<'scope, R, T>(
+    scope: &rayon::Scope<'scope>,
+    rng: &mut R,
+    data: &'scope mut [T],
+) where
+    R: Rng + SeedableRng + Send + Sync + 'scope,
+    T: Send + Sized + 'scope,
+{
+    let config = DefaultConfiguration::default();
+
+    for (chunk, rng) in default_base_cases(rng, data) {
+        if let Some(mut rng) = rng {
+            scope.spawn(move |_| config.par_base_case_shuffle(&mut rng, chunk));
+        }
     }
 }
 
+/// Partitions `data` with the default configuration into subproblems that still need
+/// to be shuffled with their random number generator. In oracle mode, `data` is
+/// shuffled right away and returned without generator.
+fn default_base_cases<'a, R, T>(rng: &mut R, data: &'a mut [T]) -> Vec<(&'a mut [T], Option<R>)>
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send + Sized,
+{
+    let config = DefaultConfiguration::default();
+
+    if crate::oracle::is_enabled() {
+        crate::fisher_yates::naive::fisher_yates(rng, data);
+        return vec![(data, None)];
+    }
+
+    // unlike `par_scatter_shuffle`, small inputs are partitioned as well
+    let base_cases = match num_buckets_for_bytes(std::mem::size_of_val(data)) {
+        None | Some(64) => {
+            ParScatterShuffleImpl::<R, T, _, 64>::new(config).into_base_cases(rng, data)
+        }
+        Some(256) => ParScatterShuffleImpl::<R, T, _, 256>::new(config).into_base_cases(rng, data),
+        Some(512) => ParScatterShuffleImpl::<R, T, _, 512>::new(config).into_base_cases(rng, data),
+        Some(_) => ParScatterShuffleImpl::<R, T, _, 1024>::new(config).into_base_cases(rng, data),
+    };
+
+    base_cases.into_iter().map(|(d, r)| (d, Some(r))).collect()
+}
+
 pub struct ParScatterShuffleImpl<R, T, C, const NUM_BUCKETS: usize> {
     config: C,
     _phantom_r: PhantomData<R>,
-    _phantom_t: PhantomData<T>,
+    _phantom_t: PhantomData<fn() -> T>,
 }
 
 impl<R, T, C, const NUM_BUCKETS: usize> Default for ParScatterShuffleImpl<R, T, C, NUM_BUCKETS>
@@ -73,7 +289,7 @@ where
 impl<R, T, C, const NUM_BUCKETS: usize> ParScatterShuffleImpl<R, T, C, NUM_BUCKETS>
 where
     R: Rng + SeedableRng + Send + Sync,
-    T: Send + Sync + Sized,
+    T: Send + Sized,
     C: ParConfiguration,
     NumberOfBuckets<NUM_BUCKETS>: IsPowerOfTwo,
 {
@@ -85,23 +301,115 @@ where
         }
     }
 
+    /// Same as [`ParScatterShuffleImpl::new`], but rejects invalid configurations
+    /// (see [`ParConfiguration::par_validate`]).
+    pub fn try_new(config: C) -> Result<Self, ShuffleError> {
+        config.par_validate()?;
+        Ok(Self::new(config))
+    }
+
     pub fn shuffle(&self, rng: &mut R, data: &mut [T]) {
-        let n = data.len();
+        let num_bytes = std::mem::size_of_val(data);
+        self.config.get_profiler().measure_throughput(
+            rayon::current_num_threads(),
+            num_bytes,
+            || self.shuffle_at_depth(rng, data, 0),
+        );
+    }
 
-        if n <= self.config.par_base_case_size() {
-            return self.config.par_base_case_shuffle(rng, data);
+    /// Same as [`ParScatterShuffleImpl::shuffle`]; `depth` is the recursion depth
+    /// reported to the profiler.
+    fn shuffle_at_depth(&self, rng: &mut R, data: &mut [T], depth: usize) {
+        if data.len() <= self.config.par_base_case_size() {
+            let config = &self.config;
+            return config.run_per_element(rng, BaseCaseTask { config, data });
         }
 
-        let mut profiler = self.config.get_profiler().start("ParScatter");
+        let num_subproblems = self.config.par_number_of_subproblems(data.len());
+        let mut buckets = self.scatter(rng, data, depth);
+
+        if !self.config.par_disable_recursion() {
+            let parts = Subproblems::new(num_subproblems);
+            self.recurse(rng, &mut buckets, parts, depth + 1);
+        }
+    }
+
+    /// Executes all phases of [`ParScatterShuffleImpl::shuffle`] except for the
+    /// shuffling of the base cases, which are returned together with an independently
+    /// seeded random number generator each. Shuffling each base case with its generator
+    /// yields a uniform permutation of `data`.
+    pub fn into_base_cases<'a>(&self, rng: &mut R, data: &'a mut [T]) -> Vec<(&'a mut [T], R)> {
+        self.base_cases_at_depth(rng, data, 0)
+    }
+
+    fn base_cases_at_depth<'a>(
+        &self,
+        rng: &mut R,
+        data: &'a mut [T],
+        depth: usize,
+    ) -> Vec<(&'a mut [T], R)> {
+        if data.len() <= self.config.par_base_case_size() || self.config.par_disable_recursion() {
+            return vec![(data, seed_new_rng(rng))];
+        }
+
+        let buckets = self.scatter(rng, data, depth);
+        self.collect_base_cases(
+            rng,
+            buckets.into_iter().map(Bucket::into_data).collect(),
+            depth + 1,
+        )
+    }
+
+    fn collect_base_cases<'a>(
+        &self,
+        rng: &mut R,
+        mut parts: Vec<&'a mut [T]>,
+        depth: usize,
+    ) -> Vec<(&'a mut [T], R)> {
+        if parts.len() == 1 {
+            return self.base_cases_at_depth(rng, parts.pop().unwrap(), depth);
+        }
+
+        let right_parts = parts.split_off(weighted_split_point(parts.iter().map(|p| p.len())));
+        let mut right_rng: R = seed_new_rng(rng);
+
+        let (mut left, right) = rayon::join(
+            || self.collect_base_cases(rng, parts, depth),
+            || self.collect_base_cases(&mut right_rng, right_parts, depth),
+        );
+
+        left.extend(right);
+        left
+    }
+
+    fn scatter<'a>(
+        &self,
+        rng: &mut R,
+        data: &'a mut [T],
+        depth: usize,
+    ) -> Buckets<'a, T, NUM_BUCKETS> {
+        let n = data.len();
+        let mut profiler = self
+            .config
+            .get_profiler()
+            .start_at_depth("ParScatter", depth);
 
         profiler.new_region("RoughScatter");
         let mut buckets = split_slice_into_equally_sized_buckets(data);
-        Self::invoke_rough_shuffle(rng, &mut buckets, self.config.par_number_of_subproblems(n));
+        self.invoke_rough_shuffle(
+            rng,
+            &mut buckets,
+            Subproblems::new(self.config.par_number_of_subproblems(n)),
+            self.config.rough_shuffle_handover_rounds(),
+        );
+        self.config
+            .get_profiler()
+            .bucket_layout("RoughScatter", depth, || buckets.dump(DUMP_WIDTH));
 
         profiler.new_region("ShuffleStashes");
         let num_unprocessed =
             sequential::shuffle_stashes(rng, &mut buckets, |r: &mut R, d: &mut [T]| {
-                self.shuffle(r, d)
+                self.shuffle_at_depth(r, d, depth + 1)
             });
 
         profiler.new_region("SampleFinalBucketSize");
@@ -109,66 +417,196 @@ where
 
         profiler.new_region("TwoSweep");
         sequential::move_buckets_to_fit_target_len(&mut buckets, &target_lengths);
+        self.config
+            .get_profiler()
+            .bucket_layout("TwoSweep", depth, || buckets.dump(DUMP_WIDTH));
 
-        drop(profiler);
-
-        if !self.config.par_disable_recursion() {
-            self.recurse(rng, &mut buckets);
-        }
+        buckets
     }
 
+    /// Rough shuffles `buckets` with one leaf task per subproblem of `parts` (by default
+    /// one per thread of the current rayon pool, see [`default_number_of_subproblems`]).
+    /// Each bucket is split in proportion to the weights of the subproblems (see
+    /// [`ParConfiguration::par_subproblem_weight`]), so the leaves carry the intended
+    /// work even if their number is not a power of two.
     fn invoke_rough_shuffle(
+        &self,
         rng: &mut R,
         buckets: &mut Buckets<T, NUM_BUCKETS>,
-        num_problems: usize,
+        parts: Subproblems,
+        handover_rounds: usize,
     ) {
-        if num_problems == 1 {
-            return rough_shuffle(rng, buckets);
+        if parts.len() <= 1 {
+            let task = RoughShuffleTask {
+                buckets,
+                handover_rounds,
+            };
+            return self.config.run_per_element(rng, task);
         }
 
+        let (left, right) = parts.split();
+        let left_weight = left.weight(&self.config);
+        let total_weight = left_weight + right.weight(&self.config);
+
         let mut right_rng: R = seed_new_rng(rng);
-        let mut right_halves = split_each_bucket_in_half(buckets);
+        let mut right_parts = split_each_bucket_into(buckets, total_weight, left_weight);
 
         rayon::join(
-            || Self::invoke_rough_shuffle(rng, buckets, num_problems / 2),
-            || {
-                Self::invoke_rough_shuffle(
-                    &mut right_rng,
-                    &mut right_halves,
-                    (num_problems + 1) / 2,
-                )
-            },
+            || self.invoke_rough_shuffle(rng, buckets, left, handover_rounds),
+            || self.invoke_rough_shuffle(&mut right_rng, &mut right_parts, right, handover_rounds),
         );
 
         buckets
             .iter_mut()
-            .zip(right_halves.iter_mut())
+            .zip(right_parts.iter_mut())
             .for_each(|(left, right)| {
                 let left_taken = std::mem::take(left);
                 let right = std::mem::take(right);
                 *left = left_taken.merge_with_right_neighbor(right)
             });
 
-        rough_shuffle(rng, buckets)
+        let task = RoughShuffleTask {
+            buckets,
+            handover_rounds,
+        };
+        self.config.run_per_element(rng, task)
     }
 
-    fn recurse(&self, rng: &mut R, buckets: &mut [Bucket<T>]) {
+    /// Shuffles each bucket recursively. While `parts` holds several subproblems, the
+    /// buckets are split in proportion to their weights; afterwards, in halves.
+    fn recurse(&self, rng: &mut R, buckets: &mut [Bucket<T>], parts: Subproblems, depth: usize) {
         if buckets.len() == 1 {
-            return self.shuffle(rng, buckets[0].data_mut());
+            return self.shuffle_at_depth(rng, buckets[0].data_mut(), depth);
         }
 
-        let (left_buckets, right_buckets) = buckets.split_at_mut(buckets.len() / 2);
+        let lengths = buckets.iter().map(|b| b.len());
+        let (mid, left_parts, right_parts) = if parts.len() <= 1 {
+            (weighted_split_point(lengths), parts, parts)
+        } else {
+            let (left, right) = parts.split();
+            let left_weight = left.weight(&self.config);
+            let total_weight = left_weight + right.weight(&self.config);
+            let mid = proportional_split_point(lengths, left_weight, total_weight);
+            (mid, left, right)
+        };
+        let (left_buckets, right_buckets) = buckets.split_at_mut(mid);
 
         let mut right_rng: R = seed_new_rng(rng);
         let left_rng = rng;
 
         rayon::join(
-            || self.recurse(left_rng, left_buckets),
-            || self.recurse(&mut right_rng, right_buckets),
+            || self.recurse(left_rng, left_buckets, left_parts, depth),
+            || self.recurse(&mut right_rng, right_buckets, right_parts, depth),
         );
     }
 }
 
+struct BaseCaseTask<'a, C, T> {
+    config: &'a C,
+    data: &'a mut [T],
+}
+
+impl<C: ParConfiguration, T> IndexRngTask for BaseCaseTask<'_, C, T> {
+    type Output = ();
+
+    fn run<G: Rng>(self, rng: &mut G) {
+        self.config.par_base_case_shuffle(rng, self.data)
+    }
+}
+
+struct RoughShuffleTask<'a, 'b, T, const NUM_BUCKETS: usize> {
+    buckets: &'a mut Buckets<'b, T, NUM_BUCKETS>,
+    handover_rounds: usize,
+}
+
+impl<T, const NUM_BUCKETS: usize> IndexRngTask for RoughShuffleTask<'_, '_, T, NUM_BUCKETS>
+where
+    NumberOfBuckets<NUM_BUCKETS>: IsPowerOfTwo,
+{
+    type Output = ();
+
+    fn run<G: Rng>(self, rng: &mut G) {
+        rough_shuffle_with_handover(rng, self.buckets, self.handover_rounds)
+    }
+}
+
+/// A range of the subproblems of a parallel scatter shuffle, i.e. the indices passed to
+/// [`ParConfiguration::par_subproblem_weight`].
+#[derive(Clone, Copy)]
+struct Subproblems {
+    begin: usize,
+    end: usize,
+    total: usize,
+}
+
+impl Subproblems {
+    fn new(total: usize) -> Self {
+        Self {
+            begin: 0,
+            end: total,
+            total,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.end - self.begin
+    }
+
+    /// Splits the range in halves (by count); requires at least two subproblems.
+    fn split(self) -> (Self, Self) {
+        let mid = self.begin + self.len() / 2;
+        (Self { end: mid, ..self }, Self { begin: mid, ..self })
+    }
+
+    fn weight<C: ParConfiguration>(&self, config: &C) -> usize {
+        (self.begin..self.end)
+            .map(|i| config.par_subproblem_weight(i, self.total))
+            .sum()
+    }
+}
+
+/// Returns the index that splits a sequence of at least two items with the given
+/// weights into two non-empty halves of (almost) equal total weight. The sizes of the
+/// buckets sampled for the recursion vary, so splitting by count could assign one task
+/// far more elements than the other.
+fn weighted_split_point(weights: impl ExactSizeIterator<Item = usize> + Clone) -> usize {
+    proportional_split_point(weights, 1, 2)
+}
+
+/// Same as [`weighted_split_point`], but the items before the split point receive
+/// (almost) a share of `left_share / total_share` of the total weight.
+fn proportional_split_point(
+    weights: impl ExactSizeIterator<Item = usize> + Clone,
+    left_share: usize,
+    total_share: usize,
+) -> usize {
+    let len = weights.len();
+    debug_assert!(len >= 2);
+    debug_assert!(left_share <= total_share && total_share > 0);
+
+    // compare `prefix / total` with `left_share / total_share` without rounding
+    let total = weights.clone().map(|w| w as u128).sum::<u128>() * left_share as u128;
+    let scale = total_share as u128;
+    let mut prefix = 0u128;
+    let mut split = len * left_share / total_share; // only kept if all weights are zero
+
+    for (i, weight) in weights.enumerate() {
+        let next = prefix + weight as u128 * scale;
+        if next > total {
+            // stop before or after the item, whichever is closer to the target
+            split = if total - prefix <= next - total {
+                i
+            } else {
+                i + 1
+            };
+            break;
+        }
+        prefix = next;
+    }
+
+    split.clamp(1, len - 1)
+}
+
 pub fn seed_new_rng<RIn: Rng, ROut: SeedableRng>(base: &mut RIn) -> ROut {
     let mut seed = ROut::Seed::default();
     base.try_fill_bytes(seed.as_mut()).unwrap();
@@ -204,10 +642,7 @@ mod integration_test {
         }
     }
 
-    pub fn inplace_scatter_shuffle_test<
-        R: Rng + SeedableRng + Send + Sync,
-        T: Send + Sync + Sized,
-    >(
+    pub fn inplace_scatter_shuffle_test<R: Rng + SeedableRng + Send + Sync, T: Send + Sized>(
         rng: &mut R,
         data: &mut [T],
     ) {
@@ -217,3 +652,291 @@ mod integration_test {
 
     crate::statistical_tests::test_shuffle_algorithm!(inplace_scatter_shuffle_test);
 }
+
+#[cfg(test)]
+mod bucket_tiers_test {
+    use super::*;
+
+    #[test]
+    fn selects_tiers() {
+        assert_eq!(num_buckets_for_bytes(0), None);
+        assert_eq!(num_buckets_for_bytes(SEQUENTIAL_MAX_BYTES), None);
+        assert_eq!(num_buckets_for_bytes(SEQUENTIAL_MAX_BYTES + 1), Some(64));
+        assert_eq!(num_buckets_for_bytes((1 << 27) - 1), Some(64));
+        assert_eq!(num_buckets_for_bytes(1 << 27), Some(256));
+
+        #[cfg(target_pointer_width = "64")]
+        {
+            assert_eq!(num_buckets_for_bytes((1 << 34) - 1), Some(256));
+            assert_eq!(num_buckets_for_bytes(1 << 34), Some(512));
+            assert_eq!(num_buckets_for_bytes(1 << 36), Some(1024));
+            assert_eq!(num_buckets_for_bytes(usize::MAX), Some(1024));
+        }
+    }
+
+    #[test]
+    fn tiers_are_sorted() {
+        assert!(BUCKET_TIERS
+            .windows(2)
+            .all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        assert_eq!(BUCKET_TIERS.last().unwrap().0, u64::MAX);
+    }
+
+    #[derive(Clone, Copy, Default)]
+    struct SmallBaseCases {}
+
+    implement_seq_config!(SmallBaseCases, fisher_yates, 2);
+    implement_par_config!(SmallBaseCases, fisher_yates, 4, |n| (n / 64).clamp(1, 4));
+
+    fn shuffle_with_many_buckets<R: Rng + SeedableRng + Send + Sync, T: Send>(
+        rng: &mut R,
+        data: &mut [T],
+    ) {
+        ParScatterShuffleImpl::<R, T, _, 512>::new(SmallBaseCases::default()).shuffle(rng, data)
+    }
+
+    crate::statistical_tests::test_shuffle_algorithm!(shuffle_with_many_buckets);
+
+    #[test]
+    fn large_input_with_many_buckets() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+        let mut data: Vec<u32> = (0..1 << 22).collect();
+
+        let algo = ParScatterShuffleImpl::<_, _, _, 1024>::new(DefaultConfiguration::default());
+        algo.shuffle(&mut rng, &mut data);
+
+        assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i as u32 == x));
+    }
+}
+
+#[cfg(test)]
+mod uneven_pool_test {
+    use super::*;
+    use std::sync::OnceLock;
+
+    #[derive(Clone, Copy, Default)]
+    struct SmallBaseCases {}
+
+    implement_seq_config!(SmallBaseCases, fisher_yates, 2);
+    implement_par_config!(SmallBaseCases, fisher_yates, 4, |n| {
+        default_number_of_subproblems(n, 16, 8)
+    });
+
+    // three threads yield a number of leaves that is not a power of two
+    fn shuffle_with_three_threads<R: Rng + SeedableRng + Send + Sync, T: Send>(
+        rng: &mut R,
+        data: &mut [T],
+    ) {
+        static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+        let pool = POOL.get_or_init(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(3)
+                .build()
+                .unwrap()
+        });
+
+        pool.install(|| {
+            ParScatterShuffleImpl::<R, T, _, 4>::new(SmallBaseCases::default()).shuffle(rng, data)
+        })
+    }
+
+    crate::statistical_tests::test_shuffle_algorithm!(shuffle_with_three_threads);
+
+    #[test]
+    fn subproblems_follow_pool_size() {
+        for num_threads in [1, 3, 8] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap();
+
+            pool.install(|| {
+                assert_eq!(
+                    default_number_of_subproblems(1 << 30, 1 << 18, 2040),
+                    num_threads
+                );
+                assert_eq!(
+                    default_number_of_subproblems(1 << 30, 1 << 18, 2),
+                    num_threads.min(2)
+                );
+                assert_eq!(
+                    default_number_of_subproblems(1 << 19, 1 << 18, 2040),
+                    num_threads.min(2)
+                );
+                assert_eq!(default_number_of_subproblems(100, 1 << 18, 2040), 1);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod weighted_split_test {
+    use super::{proportional_split_point, weighted_split_point};
+
+    #[test]
+    fn balances_weights() {
+        let split = |w: &[usize]| weighted_split_point(w.iter().copied());
+
+        assert_eq!(split(&[1, 1]), 1);
+        assert_eq!(split(&[5, 5, 5, 5]), 2);
+        assert_eq!(split(&[1, 1, 1, 9]), 3);
+        assert_eq!(split(&[9, 1, 1, 1]), 1);
+        assert_eq!(split(&[3, 1, 1, 1, 1, 1]), 2);
+        assert_eq!(split(&[0, 0, 0]), 1);
+        assert_eq!(split(&[100, 0]), 1);
+    }
+
+    #[test]
+    fn splits_by_share() {
+        let split =
+            |w: &[usize], left, total| proportional_split_point(w.iter().copied(), left, total);
+
+        assert_eq!(split(&[5, 5, 5, 5], 1, 2), 2);
+        assert_eq!(split(&[5, 5, 5, 5], 1, 4), 1);
+        assert_eq!(split(&[5, 5, 5, 5], 3, 4), 3);
+        assert_eq!(split(&[1; 12], 2, 3), 8);
+        assert_eq!(split(&[1; 12], 0, 3), 1);
+        assert_eq!(split(&[1; 12], 3, 3), 11);
+        assert_eq!(split(&[usize::MAX / 2; 4], 1, 4), 1);
+    }
+}
+
+#[cfg(test)]
+mod shuffled_chunks_test {
+    use super::*;
+
+    const NUM_BUCKETS: usize = 4;
+
+    #[derive(Clone, Copy, Default)]
+    struct SmallBaseCases {}
+
+    implement_seq_config!(SmallBaseCases, fisher_yates, 2);
+    implement_par_config!(SmallBaseCases, fisher_yates, 4, |_| 2);
+
+    fn shuffle_via_base_cases<R: Rng + SeedableRng + Send + Sync, T: Send>(
+        rng: &mut R,
+        data: &mut [T],
+    ) {
+        let config = SmallBaseCases::default();
+        let algo = ParScatterShuffleImpl::<R, T, _, NUM_BUCKETS>::new(config);
+
+        for (chunk, mut rng) in algo.into_base_cases(rng, data) {
+            assert!(chunk.len() <= config.par_base_case_size());
+            config.par_base_case_shuffle(&mut rng, chunk);
+        }
+    }
+
+    crate::statistical_tests::test_shuffle_algorithm!(shuffle_via_base_cases);
+
+    // the oracle mode shuffles sequentially and yields a single chunk
+    #[test]
+    #[cfg(not(feature = "oracle"))]
+    fn chunks_cover_input() {
+        let mut rng = Pcg64Mcg::seed_from_u64(123);
+        let n = 3_000_000;
+        let mut data: Vec<usize> = (0..n).collect();
+        let base = data.as_ptr() as usize;
+
+        let chunks: Vec<_> = par_shuffled_chunks(&mut rng, &mut data)
+            .map(|chunk| {
+                let begin = (chunk.as_ptr() as usize - base) / std::mem::size_of::<usize>();
+                begin..begin + chunk.len()
+            })
+            .collect();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.first().unwrap().start, 0);
+        assert_eq!(chunks.last().unwrap().end, n);
+        for (chunk, next) in chunks.iter().zip(chunks.iter().skip(1)) {
+            assert_eq!(chunk.end, next.start);
+        }
+
+        assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+    }
+}
+
+#[cfg(test)]
+mod in_scope_test {
+    use super::*;
+
+    fn shuffle_in_scope<R: Rng + SeedableRng + Send + Sync, T: Send>(rng: &mut R, data: &mut [T]) {
+        rayon::scope(|scope| par_scatter_shuffle_in_scope(scope, rng, data));
+    }
+
+    crate::statistical_tests::test_shuffle_algorithm!(shuffle_in_scope);
+
+    #[test]
+    fn composes_with_user_tasks() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+
+        let mut inputs: Vec<Vec<usize>> = (0..3).map(|_| (0..1 << 21).collect()).collect();
+        let mut sum = 0;
+
+        pool.in_place_scope(|scope| {
+            for data in inputs.iter_mut() {
+                par_scatter_shuffle_in_scope(scope, &mut rng, data);
+            }
+            scope.spawn(|_| sum = (0..1000).sum());
+        });
+
+        assert_eq!(sum, 499_500);
+        assert_ne!(inputs[0], inputs[1]);
+        for mut data in inputs {
+            assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+            data.sort();
+            assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+        }
+    }
+}
+
+#[cfg(test)]
+mod callback_test {
+    use super::*;
+
+    fn shuffle_with_callback<R: Rng + SeedableRng + Send + Sync, T: Send>(
+        rng: &mut R,
+        data: &mut [T],
+    ) {
+        par_scatter_shuffle_with_callback(rng, data, |_| {});
+    }
+
+    crate::statistical_tests::test_shuffle_algorithm!(shuffle_with_callback);
+
+    // the oracle mode shuffles sequentially and reports a single part
+    #[test]
+    #[cfg(not(feature = "oracle"))]
+    fn parts_are_final() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+        let n = 3_000_000;
+        let mut data: Vec<usize> = (0..n).collect();
+        let base = data.as_ptr() as usize;
+
+        let mut parts = Vec::new();
+        par_scatter_shuffle_with_callback(&mut rng, &mut data, |part| {
+            let begin = (part.as_ptr() as usize - base) / std::mem::size_of::<usize>();
+            parts.push((begin, part.to_vec()));
+        });
+
+        assert!(parts.len() > 1);
+        parts.sort_by_key(|(begin, _)| *begin);
+
+        let mut end = 0;
+        for (begin, content) in parts {
+            assert_eq!(begin, end);
+            end = begin + content.len();
+            assert_eq!(data[begin..end], content);
+        }
+        assert_eq!(end, n);
+
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+    }
+}
@@ -4,6 +4,8 @@ use std::marker::PhantomData;
 use super::*;
 use crate::bucketing::slicing::Slicing;
 use crate::bucketing::*;
+use crate::error::ShuffleError;
+use crate::fisher_yates::naive::fisher_yates as naive_fisher_yates;
 use crate::fisher_yates::noncontiguous::noncontiguous_fisher_yates;
 use crate::prelude::*;
 use crate::rough_shuffle::*;
@@ -16,14 +18,70 @@ pub const LOG_NUM_BUCKETS: usize = 7;
 pub const NUM_BUCKETS: usize = 1 << LOG_NUM_BUCKETS;
 pub const BASE_CASE_SIZE: usize = 1 << 18;
 
+/// Base case size used by [`seq_scatter_shuffle`]; tuned on x86-64. Other targets can
+/// override it with [`crate::plan::ShufflePlan::with_base_case_size`].
+pub(crate) const DEFAULT_BASE_CASE_SIZE: usize = 1 << 19;
+
 #[derive(Clone, Copy, Default)]
 struct DefaultConfiguration {}
-implement_seq_config!(DefaultConfiguration, fisher_yates, 1 << 19);
+implement_seq_config!(DefaultConfiguration, fisher_yates, DEFAULT_BASE_CASE_SIZE);
 
 pub fn seq_scatter_shuffle<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+    if crate::oracle::is_enabled() {
+        return naive_fisher_yates(rng, data);
+    }
+
     SeqScatterShuffleImpl::<R, T, DefaultConfiguration, NUM_BUCKETS>::default().shuffle(rng, data)
 }
 
+/// Splits `data` into `N` (a power of two) contiguous parts such that each element ends
+/// up in a uniformly random part. This is the partitioning phase of
+/// [`seq_scatter_shuffle`] without the recursive shuffles of the parts: shuffling
+/// each part independently (e.g., with a different executor or on another machine)
+/// yields a uniform random permutation of `data`. The order within a part is not random.
+///
+/// The lengths of the parts are random as well (they follow a multinomial
+/// distribution), but concentrate around `data.len() / N` for large inputs.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::scatter_shuffle::sequential::partition_randomly;
+/// use rip_shuffle::RipShuffleSequential;
+///
+/// let mut rng = rand::thread_rng();
+/// let mut data: Vec<_> = (0..1_000_000).collect();
+///
+/// let parts = partition_randomly::<_, _, 16>(&mut rng, &mut data);
+/// assert_eq!(parts.iter().map(|p| p.len()).sum::<usize>(), 1_000_000);
+///
+/// for part in parts {
+///     part.seq_shuffle(&mut rng);
+/// }
+/// ```
+pub fn partition_randomly<'a, R: Rng, T, const N: usize>(
+    rng: &mut R,
+    data: &'a mut [T],
+) -> ArrayVec<&'a mut [T], N>
+where
+    NumberOfBuckets<N>: IsPowerOfTwo,
+{
+    let n = data.len();
+    let mut buckets: Buckets<T, N> = split_slice_into_equally_sized_buckets(data);
+
+    // the rough shuffle needs a few elements per bucket; otherwise, all elements
+    // remain in the stashes, which are shuffled exactly below
+    if n > N {
+        rough_shuffle(rng, &mut buckets);
+    }
+
+    let num_unprocessed = buckets.total_unprocessed();
+    let target_lengths = sample_final_bucket_size(rng, num_unprocessed, &buckets);
+    move_buckets_to_fit_target_len(&mut buckets, &target_lengths);
+    shuffle_stashes(rng, &mut buckets, seq_scatter_shuffle);
+
+    buckets_into_slices(buckets)
+}
+
 pub struct SeqScatterShuffleImpl<R, T, C, const NUM_BUCKETS: usize> {
     config: C,
     _phantom_r: PhantomData<R>,
@@ -58,6 +116,13 @@ where
         }
     }
 
+    /// Same as [`SeqScatterShuffleImpl::new`], but rejects invalid configurations
+    /// (see [`SeqConfiguration::seq_validate`]).
+    pub fn try_new(config: C) -> Result<Self, ShuffleError> {
+        config.seq_validate()?;
+        Ok(Self::new(config))
+    }
+
     pub fn shuffle(&self, rng: &mut R, data: &mut [T]) {
         if data.len() <= self.config.seq_base_case_size() {
             return self.config.seq_base_case_shuffle(rng, data);
@@ -65,9 +130,13 @@ where
 
         let mut buckets = split_slice_into_equally_sized_buckets(data);
 
-        rough_shuffle(rng, &mut buckets);
+        seq_rough_shuffle_with_handover(
+            rng,
+            &mut buckets,
+            self.config.rough_shuffle_handover_rounds(),
+        );
 
-        let num_unprocessed = buckets.iter().map(|b| b.num_unprocessed()).sum();
+        let num_unprocessed = buckets.total_unprocessed();
 
         let target_lengths = sample_final_bucket_size(rng, num_unprocessed, &buckets);
         move_buckets_to_fit_target_len(&mut buckets, &target_lengths);
@@ -89,17 +158,26 @@ pub fn shuffle_stashes<R: Rng, T, const NUM_BUCKETS: usize>(
     buckets: &mut Buckets<T, NUM_BUCKETS>,
     mut recurse: impl FnMut(&mut R, &mut [T]),
 ) -> usize {
-    let stash_size = buckets.iter().map(|blk| blk.num_unprocessed()).sum();
-    if stash_size <= buckets[NUM_BUCKETS - 1].len() {
-        // typically the unprocessed items should easily fit the last bucket. Then, it's fastes
-        // to compact all stashes into a contiguous range and recurse to shuffle them
-        compact_ranges(buckets);
-        recurse(rng, buckets[NUM_BUCKETS - 1].data_mut().suffix(stash_size));
-        compact_ranges(buckets);
+    let stash_size = buckets.total_unprocessed();
+
+    // typically the unprocessed items should easily fit the last bucket. Then, it's fastest
+    // to compact all stashes into a contiguous range and recurse to shuffle them. Otherwise,
+    // the largest bucket may still be able to host them.
+    let acceptor = if stash_size <= buckets[NUM_BUCKETS - 1].len() {
+        Some(NUM_BUCKETS - 1)
+    } else {
+        Some(largest_bucket(buckets)).filter(|&i| stash_size <= buckets[i].len())
+    };
+
+    if let Some(acceptor) = acceptor {
+        compact_ranges_into(buckets, acceptor);
+        recurse(rng, buckets[acceptor].data_mut().suffix(stash_size));
+        compact_ranges_into(buckets, acceptor);
     } else {
         // however, for really small input (or astronomically unlikely cases), the number of
         // unprocessed items may be too large. It's really not worth the effort of doing something
         // clever/error-prone. We rather use the slow noncontigous Fisher Yates implementation.
+        crate::telemetry::record_fallback(crate::telemetry::Fallback::NoncontiguousStash);
         let mut unprocessed: ArrayVec<&mut [T], NUM_BUCKETS> = buckets
             .iter_mut()
             .map(|blk| blk.data_unprocessed_mut())
@@ -110,13 +188,41 @@ pub fn shuffle_stashes<R: Rng, T, const NUM_BUCKETS: usize>(
     stash_size
 }
 
+/// Returns the index of the (first) longest bucket.
+pub fn largest_bucket<T>(buckets: &[Bucket<T>]) -> usize {
+    buckets
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, b)| b.len())
+        .map_or(0, |(i, _)| i)
+}
+
+/// Same as [`compact_ranges_into`] with the last bucket as acceptor.
+///
+/// # Panics
+/// If `buckets` is empty.
 pub fn compact_ranges<T>(buckets: &mut [Bucket<T>]) -> usize {
-    let (acceptor, doners) = buckets.split_last_mut().unwrap();
+    compact_ranges_into(buckets, buckets.len() - 1)
+}
+
+/// Swaps the unprocessed elements of all buckets into a contiguous range directly in
+/// front of the unprocessed suffix of `buckets[acceptor]` and returns the length of the
+/// resulting suffix. The acceptor needs to be at least as long as the total stash.
+/// Calling the function a second time with the same arguments undoes the compaction.
+///
+/// # Panics
+/// If `acceptor` is not a valid index into `buckets`.
+pub fn compact_ranges_into<T>(buckets: &mut [Bucket<T>], acceptor: usize) -> usize {
+    let (left, rest) = buckets.split_at_mut(acceptor);
+    let (acceptor, right) = rest
+        .split_first_mut()
+        .expect("acceptor needs to be a valid bucket index");
 
     let mut num_accepted = acceptor.num_unprocessed();
     let mut space_available = acceptor.num_processed();
 
-    for bucket in doners.iter_mut().rev() {
+    for bucket in left.iter_mut().rev().chain(right.iter_mut()) {
         if bucket.num_unprocessed() == 0 {
             continue;
         }
@@ -163,7 +269,9 @@ fn shrink_sweep_to_right<T, const NUM_BUCKETS: usize>(
 
     for &target_length in &target_lengths[0..NUM_BUCKETS - 1] {
         let this_bucket;
-        (this_bucket, buckets) = buckets.split_first_mut().unwrap();
+        (this_bucket, buckets) = buckets
+            .split_first_mut()
+            .expect("one iteration per bucket but the last");
 
         let reservation_for_left = growth_needed_left.max(0) as usize;
         let target_with_reservation = target_length + reservation_for_left;
@@ -171,7 +279,8 @@ fn shrink_sweep_to_right<T, const NUM_BUCKETS: usize>(
         if this_bucket.len() > target_with_reservation {
             let num_to_move = this_bucket.len() - target_with_reservation;
 
-            this_bucket.shrink_to_right(buckets.first_mut().unwrap(), num_to_move);
+            let right_neighbor = buckets.first_mut().expect("the last bucket is not visited");
+            this_bucket.shrink_to_right(right_neighbor, num_to_move);
         }
 
         growth_needed_left += target_length as isize - this_bucket.len() as isize;
@@ -185,7 +294,9 @@ fn shrink_sweep_to_left<T, const NUM_BUCKETS: usize>(
     let mut buckets = buckets.as_mut_slice();
     for &target in target_lengths[1..].iter().rev() {
         let this_bucket;
-        (this_bucket, buckets) = buckets.split_last_mut().unwrap();
+        (this_bucket, buckets) = buckets
+            .split_last_mut()
+            .expect("one iteration per bucket but the first");
 
         if this_bucket.len() <= target {
             continue;
@@ -194,7 +305,7 @@ fn shrink_sweep_to_left<T, const NUM_BUCKETS: usize>(
         let too_long_by = this_bucket.len() - target;
         buckets
             .last_mut()
-            .unwrap()
+            .expect("the first bucket is not visited")
             .grow_from_right(this_bucket, too_long_by);
     }
 }
@@ -209,11 +320,11 @@ pub fn sample_final_bucket_size<R: Rng, T, const NUM_BUCKETS: usize>(
         num_bins: usize,
         mut num_balls: usize,
     ) -> impl Iterator<Item = usize> + '_ {
-        (0..num_bins).into_iter().map(move |i| {
+        (0..num_bins).map(move |i| {
             let remaining_bins = num_bins - i;
             let into_this_bin =
                 rand_distr::Binomial::new(num_balls as u64, 1.0 / (remaining_bins as f64))
-                    .unwrap()
+                    .expect("1 / remaining_bins is a valid probability")
                     .sample(rng) as usize;
             num_balls -= into_this_bin;
             into_this_bin
@@ -262,7 +373,7 @@ mod test {
 
                 for _ in 0..10 {
                     let buckets = generate_random_buckets::<NUM_BUCKETS>(rng, &mut data);
-                    let num_unprocessed = buckets.iter().map(|blk| blk.num_unprocessed()).sum();
+                    let num_unprocessed = buckets.total_unprocessed();
                     let target_lengths: [usize; NUM_BUCKETS] =
                         sample_final_bucket_size(rng, num_unprocessed, &buckets);
 
@@ -316,7 +427,7 @@ mod test {
             mut buckets: Buckets<usize, NUM_BUCKETS>,
             _target_lengths: [usize; NUM_BUCKETS],
         ) {
-            let num_stash: usize = buckets.iter().map(|r| r.num_unprocessed()).sum();
+            let num_stash = buckets.total_unprocessed();
             if num_stash > buckets.last().unwrap().len() {
                 return;
             }
@@ -325,10 +436,7 @@ mod test {
 
             super::compact_ranges(&mut buckets);
 
-            assert_eq!(
-                buckets.iter().map(|r| r.num_unprocessed()).sum::<usize>(),
-                num_stash
-            );
+            assert_eq!(buckets.total_unprocessed(), num_stash);
 
             assert!(buckets
                 .last()
@@ -342,7 +450,7 @@ mod test {
 
             assert_eq!(
                 sort_dedup(&data),
-                (0..=num_stash).into_iter().collect_vec(),
+                (0..=num_stash).collect_vec(),
                 "num_stash = {num_stash}"
             );
         }
@@ -350,6 +458,54 @@ mod test {
         invoke_with_random_buckets!(test_impl);
     }
 
+    mod partition_randomly {
+        use super::*;
+
+        fn shuffle_partitions<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            for part in super::super::partition_randomly::<R, T, 4>(rng, data) {
+                naive_fisher_yates(rng, part);
+            }
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_partitions);
+    }
+
+    #[test]
+    fn compact_ranges_into_largest() {
+        fn test_impl<const NUM_BUCKETS: usize>(
+            _rng: &mut impl Rng,
+            mut buckets: Buckets<usize, NUM_BUCKETS>,
+            _target_lengths: [usize; NUM_BUCKETS],
+        ) {
+            let num_stash = buckets.total_unprocessed();
+            let acceptor = super::largest_bucket(&buckets);
+            if num_stash > buckets[acceptor].len() {
+                return;
+            }
+
+            mark_unprocessed_data(&mut buckets);
+            let before = merge_data(&buckets);
+
+            assert_eq!(
+                super::compact_ranges_into(&mut buckets, acceptor),
+                num_stash
+            );
+            assert!(buckets[acceptor]
+                .data()
+                .suffix(num_stash)
+                .iter()
+                .all(|x| *x != 0));
+
+            let data = merge_data(&buckets);
+            assert_eq!(sort_dedup(&data), (0..=num_stash).collect_vec());
+
+            super::compact_ranges_into(&mut buckets, acceptor);
+            assert_eq!(merge_data(&buckets), before);
+        }
+
+        invoke_with_random_buckets!(test_impl);
+    }
+
     macro_rules! shrink_sweep_test_skeleton {
         ($sweep : ident) => {
             fn test_impl<const NUM_BUCKETS: usize>(
@@ -413,7 +569,6 @@ mod test {
         storage: &'a mut Vec<usize>,
     ) -> Buckets<'a, usize, NUM_BUCKETS> {
         let sizes = (0..NUM_BUCKETS)
-            .into_iter()
             .map(|_| (rng.gen_range(0..30), rng.gen_range(0..10)))
             .collect_vec();
         storage.resize(sizes.iter().map(|(a, b)| a + b).sum(), 0);
@@ -493,4 +648,22 @@ mod integration_test {
     }
 
     crate::statistical_tests::test_shuffle_algorithm!(inplace_scatter_shuffle_test);
+
+    #[test]
+    fn try_new_rejects_zero_base_case() {
+        #[derive(Clone, Copy, Default)]
+        struct ZeroBaseCase {}
+        implement_seq_config!(ZeroBaseCase, fisher_yates, 0);
+
+        assert!(matches!(
+            SeqScatterShuffleImpl::<rand_pcg::Pcg64Mcg, u32, _, 4>::try_new(ZeroBaseCase {}),
+            Err(ShuffleError::ZeroBaseCaseSize)
+        ));
+        assert!(
+            SeqScatterShuffleImpl::<rand_pcg::Pcg64Mcg, u32, _, 4>::try_new(
+                DefaultConfiguration {}
+            )
+            .is_ok()
+        );
+    }
 }
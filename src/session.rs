@@ -0,0 +1,191 @@
+//! Incremental shuffling with resumable state.
+//!
+//! Interactive applications (e.g., game engines or UI threads) cannot block for the
+//! whole duration of a large shuffle. A [`ShuffleSession`] performs a bounded amount of
+//! work per call and is resumed until it is finished. Between two calls, `data` remains
+//! a valid permutation of the input, but it is only uniformly shuffled once the session
+//! is finished.
+//!
+//! The session runs a Fisher-Yates shuffle from the back of the input; it only stores
+//! the progress, so `data` and the random number generator are passed to each call and
+//! need not be borrowed for the whole session.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::session::ShuffleSession;
+//! use std::time::Duration;
+//!
+//! let mut rng = rand::thread_rng();
+//! let mut data: Vec<_> = (0..1_000_000).collect();
+//!
+//! let mut session = ShuffleSession::new(data.len());
+//! while !session.is_finished() {
+//!     // at most one millisecond per frame
+//!     session.run_for(&mut rng, &mut data, Duration::from_millis(1)).unwrap();
+//! }
+//! ```
+
+use super::error::ShuffleError;
+use super::uniform_index;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// Number of swaps between two deadline checks of [`ShuffleSession::run_for`].
+const SWAPS_PER_DEADLINE_CHECK: usize = 1 << 12;
+
+/// Progress of an incremental shuffle of an input with a fixed length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShuffleSession {
+    len: usize,
+    // positions `num_open..len` are final
+    num_open: usize,
+}
+
+impl ShuffleSession {
+    /// Starts a session for an input of `len` elements.
+    pub fn new(len: usize) -> Self {
+        Self { len, num_open: len }
+    }
+
+    /// Returns the length of the input the session was started for.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the session was started for an empty input.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if `data` is uniformly shuffled.
+    pub fn is_finished(&self) -> bool {
+        self.num_open <= 1
+    }
+
+    /// Returns the fraction of the work done so far (between 0 and 1).
+    pub fn progress(&self) -> f64 {
+        if self.len <= 1 {
+            1.0
+        } else {
+            (self.len - self.num_open) as f64 / (self.len - 1) as f64
+        }
+    }
+
+    /// Performs at most `max_swaps` swaps and returns whether the session is finished.
+    /// Returns [`ShuffleError::ShapeMismatch`] and leaves `data` untouched if its length
+    /// differs from the one passed to [`ShuffleSession::new`].
+    pub fn step<R: Rng, T>(
+        &mut self,
+        rng: &mut R,
+        data: &mut [T],
+        max_swaps: usize,
+    ) -> Result<bool, ShuffleError> {
+        if data.len() != self.len {
+            return Err(ShuffleError::ShapeMismatch);
+        }
+
+        let end = self.num_open.saturating_sub(max_swaps).max(1);
+        for i in (end..self.num_open).rev() {
+            let j = uniform_index::gen_index(rng, i + 1);
+            data.swap(i, j);
+        }
+        self.num_open = end.min(self.num_open);
+
+        Ok(self.is_finished())
+    }
+
+    /// Works until the session is finished or `budget` has elapsed, and returns whether
+    /// the session is finished. The deadline is checked every few thousand swaps, so the
+    /// budget may be exceeded by some microseconds.
+    pub fn run_for<R: Rng, T>(
+        &mut self,
+        rng: &mut R,
+        data: &mut [T],
+        budget: Duration,
+    ) -> Result<bool, ShuffleError> {
+        let start = Instant::now();
+
+        while !self.step(rng, data, SWAPS_PER_DEADLINE_CHECK)? {
+            if start.elapsed() >= budget {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn shape_mismatch() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let mut data = [1, 2, 3];
+
+        let mut session = ShuffleSession::new(4);
+        assert_eq!(
+            session.step(&mut rng, &mut data, 10),
+            Err(ShuffleError::ShapeMismatch)
+        );
+        assert_eq!(data, [1, 2, 3]);
+        assert_eq!(session.progress(), 0.0);
+    }
+
+    #[test]
+    fn progress() {
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        let mut data: Vec<usize> = (0..101).collect();
+
+        let mut session = ShuffleSession::new(data.len());
+        assert_eq!(session.step(&mut rng, &mut data, 50), Ok(false));
+        assert_eq!(session.progress(), 0.5);
+        assert_eq!(session.step(&mut rng, &mut data, 50), Ok(true));
+        assert_eq!(session.progress(), 1.0);
+        assert_eq!(session.step(&mut rng, &mut data, 50), Ok(true));
+
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+
+        for n in [0, 1] {
+            let mut session = ShuffleSession::new(n);
+            assert!(session.is_finished());
+            assert_eq!(session.step(&mut rng, &mut data[..n], 0), Ok(true));
+        }
+    }
+
+    #[test]
+    fn run_for() {
+        let mut rng = Pcg64Mcg::seed_from_u64(3);
+        let mut data: Vec<usize> = (0..1 << 20).collect();
+
+        let mut session = ShuffleSession::new(data.len());
+        assert_eq!(
+            session.run_for(&mut rng, &mut data, Duration::ZERO),
+            Ok(false)
+        );
+        assert!(session.progress() > 0.0);
+
+        while !session
+            .run_for(&mut rng, &mut data, Duration::from_micros(100))
+            .unwrap()
+        {}
+
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn shuffle_in_steps<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            let mut session = ShuffleSession::new(data.len());
+            while !session.step(rng, data, 3).unwrap() {}
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_in_steps);
+    }
+}
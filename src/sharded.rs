@@ -0,0 +1,279 @@
+//! Coordinator-free shuffling of data that is sharded over several workers.
+//!
+//! Consider `w` workers (e.g., the nodes holding the partitions of a distributed
+//! dataframe) where worker `i` holds `local_lens[i]` elements and shall hold
+//! `output_lens[i]` elements afterwards. If all workers know these sizes and share a
+//! seed, each of them independently computes the same [`ShardedShuffle`] and then:
+//!
+//! 1. shuffles its local elements with [`ShardedShuffle::prepare_send`] and sends the
+//!    elements in [`ShardedShuffle::send_ranges`]`[j]` to worker `j`;
+//! 2. places the elements received from worker `i` into the range
+//!    [`ShardedShuffle::placement_ranges`]`[i]` of its output shard (so messages can be
+//!    received concurrently and in any order);
+//! 3. shuffles its output shard with [`ShardedShuffle::finish_receive`].
+//!
+//! The concatenation of all output shards is then a uniform random permutation of the
+//! concatenation of all input shards. No worker needs to coordinate with any other
+//! beyond the exchange of the elements themselves.
+//!
+//! The number of elements worker `i` sends to worker `j` is given by
+//! [`ShardedShuffle::exchange_count`]. These counts follow the distribution they
+//! have under a uniform permutation: the positions of the elements of worker `i`
+//! form a uniform subset of the output positions not taken by workers `0..i`, so the
+//! counts of each row are drawn from the multivariate hypergeometric distribution.
+//!
+//! All workers need to use the same build of this crate and the same type of random
+//! number generator `R`, as the computation has to be reproducible on each of them.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::sharded::ShardedShuffle;
+//!
+//! let shards: Vec<Vec<u32>> = vec![(0..10).collect(), (10..15).collect(), (15..40).collect()];
+//! let lens: Vec<usize> = shards.iter().map(|s| s.len()).collect();
+//!
+//! // each worker computes the same plan from the shared seed
+//! let plan = ShardedShuffle::<rand_pcg::Pcg64Mcg>::new(1234, &lens);
+//!
+//! // worker `i` sends `messages[i][j]` to worker `j`
+//! let messages: Vec<Vec<Vec<u32>>> = shards
+//!     .into_iter()
+//!     .enumerate()
+//!     .map(|(i, mut local)| {
+//!         plan.prepare_send(i, &mut local);
+//!         plan.send_ranges(i).into_iter().map(|r| local[r].to_vec()).collect()
+//!     })
+//!     .collect();
+//!
+//! // worker `j` places the messages and finishes its output shard
+//! for j in 0..plan.num_workers() {
+//!     let mut output = vec![0; lens[j]];
+//!     for (i, range) in plan.placement_ranges(j).into_iter().enumerate() {
+//!         output[range].copy_from_slice(&messages[i][j]);
+//!     }
+//!     plan.finish_receive(j, &mut output);
+//! }
+//! ```
+
+use super::bitset::sample_ones_in_chunk;
+use super::error::ShuffleError;
+use super::scatter_shuffle::parallel::seed_new_rng;
+use super::RipShuffleSequential;
+use rand::{Rng, SeedableRng};
+use std::marker::PhantomData;
+use std::ops::Range;
+
+/// The state shared by all workers of a sharded shuffle (see the
+/// [module documentation](self)).
+#[derive(Clone, Debug)]
+pub struct ShardedShuffle<R> {
+    seed: u64,
+    local_lens: Vec<usize>,
+    output_lens: Vec<usize>,
+    /// `counts[i * w + j]` elements are sent from worker `i` to worker `j`
+    counts: Vec<usize>,
+    _rng: PhantomData<fn() -> R>,
+}
+
+impl<R: Rng + SeedableRng> ShardedShuffle<R> {
+    /// Prepares a shuffle in which each worker holds as many elements afterwards as
+    /// before, i.e., `local_lens[i]` for worker `i`.
+    pub fn new(seed: u64, local_lens: &[usize]) -> Self {
+        Self::try_with_output_lens(seed, local_lens, local_lens).unwrap()
+    }
+
+    /// Prepares a shuffle in which worker `i` holds `local_lens[i]` elements before and
+    /// `output_lens[i]` elements after the shuffle. Returns
+    /// [`ShuffleError::InvalidShards`] if the numbers of workers or the total numbers
+    /// of elements differ.
+    pub fn try_with_output_lens(
+        seed: u64,
+        local_lens: &[usize],
+        output_lens: &[usize],
+    ) -> Result<Self, ShuffleError> {
+        if local_lens.len() != output_lens.len()
+            || local_lens.iter().sum::<usize>() != output_lens.iter().sum::<usize>()
+        {
+            return Err(ShuffleError::InvalidShards);
+        }
+
+        let mut result = Self {
+            seed,
+            local_lens: local_lens.to_vec(),
+            output_lens: output_lens.to_vec(),
+            counts: Vec::with_capacity(local_lens.len() * output_lens.len()),
+            _rng: PhantomData,
+        };
+
+        let mut rng = result.derived_rng(0);
+        let mut remaining_capacity = result.output_lens.clone();
+        let mut remaining_slots: usize = remaining_capacity.iter().sum();
+
+        for &local_len in &result.local_lens {
+            let mut to_assign = local_len;
+            let mut slots = remaining_slots;
+
+            for capacity in remaining_capacity.iter_mut() {
+                let count = sample_ones_in_chunk(&mut rng, slots, to_assign, *capacity);
+                slots -= *capacity;
+                to_assign -= count;
+                *capacity -= count;
+                result.counts.push(count);
+            }
+
+            debug_assert_eq!(to_assign, 0);
+            remaining_slots -= local_len;
+        }
+
+        Ok(result)
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.local_lens.len()
+    }
+
+    /// Number of elements worker `from` sends to worker `to`.
+    pub fn exchange_count(&self, from: usize, to: usize) -> usize {
+        self.counts[from * self.num_workers() + to]
+    }
+
+    /// Ranges of the local elements of `worker` (after [`ShardedShuffle::prepare_send`])
+    /// that are sent to each worker.
+    pub fn send_ranges(&self, worker: usize) -> Vec<Range<usize>> {
+        prefix_ranges((0..self.num_workers()).map(|to| self.exchange_count(worker, to)))
+    }
+
+    /// Ranges of the output shard of `worker` that receive the elements of each worker.
+    pub fn placement_ranges(&self, worker: usize) -> Vec<Range<usize>> {
+        prefix_ranges((0..self.num_workers()).map(|from| self.exchange_count(from, worker)))
+    }
+
+    /// Shuffles the local elements of `worker` before they are sent.
+    ///
+    /// # Panics
+    /// If `local` does not hold as many elements as announced for `worker`.
+    pub fn prepare_send<T>(&self, worker: usize, local: &mut [T]) {
+        assert_eq!(local.len(), self.local_lens[worker]);
+        local.seq_shuffle(&mut self.derived_rng(1 + worker));
+    }
+
+    /// Shuffles the output shard of `worker` after all elements have been placed.
+    ///
+    /// # Panics
+    /// If `output` does not hold as many elements as announced for `worker`.
+    pub fn finish_receive<T>(&self, worker: usize, output: &mut [T]) {
+        assert_eq!(output.len(), self.output_lens[worker]);
+        output.seq_shuffle(&mut self.derived_rng(1 + self.num_workers() + worker));
+    }
+
+    /// Returns the `index`-th generator derived from the shared seed; index `0` samples
+    /// the exchange counts, the others are used by the workers.
+    fn derived_rng(&self, index: usize) -> R {
+        let mut base = R::seed_from_u64(self.seed);
+        for _ in 0..index {
+            let _: R = seed_new_rng(&mut base);
+        }
+        seed_new_rng(&mut base)
+    }
+}
+
+/// Returns consecutive ranges starting at zero with the given lengths.
+fn prefix_ranges(lens: impl Iterator<Item = usize>) -> Vec<Range<usize>> {
+    let mut begin = 0;
+    lens.map(|len| {
+        begin += len;
+        (begin - len)..begin
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    /// Simulates the workers holding the shards of `order` and returns the
+    /// concatenation of the output shards.
+    fn simulate(plan: &ShardedShuffle<Pcg64Mcg>, order: Vec<u32>) -> Vec<u32> {
+        let w = plan.num_workers();
+
+        let mut rest = order.as_slice();
+        let messages: Vec<Vec<Vec<u32>>> = (0..w)
+            .map(|i| {
+                let (shard, tail) = rest.split_at(plan.local_lens[i]);
+                rest = tail;
+
+                let mut local = shard.to_vec();
+                plan.prepare_send(i, &mut local);
+                plan.send_ranges(i)
+                    .into_iter()
+                    .map(|r| local[r].to_vec())
+                    .collect()
+            })
+            .collect();
+
+        // receive in reverse order to check that the placement does not depend on it
+        let mut outputs: Vec<Vec<u32>> = plan.output_lens.iter().map(|&n| vec![0; n]).collect();
+        for (j, output) in outputs.iter_mut().enumerate() {
+            for (i, range) in plan.placement_ranges(j).into_iter().enumerate().rev() {
+                output[range].copy_from_slice(&messages[i][j]);
+            }
+            plan.finish_receive(j, output);
+        }
+
+        outputs.concat()
+    }
+
+    #[test]
+    fn exchange_counts_match_shards() {
+        let local_lens = [10, 0, 7, 30];
+        let output_lens = [20, 20, 0, 7];
+        let plan =
+            ShardedShuffle::<Pcg64Mcg>::try_with_output_lens(3, &local_lens, &output_lens).unwrap();
+
+        for i in 0..4 {
+            let sent: usize = (0..4).map(|j| plan.exchange_count(i, j)).sum();
+            let received: usize = (0..4).map(|j| plan.exchange_count(j, i)).sum();
+            assert_eq!(sent, local_lens[i]);
+            assert_eq!(received, output_lens[i]);
+        }
+
+        assert_eq!(
+            ShardedShuffle::<Pcg64Mcg>::try_with_output_lens(3, &[1, 2], &[2, 2]).err(),
+            Some(ShuffleError::InvalidShards)
+        );
+    }
+
+    #[test]
+    fn workers_agree() {
+        let lens = [5, 8, 13];
+        let a = ShardedShuffle::<Pcg64Mcg>::new(7, &lens);
+        let b = ShardedShuffle::<Pcg64Mcg>::new(7, &lens);
+        assert_eq!(a.counts, b.counts);
+
+        let order: Vec<u32> = (0..26).collect();
+        assert_eq!(simulate(&a, order.clone()), simulate(&b, order));
+    }
+
+    mod statistical {
+        use super::*;
+
+        /// Distributes the input over three workers with unequal input and output shards
+        fn sharded_shuffle<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            let n = data.len();
+            let local_lens = [n / 4, n / 2, n - n / 4 - n / 2];
+            let output_lens = [n / 3, n - n / 3 - n / 5, n / 5];
+            let plan = ShardedShuffle::<Pcg64Mcg>::try_with_output_lens(
+                rng.gen(),
+                &local_lens,
+                &output_lens,
+            )
+            .unwrap();
+
+            let mut order = simulate(&plan, (0..n as u32).collect());
+            crate::indirection::apply_in_place(data, &mut order);
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(sharded_shuffle);
+    }
+}
@@ -0,0 +1,410 @@
+//! Ordered random sampling without replacement.
+//!
+//! [`sample_sorted_indices`] yields a uniformly random `k`-subset of `0..n` in
+//! increasing order, using Vitter's Algorithm D ("An Efficient Algorithm for
+//! Sequential Random Sampling", ACM TOMS 1987). It generates the gaps between
+//! consecutive samples directly, so it runs in expected `O(k)` time with `O(1)`
+//! memory, independently of `n`. This is suited for a single sequential scan over
+//! huge inputs (e.g., files) where random access is impossible.
+//!
+//! [`random_combinations`] builds on it to draw independent random subsets on demand,
+//! e.g., for simulations, without shuffling a full index array for each draw.
+
+use super::error::ShuffleError;
+use rand::distributions::Open01;
+use rand::Rng;
+
+/// If fewer than `n / ALPHA_INV` samples remain, gaps are sampled with the rejection
+/// method D; otherwise with the sequential search of method A.
+const ALPHA_INV: f64 = 13.0;
+
+/// Returns an iterator over `k` indices drawn uniformly without replacement from
+/// `0..n`, in increasing order.
+///
+/// # Panics
+/// Panics if `k > n`; see [`try_sample_sorted_indices`] for a non-panicking variant.
+///
+/// # Warning
+/// Internally, floating-point arithmetic is used. For `n` beyond `2^53`, the
+/// distribution is only approximately uniform.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::sorted_sample::sample_sorted_indices;
+///
+/// let sample: Vec<_> = sample_sorted_indices(&mut rand::thread_rng(), 1 << 40, 1000).collect();
+///
+/// assert_eq!(sample.len(), 1000);
+/// assert!(sample.windows(2).all(|w| w[0] < w[1]));
+/// ```
+pub fn sample_sorted_indices<R: Rng>(rng: &mut R, n: u64, k: u64) -> SortedSample<'_, R> {
+    assert!(
+        k <= n,
+        "cannot sample {k} elements from a population of {n}"
+    );
+
+    SortedSample {
+        rng,
+        population: n,
+        remaining: k,
+        position: 0,
+        vprime: None,
+    }
+}
+
+/// Same as [`sample_sorted_indices`], but returns [`ShuffleError::SampleTooLarge`]
+/// instead of panicking if `k > n`.
+pub fn try_sample_sorted_indices<R: Rng>(
+    rng: &mut R,
+    n: u64,
+    k: u64,
+) -> Result<SortedSample<'_, R>, ShuffleError> {
+    if k > n {
+        return Err(ShuffleError::SampleTooLarge);
+    }
+
+    Ok(sample_sorted_indices(rng, n, k))
+}
+
+/// Iterator returned by [`sample_sorted_indices`].
+pub struct SortedSample<'a, R> {
+    rng: &'a mut R,
+    population: u64,
+    remaining: u64,
+    position: u64,
+    vprime: Option<f64>,
+}
+
+impl<'a, R: Rng> SortedSample<'a, R> {
+    fn uniform(&mut self) -> f64 {
+        self.rng.sample(Open01)
+    }
+
+    /// Number of unselected elements to skip before the next selected one.
+    fn skip(&mut self) -> u64 {
+        let n = self.remaining;
+        let big_n = self.population;
+
+        if n == 1 {
+            return ((big_n as f64 * self.uniform()) as u64).min(big_n - 1);
+        }
+
+        if ALPHA_INV * (n as f64) < big_n as f64 {
+            self.skip_method_d()
+        } else {
+            self.vprime = None;
+            self.skip_method_a()
+        }
+    }
+
+    fn skip_method_a(&mut self) -> u64 {
+        let mut top = (self.population - self.remaining) as f64;
+        let mut big_n = self.population as f64;
+
+        let v = self.uniform();
+        let mut skip = 0;
+        let mut quot = top / big_n;
+
+        while quot > v {
+            skip += 1;
+            top -= 1.0;
+            big_n -= 1.0;
+            quot = quot * top / big_n;
+        }
+
+        skip
+    }
+
+    fn skip_method_d(&mut self) -> u64 {
+        let n = self.remaining as f64;
+        let big_n = self.population as f64;
+        let ninv = 1.0 / n;
+        let nmin1inv = 1.0 / (n - 1.0);
+        let qu1 = big_n - n + 1.0;
+
+        let mut vprime = match self.vprime {
+            Some(v) => v,
+            None => (self.uniform().ln() * ninv).exp(),
+        };
+
+        loop {
+            let (x, skip) = loop {
+                let x = big_n * (1.0 - vprime);
+                let skip = x.floor();
+                if skip < qu1 {
+                    break (x, skip);
+                }
+                vprime = (self.uniform().ln() * ninv).exp();
+            };
+
+            let u = self.uniform();
+            let y1 = ((u * big_n / qu1).ln() * nmin1inv).exp();
+            vprime = y1 * (1.0 - x / big_n) * (qu1 / (qu1 - skip));
+
+            if vprime <= 1.0 {
+                // the squeeze test accepted; `vprime` is a valid seed for the next call
+                self.vprime = Some(vprime);
+                return skip as u64;
+            }
+
+            let mut y2 = 1.0;
+            let mut top = big_n - 1.0;
+            let (mut bottom, limit) = if n - 1.0 > skip {
+                (big_n - n, big_n - skip)
+            } else {
+                (big_n - skip - 1.0, qu1)
+            };
+
+            let mut t = big_n - 1.0;
+            while t >= limit {
+                y2 = y2 * top / bottom;
+                top -= 1.0;
+                bottom -= 1.0;
+                t -= 1.0;
+            }
+
+            if big_n / (big_n - x) >= y1 * (y2.ln() * nmin1inv).exp() {
+                self.vprime = Some((self.uniform().ln() * nmin1inv).exp());
+                return skip as u64;
+            }
+
+            vprime = (self.uniform().ln() * ninv).exp();
+        }
+    }
+}
+
+impl<'a, R: Rng> Iterator for SortedSample<'a, R> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let skip = self.skip();
+        debug_assert!(skip <= self.population - self.remaining);
+
+        let index = self.position + skip;
+        self.position = index + 1;
+        self.population -= skip + 1;
+        self.remaining -= 1;
+
+        Some(index)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // on 32-bit targets, `remaining` may not fit into usize
+        match usize::try_from(self.remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl<'a, R: Rng> ExactSizeIterator for SortedSample<'a, R> {}
+
+/// Returns an endless iterator over independent, uniformly random `k`-subsets of
+/// `0..n`. Each subset is produced with [`sample_sorted_indices`] and hence sorted.
+/// Use [`RandomCombinations::next_into`] to reuse an allocation across draws.
+///
+/// # Panics
+/// Panics if `k > n`.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::sorted_sample::random_combinations;
+///
+/// for subset in random_combinations(&mut rand::thread_rng(), 49, 6).take(10) {
+///     assert_eq!(subset.len(), 6);
+///     assert!(subset.windows(2).all(|w| w[0] < w[1] && w[1] < 49));
+/// }
+/// ```
+pub fn random_combinations<R: Rng>(rng: &mut R, n: u64, k: u64) -> RandomCombinations<'_, R> {
+    assert!(
+        k <= n,
+        "cannot sample {k} elements from a population of {n}"
+    );
+    RandomCombinations { rng, n, k }
+}
+
+/// Iterator returned by [`random_combinations`].
+pub struct RandomCombinations<'a, R> {
+    rng: &'a mut R,
+    n: u64,
+    k: u64,
+}
+
+impl<'a, R: Rng> RandomCombinations<'a, R> {
+    /// Overwrites `subset` with the next random subset.
+    pub fn next_into(&mut self, subset: &mut Vec<u64>) {
+        subset.clear();
+        subset.extend(sample_sorted_indices(self.rng, self.n, self.k));
+    }
+}
+
+impl<'a, R: Rng> Iterator for RandomCombinations<'a, R> {
+    type Item = Vec<u64>;
+
+    fn next(&mut self) -> Option<Vec<u64>> {
+        let mut subset = Vec::with_capacity(usize::try_from(self.k).unwrap_or(usize::MAX));
+        self.next_into(&mut subset);
+        Some(subset)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn sorted_distinct_and_in_range() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for n in [0, 1, 2, 10, 100, 1000, 1 << 30, 1 << 50] {
+            for k in [0, 1, 2, 5, 10, 50, 500, 1000] {
+                if k > n {
+                    continue;
+                }
+
+                let sample: Vec<_> = sample_sorted_indices(&mut rng, n, k).collect();
+                assert_eq!(sample.len() as u64, k, "n={n} k={k}");
+                assert!(sample.windows(2).all(|w| w[0] < w[1]), "n={n} k={k}");
+                assert!(sample.iter().all(|&x| x < n), "n={n} k={k}");
+            }
+        }
+    }
+
+    #[test]
+    fn select_all() {
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+        let sample: Vec<_> = sample_sorted_indices(&mut rng, 100, 100).collect();
+        assert_eq!(sample, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_samples() {
+        sample_sorted_indices(&mut Pcg64Mcg::seed_from_u64(1), 10, 11);
+    }
+
+    #[test]
+    fn too_many_samples_is_error() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        assert!(matches!(
+            try_sample_sorted_indices(&mut rng, 10, 11),
+            Err(ShuffleError::SampleTooLarge)
+        ));
+        assert_eq!(
+            try_sample_sorted_indices(&mut rng, 10, 10).unwrap().len(),
+            10
+        );
+    }
+
+    fn assert_uniform(n: u64, k: u64, runs: usize) {
+        let mut rng = Pcg64Mcg::seed_from_u64(n * 1000 + k);
+        let mut counts = vec![0usize; n as usize];
+
+        for _ in 0..runs {
+            for x in sample_sorted_indices(&mut rng, n, k) {
+                counts[x as usize] += 1;
+            }
+        }
+
+        let expected = runs as f64 * k as f64 / n as f64;
+        for (i, &c) in counts.iter().enumerate() {
+            let dev = (c as f64 - expected).abs() / expected;
+            assert!(
+                dev < 0.15,
+                "n={n} k={k} i={i} count={c} expected={expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_method_a() {
+        assert_uniform(20, 5, 20_000);
+        assert_uniform(50, 25, 10_000);
+    }
+
+    #[test]
+    fn uniform_method_d() {
+        assert_uniform(200, 3, 100_000);
+        assert_uniform(1000, 10, 200_000);
+    }
+
+    #[test]
+    fn combinations_are_independent_and_uniform() {
+        let mut rng = Pcg64Mcg::seed_from_u64(23456);
+
+        // binomial(6, 3) = 20 subsets, encoded as bitmasks
+        const RUNS: usize = 40_000;
+        let mut counts = [0usize; 64];
+        let mut pair_counts = vec![0usize; 64 * 64];
+        let mut last = None;
+
+        let mut combinations = random_combinations(&mut rng, 6, 3);
+        let mut subset = Vec::new();
+        for _ in 0..RUNS {
+            combinations.next_into(&mut subset);
+            let mask = subset.iter().map(|&x| 1usize << x).sum::<usize>();
+            counts[mask] += 1;
+
+            if let Some(prev) = last.replace(mask) {
+                pair_counts[prev * 64 + mask] += 1;
+            }
+        }
+
+        let expected = RUNS / 20;
+        for (mask, &c) in counts.iter().enumerate() {
+            if mask.count_ones() == 3 {
+                assert!(
+                    4 * c > 3 * expected && 4 * c < 5 * expected,
+                    "mask={mask:06b}"
+                );
+            } else {
+                assert_eq!(c, 0);
+            }
+        }
+
+        // consecutive draws do not depend on each other
+        let expected_pairs = RUNS / 400;
+        assert!(pair_counts
+            .iter()
+            .filter(|&&c| c > 0)
+            .all(|&c| 2 * c > expected_pairs && c < 2 * expected_pairs));
+        assert_eq!(pair_counts.iter().filter(|&&c| c > 0).count(), 400);
+    }
+
+    #[test]
+    fn all_pairs_appear() {
+        // there are binomial(8, 2) = 28 subsets; 8 < 13 * 2 selects method A throughout,
+        // so we additionally check a population that starts with method D
+        for n in [8u64, 40] {
+            let mut rng = Pcg64Mcg::seed_from_u64(n);
+            let mut counts = vec![0usize; (n * n) as usize];
+            let runs = 200 * (n * (n - 1) / 2) as usize;
+
+            for _ in 0..runs {
+                let sample: Vec<_> = sample_sorted_indices(&mut rng, n, 2).collect();
+                counts[(sample[0] * n + sample[1]) as usize] += 1;
+            }
+
+            for a in 0..n {
+                for b in 0..n {
+                    let c = counts[(a * n + b) as usize];
+                    if a < b {
+                        assert!(c > 120 && c < 280, "n={n} a={a} b={b} c={c}");
+                    } else {
+                        assert_eq!(c, 0);
+                    }
+                }
+            }
+        }
+    }
+}
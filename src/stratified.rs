@@ -0,0 +1,392 @@
+//! Stratified shuffling and sampling.
+//!
+//! The input is partitioned into strata (e.g., classes of a labeled dataset) given as
+//! consecutive ranges. A stratified shuffle randomizes the order while every prefix of
+//! the output, and hence every minibatch cut from it, contains each stratum in almost
+//! exactly its overall proportion.
+//!
+//! To this end, the elements of each stratum are shuffled independently. Then the `i`-th
+//! element of a stratum of size `n_s` receives the key `(i + u_s) / n_s` where the phase
+//! `u_s` is drawn uniformly from `[0, 1)` for each stratum, and the output interleaves the
+//! strata by increasing key. Hence, a prefix of length `p` contains `p * n_s / n` elements
+//! of each stratum, up to a deviation of less than one element for two strata (and less
+//! than `m - 1` elements for `m > 2` non-empty strata in the worst case).
+
+use super::error::ShuffleError;
+use super::scatter_shuffle::sequential::seq_scatter_shuffle;
+use super::sorted_sample::sample_sorted_indices;
+use rand::Rng;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::ops::Range;
+
+/// Shuffles `data` such that each stratum appears in every prefix of the output
+/// proportional to its size (see the [module documentation](self)). The strata need
+/// to be consecutive ranges that cover `data` in order; empty strata are allowed.
+///
+/// # Panics
+/// Panics if the strata do not tile `0..data.len()`; see [`try_stratified_shuffle`]
+/// for a non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::stratified::stratified_shuffle;
+///
+/// // 100 elements of class 0, followed by 300 elements of class 1
+/// let mut data: Vec<_> = (0..400).map(|i| (i >= 100) as usize).collect();
+/// stratified_shuffle(&mut rand::thread_rng(), &mut data, &[0..100, 100..400]);
+///
+/// for batch in data.chunks(40) {
+///     let class0 = batch.iter().filter(|&&c| c == 0).count();
+///     assert!((8..=12).contains(&class0));
+/// }
+/// ```
+pub fn stratified_shuffle<R: Rng, T>(rng: &mut R, data: &mut [T], strata: &[Range<usize>]) {
+    try_stratified_shuffle(rng, data, strata).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`stratified_shuffle`], but returns [`ShuffleError::InvalidStrata`] instead
+/// of panicking if the strata do not tile `0..data.len()`. In this case, the input
+/// remains untouched.
+pub fn try_stratified_shuffle<R: Rng, T>(
+    rng: &mut R,
+    data: &mut [T],
+    strata: &[Range<usize>],
+) -> Result<(), ShuffleError> {
+    check_tiling(data.len(), strata)?;
+
+    for stratum in strata {
+        seq_scatter_shuffle(rng, &mut data[stratum.clone()]);
+    }
+
+    let mut taken = vec![0; strata.len()];
+    let mut sources: Vec<usize> = interleave_strata(rng, strata, data.len())
+        .into_iter()
+        .map(|s| {
+            taken[s] += 1;
+            strata[s].start + taken[s] - 1
+        })
+        .collect();
+
+    apply_permutation(data, &mut sources);
+
+    Ok(())
+}
+
+/// Returns the indices of `k` distinct elements drawn uniformly at random within each
+/// stratum, such that each stratum contributes in proportion to its size in every
+/// prefix of the result (see the [module documentation](self)). The input itself is not
+/// required; the strata need to be consecutive ranges starting at zero.
+///
+/// # Panics
+/// Panics if the strata are not consecutive or `k` exceeds the total size; see
+/// [`try_stratified_sample`] for a non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::stratified::stratified_sample;
+///
+/// let sample = stratified_sample(&mut rand::thread_rng(), &[0..100, 100..400], 40);
+///
+/// assert_eq!(sample.len(), 40);
+/// assert_eq!(sample.iter().filter(|&&i| i < 100).count(), 10);
+/// ```
+pub fn stratified_sample<R: Rng>(rng: &mut R, strata: &[Range<usize>], k: usize) -> Vec<usize> {
+    try_stratified_sample(rng, strata, k).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`stratified_sample`], but returns [`ShuffleError::InvalidStrata`] or
+/// [`ShuffleError::SampleTooLarge`] instead of panicking.
+pub fn try_stratified_sample<R: Rng>(
+    rng: &mut R,
+    strata: &[Range<usize>],
+    k: usize,
+) -> Result<Vec<usize>, ShuffleError> {
+    let n = strata.last().map_or(0, |s| s.end);
+    check_tiling(n, strata)?;
+    if k > n {
+        return Err(ShuffleError::SampleTooLarge);
+    }
+
+    let order = interleave_strata(rng, strata, k);
+
+    let mut counts = vec![0; strata.len()];
+    for &s in &order {
+        counts[s] += 1;
+    }
+
+    let mut selected: Vec<Vec<usize>> = strata
+        .iter()
+        .zip(&counts)
+        .map(|(stratum, &count)| {
+            let mut members: Vec<usize> =
+                sample_sorted_indices(rng, stratum.len() as u64, count as u64)
+                    .map(|i| stratum.start + i as usize)
+                    .collect();
+            seq_scatter_shuffle(rng, &mut members);
+            members
+        })
+        .collect();
+
+    Ok(order
+        .into_iter()
+        .map(|s| selected[s].pop().unwrap())
+        .collect())
+}
+
+fn check_tiling(n: usize, strata: &[Range<usize>]) -> Result<(), ShuffleError> {
+    let mut end = 0;
+    for stratum in strata {
+        if stratum.start != end || stratum.start > stratum.end {
+            return Err(ShuffleError::InvalidStrata);
+        }
+        end = stratum.end;
+    }
+
+    if end != n {
+        return Err(ShuffleError::InvalidStrata);
+    }
+
+    Ok(())
+}
+
+/// Returns the stratum of each of the first `len` output positions.
+fn interleave_strata<R: Rng>(rng: &mut R, strata: &[Range<usize>], len: usize) -> Vec<usize> {
+    #[derive(PartialEq)]
+    struct Cursor {
+        key: f64,
+        stratum: usize,
+        next: usize,
+        phase: f64,
+    }
+
+    impl Eq for Cursor {}
+
+    impl PartialOrd for Cursor {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Cursor {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.key.total_cmp(&other.key)
+        }
+    }
+
+    let key = |next: usize, phase: f64, size: usize| (next as f64 + phase) / size as f64;
+
+    let mut heap: BinaryHeap<Reverse<Cursor>> = strata
+        .iter()
+        .enumerate()
+        .filter(|(_, stratum)| !stratum.is_empty())
+        .map(|(stratum, range)| {
+            let phase: f64 = rng.gen();
+            Reverse(Cursor {
+                key: key(0, phase, range.len()),
+                stratum,
+                next: 0,
+                phase,
+            })
+        })
+        .collect();
+
+    let mut order = Vec::with_capacity(len);
+    while order.len() < len {
+        let Reverse(mut cursor) = heap.pop().unwrap();
+        order.push(cursor.stratum);
+
+        cursor.next += 1;
+        let size = strata[cursor.stratum].len();
+        if cursor.next < size {
+            cursor.key = key(cursor.next, cursor.phase, size);
+            heap.push(Reverse(cursor));
+        }
+    }
+
+    order
+}
+
+/// Rearranges `data` such that position `i` receives the element previously found
+/// at `sources[i]`. The content of `sources` is destroyed.
+fn apply_permutation<T>(data: &mut [T], sources: &mut [usize]) {
+    const DONE: usize = usize::MAX;
+
+    for start in 0..data.len() {
+        let mut current = start;
+        while sources[current] != DONE {
+            let source = std::mem::replace(&mut sources[current], DONE);
+            if source == start {
+                break;
+            }
+
+            data.swap(current, source);
+            current = source;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    fn assert_proportional_prefixes(classes: &[usize], sizes: &[usize]) {
+        let n: usize = sizes.iter().sum();
+        let num_strata = sizes.iter().filter(|&&s| s > 0).count();
+        let bound = (num_strata as f64 - 1.0).max(1.0);
+        let mut counts = vec![0; sizes.len()];
+
+        for (p, &class) in classes.iter().enumerate() {
+            counts[class] += 1;
+            for (&count, &size) in counts.iter().zip(sizes) {
+                let expected = (p + 1) as f64 * size as f64 / n as f64;
+                assert!(
+                    (count as f64 - expected).abs() < bound,
+                    "p={p} class={class}"
+                );
+            }
+        }
+    }
+
+    fn strata_of(sizes: &[usize]) -> Vec<Range<usize>> {
+        let mut begin = 0;
+        sizes
+            .iter()
+            .map(|&size| {
+                begin += size;
+                begin - size..begin
+            })
+            .collect()
+    }
+
+    #[test]
+    fn apply_permutation() {
+        let mut rng = Pcg64Mcg::seed_from_u64(123);
+        for n in [0, 1, 2, 10, 100] {
+            let mut sources: Vec<usize> = (0..n).collect();
+            seq_scatter_shuffle(&mut rng, &mut sources);
+            let expected = sources.clone();
+
+            let mut data: Vec<usize> = (0..n).collect();
+            super::apply_permutation(&mut data, &mut sources);
+            assert_eq!(data, expected);
+        }
+    }
+
+    #[test]
+    fn shuffle_keeps_proportions() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for sizes in [
+            vec![],
+            vec![5],
+            vec![10, 0, 30],
+            vec![1, 2, 3, 100],
+            vec![333, 1, 77],
+        ] {
+            let strata = strata_of(&sizes);
+            let n: usize = sizes.iter().sum();
+            let mut data: Vec<usize> = (0..n).collect();
+
+            stratified_shuffle(&mut rng, &mut data, &strata);
+
+            let classes: Vec<usize> = data
+                .iter()
+                .map(|&x| strata.iter().position(|s| s.contains(&x)).unwrap())
+                .collect();
+            assert_proportional_prefixes(&classes, &sizes);
+
+            data.sort();
+            assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+        }
+    }
+
+    #[test]
+    fn shuffle_within_stratum_is_uniform() {
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+        let strata = strata_of(&[4, 8]);
+        const RUNS: usize = 24_000;
+
+        // element 0 is equally likely to be the first, second, ... element
+        // of the first stratum in the output
+        let mut counts = [0usize; 4];
+        for _ in 0..RUNS {
+            let mut data: Vec<usize> = (0..12).collect();
+            stratified_shuffle(&mut rng, &mut data, &strata);
+            let rank = data.iter().filter(|&&x| x < 4).position(|&x| x == 0);
+            counts[rank.unwrap()] += 1;
+        }
+
+        for &count in &counts {
+            assert!(count > RUNS / 4 * 9 / 10 && count < RUNS / 4 * 11 / 10);
+        }
+    }
+
+    mod single_stratum {
+        use super::*;
+
+        fn shuffle_single_stratum<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            let n = data.len();
+            stratified_shuffle(rng, data, std::slice::from_ref(&(0..n)));
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_single_stratum);
+    }
+
+    #[test]
+    fn sample_keeps_proportions() {
+        let mut rng = Pcg64Mcg::seed_from_u64(23456);
+
+        let sizes = [100, 10, 300, 0, 40];
+        let strata = strata_of(&sizes);
+        let n: usize = sizes.iter().sum();
+
+        for k in [0, 1, 10, 45, 200, n] {
+            let sample = stratified_sample(&mut rng, &strata, k);
+            assert_eq!(sample.len(), k);
+
+            let mut sorted = sample.clone();
+            sorted.sort();
+            sorted.dedup();
+            assert_eq!(sorted.len(), k);
+
+            let classes: Vec<usize> = sample
+                .iter()
+                .map(|x| strata.iter().position(|s| s.contains(x)).unwrap())
+                .collect();
+            assert_proportional_prefixes(&classes, &sizes);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn strata_need_to_tile() {
+        let mut data = vec![0; 10];
+        stratified_shuffle(&mut Pcg64Mcg::seed_from_u64(1), &mut data, &[0..4, 5..10]);
+    }
+
+    #[test]
+    fn invalid_strata_are_error() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let mut data: Vec<usize> = (0..10).collect();
+
+        for strata in [vec![0..4, 5..10], vec![0..4, 4..9], vec![1..4, 4..10]] {
+            assert_eq!(
+                try_stratified_shuffle(&mut rng, &mut data, &strata),
+                Err(ShuffleError::InvalidStrata)
+            );
+            assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+        }
+
+        assert_eq!(
+            try_stratified_sample(&mut rng, &[0..4, 5..10], 2),
+            Err(ShuffleError::InvalidStrata)
+        );
+        assert_eq!(
+            try_stratified_sample(&mut rng, &[0..4, 4..10], 11),
+            Err(ShuffleError::SampleTooLarge)
+        );
+    }
+}
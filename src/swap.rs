@@ -0,0 +1,96 @@
+//! Element exchanges for the inner loops of the Fisher-Yates and rough shuffles.
+//!
+//! [`slice::swap`] handles arbitrary (possibly overlapping) elements via
+//! [`std::ptr::swap`], which moves large elements in chunks through a stack buffer.
+//! For elements that fit into a few registers, it is cheaper to load both elements
+//! and store them crosswise. Stable Rust offers no specialization for `T: Copy`, so
+//! the variant is selected by the size of `T` at compile time; a swap only moves
+//! bits, which is sound for any `T`. Without the `unsafe_algos` feature, the
+//! exchanges always use [`slice::swap`] and [`std::mem::swap`]. Both variants produce
+//! the same result, so the output of a shuffle does not depend on the selection.
+
+/// Elements of at most this many bytes are exchanged by value (see [module documentation](self)).
+#[cfg_attr(not(feature = "unsafe_algos"), allow(dead_code))]
+pub const MAX_BY_VALUE_BYTES: usize = 32;
+
+/// Exchanges `data[i]` and `data[j]`; the indices may be equal.
+///
+/// # Panics
+/// If `i` or `j` is out of bounds.
+#[inline(always)]
+pub fn swap<T>(data: &mut [T], i: usize, j: usize) {
+    #[cfg(feature = "unsafe_algos")]
+    if std::mem::size_of::<T>() <= MAX_BY_VALUE_BYTES {
+        assert!(i < data.len() && j < data.len());
+
+        // SAFETY: both indices are in bounds, and reading both elements before writing
+        // either of them keeps the exchange correct for `i == j`
+        unsafe {
+            let ptr = data.as_mut_ptr();
+            let a = ptr.add(i).read();
+            let b = ptr.add(j).read();
+            ptr.add(i).write(b);
+            ptr.add(j).write(a);
+        }
+        return;
+    }
+
+    data.swap(i, j)
+}
+
+/// Exchanges the elements behind `a` and `b`; same as [`std::mem::swap`], but by
+/// value for small elements.
+#[inline(always)]
+pub fn swap_refs<T>(a: &mut T, b: &mut T) {
+    #[cfg(feature = "unsafe_algos")]
+    if std::mem::size_of::<T>() <= MAX_BY_VALUE_BYTES {
+        // SAFETY: `a` and `b` are valid and, being mutable references, do not overlap
+        unsafe {
+            let tmp = std::ptr::read(a);
+            std::ptr::copy_nonoverlapping(b, a, 1);
+            std::ptr::write(b, tmp);
+        }
+        return;
+    }
+
+    std::mem::swap(a, b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check_swap<T: Clone + PartialEq + std::fmt::Debug>(make: impl Fn(usize) -> T) {
+        let orig: Vec<T> = (0..5).map(&make).collect();
+
+        for i in 0..5 {
+            for j in 0..5 {
+                let mut data = orig.clone();
+                swap(&mut data, i, j);
+
+                let mut expected = orig.clone();
+                expected.swap(i, j);
+                assert_eq!(data, expected, "i={i} j={j}");
+            }
+        }
+
+        let (mut a, mut b) = (make(1), make(2));
+        swap_refs(&mut a, &mut b);
+        assert_eq!((a, b), (make(2), make(1)));
+    }
+
+    #[test]
+    fn swaps_small_and_large_elements() {
+        check_swap(|i| i as u8);
+        check_swap(|i| [i as u64; 4]);
+        check_swap(|i| [i as u64; 5]);
+        check_swap(|i| vec![i; i]);
+        check_swap(|_| ());
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_out_of_bounds() {
+        swap(&mut [1, 2, 3], 0, 3);
+    }
+}
@@ -0,0 +1,290 @@
+//! Production telemetry via the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! With the `metrics` feature, the shuffling entry points emit the following metrics to
+//! the recorder installed by the application (e.g., a Prometheus exporter). Without the
+//! feature, the instrumentation compiles to nothing. The metrics use static keys, so the
+//! entry points remain allocation-free unless the installed recorder allocates. Only
+//! regions of custom configurations that `MetricsProfiler` does not know by name
+//! allocate their key.
+//!
+//! | Name | Type | Labels |
+//! |------|------|--------|
+//! | [`BYTES_TOTAL`] | counter | `mode` (`seq` or `par`) |
+//! | [`DURATION_SECONDS`] | histogram | `mode` (`seq` or `par`) |
+//! | [`PHASE_DURATION_SECONDS`] | histogram | `phase` (e.g., `RoughScatter`) |
+//! | [`FALLBACKS_TOTAL`] | counter | `fallback` (see [`Fallback`]) |
+//!
+//! The durations of the phases are recorded by `MetricsProfiler`, which the default
+//! configuration of [`crate::scatter_shuffle::parallel::par_scatter_shuffle`] uses
+//! with the feature enabled. Custom configurations can use it as their
+//! [`crate::scatter_shuffle::ParConfiguration::Profiler`].
+
+#[cfg(feature = "metrics")]
+use crate::profiler::{Profiler, ProfilerFrame};
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+/// Counter of the bytes shuffled by [`crate::RipShuffleSequential::seq_shuffle`]
+/// and [`crate::RipShuffleParallel::par_shuffle`].
+pub const BYTES_TOTAL: &str = "rip_shuffle_bytes_total";
+
+/// Histogram of the durations of [`crate::RipShuffleSequential::seq_shuffle`]
+/// and [`crate::RipShuffleParallel::par_shuffle`].
+pub const DURATION_SECONDS: &str = "rip_shuffle_duration_seconds";
+
+/// Histogram of the durations of the phases of the parallel scatter shuffle.
+pub const PHASE_DURATION_SECONDS: &str = "rip_shuffle_phase_duration_seconds";
+
+/// Counter of the occurrences of slower fallback paths.
+pub const FALLBACKS_TOTAL: &str = "rip_shuffle_fallbacks_total";
+
+/// Slower code paths that are taken for unusual inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fallback {
+    /// The stashes of the scatter shuffle did not fit into a single bucket and were
+    /// shuffled with the non-contiguous Fisher-Yates implementation.
+    NoncontiguousStash,
+    /// The dart-throwing shuffle received `2^32` or more elements and used the
+    /// scatter shuffle instead.
+    DartToScatter,
+}
+
+impl Fallback {
+    pub fn label(self) -> &'static str {
+        match self {
+            Fallback::NoncontiguousStash => "noncontiguous_stash",
+            Fallback::DartToScatter => "dart_to_scatter",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Mode {
+    Sequential,
+    Parallel,
+}
+
+/// Records the bytes and the duration of a shuffle when dropped.
+pub(crate) struct ShuffleGuard {
+    #[cfg(feature = "metrics")]
+    mode: Mode,
+    #[cfg(feature = "metrics")]
+    start: Instant,
+}
+
+impl ShuffleGuard {
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    #[inline]
+    pub(crate) fn new(mode: Mode, num_bytes: usize) -> Self {
+        #[cfg(feature = "metrics")]
+        {
+            let bytes = num_bytes as u64;
+            match mode {
+                Mode::Sequential => {
+                    metrics::counter!(BYTES_TOTAL, "mode" => "seq").increment(bytes)
+                }
+                Mode::Parallel => metrics::counter!(BYTES_TOTAL, "mode" => "par").increment(bytes),
+            }
+
+            Self {
+                mode,
+                start: Instant::now(),
+            }
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        Self {}
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for ShuffleGuard {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        match self.mode {
+            Mode::Sequential => {
+                metrics::histogram!(DURATION_SECONDS, "mode" => "seq").record(elapsed)
+            }
+            Mode::Parallel => {
+                metrics::histogram!(DURATION_SECONDS, "mode" => "par").record(elapsed)
+            }
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+#[inline]
+pub(crate) fn record_fallback(fallback: Fallback) {
+    // literal labels yield a static key; a label computed at runtime would allocate
+    #[cfg(feature = "metrics")]
+    match fallback {
+        Fallback::NoncontiguousStash => {
+            metrics::counter!(FALLBACKS_TOTAL, "fallback" => "noncontiguous_stash").increment(1)
+        }
+        Fallback::DartToScatter => {
+            metrics::counter!(FALLBACKS_TOTAL, "fallback" => "dart_to_scatter").increment(1)
+        }
+    }
+}
+
+/// Profiler that records the duration of each region in the histogram
+/// [`PHASE_DURATION_SECONDS`].
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetricsProfiler;
+
+#[cfg(feature = "metrics")]
+impl Profiler for MetricsProfiler {
+    type Frame = MetricsFrame;
+
+    fn start(&self, _region: &'static str) -> Self::Frame {
+        MetricsFrame { region: None }
+    }
+}
+
+/// Frame of a [`MetricsProfiler`]; only the regions within the frame are recorded.
+#[cfg(feature = "metrics")]
+pub struct MetricsFrame {
+    region: Option<(&'static str, Instant)>,
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsFrame {
+    fn finish_region(&mut self) {
+        if let Some((phase, start)) = self.region.take() {
+            record_phase(phase, start.elapsed());
+        }
+    }
+}
+
+/// Records a phase of the parallel scatter shuffle without allocating; as for
+/// [`record_fallback`], only literal labels yield a static key.
+#[cfg(feature = "metrics")]
+fn record_phase(phase: &'static str, elapsed: std::time::Duration) {
+    macro_rules! record {
+        ($($name:literal),*) => {
+            match phase {
+                $($name => metrics::histogram!(PHASE_DURATION_SECONDS, "phase" => $name),)*
+                _ => metrics::histogram!(PHASE_DURATION_SECONDS, "phase" => phase),
+            }
+            .record(elapsed)
+        };
+    }
+
+    record!(
+        "RoughScatter",
+        "ShuffleStashes",
+        "SampleFinalBucketSize",
+        "TwoSweep"
+    );
+}
+
+#[cfg(feature = "metrics")]
+impl ProfilerFrame for MetricsFrame {
+    fn new_region(&mut self, name: &'static str) {
+        self.finish_region();
+        self.region = Some((name, Instant::now()));
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for MetricsFrame {
+    fn drop(&mut self) {
+        self.finish_region();
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod test {
+    use super::*;
+    use crate::RipShuffleSequential;
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use rand::SeedableRng;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects the names and labels of all updates
+    #[derive(Default)]
+    struct Collector(Arc<Mutex<Vec<String>>>);
+
+    struct Handle(Key, Arc<Mutex<Vec<String>>>);
+
+    impl Handle {
+        fn push(&self) {
+            let labels: Vec<_> = self.0.labels().map(|l| l.value().to_owned()).collect();
+            let entry = format!("{}{:?}", self.0.name(), labels);
+            self.1.lock().unwrap().push(entry);
+        }
+    }
+
+    impl CounterFn for Handle {
+        fn increment(&self, _value: u64) {
+            self.push()
+        }
+        fn absolute(&self, _value: u64) {
+            self.push()
+        }
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, _value: f64) {
+            self.push()
+        }
+    }
+
+    impl Recorder for Collector {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(Arc::new(Handle(key.clone(), self.0.clone())))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(Arc::new(Handle(key.clone(), self.0.clone())))
+        }
+    }
+
+    #[test]
+    fn seq_shuffle_emits_metrics() {
+        let collector = Collector::default();
+        let mut data: Vec<u32> = (0..1000).collect();
+
+        metrics::with_local_recorder(&collector, || {
+            data.seq_shuffle(&mut rand_pcg::Pcg64Mcg::seed_from_u64(1));
+            record_fallback(Fallback::DartToScatter);
+        });
+
+        let entries = collector.0.lock().unwrap();
+        assert!(entries.contains(&format!("{BYTES_TOTAL}[\"seq\"]")));
+        assert!(entries.contains(&format!("{DURATION_SECONDS}[\"seq\"]")));
+        assert!(entries.contains(&format!("{FALLBACKS_TOTAL}[\"dart_to_scatter\"]")));
+    }
+
+    #[test]
+    fn profiler_records_regions() {
+        let collector = Collector::default();
+
+        metrics::with_local_recorder(&collector, || {
+            let mut frame = MetricsProfiler.start("ParScatter");
+            frame.new_region("RoughScatter");
+            frame.new_region("TwoSweep");
+        });
+
+        let entries = collector.0.lock().unwrap();
+        assert_eq!(
+            *entries,
+            [
+                format!("{PHASE_DURATION_SECONDS}[\"RoughScatter\"]"),
+                format!("{PHASE_DURATION_SECONDS}[\"TwoSweep\"]"),
+            ]
+        );
+    }
+}
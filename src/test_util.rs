@@ -0,0 +1,308 @@
+//! Deterministic random number generators for tests.
+//!
+//! Tests of custom configurations and base cases (see
+//! [`crate::scatter_shuffle::ParConfiguration`]) often need to know exactly which random
+//! words a shuffle receives. This module offers three small generators:
+//!
+//! - [`CountingRng`] returns consecutive integers and counts how many words were drawn.
+//! - [`SequenceRng`] cycles through a fixed sequence of words.
+//! - [`RecordingRng`] wraps another generator and records all bytes it hands out, so a
+//!   failing run can be replayed with [`crate::replay_rng::ReplayRng`].
+//!
+//! All of them implement [`rand::SeedableRng`] and can hence be passed to the parallel
+//! entry points, which seed one generator per task from their parent (see the
+//! documentation of the individual types). None of them is random in any sense;
+//! shuffles with [`CountingRng`] and [`SequenceRng`] are far from uniform.
+//!
+//! The module is available with the `test_util` feature.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::test_util::{CountingRng, RecordingRng};
+//! use rip_shuffle::replay_rng::ReplayRng;
+//! use rip_shuffle::RipShuffleSequential;
+//!
+//! let mut rng = RecordingRng::new(CountingRng::new(1));
+//! let mut first: Vec<u32> = (0..100).collect();
+//! first.seq_shuffle(&mut rng);
+//! assert!(rng.inner().count() > 0);
+//!
+//! let mut second: Vec<u32> = (0..100).collect();
+//! second.seq_shuffle(&mut ReplayRng::new(rng.recorded()));
+//! assert_eq!(first, second);
+//! ```
+
+use rand::{RngCore, SeedableRng};
+
+/// Writes the words of `next_u64` to `dest` in little-endian order; a final partial
+/// word is truncated.
+fn fill_bytes_via_u64<R: RngCore>(rng: &mut R, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+        let word = rng.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&word[..chunk.len()]);
+    }
+}
+
+/// Returns `start`, `start + 1`, ... (wrapping) from `next_u64`; `next_u32` returns the
+/// lower half of the next word. Task generators seeded via [`SeedableRng::from_seed`]
+/// start at the first eight bytes of the seed (little-endian).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CountingRng {
+    next: u64,
+    count: usize,
+}
+
+impl CountingRng {
+    pub fn new(start: u64) -> Self {
+        Self {
+            next: start,
+            count: 0,
+        }
+    }
+
+    /// Number of words (of either width) drawn so far; [`RngCore::fill_bytes`] draws one
+    /// word per started group of eight bytes.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl RngCore for CountingRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let word = self.next;
+        self.next = self.next.wrapping_add(1);
+        self.count += 1;
+        word
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for CountingRng {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::new(u64::from_le_bytes(seed))
+    }
+}
+
+/// Cycles through a fixed, non-empty sequence of words; `next_u32` returns the lower
+/// half of the next word. Task generators seeded via [`SeedableRng::from_seed`] cycle
+/// through the four little-endian words of the seed.
+///
+/// Uniform indices are drawn by rejection sampling, which loops forever if every word
+/// of the sequence is rejected; a sequence consisting of zeros is the typical example.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SequenceRng {
+    words: Vec<u64>,
+    pos: usize,
+}
+
+impl SequenceRng {
+    /// # Panics
+    /// If `words` is empty.
+    pub fn new(words: impl Into<Vec<u64>>) -> Self {
+        let words = words.into();
+        assert!(!words.is_empty(), "the sequence must not be empty");
+        Self { words, pos: 0 }
+    }
+
+    /// Index of the next word within the sequence.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl RngCore for SequenceRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let word = self.words[self.pos];
+        self.pos = (self.pos + 1) % self.words.len();
+        word
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for SequenceRng {
+    type Seed = [u8; 32];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let words: Vec<u64> = seed
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Self::new(words)
+    }
+}
+
+/// Forwards to the generator `R` and records the bytes it returns, in the format
+/// consumed by [`crate::replay_rng::ReplayRng`]: `next_u32` and `next_u64` append four
+/// and eight little-endian bytes, respectively. Task generators seeded via
+/// [`SeedableRng::from_seed`] record on their own; their records are not merged into
+/// the parent's.
+#[derive(Clone, Debug, Default)]
+pub struct RecordingRng<R> {
+    inner: R,
+    recorded: Vec<u8>,
+}
+
+impl<R> RecordingRng<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            recorded: Vec::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// The bytes handed out so far.
+    pub fn recorded(&self) -> &[u8] {
+        &self.recorded
+    }
+
+    /// Discards the recorded bytes.
+    pub fn clear(&mut self) {
+        self.recorded.clear()
+    }
+
+    pub fn into_parts(self) -> (R, Vec<u8>) {
+        (self.inner, self.recorded)
+    }
+}
+
+impl<R: RngCore> RngCore for RecordingRng<R> {
+    fn next_u32(&mut self) -> u32 {
+        let word = self.inner.next_u32();
+        self.recorded.extend_from_slice(&word.to_le_bytes());
+        word
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let word = self.inner.next_u64();
+        self.recorded.extend_from_slice(&word.to_le_bytes());
+        word
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest);
+        self.recorded.extend_from_slice(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.inner.try_fill_bytes(dest)?;
+        self.recorded.extend_from_slice(dest);
+        Ok(())
+    }
+}
+
+impl<R: SeedableRng> SeedableRng for RecordingRng<R> {
+    type Seed = R::Seed;
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::new(R::from_seed(seed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::replay_rng::ReplayRng;
+    use crate::{RipShuffleParallel, RipShuffleSequential};
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn counting_rng() {
+        let mut rng = CountingRng::new(u64::MAX - 1);
+        assert_eq!(rng.next_u64(), u64::MAX - 1);
+        assert_eq!(rng.next_u64(), u64::MAX);
+        assert_eq!(rng.next_u32(), 0);
+
+        let mut bytes = [0; 10];
+        rng.fill_bytes(&mut bytes);
+        assert_eq!(bytes, [1, 0, 0, 0, 0, 0, 0, 0, 2, 0]);
+        assert_eq!(rng.count(), 5);
+
+        assert_eq!(CountingRng::seed_from_u64(3), CountingRng::seed_from_u64(3));
+        assert_eq!(CountingRng::from_seed(7u64.to_le_bytes()).next_u64(), 7);
+    }
+
+    #[test]
+    fn sequence_rng() {
+        let mut rng = SequenceRng::new([1, 2, 3]);
+        let words: Vec<u64> = (0..7).map(|_| rng.next_u64()).collect();
+        assert_eq!(words, [1, 2, 3, 1, 2, 3, 1]);
+        assert_eq!(rng.position(), 1);
+        assert_eq!(rng.next_u32(), 2);
+
+        let mut seed = [0; 32];
+        seed[8] = 5;
+        let mut rng = SequenceRng::from_seed(seed);
+        assert_eq!([rng.next_u64(), rng.next_u64()], [0, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty")]
+    fn sequence_rng_rejects_empty_sequences() {
+        SequenceRng::new(Vec::new());
+    }
+
+    #[test]
+    fn recording_rng_replays() {
+        let mut rng = RecordingRng::new(Pcg64Mcg::seed_from_u64(1));
+        let mut data: Vec<u32> = (0..1000).collect();
+        data.seq_shuffle(&mut rng);
+
+        let mut replayed: Vec<u32> = (0..1000).collect();
+        let mut replay = ReplayRng::new(rng.recorded());
+        replayed.seq_shuffle(&mut replay);
+        assert_eq!(data, replayed);
+        assert_eq!(replay.remaining(), Some(0));
+
+        rng.clear();
+        assert!(rng.recorded().is_empty());
+    }
+
+    #[test]
+    fn shuffles_are_deterministic() {
+        for n in [100, 1 << 20] {
+            let shuffle = |rng: &mut SequenceRng| {
+                let mut data: Vec<u32> = (0..n).collect();
+                data.par_shuffle(rng);
+                data
+            };
+
+            let words = [3, 1 << 63, u64::MAX, 12345];
+            let data = shuffle(&mut SequenceRng::new(words));
+            assert_eq!(data, shuffle(&mut SequenceRng::new(words)));
+
+            let mut sorted = data.clone();
+            sorted.sort();
+            assert!(sorted.iter().copied().eq(0..n));
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Shuffling of UTF-8 strings.
+//!
+//! Strings cannot be shuffled byte-wise without risking invalid UTF-8 (unless
+//! they are pure ASCII, which we shuffle in place). We therefore rearrange whole
+//! segments (chars or grapheme clusters) in place: each segment flips a coin and the
+//! segments are partitioned accordingly, recursively splitting the range and swapping
+//! the middle parts with a rotation of the byte ranges. Both parts are then shuffled
+//! recursively; short ranges are shuffled with a Fisher-Yates on a stack buffer. Only
+//! whole segments are moved, so the text is valid UTF-8 at all times. We store the
+//! length of each segment (one byte per char, four bytes per grapheme cluster for
+//! strings of less than 4 GiB), but no copy of the text. Rearranging `b` bytes of `m`
+//! segments takes expected `O((b + m) log^2 m)` time; this is several times slower
+//! than a round trip through a `Vec<char>`, but needs no copy of the text.
+
+use super::fisher_yates::fisher_yates;
+use super::scatter_shuffle::sequential::seq_scatter_shuffle;
+use rand::Rng;
+
+/// Ranges of at most this many bytes are partitioned and shuffled in a stack buffer;
+/// together with the segment indices, the base cases use about 40 KiB of stack.
+const BASE_CASE_BYTES: usize = 1 << 12;
+
+/// Rearranges the chars of `text` in a random permutation, such that any order
+/// appears with equal probability.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::text::shuffle_chars;
+///
+/// let mut text = String::from("Grüße, 世界!");
+/// shuffle_chars(&mut rand::thread_rng(), &mut text);
+///
+/// assert_eq!(text.chars().count(), 10);
+/// ```
+pub fn shuffle_chars<R: Rng>(rng: &mut R, text: &mut str) {
+    if text.is_ascii() {
+        // every byte is a char on its own and any permutation of ASCII bytes is valid UTF-8
+        let bytes = unsafe { text.as_bytes_mut() };
+        return seq_scatter_shuffle(rng, bytes);
+    }
+
+    let mut lens: Vec<u8> = text.chars().map(|c| c.len_utf8() as u8).collect();
+    // SAFETY: only whole segments are moved, so `text` remains valid UTF-8
+    shuffle_segments(rng, unsafe { text.as_bytes_mut() }, &mut lens);
+}
+
+/// Rearranges the extended grapheme clusters of `text` in a random permutation,
+/// such that any order appears with equal probability. In contrast to [`shuffle_chars`],
+/// combining characters stay attached to their base character.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::text::shuffle_graphemes;
+///
+/// let mut text = String::from("e\u{301}a\u{301}o\u{301}");
+/// shuffle_graphemes(&mut rand::thread_rng(), &mut text);
+///
+/// assert!(text.chars().step_by(2).all(|c| "eao".contains(c)));
+/// ```
+#[cfg(feature = "graphemes")]
+pub fn shuffle_graphemes<R: Rng>(rng: &mut R, text: &mut str) {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    // SAFETY: only whole segments are moved, so `text` remains valid UTF-8
+    if u32::try_from(text.len()).is_ok() {
+        let mut lens: Vec<u32> = text.graphemes(true).map(|g| g.len() as u32).collect();
+        shuffle_segments(rng, unsafe { text.as_bytes_mut() }, &mut lens);
+    } else {
+        let mut lens: Vec<usize> = text.graphemes(true).map(str::len).collect();
+        shuffle_segments(rng, unsafe { text.as_bytes_mut() }, &mut lens);
+    }
+}
+
+/// Unsigned integer type storing the lengths of the segments.
+trait SegmentLen: Copy + Default {
+    fn to_usize(self) -> usize;
+}
+
+impl SegmentLen for u8 {
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+impl SegmentLen for u32 {
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+impl SegmentLen for usize {
+    fn to_usize(self) -> usize {
+        self
+    }
+}
+
+fn total_len<L: SegmentLen>(lens: &[L]) -> usize {
+    lens.iter().map(|l| l.to_usize()).sum()
+}
+
+/// Rearranges the consecutive segments of `bytes` with lengths `lens` in a random order.
+/// Afterwards, `lens` is in an unspecified order.
+fn shuffle_segments<R: Rng, L: SegmentLen>(rng: &mut R, bytes: &mut [u8], lens: &mut [L]) {
+    debug_assert_eq!(total_len(lens), bytes.len());
+
+    if lens.len() <= 1 {
+        return;
+    }
+
+    if bytes.len() <= BASE_CASE_BYTES {
+        return shuffle_segments_small(rng, bytes, lens);
+    }
+
+    let (num_lower, lower_bytes) = partition_segments(rng, bytes, lens);
+
+    let (bytes_lower, bytes_upper) = bytes.split_at_mut(lower_bytes);
+    let (lens_lower, lens_upper) = lens.split_at_mut(num_lower);
+
+    shuffle_segments(rng, bytes_lower, lens_lower);
+    shuffle_segments(rng, bytes_upper, lens_upper);
+}
+
+/// Fisher-Yates shuffle of the segments via a stack buffer of [`BASE_CASE_BYTES`].
+fn shuffle_segments_small<R: Rng, L: SegmentLen>(rng: &mut R, bytes: &mut [u8], lens: &[L]) {
+    let mut buffer = [0u8; BASE_CASE_BYTES];
+    let buffer = &mut buffer[..bytes.len()];
+    buffer.copy_from_slice(bytes);
+
+    // each segment has at least one byte, so there are at most `BASE_CASE_BYTES` of them
+    let mut starts = [0u16; BASE_CASE_BYTES];
+    let mut order = [0u16; BASE_CASE_BYTES];
+    let mut begin = 0;
+    for (i, len) in lens.iter().enumerate() {
+        starts[i] = begin as u16;
+        order[i] = i as u16;
+        begin += len.to_usize();
+    }
+
+    let order = &mut order[..lens.len()];
+    fisher_yates(rng, order);
+
+    let mut end = 0;
+    for &i in order.iter() {
+        let begin = starts[i as usize] as usize;
+        let len = lens[i as usize].to_usize();
+        bytes[end..end + len].copy_from_slice(&buffer[begin..begin + len]);
+        end += len;
+    }
+}
+
+/// Moves the segments that draw a zero bit to the front and returns their number and
+/// total length. The order within both parts is not random.
+fn partition_segments<R: Rng, L: SegmentLen>(
+    rng: &mut R,
+    bytes: &mut [u8],
+    lens: &mut [L],
+) -> (usize, usize) {
+    if bytes.len() <= BASE_CASE_BYTES {
+        return partition_segments_small(rng, bytes, lens);
+    }
+
+    let mid = lens.len() / 2;
+    let mid_bytes = total_len(&lens[..mid]);
+
+    let (left_lower, left_bytes) = {
+        let (bytes, _) = bytes.split_at_mut(mid_bytes);
+        partition_segments(rng, bytes, &mut lens[..mid])
+    };
+    let (right_lower, right_bytes) = {
+        let (_, bytes) = bytes.split_at_mut(mid_bytes);
+        partition_segments(rng, bytes, &mut lens[mid..])
+    };
+
+    // [lower left | upper left | lower right | upper right]: swap the middle parts
+    bytes[left_bytes..mid_bytes + right_bytes].rotate_left(mid_bytes - left_bytes);
+    lens[left_lower..mid + right_lower].rotate_left(mid - left_lower);
+
+    (left_lower + right_lower, left_bytes + right_bytes)
+}
+
+/// Same as [`partition_segments`] via a stack buffer of [`BASE_CASE_BYTES`]; the lower
+/// segments are written from the front, the upper ones from the back.
+fn partition_segments_small<R: Rng, L: SegmentLen>(
+    rng: &mut R,
+    bytes: &mut [u8],
+    lens: &mut [L],
+) -> (usize, usize) {
+    let mut buffer = [0u8; BASE_CASE_BYTES];
+    let buffer = &mut buffer[..bytes.len()];
+    buffer.copy_from_slice(bytes);
+
+    let mut lens_buffer = [L::default(); BASE_CASE_BYTES];
+    let lens_buffer = &mut lens_buffer[..lens.len()];
+    lens_buffer.copy_from_slice(lens);
+
+    let (mut num_lower, mut num_upper) = (0, 0);
+    let (mut lower_bytes, mut upper_bytes) = (0, 0);
+    let mut begin = 0;
+    let mut coins = 0u64;
+
+    for (i, &len) in lens_buffer.iter().enumerate() {
+        if i % 64 == 0 {
+            coins = rng.gen();
+        }
+
+        let segment = &buffer[begin..begin + len.to_usize()];
+        begin += segment.len();
+
+        if coins & 1 == 0 {
+            bytes[lower_bytes..lower_bytes + segment.len()].copy_from_slice(segment);
+            lower_bytes += segment.len();
+            lens[num_lower] = len;
+            num_lower += 1;
+        } else {
+            upper_bytes += segment.len();
+            let end = bytes.len() - upper_bytes;
+            bytes[end..end + segment.len()].copy_from_slice(segment);
+            num_upper += 1;
+            lens[lens.len() - num_upper] = len;
+        }
+
+        coins >>= 1;
+    }
+
+    (num_lower, lower_bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    fn sorted_chars(text: &str) -> Vec<char> {
+        let mut chars: Vec<_> = text.chars().collect();
+        chars.sort();
+        chars
+    }
+
+    #[test]
+    fn preserve_chars() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        let long = "Grüße, 世界! 🦀".repeat(10_000); // exceeds the base case
+        for input in [
+            "",
+            "a",
+            "hello world",
+            "Grüße, 世界!",
+            "🦀🦀 crab 🦀",
+            "ñ",
+            &long,
+        ] {
+            let mut text = String::from(input);
+            shuffle_chars(&mut rng, &mut text);
+            assert_eq!(text.len(), input.len());
+            assert_eq!(sorted_chars(&text), sorted_chars(input));
+        }
+    }
+
+    #[cfg(feature = "graphemes")]
+    #[test]
+    fn preserve_graphemes() {
+        use unicode_segmentation::UnicodeSegmentation;
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+
+        let short = "e\u{301}a\u{301}o\u{301}🇩🇪🇫🇷xyz";
+        let long = short.repeat(1000); // exceeds the base case
+
+        for (input, runs) in [(short, 100), (long.as_str(), 3)] {
+            let mut expected: Vec<_> = input.graphemes(true).collect();
+            expected.sort();
+
+            for _ in 0..runs {
+                let mut text = String::from(input);
+                shuffle_graphemes(&mut rng, &mut text);
+
+                let mut graphemes: Vec<_> = text.graphemes(true).collect();
+                graphemes.sort();
+                assert_eq!(graphemes, expected);
+            }
+        }
+    }
+
+    mod ascii {
+        use super::*;
+
+        fn shuffle_via_string<R: Rng>(rng: &mut R, data: &mut [usize]) {
+            let mut text: String = data.iter().map(|&x| (x % 128) as u8 as char).collect();
+            shuffle_chars(rng, &mut text);
+            for (x, c) in data.iter_mut().zip(text.chars()) {
+                *x = c as usize;
+            }
+        }
+
+        #[test]
+        fn test_1_independence() {
+            let mut rng = Pcg64Mcg::seed_from_u64(2345);
+            let n = 20;
+            let mut seen = vec![vec![false; n]; n];
+
+            for _ in 0..2000 {
+                let mut data: Vec<usize> = (0..n).collect();
+                shuffle_via_string(&mut rng, &mut data);
+                for (pos, &x) in data.iter().enumerate() {
+                    seen[x][pos] = true;
+                }
+            }
+
+            assert!(seen.iter().flatten().all(|&s| s));
+        }
+    }
+
+    mod multibyte {
+        use super::*;
+
+        fn shuffle_via_string<R: Rng>(rng: &mut R, data: &mut [usize]) {
+            const OFFSET: usize = 0x100; // ensures multi-byte encodings
+            let mut text: String = data
+                .iter()
+                .map(|&x| char::from_u32((x + OFFSET) as u32).unwrap())
+                .collect();
+            shuffle_chars(rng, &mut text);
+            for (x, c) in data.iter_mut().zip(text.chars()) {
+                *x = c as usize - OFFSET;
+            }
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_via_string);
+
+        #[test]
+        fn marker_spreads_beyond_base_case() {
+            let mut rng = Pcg64Mcg::seed_from_u64(3456);
+            const RUNS: usize = 2000;
+            let n = 4 * BASE_CASE_BYTES / 3; // three bytes per char
+
+            let input: String = std::iter::once('€')
+                .chain(std::iter::repeat_n('世', n - 1))
+                .collect();
+            let mut counts = [0usize; 10];
+            for _ in 0..RUNS {
+                let mut text = input.clone();
+                shuffle_chars(&mut rng, &mut text);
+                let pos = text.chars().position(|c| c == '€').unwrap();
+                counts[10 * pos / n] += 1;
+            }
+
+            assert!(counts
+                .iter()
+                .all(|&c| 4 * c > 3 * RUNS / 10 && 4 * c < 5 * RUNS / 10));
+        }
+    }
+}
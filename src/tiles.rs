@@ -0,0 +1,236 @@
+//! Shuffling rectangular tiles of images.
+//!
+//! Data augmentation and jigsaw-style pretraining cut an image into a grid of equally
+//! sized tiles and rearrange them randomly. [`shuffle_tiles`] does this in place on a
+//! row-major pixel buffer (a pixel may be any type, e.g. `[u8; 3]` for RGB images).
+//!
+//! A tile is not contiguous in memory; swapping two tiles amounts to swapping
+//! `tile_h` strided row segments. The random permutation of the tiles is drawn once as a
+//! sequence of Fisher-Yates swaps. Since the `r`-th rows of all tiles are disjoint from
+//! the other rows, the sequence is then applied to each row offset `r` in parallel.
+
+use super::error::ShuffleError;
+use super::uniform_index;
+use rand::Rng;
+use rayon::prelude::*;
+
+/// Randomly permutes the `tile_w x tile_h` tiles of the `width x height` image stored in
+/// row-major order in `pixels`. Each tile is moved as a whole; all arrangements of the
+/// tiles are equally likely.
+///
+/// # Panics
+/// Panics if `pixels` does not contain `width * height` pixels or if the tiles do not
+/// evenly divide the image; see [`try_shuffle_tiles`] for a non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::tiles::shuffle_tiles;
+///
+/// // a 4x4 image consisting of four 2x2 tiles with values 0, 1, 2, and 3
+/// let mut pixels = vec![
+///     0, 0, 1, 1,
+///     0, 0, 1, 1,
+///     2, 2, 3, 3,
+///     2, 2, 3, 3,
+/// ];
+///
+/// shuffle_tiles(&mut rand::thread_rng(), &mut pixels, 4, 4, 2, 2);
+///
+/// for (ty, tx) in [(0, 0), (0, 2), (2, 0), (2, 2)] {
+///     let value = pixels[ty * 4 + tx];
+///     assert!([0, 1, 4, 5].iter().all(|&offset| pixels[ty * 4 + tx + offset] == value));
+/// }
+/// ```
+pub fn shuffle_tiles<R: Rng, P: Send>(
+    rng: &mut R,
+    pixels: &mut [P],
+    width: usize,
+    height: usize,
+    tile_w: usize,
+    tile_h: usize,
+) {
+    try_shuffle_tiles(rng, pixels, width, height, tile_w, tile_h).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`shuffle_tiles`], but returns [`ShuffleError::ShapeMismatch`] if `pixels`
+/// does not contain `width * height` pixels, and [`ShuffleError::InvalidTiles`] if the
+/// tiles do not evenly divide the image. In both cases, the input remains untouched.
+pub fn try_shuffle_tiles<R: Rng, P: Send>(
+    rng: &mut R,
+    pixels: &mut [P],
+    width: usize,
+    height: usize,
+    tile_w: usize,
+    tile_h: usize,
+) -> Result<(), ShuffleError> {
+    if width.checked_mul(height) != Some(pixels.len()) {
+        return Err(ShuffleError::ShapeMismatch);
+    }
+
+    if tile_w == 0 || tile_h == 0 || !width.is_multiple_of(tile_w) || !height.is_multiple_of(tile_h)
+    {
+        return Err(ShuffleError::InvalidTiles);
+    }
+
+    if pixels.is_empty() {
+        return Ok(());
+    }
+
+    let tiles_x = width / tile_w;
+    let num_tiles = tiles_x * (height / tile_h);
+
+    let swaps: Vec<(usize, usize)> = (1..num_tiles)
+        .rev()
+        .map(|i| (i, uniform_index::gen_index(rng, i + 1)))
+        .filter(|&(i, j)| i != j)
+        .collect();
+
+    // `rows_by_offset[r][ty]` is the `r`-th row of the `ty`-th row of tiles
+    let mut rows_by_offset: Vec<Vec<&mut [P]>> = (0..tile_h).map(|_| Vec::new()).collect();
+    for (y, row) in pixels.chunks_mut(width).enumerate() {
+        rows_by_offset[y % tile_h].push(row);
+    }
+
+    rows_by_offset.par_iter_mut().for_each(|rows| {
+        for &(i, j) in &swaps {
+            swap_segments(
+                rows,
+                (i / tiles_x, i % tiles_x),
+                (j / tiles_x, j % tiles_x),
+                tile_w,
+            );
+        }
+    });
+
+    Ok(())
+}
+
+/// Swaps the `tile_w` pixels of tile column `a.1` in row `a.0` with those of tile column
+/// `b.1` in row `b.0`.
+fn swap_segments<P>(rows: &mut [&mut [P]], a: (usize, usize), b: (usize, usize), tile_w: usize) {
+    let (a, b) = if a < b { (a, b) } else { (b, a) };
+
+    if a.0 == b.0 {
+        let (left, right) = rows[a.0].split_at_mut(b.1 * tile_w);
+        left[a.1 * tile_w..][..tile_w].swap_with_slice(&mut right[..tile_w]);
+    } else {
+        let (upper, lower) = rows.split_at_mut(b.0);
+        upper[a.0][a.1 * tile_w..][..tile_w]
+            .swap_with_slice(&mut lower[0][b.1 * tile_w..][..tile_w]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn tiles_stay_intact() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for (width, height, tile_w, tile_h) in
+            [(12, 6, 4, 3), (12, 6, 12, 1), (7, 5, 1, 5), (8, 8, 8, 8)]
+        {
+            let tiles_x = width / tile_w;
+
+            // each pixel stores its tile and its offset within the tile
+            let tile_and_offset = |x: usize, y: usize| {
+                (
+                    (y / tile_h) * tiles_x + x / tile_w,
+                    (y % tile_h) * tile_w + x % tile_w,
+                )
+            };
+
+            let mut pixels: Vec<_> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| tile_and_offset(x, y))
+                .collect();
+
+            shuffle_tiles(&mut rng, &mut pixels, width, height, tile_w, tile_h);
+
+            let mut tiles = Vec::new();
+            for y in 0..height {
+                for x in 0..width {
+                    let (tile, offset) = pixels[y * width + x];
+                    let (pos, expected_offset) = tile_and_offset(x, y);
+                    assert_eq!(offset, expected_offset);
+                    assert_eq!(
+                        tile,
+                        pixels[pos_of_tile_origin(pos, tiles_x, tile_w, tile_h, width)].0
+                    );
+                    tiles.push(tile);
+                }
+            }
+
+            tiles.sort();
+            tiles.dedup();
+            assert_eq!(tiles.len(), (width / tile_w) * (height / tile_h));
+        }
+    }
+
+    fn pos_of_tile_origin(
+        tile: usize,
+        tiles_x: usize,
+        tile_w: usize,
+        tile_h: usize,
+        width: usize,
+    ) -> usize {
+        (tile / tiles_x) * tile_h * width + (tile % tiles_x) * tile_w
+    }
+
+    #[test]
+    fn invalid_input() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let mut pixels: Vec<usize> = (0..24).collect();
+
+        for (width, height, tile_w, tile_h, err) in [
+            (5, 5, 1, 1, ShuffleError::ShapeMismatch),
+            (usize::MAX, 2, 1, 1, ShuffleError::ShapeMismatch),
+            (6, 4, 0, 1, ShuffleError::InvalidTiles),
+            (6, 4, 1, 0, ShuffleError::InvalidTiles),
+            (6, 4, 4, 2, ShuffleError::InvalidTiles),
+            (6, 4, 3, 3, ShuffleError::InvalidTiles),
+        ] {
+            assert_eq!(
+                try_shuffle_tiles(&mut rng, &mut pixels, width, height, tile_w, tile_h),
+                Err(err)
+            );
+        }
+
+        assert!(pixels.iter().enumerate().all(|(i, &x)| i == x));
+        assert!(try_shuffle_tiles(&mut rng, &mut pixels[..0], 0, 5, 1, 1).is_ok());
+    }
+
+    mod statistical {
+        use super::*;
+
+        // every element is a 1x1 tile of a single-row image
+        fn shuffle_as_tiles<R: Rng, T: Send>(rng: &mut R, data: &mut [T]) {
+            let n = data.len();
+            shuffle_tiles(rng, data, n, 1, 1, 1);
+        }
+
+        // every element is a 2x3 tile of an image with three rows
+        fn shuffle_as_2d_tiles<R: Rng, T: Send + Clone>(rng: &mut R, data: &mut [T]) {
+            let n = data.len();
+            let mut pixels: Vec<T> = (0..3)
+                .flat_map(|_| data.iter().flat_map(|x| [x.clone(), x.clone()]))
+                .collect();
+
+            shuffle_tiles(rng, &mut pixels, 2 * n, 3, 2, 3);
+
+            for (x, pixel) in data.iter_mut().zip(pixels.iter().step_by(2)) {
+                *x = pixel.clone();
+            }
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_as_tiles);
+
+        mod two_dimensional {
+            use super::*;
+            crate::statistical_tests::test_shuffle_algorithm!(shuffle_as_2d_tiles);
+        }
+    }
+}
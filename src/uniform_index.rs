@@ -1,16 +1,50 @@
 use rand::Rng;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 /// While `impl_32::gen_index` supports producing indices up to
 /// [`u32::MAX`], in practice, the rejection rate rises significantly
 /// for large upper bounds and it's then typically faster to use
 /// `impl_64::gen_index` in these regimes. This constant gives
-/// the recommended size when to switch.
+/// the recommended size when to switch and is the default of [`u32_upper_bound`].
 pub const U32_MAX_UPPER_BOUND: u32 = u32::MAX / 16;
 
+static U32_UPPER_BOUND: AtomicU32 = AtomicU32::new(U32_MAX_UPPER_BOUND);
+
+/// Returns the largest upper bound for which [`gen_index`] and the Fisher-Yates
+/// implementations use 32-bit arithmetic (see [`set_u32_upper_bound`]).
+#[inline]
+pub fn u32_upper_bound() -> u32 {
+    U32_UPPER_BOUND.load(Ordering::Relaxed)
+}
+
+/// Moves the crossover between 32-bit and 64-bit index generation for all subsequent
+/// calls (in all threads). The default [`U32_MAX_UPPER_BOUND`] was chosen on x86-64;
+/// on CPUs with cheap 64x64->128 bit multiplications a smaller bound may be faster.
+/// Use the `index_stats` feature to measure the rejection rates (see `uniform_index::stats`).
+///
+/// The bound does not affect the uniformity of the output, but the permutation
+/// produced for a given seed changes if it is moved. It has no effect on 32-bit
+/// targets, where 32-bit arithmetic is always used.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::uniform_index::{self, U32_MAX_UPPER_BOUND};
+///
+/// uniform_index::set_u32_upper_bound(1 << 20);
+/// assert!(uniform_index::gen_index(&mut rand::thread_rng(), 1 << 24) < 1 << 24);
+/// uniform_index::set_u32_upper_bound(U32_MAX_UPPER_BOUND);
+/// ```
+pub fn set_u32_upper_bound(bound: u32) {
+    U32_UPPER_BOUND.store(bound, Ordering::Relaxed);
+}
+
 /// Generates an index from the exclusive range `0..ub`
 /// uniformly at random. It is functionally equivalent to
 /// `rng.gen_range(0..ub)` but is much faster.
 ///
+/// On 32-bit targets, all bounds are handled by `impl_u32::gen_index`,
+/// since the wide multiplication of `impl_u64` would need to be emulated.
+///
 /// # Warning
 /// The upper bound must be strictly positive. This is not
 /// checked in release builds!
@@ -26,15 +60,99 @@ pub const U32_MAX_UPPER_BOUND: u32 = u32::MAX / 16;
 /// }
 /// ```
 pub fn gen_index(rng: &mut impl Rng, exclusive_ub: usize) -> usize {
-    if exclusive_ub <= U32_MAX_UPPER_BOUND as usize {
+    if cfg!(target_pointer_width = "32") || exclusive_ub <= u32_upper_bound() as usize {
         impl_u32::gen_index(rng, exclusive_ub as u32) as usize
     } else {
         impl_u64::gen_index(rng, exclusive_ub as u64) as usize
     }
 }
 
+/// Generates indices while consuming close to the information-theoretic minimum
+/// of `log2(ub)` random bits per index. This is much slower than [`gen_index`] in
+/// terms of arithmetic, but pays off if the random number generator is expensive
+/// (e.g., a hardware TRNG or a DRBG with frequent reseeding).
+///
+/// The pool maintains a value `x` that is uniformly distributed in `0..m`. An index
+/// below `ub` is taken as `x % ub` if `x` falls into the largest multiple of `ub`
+/// below `m`; the quotient `x / ub` then remains uniform and is kept for subsequent
+/// calls. Otherwise, `x` is uniform in the remainder range, which is also kept.
+/// In both cases no entropy is discarded and new bits are only requested once `m`
+/// drops below `2^64`.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::uniform_index::EntropyPool;
+///
+/// let mut pool = EntropyPool::new();
+/// let mut rng = rand::thread_rng();
+///
+/// // 100 dice rolls consume only 5 words of 64 bits (instead of 100)
+/// for _ in 0..100 {
+///     assert!(pool.gen_index(&mut rng, 6) < 6);
+/// }
+/// assert!(pool.bits_consumed() <= 5 * 64);
+/// ```
+#[derive(Clone, Debug)]
+pub struct EntropyPool {
+    value: u128,
+    range: u128,
+    bits_consumed: u64,
+}
+
+impl Default for EntropyPool {
+    fn default() -> Self {
+        Self {
+            value: 0,
+            range: 1,
+            bits_consumed: 0,
+        }
+    }
+}
+
+impl EntropyPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generates an index from the exclusive range `0..exclusive_ub`
+    /// uniformly at random.
+    ///
+    /// # Warning
+    /// The upper bound must be strictly positive. This is not
+    /// checked in release builds!
+    pub fn gen_index(&mut self, rng: &mut impl Rng, exclusive_ub: usize) -> usize {
+        debug_assert!(exclusive_ub != 0);
+        let ub = exclusive_ub as u128;
+
+        loop {
+            if self.range <= u64::MAX as u128 {
+                self.value = (self.value << 64) | rng.gen::<u64>() as u128;
+                self.range <<= 64;
+                self.bits_consumed += 64;
+            }
+
+            let accepted_range = self.range - self.range % ub;
+
+            if self.value < accepted_range {
+                let index = self.value % ub;
+                self.value /= ub;
+                self.range = accepted_range / ub;
+                return index as usize;
+            }
+
+            self.value -= accepted_range;
+            self.range -= accepted_range;
+        }
+    }
+
+    /// Number of random bits requested from the random number generators so far.
+    pub fn bits_consumed(&self) -> u64 {
+        self.bits_consumed
+    }
+}
+
 macro_rules! impl_gen_index {
-    ( $t : ty) => {
+    ( $t : ty, $counters : ident) => {
         use super::*;
 
         #[inline]
@@ -46,6 +164,8 @@ macro_rules! impl_gen_index {
         #[inline]
         pub fn gen_index_impl(rng: &mut impl Rng, initial: $t, exclusive_ub: $t) -> $t {
             debug_assert!(exclusive_ub != 0);
+            #[cfg(feature = "index_stats")]
+            stats::$counters.record_samples(1);
 
             let (mut lo, mut hi) = initial.wide_multiply(exclusive_ub);
 
@@ -60,6 +180,9 @@ macro_rules! impl_gen_index {
                     return hi;
                 }
 
+                #[cfg(feature = "index_stats")]
+                stats::$counters.record_rejection();
+
                 let rand: $t = rng.gen();
                 (lo, hi) = rand.wide_multiply(exclusive_ub);
             }
@@ -68,11 +191,11 @@ macro_rules! impl_gen_index {
 }
 
 pub mod impl_u16 {
-    impl_gen_index!(u16);
+    impl_gen_index!(u16, U16);
 }
 
 pub mod impl_u32 {
-    impl_gen_index!(u32);
+    impl_gen_index!(u32, U32);
 
     #[inline]
     pub fn gen_index_pair(rng: &mut impl Rng, exclusive_ub: (u32, u32)) -> (u32, u32) {
@@ -90,13 +213,117 @@ pub mod impl_u32 {
                 gen_index_impl(rng, r1, exclusive_ub.1),
             )
         } else {
+            #[cfg(feature = "index_stats")]
+            stats::U32.record_samples(2);
+
             (hi0, hi1)
         }
     }
 }
 
 pub mod impl_u64 {
-    impl_gen_index!(u64);
+    impl_gen_index!(u64, U64);
+}
+
+/// Rejection-rate counters of the index generators, available with the
+/// `index_stats` feature. The counters are global and shared by all threads;
+/// updating them slows down index generation noticeably, so the feature is
+/// intended for profiling only. Indices drawn by the NEON Fisher-Yates kernel
+/// are only counted if they need a rejection step.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::uniform_index::{self, stats};
+///
+/// stats::reset();
+/// for _ in 0..1000 {
+///     uniform_index::impl_u32::gen_index(&mut rand::thread_rng(), 3 << 30);
+/// }
+/// let u32_stats = stats::u32_stats();
+/// assert!(u32_stats.samples >= 1000);
+/// println!("rejection rate: {}", u32_stats.rejection_rate());
+/// ```
+#[cfg(feature = "index_stats")]
+pub mod stats {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Snapshot of the counters of one index width.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct IndexStats {
+        /// Number of indices generated
+        pub samples: u64,
+        /// Number of random words that were rejected
+        pub rejections: u64,
+    }
+
+    impl IndexStats {
+        /// Average number of rejected random words per index.
+        pub fn rejection_rate(&self) -> f64 {
+            self.rejections as f64 / self.samples.max(1) as f64
+        }
+    }
+
+    pub(super) struct Counters {
+        samples: AtomicU64,
+        rejections: AtomicU64,
+    }
+
+    impl Counters {
+        const fn new() -> Self {
+            Self {
+                samples: AtomicU64::new(0),
+                rejections: AtomicU64::new(0),
+            }
+        }
+
+        #[inline]
+        pub(super) fn record_samples(&self, num: u64) {
+            self.samples.fetch_add(num, Ordering::Relaxed);
+        }
+
+        #[inline]
+        pub(super) fn record_rejection(&self) {
+            self.rejections.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn snapshot(&self) -> IndexStats {
+            IndexStats {
+                samples: self.samples.load(Ordering::Relaxed),
+                rejections: self.rejections.load(Ordering::Relaxed),
+            }
+        }
+
+        fn reset(&self) {
+            self.samples.store(0, Ordering::Relaxed);
+            self.rejections.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub(super) static U16: Counters = Counters::new();
+    pub(super) static U32: Counters = Counters::new();
+    pub(super) static U64: Counters = Counters::new();
+
+    /// Counters of [`super::impl_u16`].
+    pub fn u16_stats() -> IndexStats {
+        U16.snapshot()
+    }
+
+    /// Counters of [`super::impl_u32`] (including the 32-bit path of [`super::gen_index`]).
+    pub fn u32_stats() -> IndexStats {
+        U32.snapshot()
+    }
+
+    /// Counters of [`super::impl_u64`] (including the 64-bit path of [`super::gen_index`]).
+    pub fn u64_stats() -> IndexStats {
+        U64.snapshot()
+    }
+
+    /// Resets all counters to zero.
+    pub fn reset() {
+        U16.reset();
+        U32.reset();
+        U64.reset();
+    }
 }
 
 trait WideMul: Sized {
@@ -134,12 +361,12 @@ mod test {
         let mut urng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
 
         for _ in 0..1000 {
-            let a : u32 = urng.gen();
-            let b : u32 = urng.gen();
+            let a: u32 = urng.gen();
+            let b: u32 = urng.gen();
 
             let (lo, hi) = a.wide_multiply(b);
 
-            assert_eq!((lo as u64) | ((hi as u64) << 32),  (a as u64) * (b as u64));
+            assert_eq!((lo as u64) | ((hi as u64) << 32), (a as u64) * (b as u64));
         }
     }
 
@@ -185,4 +412,65 @@ mod test {
     mod test_usize {
         impl_tests!(gen_index, usize);
     }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn usize_uses_u32_on_32bit_targets() {
+        use rand::SeedableRng;
+        let rng = rand_pcg::Pcg64::seed_from_u64(123);
+
+        for ub in [1usize, 1000, U32_MAX_UPPER_BOUND as usize + 1, usize::MAX] {
+            assert_eq!(
+                gen_index(&mut rng.clone(), ub),
+                impl_u32::gen_index(&mut rng.clone(), ub as u32) as usize
+            );
+        }
+    }
+
+    #[cfg(feature = "index_stats")]
+    #[test]
+    fn stats_count_rejections() {
+        use rand::SeedableRng;
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(1234);
+        let before = stats::u32_stats();
+
+        // a quarter of the random words is rejected for this bound
+        for _ in 0..10_000 {
+            impl_u32::gen_index(&mut rng, 3 << 30);
+        }
+
+        let after = stats::u32_stats();
+        assert!(after.samples - before.samples >= 10_000);
+        assert!(after.rejections - before.rejections >= 2_000);
+    }
+
+    mod test_entropy_pool {
+        fn gen_index(rng: &mut impl rand::Rng, ub: usize) -> usize {
+            super::EntropyPool::new().gen_index(rng, ub)
+        }
+
+        impl_tests!(gen_index, usize);
+
+        #[test]
+        fn reused_pool_is_uniform() {
+            let mut rng = Pcg64::seed_from_u64(123456);
+            let mut pool = EntropyPool::new();
+            const ITERATIONS: usize = 60_000;
+
+            for ub in [2, 3, 6, 7, 100] {
+                let mut counts = vec![0usize; ub];
+                for _ in 0..ITERATIONS {
+                    counts[pool.gen_index(&mut rng, ub)] += 1;
+                }
+
+                let expected = ITERATIONS / ub;
+                assert!(
+                    counts
+                        .iter()
+                        .all(|&c| 5 * c > 4 * expected && 5 * c < 6 * expected),
+                    "ub={ub} counts={counts:?}"
+                );
+            }
+        }
+    }
 }
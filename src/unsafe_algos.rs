@@ -0,0 +1,54 @@
+//! Runtime control over the algorithms enabled by the `unsafe_algos` feature.
+//!
+//! Even if the crate is compiled with `unsafe_algos`, the fast paths relying on
+//! pointer arithmetic can be switched off at runtime; the entry points then use their
+//! safe counterparts. This allows to rule out (or confirm) the unsafe code as the cause
+//! of a crash in production without rebuilding. The fast paths are disabled if
+//! [`disable`] was called, or if the environment variable [`DISABLE_ENV_VAR`] is set to
+//! a value other than `0` when the first shuffle is executed.
+//!
+//! Both implementations produce uniform random permutations, but not the same ones:
+//! the output for a given seed changes when switching.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::{unsafe_algos, RipShuffleSequential};
+//!
+//! unsafe_algos::disable();
+//! assert!(!unsafe_algos::is_enabled());
+//!
+//! let mut data: Vec<_> = (0..1_000_000).collect();
+//! data.seq_shuffle(&mut rand::thread_rng());
+//! # unsafe_algos::enable();
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Environment variable that disables the unsafe algorithms if set (to a value other than `0`).
+pub const DISABLE_ENV_VAR: &str = "RIP_SHUFFLE_DISABLE_UNSAFE";
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+static DISABLED_BY_ENV: OnceLock<bool> = OnceLock::new();
+
+/// Disables the unsafe algorithms for all subsequent calls (in all threads).
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Re-enables the unsafe algorithms, unless they are disabled via [`DISABLE_ENV_VAR`]
+/// or the crate is compiled without the `unsafe_algos` feature.
+pub fn enable() {
+    DISABLED.store(false, Ordering::Relaxed);
+}
+
+/// Returns whether the shuffling entry points currently use the unsafe algorithms.
+#[inline]
+pub fn is_enabled() -> bool {
+    cfg!(feature = "unsafe_algos") && !DISABLED.load(Ordering::Relaxed) && !disabled_by_env()
+}
+
+fn disabled_by_env() -> bool {
+    *DISABLED_BY_ENV
+        .get_or_init(|| std::env::var_os(DISABLE_ENV_VAR).is_some_and(|value| value != "0"))
+}
@@ -0,0 +1,130 @@
+//! Skipping the shuffle of inputs whose elements are all equal.
+//!
+//! Data pipelines frequently shuffle columns that hold a single value. Shuffling them
+//! costs the same bandwidth as for any other input, but cannot change their contents.
+//! [`shuffle_if_varied`] and [`par_shuffle_if_varied`] first compare
+//! [`NUM_PROBES`] evenly spaced elements with the first one. If one of them differs,
+//! the input is shuffled right away; otherwise, all elements are compared (which reads
+//! the input once) and the shuffle is skipped if they are all equal.
+//!
+//! A skipped shuffle does not draw from the random number generator, so subsequent
+//! outputs of the generator differ from those after an actual shuffle.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::varied::shuffle_if_varied;
+//! use rand::SeedableRng;
+//!
+//! let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+//!
+//! let mut constant = vec![7u32; 1000];
+//! assert!(!shuffle_if_varied(&mut rng, &mut constant));
+//!
+//! let mut data: Vec<u32> = (0..1000).collect();
+//! assert!(shuffle_if_varied(&mut rng, &mut data));
+//! ```
+
+use super::{RipShuffleParallel, RipShuffleSequential};
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Number of elements compared with the first one before the whole input is checked.
+pub const NUM_PROBES: usize = 16;
+
+/// Inputs of at most this many elements are checked sequentially by [`par_is_all_equal`].
+const PAR_CHECK_MIN_LEN: usize = 1 << 16;
+
+/// Returns `true` if some of [`NUM_PROBES`] evenly spaced elements differs from the first.
+fn probes_differ<T: PartialEq>(data: &[T]) -> bool {
+    let Some(first) = data.first() else {
+        return false;
+    };
+
+    let n = data.len();
+    (1..=NUM_PROBES).any(|i| data[i * (n - 1) / NUM_PROBES] != *first)
+}
+
+/// Returns whether all elements of `data` are equal (which holds for empty inputs).
+pub fn is_all_equal<T: PartialEq>(data: &[T]) -> bool {
+    if probes_differ(data) {
+        return false;
+    }
+
+    data.windows(2).all(|w| w[0] == w[1])
+}
+
+/// Same as [`is_all_equal`], but compares the elements in parallel.
+pub fn par_is_all_equal<T: PartialEq + Sync>(data: &[T]) -> bool {
+    if data.len() <= PAR_CHECK_MIN_LEN {
+        return is_all_equal(data);
+    }
+
+    if probes_differ(data) {
+        return false;
+    }
+
+    let first = &data[0];
+    data.par_iter().all(|x| x == first)
+}
+
+/// Shuffles `data` with [`RipShuffleSequential::seq_shuffle`] unless all of its elements
+/// are equal (see the [module documentation](self)). Returns whether it was shuffled.
+pub fn shuffle_if_varied<R: Rng, T: PartialEq>(rng: &mut R, data: &mut [T]) -> bool {
+    if is_all_equal(data) {
+        return false;
+    }
+
+    data.seq_shuffle(rng);
+    true
+}
+
+/// Same as [`shuffle_if_varied`], but checks and shuffles in parallel (see
+/// [`RipShuffleParallel::par_shuffle`]).
+pub fn par_shuffle_if_varied<R, T>(rng: &mut R, data: &mut [T]) -> bool
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: PartialEq + Send + Sync,
+{
+    if par_is_all_equal(data) {
+        return false;
+    }
+
+    data.par_shuffle(rng);
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn detects_all_equal() {
+        for n in [0, 1, 2, 17, 1000, PAR_CHECK_MIN_LEN + 1] {
+            let mut data = vec![3u8; n];
+            assert!(is_all_equal(&data), "n={n}");
+            assert!(par_is_all_equal(&data), "n={n}");
+
+            // a deviation between the probes is found by the full check
+            if n > 2 {
+                data[n / NUM_PROBES / 2 + 1] = 4;
+                assert!(!is_all_equal(&data), "n={n}");
+                assert!(!par_is_all_equal(&data), "n={n}");
+            }
+        }
+    }
+
+    #[test]
+    fn shuffles_varied_inputs() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+
+        let mut data: Vec<u32> = vec![0; PAR_CHECK_MIN_LEN + 10];
+        assert!(!shuffle_if_varied(&mut rng, &mut data));
+        assert!(!par_shuffle_if_varied(&mut rng, &mut data));
+
+        *data.last_mut().unwrap() = 1;
+        assert!(shuffle_if_varied(&mut rng, &mut data));
+        assert!(par_shuffle_if_varied(&mut rng, &mut data));
+        assert_eq!(data.iter().sum::<u32>(), 1);
+    }
+}
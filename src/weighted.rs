@@ -0,0 +1,290 @@
+//! Parallel shuffles with subproblems of different sizes for heterogeneous cores.
+//!
+//! The parallel scatter shuffle splits its input into one subproblem per thread of equal
+//! size. On hybrid CPUs (e.g., big.LITTLE or performance and efficiency cores), the
+//! fast cores then idle while the slow ones finish. [`WithSubproblemWeights`] assigns
+//! each subproblem a weight instead: the rough shuffle and the recursion split their
+//! input in proportion to the weights (see
+//! [`ParConfiguration::par_subproblem_weight`]). [`par_shuffle_weighted`] applies this
+//! to the default configuration, and [`core_capacities`] derives weights from the
+//! relative core performance reported by Linux.
+//!
+//! The weights only determine the sizes of the subproblems; Rayon still assigns tasks
+//! to threads dynamically. The output is a uniform random permutation for any weights.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::weighted::{core_capacities, par_shuffle_weighted};
+//! use rand::SeedableRng;
+//!
+//! // e.g. two performance cores that are three times as fast as two efficiency cores
+//! let weights = core_capacities().unwrap_or(vec![3, 3, 1, 1]);
+//!
+//! let mut data: Vec<u64> = (0..1 << 22).collect();
+//! par_shuffle_weighted(&mut rand_pcg::Pcg64Mcg::seed_from_u64(1), &mut data, &weights);
+//! ```
+
+use super::error::ShuffleError;
+use super::fisher_yates::fisher_yates;
+use super::rng_strategy::IndexRngTask;
+use super::scatter_shuffle::parallel::{
+    num_buckets_for_bytes, DefaultConfiguration, ParScatterShuffleImpl,
+};
+use super::scatter_shuffle::{ParConfiguration, SeqConfiguration};
+use rand::{Rng, SeedableRng};
+
+/// Wraps a configuration and splits inputs into one subproblem per weight (fewer if the
+/// input has fewer base cases), whose sizes are proportional to the weights.
+#[derive(Clone, Debug)]
+pub struct WithSubproblemWeights<C> {
+    config: C,
+    weights: Vec<usize>,
+}
+
+impl<C> WithSubproblemWeights<C> {
+    /// Returns [`ShuffleError::InvalidSubproblemWeights`] if `weights` is empty or contains a zero.
+    pub fn try_new(config: C, weights: Vec<usize>) -> Result<Self, ShuffleError> {
+        if weights.is_empty() || weights.contains(&0) {
+            return Err(ShuffleError::InvalidSubproblemWeights);
+        }
+
+        Ok(Self { config, weights })
+    }
+
+    pub fn weights(&self) -> &[usize] {
+        &self.weights
+    }
+}
+
+impl<C: SeqConfiguration> SeqConfiguration for WithSubproblemWeights<C> {
+    fn seq_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+        self.config.seq_base_case_shuffle(rng, data)
+    }
+
+    fn seq_base_case_size(&self) -> usize {
+        self.config.seq_base_case_size()
+    }
+
+    fn seq_disable_recursion(&self) -> bool {
+        self.config.seq_disable_recursion()
+    }
+
+    fn rough_shuffle_handover_rounds(&self) -> usize {
+        self.config.rough_shuffle_handover_rounds()
+    }
+}
+
+impl<C: ParConfiguration> ParConfiguration for WithSubproblemWeights<C> {
+    fn par_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+        self.config.par_base_case_shuffle(rng, data)
+    }
+
+    fn par_base_case_size(&self) -> usize {
+        self.config.par_base_case_size()
+    }
+
+    fn par_number_of_subproblems(&self, n: usize) -> usize {
+        (n / self.config.par_base_case_size().max(1)).clamp(1, self.weights.len())
+    }
+
+    fn par_disable_recursion(&self) -> bool {
+        self.config.par_disable_recursion()
+    }
+
+    /// The given weights if the input is split into one subproblem per weight, and equal
+    /// weights otherwise.
+    fn par_subproblem_weight(&self, index: usize, num_subproblems: usize) -> usize {
+        if num_subproblems == self.weights.len() {
+            self.weights[index]
+        } else {
+            1
+        }
+    }
+
+    fn run_per_element<R, Task>(&self, rng: &mut R, task: Task) -> Task::Output
+    where
+        R: Rng + SeedableRng + Send + Sync,
+        Task: IndexRngTask,
+    {
+        self.config.run_per_element(rng, task)
+    }
+
+    type Profiler = C::Profiler;
+    fn get_profiler(&self) -> &Self::Profiler {
+        self.config.get_profiler()
+    }
+}
+
+/// Same as [`crate::scatter_shuffle::parallel::par_scatter_shuffle`], but splits the
+/// input into one subproblem per weight with sizes proportional to `weights` (see the
+/// [module documentation](self)).
+///
+/// # Panics
+/// If `weights` is empty or contains a zero; see [`try_par_shuffle_weighted`] for a
+/// non-panicking variant.
+pub fn par_shuffle_weighted<R, T>(rng: &mut R, data: &mut [T], weights: &[usize])
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send,
+{
+    try_par_shuffle_weighted(rng, data, weights).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`par_shuffle_weighted`], but returns [`ShuffleError::InvalidSubproblemWeights`] instead
+/// of panicking (leaving `data` untouched).
+pub fn try_par_shuffle_weighted<R, T>(
+    rng: &mut R,
+    data: &mut [T],
+    weights: &[usize],
+) -> Result<(), ShuffleError>
+where
+    R: Rng + SeedableRng + Send + Sync,
+    T: Send,
+{
+    let weights = weights.to_vec();
+    let config = WithSubproblemWeights::try_new(DefaultConfiguration::default(), weights)?;
+
+    if crate::oracle::is_enabled() {
+        crate::fisher_yates::naive::fisher_yates(rng, data);
+        return Ok(());
+    }
+
+    match num_buckets_for_bytes(std::mem::size_of_val(data)) {
+        None => fisher_yates(rng, data),
+        Some(64) => ParScatterShuffleImpl::<R, T, _, 64>::new(config).shuffle(rng, data),
+        Some(256) => ParScatterShuffleImpl::<R, T, _, 256>::new(config).shuffle(rng, data),
+        Some(512) => ParScatterShuffleImpl::<R, T, _, 512>::new(config).shuffle(rng, data),
+        Some(_) => ParScatterShuffleImpl::<R, T, _, 1024>::new(config).shuffle(rng, data),
+    }
+
+    Ok(())
+}
+
+/// Returns the relative performance of each logical CPU (in the order of their ids) as
+/// reported by Linux in `/sys/devices/system/cpu/cpu*/cpu_capacity`, where the fastest
+/// cores have a capacity of 1024. Returns `None` if the capacities are not available,
+/// which is the case on most x86 systems and on other operating systems.
+pub fn core_capacities() -> Option<Vec<usize>> {
+    let mut cpus: Vec<(usize, usize)> = std::fs::read_dir("/sys/devices/system/cpu")
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let id = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("cpu")?
+                .parse()
+                .ok()?;
+            let capacity = std::fs::read_to_string(entry.path().join("cpu_capacity")).ok()?;
+            Some((id, capacity.trim().parse().ok()?))
+        })
+        .collect();
+
+    if cpus.is_empty() || cpus.iter().any(|&(_, capacity)| capacity == 0) {
+        return None;
+    }
+
+    cpus.sort_unstable();
+    Some(cpus.into_iter().map(|(_, capacity)| capacity).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scatter_shuffle::{implement_no_profiler, implement_seq_config};
+    use rand_pcg::Pcg64Mcg;
+    use std::sync::OnceLock;
+
+    #[derive(Clone, Copy, Default)]
+    struct SmallBaseCases {}
+
+    implement_seq_config!(SmallBaseCases, fisher_yates, 2);
+
+    impl ParConfiguration for SmallBaseCases {
+        implement_no_profiler!();
+
+        fn par_base_case_shuffle<R: Rng, T: Sized>(&self, rng: &mut R, data: &mut [T]) {
+            fisher_yates(rng, data)
+        }
+
+        fn par_base_case_size(&self) -> usize {
+            4
+        }
+
+        fn par_number_of_subproblems(&self, _n: usize) -> usize {
+            unreachable!("the number of subproblems follows the weights")
+        }
+    }
+
+    #[test]
+    fn uses_weights_per_subproblem() {
+        let config =
+            WithSubproblemWeights::try_new(SmallBaseCases::default(), vec![3, 1, 2]).unwrap();
+
+        assert_eq!(config.par_number_of_subproblems(1000), 3);
+        assert_eq!(config.par_number_of_subproblems(8), 2);
+        assert_eq!(config.par_number_of_subproblems(0), 1);
+
+        let weights = |k: usize| {
+            (0..k)
+                .map(|i| config.par_subproblem_weight(i, k))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(weights(3), [3, 1, 2]);
+        assert_eq!(weights(2), [1, 1]);
+    }
+
+    #[test]
+    fn rejects_invalid_weights() {
+        for weights in [vec![], vec![1, 0]] {
+            assert!(matches!(
+                WithSubproblemWeights::try_new(SmallBaseCases::default(), weights),
+                Err(ShuffleError::InvalidSubproblemWeights)
+            ));
+        }
+
+        let mut data = [1, 2, 3];
+        assert_eq!(
+            try_par_shuffle_weighted(&mut Pcg64Mcg::seed_from_u64(1), &mut data, &[]),
+            Err(ShuffleError::InvalidSubproblemWeights)
+        );
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn shuffles_large_inputs() {
+        let mut data: Vec<u64> = (0..1 << 21).collect();
+        par_shuffle_weighted(&mut Pcg64Mcg::seed_from_u64(2), &mut data, &[4, 4, 1, 1, 1]);
+
+        assert_ne!(data[..100], (0..100).collect::<Vec<_>>());
+        data.sort();
+        assert!(data.iter().enumerate().all(|(i, &x)| i as u64 == x));
+    }
+
+    #[test]
+    fn capacities_are_positive() {
+        if let Some(capacities) = core_capacities() {
+            assert!(capacities.iter().all(|&c| c > 0));
+        }
+    }
+
+    mod statistical {
+        use super::*;
+
+        fn weighted<R: Rng + SeedableRng + Send + Sync, T: Send>(rng: &mut R, data: &mut [T]) {
+            static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+            let pool = POOL.get_or_init(|| {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(3)
+                    .build()
+                    .unwrap()
+            });
+
+            let config =
+                WithSubproblemWeights::try_new(SmallBaseCases::default(), vec![5, 1, 2]).unwrap();
+            pool.install(|| ParScatterShuffleImpl::<R, T, _, 4>::new(config).shuffle(rng, data))
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(weighted);
+    }
+}
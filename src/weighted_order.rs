@@ -0,0 +1,321 @@
+//! Random permutations that favor elements of higher weight.
+//!
+//! [`seq_shuffle_weighted`] arranges the elements in the order of successive weighted
+//! sampling without replacement: the first element is drawn with probability
+//! proportional to its weight, the second one among the remaining elements in the
+//! same way, and so on. This is the order in which, e.g., a weighted lottery draws its
+//! winners. (Not to be confused with [`crate::weighted`], which shuffles uniformly with
+//! differently sized subproblems.)
+//!
+//! Instead of maintaining a priority queue or a sampling tree, we assign each element
+//! an exponentially distributed key with its weight as rate and sort the elements by
+//! their keys (Efraimidis and Spirakis, "Weighted random sampling with a reservoir",
+//! 2006): the element with the smallest key is distributed as the first draw, and by
+//! memorylessness, the same holds for the remaining ones. The keys are non-negative
+//! floats, so we sort them by their bit patterns. [`par_shuffle_weighted`] draws the
+//! keys and sorts them in parallel.
+//!
+//! Elements of weight zero are never drawn while elements of positive weight remain;
+//! they are placed at the end in uniformly random order. The keys are computed with
+//! floating-point arithmetic, so the distribution is exact up to rounding errors.
+//!
+//! # Example
+//! ```
+//! use rip_shuffle::weighted_order::seq_shuffle_weighted;
+//!
+//! struct Ticket {
+//!     owner: &'static str,
+//!     weight: f64,
+//! }
+//!
+//! let mut tickets = vec![
+//!     Ticket { owner: "alice", weight: 3.0 },
+//!     Ticket { owner: "bob", weight: 1.0 },
+//!     Ticket { owner: "carol", weight: 0.5 },
+//! ];
+//!
+//! // alice wins with probability 2/3
+//! seq_shuffle_weighted(&mut rand::thread_rng(), &mut tickets, |t| t.weight);
+//! println!("{} wins", tickets[0].owner);
+//! ```
+
+use super::error::ShuffleError;
+use super::fisher_yates::fisher_yates;
+use super::indirection::apply_in_place;
+use super::scatter_shuffle::parallel::{par_scatter_shuffle, seed_new_rng};
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Keys of this many elements are drawn with the same random number generator.
+const CHUNK_SIZE: usize = 1 << 14;
+
+/// Rearranges `data` in the order of successive weighted sampling without replacement,
+/// where `weight` returns the weight of an element (see the
+/// [module documentation](self)).
+///
+/// # Panics
+/// Panics if a weight is negative, infinite, or NaN, or if `data` has `2^32` or more
+/// elements; see [`try_seq_shuffle_weighted`] for a non-panicking variant.
+pub fn seq_shuffle_weighted<R, T, W>(rng: &mut R, data: &mut [T], weight: W)
+where
+    R: Rng,
+    W: FnMut(&T) -> f64,
+{
+    try_seq_shuffle_weighted(rng, data, weight).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`seq_shuffle_weighted`], but returns [`ShuffleError::InvalidElementWeights`] or
+/// [`ShuffleError::InputTooLarge`] instead of panicking (leaving `data` untouched).
+pub fn try_seq_shuffle_weighted<R, T, W>(
+    rng: &mut R,
+    data: &mut [T],
+    weight: W,
+) -> Result<(), ShuffleError>
+where
+    R: Rng,
+    W: FnMut(&T) -> f64,
+{
+    let weights = collect_weights(data, weight)?;
+
+    let mut keys: Vec<(u64, u32)> = (0..data.len() as u32)
+        .zip(&weights)
+        .map(|(i, &w)| (draw_key(rng, w), i))
+        .collect();
+    keys.sort_unstable_by_key(|&(key, _)| key);
+
+    let mut order: Vec<u32> = keys.into_iter().map(|(_, i)| i).collect();
+    let num_positive = weights.iter().filter(|&&w| w > 0.0).count();
+    fisher_yates(rng, &mut order[num_positive..]);
+
+    apply_in_place(data, &mut order);
+    Ok(())
+}
+
+/// Same as [`seq_shuffle_weighted`], but draws the keys and sorts them in parallel.
+/// The weights are queried sequentially. The output only depends on the random number
+/// generator, not on the number of threads.
+///
+/// # Panics
+/// Panics if a weight is negative, infinite, or NaN, or if `data` has `2^32` or more
+/// elements; see [`try_par_shuffle_weighted`] for a non-panicking variant.
+pub fn par_shuffle_weighted<R, T, W>(rng: &mut R, data: &mut [T], weight: W)
+where
+    R: Rng + SeedableRng + Send + Sync,
+    W: FnMut(&T) -> f64,
+{
+    try_par_shuffle_weighted(rng, data, weight).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`par_shuffle_weighted`], but returns [`ShuffleError::InvalidElementWeights`] or
+/// [`ShuffleError::InputTooLarge`] instead of panicking (leaving `data` untouched).
+pub fn try_par_shuffle_weighted<R, T, W>(
+    rng: &mut R,
+    data: &mut [T],
+    weight: W,
+) -> Result<(), ShuffleError>
+where
+    R: Rng + SeedableRng + Send + Sync,
+    W: FnMut(&T) -> f64,
+{
+    let weights = collect_weights(data, weight)?;
+
+    // derive the generators sequentially, so the result does not depend on the threads
+    let mut rngs: Vec<R> = (0..weights.len().div_ceil(CHUNK_SIZE))
+        .map(|_| seed_new_rng(rng))
+        .collect();
+
+    let mut keys: Vec<(u64, u32)> = weights
+        .par_chunks(CHUNK_SIZE)
+        .zip(rngs.par_iter_mut())
+        .enumerate()
+        .flat_map_iter(|(chunk, (weights, rng))| {
+            let begin = (chunk * CHUNK_SIZE) as u32;
+            (begin..)
+                .zip(weights)
+                .map(|(i, &w)| (draw_key(rng, w), i))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    keys.par_sort_unstable_by_key(|&(key, _)| key);
+
+    let mut order: Vec<u32> = keys.into_par_iter().map(|(_, i)| i).collect();
+    let num_positive = weights.par_iter().filter(|&&w| w > 0.0).count();
+    par_scatter_shuffle(rng, &mut order[num_positive..]);
+
+    apply_in_place(data, &mut order);
+    Ok(())
+}
+
+fn collect_weights<T, W>(data: &[T], mut weight: W) -> Result<Vec<f64>, ShuffleError>
+where
+    W: FnMut(&T) -> f64,
+{
+    if u32::try_from(data.len()).is_err() {
+        return Err(ShuffleError::InputTooLarge);
+    }
+
+    data.iter()
+        .map(|x| {
+            let w = weight(x);
+            (w.is_finite() && w >= 0.0)
+                .then_some(w)
+                .ok_or(ShuffleError::InvalidElementWeights)
+        })
+        .collect()
+}
+
+/// Returns the bit pattern of an exponentially distributed key with rate `weight`.
+/// Since the keys are non-negative (possibly positive infinity for tiny weights), their
+/// bit patterns are ordered as the keys themselves. Elements of weight zero receive
+/// the largest pattern, so they are sorted after all others.
+fn draw_key<R: Rng>(rng: &mut R, weight: f64) -> u64 {
+    if weight == 0.0 {
+        return u64::MAX;
+    }
+
+    // `1 / (1 - u)` is in `[1, 2^53]`, so the logarithm is finite and not negative zero
+    let exponential = (1.0 / (1.0 - rng.gen::<f64>())).ln();
+    (exponential / weight).to_bits()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_pcg::Pcg64Mcg;
+
+    type Shuffle = fn(&mut Pcg64Mcg, &mut [(usize, f64)]);
+
+    const SHUFFLES: [Shuffle; 2] = [
+        |rng, data| seq_shuffle_weighted(rng, data, |x| x.1),
+        |rng, data| par_shuffle_weighted(rng, data, |x| x.1),
+    ];
+
+    #[test]
+    fn first_draw_is_proportional_to_weight() {
+        const RUNS: usize = 20_000;
+        let weights = [1.0, 2.0, 3.0, 4.0];
+
+        for shuffle in SHUFFLES {
+            let mut rng = Pcg64Mcg::seed_from_u64(1);
+            let mut counts = [0usize; 4];
+            for _ in 0..RUNS {
+                let mut data: Vec<(usize, f64)> = weights.iter().copied().enumerate().collect();
+                shuffle(&mut rng, &mut data);
+                counts[data[0].0] += 1;
+            }
+
+            for (i, &c) in counts.iter().enumerate() {
+                let expected = RUNS * (i + 1) / 10;
+                assert!(c.abs_diff(expected) < expected / 10, "{counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn second_draw_follows_successive_sampling() {
+        // P(second = 2) = sum over first f != 2 of w_f / 10 * 3 / (10 - w_f)
+        const RUNS: usize = 40_000;
+        let weights = [1.0, 2.0, 3.0, 4.0];
+
+        let expected: f64 = [0, 1, 3]
+            .iter()
+            .map(|&f| weights[f] / 10.0 * 3.0 / (10.0 - weights[f]))
+            .sum();
+
+        let mut rng = Pcg64Mcg::seed_from_u64(2);
+        let mut count = 0;
+        for _ in 0..RUNS {
+            let mut data: Vec<(usize, f64)> = weights.iter().copied().enumerate().collect();
+            seq_shuffle_weighted(&mut rng, &mut data, |x| x.1);
+            count += (data[1].0 == 2) as usize;
+        }
+
+        let frequency = count as f64 / RUNS as f64;
+        assert!(
+            (frequency - expected).abs() < 0.01,
+            "{frequency} vs {expected}"
+        );
+    }
+
+    #[test]
+    fn zero_weights_come_last() {
+        for shuffle in SHUFFLES {
+            let mut rng = Pcg64Mcg::seed_from_u64(3);
+            for n in [0, 1, 100, 100_000] {
+                let mut data: Vec<(usize, f64)> =
+                    (0..n).map(|i| (i, (i % 3) as f64 * 0.5)).collect();
+                shuffle(&mut rng, &mut data);
+
+                let num_positive = n - n.div_ceil(3);
+                assert!(data[..num_positive].iter().all(|x| x.1 > 0.0));
+                assert!(data[num_positive..].iter().all(|x| x.1 == 0.0));
+                if n > 10 {
+                    assert!(!data[num_positive..].windows(2).all(|w| w[0].0 < w[1].0));
+                }
+
+                data.sort_by_key(|x| x.0);
+                assert!(data.iter().enumerate().all(|(i, x)| i == x.0));
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_is_independent_of_threads() {
+        let shuffle = || {
+            let mut data: Vec<(usize, f64)> = (0..100_000).map(|i| (i, 1.0 + i as f64)).collect();
+            par_shuffle_weighted(&mut Pcg64Mcg::seed_from_u64(4), &mut data, |x| x.1);
+            data
+        };
+
+        let reference = shuffle();
+        for num_threads in [1, 3] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap();
+            assert_eq!(
+                pool.install(shuffle),
+                reference,
+                "num_threads={num_threads}"
+            );
+        }
+    }
+
+    #[test]
+    fn keys_are_ordered_by_bit_pattern() {
+        let mut rng = crate::test_util::SequenceRng::new([0]);
+        assert_eq!(draw_key(&mut rng, 1.0), 0);
+        assert!(draw_key(&mut rng, f64::MIN_POSITIVE) < draw_key(&mut rng, 0.0));
+
+        let mut rng = Pcg64Mcg::seed_from_u64(6);
+        assert!(draw_key(&mut rng, 5e-324) <= f64::INFINITY.to_bits());
+    }
+
+    #[test]
+    fn invalid_weights_are_error() {
+        let mut rng = Pcg64Mcg::seed_from_u64(5);
+        for invalid in [-1.0, f64::INFINITY, f64::NAN] {
+            let mut data = vec![1.0, invalid, 2.0];
+            assert_eq!(
+                try_seq_shuffle_weighted(&mut rng, &mut data, |&w| w),
+                Err(ShuffleError::InvalidElementWeights)
+            );
+            assert_eq!(
+                try_par_shuffle_weighted(&mut rng, &mut data, |&w| w),
+                Err(ShuffleError::InvalidElementWeights)
+            );
+            assert_eq!(data[..1], [1.0]);
+        }
+    }
+
+    mod statistical {
+        use super::*;
+
+        // equal weights yield uniform permutations
+        fn uniform<R: Rng + SeedableRng + Send + Sync, T>(rng: &mut R, data: &mut [T]) {
+            par_shuffle_weighted(rng, data, |_| 1.0)
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(uniform);
+    }
+}
@@ -0,0 +1,284 @@
+//! Local shuffles that only rearrange elements within windows of bounded size.
+//!
+//! A uniform shuffle has to see the whole input before it can emit the first element.
+//! Streaming pipelines often cannot afford this latency and instead shuffle each
+//! window of consecutive elements independently. This bounds the distance an element
+//! travels (and hence latency and working set) by the window size, at the cost of
+//! global uniformity: in [`shuffle_windows`], two elements from different windows
+//! never swap their relative order.
+//!
+//! [`shuffle_windows_overlapping`] additionally shuffles windows that are shifted by
+//! half a window, so elements can cross the boundaries of the first pass. An element
+//! then moves by less than `1.5 * window` positions.
+//!
+//! [`par_shuffle_each`] shuffles many independent slices (e.g., the rows of a matrix)
+//! in parallel.
+
+use super::error::ShuffleError;
+use super::scatter_shuffle::parallel::seed_new_rng;
+use super::scatter_shuffle::sequential::seq_scatter_shuffle;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Number of consecutive slices of [`par_shuffle_each`] that share a random number
+/// generator; this amortizes the seeding for many small slices.
+const SLICES_PER_RNG: usize = 64;
+
+/// Independently rearranges each window of `window` consecutive elements in a random
+/// permutation. The last window may be shorter if `data.len()` is not a multiple of
+/// `window`.
+///
+/// # Panics
+/// Panics if `window` is zero; see [`try_shuffle_windows`] for a non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::windowed::shuffle_windows;
+///
+/// let mut data: Vec<_> = (0..1000).collect();
+/// shuffle_windows(&mut rand::thread_rng(), &mut data, 100);
+///
+/// for (i, window) in data.chunks(100).enumerate() {
+///     assert!(window.iter().all(|x| x / 100 == i));
+/// }
+/// ```
+pub fn shuffle_windows<R: Rng, T>(rng: &mut R, data: &mut [T], window: usize) {
+    try_shuffle_windows(rng, data, window).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`shuffle_windows`], but returns [`ShuffleError::ZeroWindow`] instead of
+/// panicking if `window` is zero. In this case, the input remains untouched.
+pub fn try_shuffle_windows<R: Rng, T>(
+    rng: &mut R,
+    data: &mut [T],
+    window: usize,
+) -> Result<(), ShuffleError> {
+    if window == 0 {
+        return Err(ShuffleError::ZeroWindow);
+    }
+
+    for chunk in data.chunks_mut(window) {
+        seq_scatter_shuffle(rng, chunk);
+    }
+
+    Ok(())
+}
+
+/// Executes [`shuffle_windows`] followed by a second pass over windows that are
+/// shifted by `window / 2` positions. Hence, elements may cross window boundaries,
+/// but each element still stays within `1.5 * window` positions of its origin.
+///
+/// # Panics
+/// Panics if `window` is zero; see [`try_shuffle_windows_overlapping`] for a
+/// non-panicking variant.
+///
+/// # Example
+/// ```
+/// use rip_shuffle::windowed::shuffle_windows_overlapping;
+///
+/// let mut data: Vec<usize> = (0..1000).collect();
+/// shuffle_windows_overlapping(&mut rand::thread_rng(), &mut data, 100);
+///
+/// assert!(data.iter().enumerate().all(|(i, &x)| i.abs_diff(x) < 150));
+/// ```
+pub fn shuffle_windows_overlapping<R: Rng, T>(rng: &mut R, data: &mut [T], window: usize) {
+    try_shuffle_windows_overlapping(rng, data, window).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as [`shuffle_windows_overlapping`], but returns [`ShuffleError::ZeroWindow`]
+/// instead of panicking if `window` is zero. In this case, the input remains untouched.
+pub fn try_shuffle_windows_overlapping<R: Rng, T>(
+    rng: &mut R,
+    data: &mut [T],
+    window: usize,
+) -> Result<(), ShuffleError> {
+    try_shuffle_windows(rng, data, window)?;
+
+    let offset = (window / 2).min(data.len());
+    if offset > 0 {
+        let (head, tail) = data.split_at_mut(offset);
+        seq_scatter_shuffle(rng, head);
+        try_shuffle_windows(rng, tail, window)?;
+    }
+
+    Ok(())
+}
+
+/// Independently rearranges each slice yielded by `slices` in a random permutation,
+/// using the rayon worker pool. Groups of consecutive slices are shuffled with a
+/// generator seeded from `rng`, so the result only depends on `rng` (and not on the
+/// number of threads).
+///
+/// # Example
+/// ```
+/// use rip_shuffle::windowed::par_shuffle_each;
+/// use rand::SeedableRng;
+/// use rayon::prelude::*;
+///
+/// // shuffle each row of a 1000x100 matrix
+/// let mut matrix: Vec<usize> = (0..100_000).collect();
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(1234);
+/// par_shuffle_each(&mut rng, matrix.par_chunks_mut(100));
+///
+/// for (i, row) in matrix.chunks(100).enumerate() {
+///     assert!(row.iter().all(|x| x / 100 == i));
+/// }
+/// ```
+pub fn par_shuffle_each<'a, R, T, I>(rng: &mut R, slices: I)
+where
+    R: Rng + SeedableRng + Send,
+    T: Send + 'a,
+    I: IndexedParallelIterator<Item = &'a mut [T]>,
+{
+    let rngs: Vec<R> = (0..slices.len().div_ceil(SLICES_PER_RNG))
+        .map(|_| seed_new_rng(rng))
+        .collect();
+
+    slices
+        .chunks(SLICES_PER_RNG)
+        .zip(rngs)
+        .for_each(|(group, mut rng)| {
+            for slice in group {
+                seq_scatter_shuffle(&mut rng, slice);
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn elements_stay_in_window() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+        for n in [0, 1, 10, 99, 100, 101, 1000] {
+            for window in [1, 2, 7, 100] {
+                let mut data: Vec<usize> = (0..n).collect();
+                shuffle_windows(&mut rng, &mut data, window);
+
+                assert!(data
+                    .iter()
+                    .enumerate()
+                    .all(|(i, &x)| i / window == x / window));
+            }
+        }
+    }
+
+    #[test]
+    fn overlapping_bounded_displacement() {
+        let mut rng = Pcg64Mcg::seed_from_u64(12345);
+
+        for n in [0, 1, 10, 99, 100, 101, 1000] {
+            for window in [1, 2, 7, 100] {
+                let mut data: Vec<usize> = (0..n).collect();
+                shuffle_windows_overlapping(&mut rng, &mut data, window);
+
+                assert!(data
+                    .iter()
+                    .enumerate()
+                    .all(|(i, &x)| 2 * i.abs_diff(x) < 3 * window));
+
+                data.sort();
+                assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+            }
+        }
+    }
+
+    #[test]
+    fn overlapping_crosses_boundaries() {
+        let mut rng = Pcg64Mcg::seed_from_u64(23456);
+        let window = 10;
+        let mut crossed = false;
+
+        for _ in 0..100 {
+            let mut data: Vec<usize> = (0..100).collect();
+            shuffle_windows_overlapping(&mut rng, &mut data, window);
+            crossed |= data
+                .iter()
+                .enumerate()
+                .any(|(i, &x)| i / window != x / window);
+        }
+
+        assert!(crossed);
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty_window() {
+        shuffle_windows(&mut Pcg64Mcg::seed_from_u64(1), &mut [1, 2, 3], 0);
+    }
+
+    #[test]
+    fn empty_window_is_error() {
+        let mut rng = Pcg64Mcg::seed_from_u64(1);
+        let mut data = [1, 2, 3];
+
+        for result in [
+            try_shuffle_windows(&mut rng, &mut data, 0),
+            try_shuffle_windows_overlapping(&mut rng, &mut data, 0),
+        ] {
+            assert_eq!(result, Err(ShuffleError::ZeroWindow));
+            assert_eq!(data, [1, 2, 3]);
+        }
+
+        assert_eq!(try_shuffle_windows(&mut rng, &mut data, 2), Ok(()));
+    }
+
+    #[test]
+    fn par_shuffle_each_row() {
+        let (num_rows, num_cols) = (1000, 37);
+        let mut reference: Vec<usize> = (0..num_rows * num_cols).collect();
+        par_shuffle_each(
+            &mut Pcg64Mcg::seed_from_u64(3),
+            reference.par_chunks_mut(num_cols),
+        );
+
+        for (i, row) in reference.chunks(num_cols).enumerate() {
+            let mut row = row.to_vec();
+            assert_ne!(row, (i * num_cols..(i + 1) * num_cols).collect::<Vec<_>>());
+            row.sort();
+            assert_eq!(row, (i * num_cols..(i + 1) * num_cols).collect::<Vec<_>>());
+        }
+
+        // the result does not depend on the number of threads
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        let mut data: Vec<usize> = (0..num_rows * num_cols).collect();
+        pool.install(|| {
+            par_shuffle_each(
+                &mut Pcg64Mcg::seed_from_u64(3),
+                data.par_chunks_mut(num_cols),
+            )
+        });
+        assert_eq!(data, reference);
+    }
+
+    mod single_slice {
+        use super::*;
+
+        fn shuffle_as_single_slice<R: Rng + SeedableRng + Send, T: Send>(
+            rng: &mut R,
+            data: &mut [T],
+        ) {
+            par_shuffle_each(rng, rayon::iter::once(data))
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_as_single_slice);
+    }
+
+    mod single_window {
+        use super::*;
+
+        fn shuffle_as_single_window<R: Rng, T>(rng: &mut R, data: &mut [T]) {
+            let window = data.len().max(1);
+            shuffle_windows(rng, data, window)
+        }
+
+        crate::statistical_tests::test_shuffle_algorithm!(shuffle_as_single_window);
+    }
+}
@@ -0,0 +1,88 @@
+//! Tests of the process-wide switches (such as the oracle mode). They live in
+//! their own test binary, since the switches change the output of concurrently running
+//! unit tests that compare shuffles; within this binary, they are serialized by a lock.
+
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
+use rip_shuffle::error::ShuffleError;
+use rip_shuffle::fisher_yates::naive;
+use rip_shuffle::plan::ShufflePlan;
+use rip_shuffle::prelude::*;
+use rip_shuffle::{oracle, unsafe_algos, RipShuffleParallel, RipShuffleSequential};
+use std::sync::{Mutex, MutexGuard};
+
+static TOGGLES: Mutex<()> = Mutex::new(());
+
+fn lock_toggles() -> MutexGuard<'static, ()> {
+    // a failing test must not poison the lock for the others
+    TOGGLES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[test]
+fn oracle_entry_points_match_reference() {
+    let _guard = lock_toggles();
+    oracle::enable();
+
+    for n in [0, 1, 10, 1000, 1 << 20] {
+        let rng = Pcg64Mcg::seed_from_u64(n as u64);
+
+        let mut reference: Vec<usize> = (0..n).collect();
+        naive::fisher_yates(&mut rng.clone(), &mut reference);
+
+        let entry_points: [fn(&mut Pcg64Mcg, &mut [usize]); 8] = [
+            fisher_yates,
+            seq_scatter_shuffle,
+            par_scatter_shuffle,
+            seq_merge_shuffle,
+            par_merge_shuffle,
+            par_dart_shuffle,
+            |r, d| d.seq_shuffle(r),
+            |r, d| d.par_shuffle(r),
+        ];
+
+        for (i, shuffle) in entry_points.iter().enumerate() {
+            let mut data: Vec<usize> = (0..n).collect();
+            shuffle(&mut rng.clone(), &mut data);
+            assert_eq!(data, reference, "n={n} entry point={i}");
+        }
+    }
+
+    oracle::disable();
+}
+
+#[test]
+fn oracle_keeps_plan_validation() {
+    let _guard = lock_toggles();
+    oracle::enable();
+
+    let mut rng = Pcg64Mcg::seed_from_u64(1);
+    let plan = ShufflePlan::parallel::<u8>(1 << 24);
+    let mut data = vec![0u8; plan.len()];
+    let result = plan.try_seq_shuffle(&mut rng, &mut data);
+
+    oracle::disable();
+    assert_eq!(result, Err(ShuffleError::ParallelPlan));
+}
+
+#[test]
+fn unsafe_algos_switch_to_safe_algorithms() {
+    let _guard = lock_toggles();
+    let mut rng = Pcg64Mcg::seed_from_u64(1234);
+    let mut data: Vec<usize> = (0..1 << 20).collect();
+
+    unsafe_algos::disable();
+    assert!(!unsafe_algos::is_enabled());
+    seq_scatter_shuffle(&mut rng, &mut data);
+    par_merge_shuffle(&mut rng, &mut data);
+    unsafe_algos::enable();
+
+    let disabled_by_env =
+        std::env::var_os(unsafe_algos::DISABLE_ENV_VAR).is_some_and(|value| value != "0");
+    assert_eq!(
+        unsafe_algos::is_enabled(),
+        cfg!(feature = "unsafe_algos") && !disabled_by_env
+    );
+
+    data.sort();
+    assert!(data.iter().enumerate().all(|(i, &x)| i == x));
+}
@@ -0,0 +1,126 @@
+//! Asserts that the sequential shuffling algorithms do not allocate heap memory.
+//! Allocations are counted per thread, so concurrently running tests do not interfere.
+
+use rand::SeedableRng;
+use rand_pcg::{Pcg64, Pcg64Mcg};
+use rip_shuffle::prelude::*;
+use rip_shuffle::{indirection, RipShuffleSequential};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(|a| a.get());
+    f();
+    ALLOCATIONS.with(|a| a.get()) - before
+}
+
+// covers the base case, a single level of the scatter shuffle, and recursion
+const LENGTHS: [usize; 6] = [0, 1, 100, 1 << 19, (1 << 19) + 1, 1 << 23];
+
+#[test]
+fn seq_shuffle() {
+    let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+    for n in LENGTHS {
+        let mut data: Vec<u32> = (0..n as u32).collect();
+        assert_eq!(count_allocations(|| data.seq_shuffle(&mut rng)), 0, "n={n}");
+    }
+}
+
+#[test]
+fn seq_scatter_shuffle_with_large_elements() {
+    let mut rng = Pcg64::seed_from_u64(12345);
+
+    for n in LENGTHS.map(|n| n / 16) {
+        let mut data = vec![[0u8; 100]; n];
+        assert_eq!(
+            count_allocations(|| seq_scatter_shuffle(&mut rng, &mut data)),
+            0,
+            "n={n}"
+        );
+    }
+}
+
+#[test]
+fn seq_shuffle_with_large_elements() {
+    let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+    // large enough for `indirection::is_applicable` if it were enabled
+    for n in LENGTHS.map(|n| n / 64) {
+        let mut data = vec![[0u8; indirection::MIN_ELEMENT_SIZE]; n];
+        assert_eq!(count_allocations(|| data.seq_shuffle(&mut rng)), 0, "n={n}");
+    }
+
+    // the only test of this binary that may observe the opt-in indirection
+    indirection::enable();
+    let mut data: arrayvec::ArrayVec<[u8; indirection::MIN_ELEMENT_SIZE], 2000> = (0..2000)
+        .map(|i| [i as u8; indirection::MIN_ELEMENT_SIZE])
+        .collect();
+    let fixed_capacity = count_allocations(|| data.seq_shuffle(&mut rng));
+    let mut data = data.to_vec();
+    let slice = count_allocations(|| data.seq_shuffle(&mut rng));
+    let uses_index_array =
+        indirection::is_applicable::<[u8; indirection::MIN_ELEMENT_SIZE]>(data.len());
+    indirection::disable();
+
+    assert_eq!(fixed_capacity, 0);
+    assert_eq!(slice, usize::from(uses_index_array));
+}
+
+#[test]
+fn fisher_yates() {
+    let mut rng = Pcg64Mcg::seed_from_u64(123456);
+
+    for n in LENGTHS {
+        let mut data: Vec<u64> = (0..n as u64).collect();
+        assert_eq!(
+            count_allocations(|| rip_shuffle::prelude::fisher_yates(&mut rng, &mut data)),
+            0,
+            "n={n}"
+        );
+    }
+}
+
+#[test]
+fn fixed_capacity_containers() {
+    let mut rng = Pcg64Mcg::seed_from_u64(1234567);
+
+    let mut data: arrayvec::ArrayVec<u32, 1000> = (0..1000).collect();
+    assert_eq!(count_allocations(|| data.seq_shuffle(&mut rng)), 0);
+
+    #[cfg(feature = "heapless")]
+    {
+        let mut data: heapless::Vec<u32, 1000> = (0..1000).collect();
+        assert_eq!(count_allocations(|| data.seq_shuffle(&mut rng)), 0);
+    }
+}
+
+#[test]
+fn counting_allocator_works() {
+    assert!(count_allocations(|| drop(std::hint::black_box(vec![1u8; 10]))) > 0);
+}
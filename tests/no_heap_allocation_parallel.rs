@@ -0,0 +1,62 @@
+//! Asserts that the parallel scatter shuffle does not allocate heap memory in steady
+//! state. Since allocations of all worker threads need to be counted, this binary
+//! consists of a single test (tests of one binary run concurrently).
+
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
+use rip_shuffle::RipShuffleParallel;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    f();
+    ALLOCATIONS.load(Ordering::SeqCst) - before
+}
+
+#[test]
+fn par_shuffle_in_steady_state() {
+    assert!(count_allocations(|| drop(std::hint::black_box(vec![1u8; 10]))) > 0);
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap();
+
+    let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+    // covers the sequential fallback, both bucket counts, and recursion
+    for n in [0, 1000, 1 << 21, 1 << 24] {
+        let mut data: Vec<u64> = (0..n as u64).collect();
+
+        pool.install(|| {
+            // warm up the thread-local state of rayon
+            data.par_shuffle(&mut rng);
+
+            assert_eq!(count_allocations(|| data.par_shuffle(&mut rng)), 0, "n={n}");
+        });
+    }
+}
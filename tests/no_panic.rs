@@ -0,0 +1,58 @@
+//! Sweeps the sequential entry points over all short lengths, the lengths around the
+//! thresholds of the scatter shuffle, and several element sizes (including zero-sized
+//! types) to back the claim that they accept every input without panicking.
+
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
+use rip_shuffle::prelude::*;
+use rip_shuffle::RipShuffleSequential;
+
+/// All lengths up to a few base cases and the neighbourhood of every power of two
+/// up to `max_len`.
+fn lengths(max_len: usize) -> impl Iterator<Item = usize> {
+    (0..=4096).chain(
+        (12..)
+            .map(|log| 1usize << log)
+            .take_while(move |&n| n <= max_len)
+            .flat_map(|n| [n - 1, n, n + 1, n + n / 3]),
+    )
+}
+
+fn sweep<T: Clone>(element: T, max_len: usize) {
+    let mut rng = Pcg64Mcg::seed_from_u64(1234);
+
+    for n in lengths(max_len) {
+        let mut data = vec![element.clone(); n];
+
+        data.seq_shuffle(&mut rng);
+        seq_scatter_shuffle(&mut rng, &mut data);
+        seq_merge_shuffle(&mut rng, &mut data);
+        fisher_yates(&mut rng, &mut data);
+        data.seq_partial_shuffle(&mut rng, n / 2);
+    }
+}
+
+#[test]
+fn zero_sized_elements() {
+    sweep((), 1 << 20);
+}
+
+#[test]
+fn bytes() {
+    sweep(0u8, 1 << 20);
+}
+
+#[test]
+fn words() {
+    sweep(0u64, 1 << 20);
+}
+
+#[test]
+fn large_elements() {
+    sweep([0u8; 100], 1 << 16);
+}
+
+#[test]
+fn heap_owning_elements() {
+    sweep(String::from("x"), 1 << 16);
+}